@@ -0,0 +1,344 @@
+//! Local control socket: JSON-RPC 2.0 access to the engine lifecycle for
+//! processes that are not our webview (shell scripts, editor plugins,
+//! status-bar widgets).
+//!
+//! Transport is newline-delimited JSON over a Unix-domain socket
+//! (`<app_data_dir>/control.sock`, mode 0600) or, on Windows, the named
+//! pipe `\\.\pipe\onebox-control`. One request per line, one response per
+//! line. Methods map 1:1 onto the existing Tauri commands:
+//!
+//!   start {path?, mode?}  stop  reload_config  get_engine_state
+//!   prestart_check {port?}  kill_orphans {port?}
+//!
+//! `subscribe` switches the connection into push mode: every transition
+//! committed by `state_machine::transition` is then written as a
+//! `{"jsonrpc":"2.0","method":"engine-state","params":<EngineState>}`
+//! notification, interleaved with responses to further requests.
+//!
+//! Only the GUI process serves the socket (single-instance guarantees one
+//! owner); a stale socket file from a crashed run is replaced on startup.
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::core::ProxyMode;
+use crate::engine::state_machine::{EngineStateCell, StateChange, EVENT_ENGINE_STATE};
+
+#[cfg(unix)]
+const SOCKET_FILE: &str = "control.sock";
+#[cfg(windows)]
+const PIPE_NAME: &str = r"\\.\pipe\onebox-control";
+
+// JSON-RPC 2.0 reserved error codes.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// Application error: the underlying command returned `Err(String)`.
+const COMMAND_FAILED: i64 = -32000;
+
+#[derive(Deserialize, Debug)]
+struct Request {
+    jsonrpc: Option<String>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+    id: Option<Value>,
+}
+
+#[derive(Deserialize, Default)]
+struct StartParams {
+    path: Option<String>,
+    #[serde(default)]
+    mode: ProxyMode,
+}
+
+#[derive(Deserialize, Default)]
+struct PortParams {
+    port: Option<u16>,
+}
+
+/// Spawn the listener. Failures are logged and leave the app running —
+/// the socket is a convenience, not a dependency of the GUI.
+pub fn spawn_control_server(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = serve(app).await {
+            log::error!("[control] server stopped: {}", e);
+        }
+    });
+}
+
+#[cfg(unix)]
+async fn serve(app: AppHandle) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| std::io::Error::other(e.to_string()))?;
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(SOCKET_FILE);
+    if path.exists() {
+        std::fs::remove_file(&path)?;
+    }
+    let listener = tokio::net::UnixListener::bind(&path)?;
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+    log::info!("[control] listening on {}", path.display());
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move {
+            let (reader, writer) = stream.into_split();
+            handle_connection(app, reader, writer).await;
+        });
+    }
+}
+
+#[cfg(windows)]
+async fn serve(app: AppHandle) -> std::io::Result<()> {
+    use tokio::net::windows::named_pipe::ServerOptions;
+
+    let mut server = ServerOptions::new()
+        .first_pipe_instance(true)
+        .reject_remote_clients(true)
+        .create(PIPE_NAME)?;
+    log::info!("[control] listening on {}", PIPE_NAME);
+
+    loop {
+        server.connect().await?;
+        // Create the next instance before handing this one off so there is
+        // never a window where clients get ERROR_FILE_NOT_FOUND.
+        let connected = std::mem::replace(
+            &mut server,
+            ServerOptions::new()
+                .reject_remote_clients(true)
+                .create(PIPE_NAME)?,
+        );
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move {
+            let (reader, writer) = tokio::io::split(connected);
+            handle_connection(app, reader, writer).await;
+        });
+    }
+}
+
+async fn handle_connection<R, W>(app: AppHandle, reader: R, mut writer: W)
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut lines = BufReader::new(reader).lines();
+    let mut changes = None;
+
+    loop {
+        let line = tokio::select! {
+            line = lines.next_line() => line,
+            change = recv_change(&mut changes) => {
+                let note = match change {
+                    Ok(change) => notification(EVENT_ENGINE_STATE, json!(change.to)),
+                    // Fell behind: resync with the current snapshot.
+                    Err(RecvError::Lagged(_)) => notification(
+                        EVENT_ENGINE_STATE,
+                        json!(app.state::<EngineStateCell>().snapshot()),
+                    ),
+                    Err(RecvError::Closed) => {
+                        changes = None;
+                        continue;
+                    }
+                };
+                if write_line(&mut writer, &note).await.is_err() {
+                    return;
+                }
+                continue;
+            }
+        };
+        let line = match line {
+            Ok(Some(line)) => line,
+            Ok(None) => return,
+            Err(e) => {
+                log::warn!("[control] read failed: {}", e);
+                return;
+            }
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match parse_request(&line) {
+            Ok(request) if request.method == "subscribe" => {
+                if changes.is_none() {
+                    changes = Some(app.state::<EngineStateCell>().subscribe());
+                }
+                request
+                    .id
+                    .map(|id| success(id, json!(app.state::<EngineStateCell>().snapshot())))
+            }
+            Ok(request) => {
+                log::info!("[control] call method={}", request.method);
+                let result = dispatch(&app, &request.method, request.params).await;
+                // Requests without an id are notifications: no reply.
+                request.id.map(|id| match result {
+                    Ok(value) => success(id, value),
+                    Err((code, message)) => error(id, code, &message),
+                })
+            }
+            Err(reply) => Some(reply),
+        };
+        if let Some(response) = response {
+            if write_line(&mut writer, &response).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// Pending forever when the connection has not subscribed, so the select
+/// above only ever wakes for input.
+async fn recv_change(
+    changes: &mut Option<broadcast::Receiver<StateChange>>,
+) -> Result<StateChange, RecvError> {
+    match changes {
+        Some(rx) => rx.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+async fn write_line<W: AsyncWrite + Unpin>(writer: &mut W, value: &Value) -> std::io::Result<()> {
+    let mut bytes = serde_json::to_vec(value).map_err(std::io::Error::other)?;
+    bytes.push(b'\n');
+    writer.write_all(&bytes).await?;
+    writer.flush().await
+}
+
+/// Parse one line into a request, or the error response to send back.
+fn parse_request(line: &str) -> Result<Request, Value> {
+    let value: Value =
+        serde_json::from_str(line).map_err(|e| error(Value::Null, PARSE_ERROR, &e.to_string()))?;
+    let id = value.get("id").cloned().unwrap_or(Value::Null);
+    let request: Request = serde_json::from_value(value)
+        .map_err(|e| error(id.clone(), INVALID_REQUEST, &e.to_string()))?;
+    if request.jsonrpc.as_deref() != Some("2.0") {
+        return Err(error(id, INVALID_REQUEST, "jsonrpc must be \"2.0\""));
+    }
+    Ok(request)
+}
+
+fn params<T: for<'de> Deserialize<'de> + Default>(params: Value) -> Result<T, (i64, String)> {
+    if params.is_null() {
+        return Ok(T::default());
+    }
+    serde_json::from_value(params).map_err(|e| (INVALID_PARAMS, e.to_string()))
+}
+
+async fn dispatch(app: &AppHandle, method: &str, raw: Value) -> Result<Value, (i64, String)> {
+    let failed = |e: String| (COMMAND_FAILED, e);
+    match method {
+        "start" => {
+            let p: StartParams = params(raw)?;
+            let path = match p.path {
+                Some(path) => path,
+                None => app
+                    .path()
+                    .app_config_dir()
+                    .map_err(|e| failed(e.to_string()))?
+                    .join("config.json")
+                    .to_string_lossy()
+                    .into_owned(),
+            };
+            crate::core::start(app.clone(), path, p.mode, None)
+                .await
+                .map(|()| Value::Null)
+                .map_err(failed)
+        }
+        "stop" => crate::core::stop(app.clone())
+            .await
+            .map(|()| Value::Null)
+            .map_err(failed),
+        "reload_config" => crate::core::reload_config(app.clone())
+            .await
+            .map(Value::String)
+            .map_err(failed),
+        "get_engine_state" => Ok(json!(crate::core::get_engine_state(app.clone()))),
+        "prestart_check" => {
+            let p: PortParams = params(raw)?;
+            let app = app.clone();
+            // Shells out to lsof / netstat — keep it off the async workers.
+            let result = tokio::task::spawn_blocking(move || {
+                crate::commands::prestart::prestart_check(app, p.port)
+            })
+            .await
+            .map_err(|e| failed(e.to_string()))?;
+            Ok(json!(result))
+        }
+        "kill_orphans" => {
+            let p: PortParams = params(raw)?;
+            let app = app.clone();
+            let result = tokio::task::spawn_blocking(move || {
+                crate::commands::prestart::kill_orphans(app, p.port)
+            })
+            .await
+            .map_err(|e| failed(e.to_string()))?;
+            Ok(json!(result))
+        }
+        other => Err((METHOD_NOT_FOUND, format!("method not found: {}", other))),
+    }
+}
+
+fn success(id: Value, result: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "result": result, "id": id })
+}
+
+fn error(id: Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "error": { "code": code, "message": message }, "id": id })
+}
+
+fn notification(method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params })
+}
+
+#[cfg(test)]
+mod control_tests {
+    use super::*;
+
+    #[test]
+    fn parse_request_accepts_valid_call() {
+        let req = parse_request(r#"{"jsonrpc":"2.0","method":"stop","id":7}"#).unwrap();
+        assert_eq!(req.method, "stop");
+        assert_eq!(req.id, Some(json!(7)));
+        assert!(req.params.is_null());
+    }
+
+    #[test]
+    fn parse_request_rejects_garbage_and_wrong_version() {
+        let err = parse_request("not json").unwrap_err();
+        assert_eq!(err["error"]["code"], PARSE_ERROR);
+        assert_eq!(err["id"], Value::Null);
+
+        let err = parse_request(r#"{"jsonrpc":"1.0","method":"stop","id":"a"}"#).unwrap_err();
+        assert_eq!(err["error"]["code"], INVALID_REQUEST);
+        assert_eq!(err["id"], "a");
+
+        let err = parse_request(r#"{"jsonrpc":"2.0","id":1}"#).unwrap_err();
+        assert_eq!(err["error"]["code"], INVALID_REQUEST);
+    }
+
+    #[test]
+    fn start_params_default_and_parse_mode() {
+        let p: StartParams = params(Value::Null).unwrap();
+        assert_eq!(p.mode, ProxyMode::SystemProxy);
+        assert!(p.path.is_none());
+
+        let p: StartParams = params(json!({"mode": "TunProxy", "path": "/c.json"})).unwrap();
+        assert_eq!(p.mode, ProxyMode::TunProxy);
+        assert_eq!(p.path.as_deref(), Some("/c.json"));
+
+        let err = params::<StartParams>(json!({"mode": "bogus"}))
+            .err()
+            .unwrap();
+        assert_eq!(err.0, INVALID_PARAMS);
+    }
+}
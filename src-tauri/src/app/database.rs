@@ -26,11 +26,154 @@ CREATE TABLE subscription_configs (
 PRAGMA foreign_keys = ON;
 "#;
 
+// 引擎状态迁移日志：state_machine::transition 每次成功迁移写入一行，
+// 由 engine::journal 负责写入与查询。
+const SQL_2: &str = r#"
+CREATE TABLE engine_state_journal (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    at INTEGER NOT NULL,              -- 迁移提交时间(Unix 秒)，即新状态的 since
+    from_kind TEXT NOT NULL,          -- 迁移前状态: idle/starting/running/degraded/stopping/failed/recovering
+    to_kind TEXT NOT NULL,            -- 迁移后状态
+    mode TEXT,                        -- tun / mixed，取迁移后状态的模式；迁移后无模式(idle/stopping/failed/recovering)时取迁移前的
+    epoch INTEGER NOT NULL,           -- 迁移后的 epoch
+    reason TEXT,                      -- 原因，仅 failed/degraded/recovering 有值
+    config_hash TEXT,                 -- 当前配置文件 sha256
+    trigger_source TEXT NOT NULL      -- user / watchdog / network-up / wake / engine / recovery / adopt
+);
+CREATE INDEX idx_engine_state_journal_at ON engine_state_journal (at);
+"#;
+
+// 订阅本地用量计量：commands::usage 按订阅 identifier + 本地日期累加
+// sing-box 实际经过的字节数；刷新订阅时与 subscription-userinfo 头对账，
+// 对账快照写入 subscription_usage_sync。
+const SQL_3: &str = r#"
+CREATE TABLE subscription_usage_daily (
+    identifier TEXT NOT NULL,         -- 对应subscriptions表的identifier
+    day TEXT NOT NULL,                -- 本地日期 YYYY-MM-DD
+    upload INTEGER NOT NULL DEFAULT 0,
+    download INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (identifier, day)
+);
+CREATE TABLE subscription_usage_sync (
+    identifier TEXT PRIMARY KEY,      -- 对应subscriptions表的identifier
+    provider_used INTEGER NOT NULL,   -- 服务商报告的已用流量(upload + download)
+    provider_total INTEGER NOT NULL,  -- 服务商报告的总流量
+    expire_time INTEGER NOT NULL,     -- 服务商报告的过期时间(Unix 秒)，0 表示未知
+    local_total INTEGER NOT NULL,     -- 对账时本地累计计量的字节数
+    synced_at INTEGER NOT NULL        -- 对账时间(Unix 秒)
+);
+"#;
+
+// 订阅后台自动更新：commands::subscription_refresh 按订阅各自的间隔刷新，
+// NULL 表示沿用全局默认间隔，0 表示该订阅不自动更新。
+const SQL_4: &str = r#"
+ALTER TABLE subscriptions ADD COLUMN refresh_interval INTEGER;  -- 自动更新间隔(秒)
+"#;
+
+// 订阅配置历史版本：commands::config_history 在每次成功拉取后写入，
+// 每个订阅只保留最近若干个版本，用于对比与回滚。
+const SQL_5: &str = r#"
+CREATE TABLE subscription_config_versions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    identifier TEXT NOT NULL,         -- 对应subscriptions表的identifier
+    version INTEGER NOT NULL,         -- 每个订阅内递增的版本号
+    sha256 TEXT NOT NULL,             -- 配置内容的 sha256
+    config_content TEXT NOT NULL,     -- 配置内容(JSON)
+    headers TEXT,                     -- 拉取时的 HTTP 响应头(JSON)，初始快照为空
+    fetched_at INTEGER NOT NULL,      -- 拉取时间(Unix 秒)
+    UNIQUE (identifier, version),
+    FOREIGN KEY (identifier) REFERENCES subscriptions(identifier) ON DELETE CASCADE
+);
+"#;
+
+// 订阅回滚固定：commands::config_history::rollback_subscription 记录回滚到的版本，
+// 非 NULL 时后台自动更新跳过该订阅，直到用户解除固定。
+const SQL_6: &str = r#"
+ALTER TABLE subscriptions ADD COLUMN pinned_version INTEGER;  -- 回滚固定的版本号，NULL 表示未固定
+"#;
+
 pub fn get_migrations() -> Vec<Migration> {
-    vec![Migration {
-        version: 1,
-        description: "create_initial_tables",
-        sql: SQL_1,
-        kind: MigrationKind::Up,
-    }]
+    vec![
+        Migration {
+            version: 1,
+            description: "create_initial_tables",
+            sql: SQL_1,
+            kind: MigrationKind::Up,
+        },
+        Migration {
+            version: 2,
+            description: "create_engine_state_journal",
+            sql: SQL_2,
+            kind: MigrationKind::Up,
+        },
+        Migration {
+            version: 3,
+            description: "create_subscription_usage",
+            sql: SQL_3,
+            kind: MigrationKind::Up,
+        },
+        Migration {
+            version: 4,
+            description: "add_subscription_refresh_interval",
+            sql: SQL_4,
+            kind: MigrationKind::Up,
+        },
+        Migration {
+            version: 5,
+            description: "create_subscription_config_versions",
+            sql: SQL_5,
+            kind: MigrationKind::Up,
+        },
+        Migration {
+            version: 6,
+            description: "add_subscription_pinned_version",
+            sql: SQL_6,
+            kind: MigrationKind::Up,
+        },
+    ]
+}
+
+/// Database URL registered with `tauri_plugin_sql` (see `tauri.conf.json`
+/// `plugins.sql.preload`). The frontend opens the same URL.
+pub const DB_URL: &str = "sqlite:data.db";
+
+/// Rust-side handle onto the pool the SQL plugin opened (and migrated) for
+/// the frontend, so both sides share one connection pool.
+pub async fn sqlite_pool(app: &tauri::AppHandle) -> Result<sqlx::SqlitePool, String> {
+    use tauri::Manager;
+    use tauri_plugin_sql::{DbInstances, DbPool};
+
+    let instances = app
+        .try_state::<DbInstances>()
+        .ok_or_else(|| "sql plugin not initialised".to_string())?;
+    let instances = instances.0.read().await;
+    match instances.get(DB_URL) {
+        Some(DbPool::Sqlite(pool)) => Ok(pool.clone()),
+        #[allow(unreachable_patterns)]
+        Some(_) => Err(format!("{} is not a sqlite pool", DB_URL)),
+        None => Err(format!("{} not loaded", DB_URL)),
+    }
+}
+
+/// The same database opened from `config_dir` (where the SQL plugin keeps
+/// it), for the headless CLI, which has no `AppHandle`. Never creates or
+/// migrates it: that stays with the GUI.
+pub async fn sqlite_pool_at(config_dir: &std::path::Path) -> Result<sqlx::SqlitePool, String> {
+    let file = DB_URL.trim_start_matches("sqlite:");
+    let options = sqlx::sqlite::SqliteConnectOptions::new()
+        .filename(config_dir.join(file))
+        .create_if_missing(false);
+    sqlx::SqlitePool::connect_with(options)
+        .await
+        .map_err(|e| format!("{}: {}", DB_URL, e))
+}
+
+/// In-memory database with every migration applied, for tests.
+#[cfg(test)]
+pub(crate) async fn test_pool() -> sqlx::SqlitePool {
+    let pool = sqlx::SqlitePool::connect("sqlite::memory:").await.unwrap();
+    for migration in get_migrations() {
+        sqlx::raw_sql(migration.sql).execute(&pool).await.unwrap();
+    }
+    pool
 }
@@ -1,23 +1,8 @@
 use tauri::{AppHandle, Manager, RunEvent, Window, WindowEvent};
 
-use crate::utils::show_dashboard;
-
-/// Builder::on_menu_event 处理器
+/// Builder::on_menu_event 处理器（托盘菜单由 `app::tray` 构建和处理）
 pub fn on_menu_event(app: &AppHandle, event: tauri::menu::MenuEvent) {
-    match event.id.as_ref() {
-        "show" => {
-            show_dashboard(app.clone());
-        }
-        "quit" => {
-            crate::commands::shell::sync_quit(app.clone());
-        }
-        "enable" => {
-            // 已在前端处理，此处略过
-        }
-        id => {
-            log::warn!("menu item {:?} not handled", id);
-        }
-    }
+    crate::app::tray::on_menu_event(app, event.id.as_ref());
 }
 
 /// Builder::on_window_event 处理器
@@ -71,7 +56,7 @@ pub fn on_run_event(app_handle: &AppHandle, event: RunEvent) {
         }
         // Tauri/WRY 已完成 delegate 安装，此时再安装 SentinelDelegate
         // 可以确保 applicationShouldTerminate: 能正确拦截关机事件。
-        #[cfg(any(target_os = "windows", target_os = "macos"))]
+        #[cfg(any(target_os = "windows", target_os = "macos", target_os = "linux"))]
         RunEvent::Ready => {
             crate::app::setup::spawn_lifecycle_listener(app_handle);
         }
@@ -84,6 +69,7 @@ pub fn on_run_event(app_handle: &AppHandle, event: RunEvent) {
             use crate::engine::cleanup_on_shutdown;
             log::info!("[exit] RunEvent::Exit fired, performing final proxy cleanup");
             cleanup_on_shutdown();
+            crate::core::instances::stop_all();
         }
         _ => {
             #[cfg(not(target_os = "macos"))]
@@ -3,8 +3,10 @@
 //! logic — they are the glue between the `tauri::Builder` and the rest
 //! of the codebase (`core`, `engine`, `commands`).
 
+pub mod control;
 pub mod database;
 pub mod events;
 pub mod plugins;
 pub mod setup;
 pub mod state;
+pub mod tray;
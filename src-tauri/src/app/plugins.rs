@@ -29,24 +29,7 @@ pub fn register_plugins(builder: Builder<Wry>, migrations: Vec<Migration>) -> Bu
             },
         ))
         .plugin(tauri_plugin_deep_link::init())
-        .plugin({
-            let targets = ["oneoh_sing_box_lib", "tauri_plugin_deep_link"];
-            tauri_plugin_log::Builder::new()
-                .filter(move |metadata| {
-                    targets
-                        .iter()
-                        .any(|&target| metadata.target().starts_with(target))
-                })
-                .level(LevelFilter::Info)
-                .timezone_strategy(TimezoneStrategy::UseLocal)
-                .max_file_size(ONEBOX_LOG_MAX_FILE_SIZE)
-                .rotation_strategy(RotationStrategy::KeepAll)
-                .targets([
-                    Target::new(TargetKind::Stdout),
-                    Target::new(TargetKind::LogDir { file_name: None }),
-                ])
-                .build()
-        })
+        .plugin(log_plugin())
         .plugin(tauri_plugin_clipboard_manager::init())
         .plugin(tauri_plugin_http::init())
         .plugin(tauri_plugin_fs::init())
@@ -56,7 +39,7 @@ pub fn register_plugins(builder: Builder<Wry>, migrations: Vec<Migration>) -> Bu
         .plugin(tauri_plugin_http::init())
         .plugin(
             tauri_plugin_sql::Builder::default()
-                .add_migrations("sqlite:data.db", migrations)
+                .add_migrations(crate::app::database::DB_URL, migrations)
                 .build(),
         )
         .plugin(tauri_plugin_process::init())
@@ -68,6 +51,25 @@ pub fn register_plugins(builder: Builder<Wry>, migrations: Vec<Migration>) -> Bu
         .plugin(tauri_plugin_opener::init())
 }
 
+fn log_plugin() -> tauri::plugin::TauriPlugin<Wry> {
+    let targets = ["oneoh_sing_box_lib", "tauri_plugin_deep_link"];
+    tauri_plugin_log::Builder::new()
+        .filter(move |metadata| {
+            targets
+                .iter()
+                .any(|&target| metadata.target().starts_with(target))
+        })
+        .level(LevelFilter::Info)
+        .timezone_strategy(TimezoneStrategy::UseLocal)
+        .max_file_size(ONEBOX_LOG_MAX_FILE_SIZE)
+        .rotation_strategy(RotationStrategy::KeepAll)
+        .targets([
+            Target::new(TargetKind::Stdout),
+            Target::new(TargetKind::LogDir { file_name: None }),
+        ])
+        .build()
+}
+
 fn show_window(app: &AppHandle) {
     let windows = app.webview_windows();
 
@@ -34,7 +34,12 @@ pub fn app_setup(app: &mut tauri::App) -> Result<(), Box<dyn std::error::Error>>
 
     app.manage(crate::app::state::AppData::new());
     app.manage(crate::engine::state_machine::EngineStateCell::new());
-    stop_orphan_tun_service_on_startup();
+    crate::engine::journal::spawn_journal_writer(app.handle().clone());
+    crate::engine::health::spawn_health_monitor(app.handle().clone());
+    crate::engine::proxy_guard::spawn_proxy_guard(app.handle().clone());
+    crate::core::traffic::spawn_traffic_stats(app.handle().clone());
+    crate::commands::usage::spawn_usage_meter(app.handle().clone());
+    reconcile_previous_session(app.handle().clone());
 
     // Purge must run before copy_database_files so the resource-bundled v2 defaults
     // are not clobbered by a later v1 cleanup pass.
@@ -50,6 +55,12 @@ pub fn app_setup(app: &mut tauri::App) -> Result<(), Box<dyn std::error::Error>>
     report_captive(app);
 
     crate::commands::whitelist::spawn_whitelist_refresh_task(app.handle().clone());
+    crate::commands::subscription_refresh::spawn_subscription_refresh_task(app.handle().clone());
+    crate::app::control::spawn_control_server(app.handle().clone());
+    #[cfg(desktop)]
+    if let Err(e) = crate::app::tray::setup_tray(app.handle()) {
+        log::error!("Failed to create tray icon: {}", e);
+    }
     report_main_window_geometry(app);
 
     // macOS：以无 Dock 图标的附件模式运行，启动时直接显示主窗口
@@ -119,6 +130,19 @@ pub fn app_setup(app: &mut tauri::App) -> Result<(), Box<dyn std::error::Error>>
     Ok(())
 }
 
+/// Adopt or clean up whatever engine session a previous GUI process left
+/// behind (`engine::runtime_record`). A TUN service still running without
+/// a record to adopt it by is stopped as before.
+fn reconcile_previous_session(app: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let adopted = crate::engine::runtime_record::reconcile(&app).await;
+        if adopted != Some(crate::engine::ProxyMode::TunProxy) {
+            stop_orphan_tun_service_on_startup();
+        }
+        crate::engine::sysproxy::recover_stale_proxy(&app);
+    });
+}
+
 #[cfg(target_os = "windows")]
 fn stop_orphan_tun_service_on_startup() {
     use tun_service::scm::{self, QueriedState};
@@ -370,26 +394,28 @@ fn report_captive(app: &tauri::App) {
 // ── Lifecycle ──────────────────────────────────────────────────────
 
 // 断网时长低于此值视为短暂抖动，不触发重启
-#[cfg(any(target_os = "windows", target_os = "macos"))]
+#[cfg(any(target_os = "windows", target_os = "macos", target_os = "linux"))]
 const MIN_OUTAGE: std::time::Duration = std::time::Duration::from_secs(2);
 // NetworkUp / DidWake 后等待此时长确认系统稳定，再执行重启
-#[cfg(any(target_os = "windows", target_os = "macos"))]
+#[cfg(any(target_os = "windows", target_os = "macos", target_os = "linux"))]
 const DEBOUNCE_SECS: u64 = 3;
 // 睡眠时长 >= 此值才触发 wake 重启。30s 足以过滤"临时锁屏-解锁"
 // 但会覆盖"开会合盖几分钟"这种真实场景。
-#[cfg(any(target_os = "windows", target_os = "macos"))]
+#[cfg(any(target_os = "windows", target_os = "macos", target_os = "linux"))]
 const WAKE_RESTART_THRESHOLD: std::time::Duration = std::time::Duration::from_secs(30);
 
 /// 调度引擎重启：DEBOUNCE_SECS 秒后若 epoch 未变则 stop + start。
-/// NetworkUp / DidWake 共用此路径，`ctx` 仅用于日志前缀区分触发源。
+/// NetworkUp / DidWake 共用此路径，`ctx` 仅用于日志前缀区分触发源，
+/// `trigger` 记入引擎状态日志（engine::journal）。
 ///
 /// 调用方负责在调度前 `fetch_add(1)` 自增 epoch（幂等取消：后来的调度
 /// 让之前已排队的任务读到不同 epoch，自动放弃）。
-#[cfg(any(target_os = "windows", target_os = "macos"))]
+#[cfg(any(target_os = "windows", target_os = "macos", target_os = "linux"))]
 fn schedule_engine_restart(
     handle: tauri::AppHandle,
     epoch_arc: std::sync::Arc<std::sync::atomic::AtomicU64>,
     ctx: &'static str,
+    trigger: crate::engine::state_machine::Trigger,
 ) {
     let current_epoch = epoch_arc.load(std::sync::atomic::Ordering::Relaxed);
     tauri::async_runtime::spawn(async move {
@@ -402,51 +428,178 @@ fn schedule_engine_restart(
             return;
         };
         log::info!("[{ctx}] restarting engine (mode: {:?})", mode);
-        if let Err(e) = crate::core::stop(handle.clone()).await {
-            log::error!("[{ctx}] stop engine failed: {}", e);
-        } else if let Err(e) = crate::core::start(handle, path, mode).await {
-            log::error!("[{ctx}] restart engine failed: {}", e);
-        } else {
-            log::info!("[{ctx}] engine restarted");
-        }
+        crate::engine::state_machine::with_trigger(trigger, async move {
+            if let Err(e) = crate::core::stop(handle.clone()).await {
+                log::error!("[{ctx}] stop engine failed: {}", e);
+            } else if let Err(e) = crate::core::start(handle, path, mode, None).await {
+                log::error!("[{ctx}] restart engine failed: {}", e);
+            } else {
+                log::info!("[{ctx}] engine restarted");
+            }
+        })
+        .await;
     });
 }
 
-/// 生命周期事件监听：仅 Windows / macOS 支持。
+/// 网络 / 睡眠事件的共享处理。事件源按平台不同：Windows / macOS 来自
+/// `onebox_lifecycle`，Linux 来自 `engine::linux::lifecycle`
+/// （NetworkManager / rtnetlink / logind）。
+///
+/// 网络恢复重启：防抖 + 最小断网时长双重过滤
+///
+/// epoch：每次 NetworkDown 自增，用于取消正在等待的重启任务（无锁取消）。
+/// network_down_at：记录断网墙钟时间，过滤短暂抖动（< MIN_OUTAGE）。
+///
+/// 策略：
+///   NetworkDown → epoch++，记录断网时间，取消已排队的重启
+///   NetworkUp   → 若断网时长 < MIN_OUTAGE 则跳过（短暂抖动）
+///                 否则等待 DEBOUNCE_SECS 秒确认网络稳定，期间若再次断网
+///                 则 epoch 已变，任务自动放弃，不会触发重启
+#[cfg(any(target_os = "windows", target_os = "macos", target_os = "linux"))]
+struct LifecycleHandler {
+    handle: tauri::AppHandle,
+    network_restart_epoch: std::sync::Arc<std::sync::atomic::AtomicU64>,
+    network_down_at: Option<std::time::SystemTime>,
+    // WillSleep 墙钟时间。DidWake 时与此值对比判断是否需要重启引擎。
+    // NWPathMonitor 在睡眠期间挂起且带 satisfied 去重，Wi-Fi
+    // 不 drop 的场景（Power Nap / 电源常连）唤醒后不会补发任何事件，
+    // 恢复链路完全断在这里——所以不能只依赖 NetworkUp。
+    will_sleep_at: Option<std::time::SystemTime>,
+}
+
+#[cfg(any(target_os = "windows", target_os = "macos", target_os = "linux"))]
+impl LifecycleHandler {
+    fn new(handle: tauri::AppHandle) -> Self {
+        Self {
+            handle,
+            network_restart_epoch: std::sync::Arc::new(std::sync::atomic::AtomicU64::new(0)),
+            network_down_at: None,
+            will_sleep_at: None,
+        }
+    }
+
+    fn will_sleep(&mut self) {
+        log::info!("[wake] WillSleep");
+        self.will_sleep_at = Some(std::time::SystemTime::now());
+    }
+
+    fn did_wake(&mut self) {
+        let sleep_dur = self
+            .will_sleep_at
+            .take()
+            .and_then(|t| t.elapsed().ok())
+            .unwrap_or_default();
+        log::info!("[wake] DidWake — slept {:.1}s", sleep_dur.as_secs_f32());
+
+        // 幂等地刷一次 TUN DNS。睡眠期间 mDNSResponder 可能已被
+        // 系统回写为 DHCP 下发的服务器；这一次调用在非 TUN 模式
+        // 下是 no-op（见 on_network_up 里的 mode gate）。
+        use crate::engine::{EngineManager, PlatformEngine};
+        PlatformEngine::on_network_up(&self.handle);
+
+        if sleep_dur < WAKE_RESTART_THRESHOLD {
+            log::info!(
+                "[wake] sleep {:.1}s < threshold, skipping restart",
+                sleep_dur.as_secs_f32()
+            );
+            return;
+        }
+
+        // 走和 NetworkUp 同一套 epoch + debounce：若期间又发
+        // NetworkDown/NetworkUp，epoch 自增会让本任务自动放弃。
+        self.network_restart_epoch
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        log::info!(
+            "[wake] sleep {:.1}s — scheduling engine restart in {}s",
+            sleep_dur.as_secs_f32(),
+            DEBOUNCE_SECS
+        );
+        schedule_engine_restart(
+            self.handle.clone(),
+            std::sync::Arc::clone(&self.network_restart_epoch),
+            "wake",
+            crate::engine::state_machine::Trigger::Wake,
+        );
+    }
+
+    fn network_down(&mut self) {
+        log::info!("[network] NetworkDown — cancelling any pending engine restart");
+        self.network_restart_epoch
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        self.network_down_at = Some(std::time::SystemTime::now());
+        // Release Setup DNS so OS-native captive detection on
+        // the next NetworkUp has a clean State layer to probe.
+        // macOS-only; Windows/Linux use trait default no-op.
+        // See docs/claude/dns-override.md.
+        use crate::engine::{EngineManager, PlatformEngine};
+        PlatformEngine::on_network_down(&self.handle);
+    }
+
+    fn network_up(&mut self) {
+        log::info!("[network] NetworkUp");
+        // 立即重设 TUN DNS —— 幂等操作,无需防抖。Wi-Fi 切换后系统
+        // 会把活动接口 DNS 重置回 DHCP 下发的服务器,哪怕后续的
+        // engine 重启被 MIN_OUTAGE 过滤掉,这一步仍然保证 DNS 继续
+        // 指向 TUN 网关。
+        //
+        // 延迟 1s 再做一次,兜底系统在 NetworkUp 事件之后的"慢一拍"
+        // DNS 写入(DHCP 续租、IPv6 RA、NetworkManager dispatcher 等)。
+        use crate::engine::{EngineManager, PlatformEngine};
+        PlatformEngine::on_network_up(&self.handle);
+        let handle_for_retry = self.handle.clone();
+        tauri::async_runtime::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+            PlatformEngine::on_network_up(&handle_for_retry);
+            // 按网络区分的绕过规则：网络已稳定，按新的 SSID/网关重算。
+            crate::engine::proxy_bypass::refresh_for_network_change(&handle_for_retry).await;
+        });
+        let down_at = match self.network_down_at.take() {
+            Some(t) => t,
+            // 初始快照就是 Up（应用刚启动时网络正常），忽略
+            None => return,
+        };
+        let outage = down_at.elapsed().unwrap_or_default();
+        if outage < MIN_OUTAGE {
+            log::info!(
+                "[network] outage {:.1}s < threshold, skipping restart",
+                outage.as_secs_f32()
+            );
+            return;
+        }
+        log::info!(
+            "[network] outage {:.1}s — scheduling engine restart in {}s",
+            outage.as_secs_f32(),
+            DEBOUNCE_SECS
+        );
+        // 取消可能被 DidWake 预先排的 wake 重启——epoch 自增一次
+        // 后新旧两个已排队任务中只有我们刚刚捕获的那个能通过检查。
+        self.network_restart_epoch
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        schedule_engine_restart(
+            self.handle.clone(),
+            std::sync::Arc::clone(&self.network_restart_epoch),
+            "network",
+            crate::engine::state_machine::Trigger::NetworkUp,
+        );
+    }
+}
+
+/// 生命周期事件监听（Windows / macOS）。
 ///
 /// **macOS**：必须在 `RunEvent::Ready` 时调用，确保 delegate 安装在 Tauri/WRY 之后，
 /// 不会被覆盖。
 #[cfg(any(target_os = "windows", target_os = "macos"))]
 pub(crate) fn spawn_lifecycle_listener(app_handle: &tauri::AppHandle) {
-    let handle = app_handle.clone();
+    let mut handler = LifecycleHandler::new(app_handle.clone());
 
     let rx = onebox_lifecycle::Sentinel::start().into_receiver();
 
     std::thread::Builder::new()
         .name("lifecycle-events".into())
         .spawn(move || {
-            // 网络恢复重启：防抖 + 最小断网时长双重过滤
-            //
-            // epoch：每次 NetworkDown 自增，用于取消正在等待的重启任务（无锁取消）。
-            // network_down_at：记录断网墙钟时间，过滤短暂抖动（< MIN_OUTAGE）。
-            //
-            // 策略：
-            //   NetworkDown → epoch++，记录断网时间，取消已排队的重启
-            //   NetworkUp   → 若断网时长 < MIN_OUTAGE 则跳过（短暂抖动）
-            //                 否则等待 DEBOUNCE_SECS 秒确认网络稳定，期间若再次断网
-            //                 则 epoch 已变，任务自动放弃，不会触发重启
-            //
             // Windows 7 / 8 / 8.1：NotifyNetworkConnectivityHintChange 不可用，
             // lifecycle 库不会产生任何 NetworkUp / NetworkDown 事件，
-            // 以下逻辑永远不会被触发，行为与未启用 network feature 时完全相同。
-            let network_restart_epoch = std::sync::Arc::new(std::sync::atomic::AtomicU64::new(0));
-            let mut network_down_at: Option<std::time::SystemTime> = None;
-            // WillSleep 墙钟时间。DidWake 时与此值对比判断是否需要重启引擎。
-            // NWPathMonitor 在睡眠期间挂起且带 satisfied 去重，Wi-Fi
-            // 不 drop 的场景（Power Nap / 电源常连）唤醒后不会补发任何事件，
-            // 恢复链路完全断在这里——所以不能只依赖 NetworkUp。
-            let mut will_sleep_at: Option<std::time::SystemTime> = None;
-
+            // 网络恢复重启逻辑永远不会被触发，行为与未启用 network feature 时完全相同。
             while let Some(event) = rx.recv() {
                 use onebox_lifecycle::SystemEvent;
                 match event {
@@ -456,99 +609,10 @@ pub(crate) fn spawn_lifecycle_listener(app_handle: &tauri::AppHandle) {
                     SystemEvent::WillPowerOff => {
                         handle_will_power_off();
                     }
-                    SystemEvent::WillSleep => {
-                        log::info!("[wake] WillSleep");
-                        will_sleep_at = Some(std::time::SystemTime::now());
-                    }
-                    SystemEvent::DidWake => {
-                        let sleep_dur = will_sleep_at
-                            .take()
-                            .and_then(|t| t.elapsed().ok())
-                            .unwrap_or_default();
-                        log::info!("[wake] DidWake — slept {:.1}s", sleep_dur.as_secs_f32());
-
-                        // 幂等地刷一次 TUN DNS。睡眠期间 mDNSResponder 可能已被
-                        // 系统回写为 DHCP 下发的服务器；这一次调用在非 TUN 模式
-                        // 下是 no-op（见 on_network_up 里的 mode gate）。
-                        use crate::engine::{EngineManager, PlatformEngine};
-                        PlatformEngine::on_network_up(&handle);
-
-                        if sleep_dur < WAKE_RESTART_THRESHOLD {
-                            log::info!(
-                                "[wake] sleep {:.1}s < threshold, skipping restart",
-                                sleep_dur.as_secs_f32()
-                            );
-                            continue;
-                        }
-
-                        // 走和 NetworkUp 同一套 epoch + debounce：若期间又发
-                        // NetworkDown/NetworkUp，epoch 自增会让本任务自动放弃。
-                        network_restart_epoch.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                        log::info!(
-                            "[wake] sleep {:.1}s — scheduling engine restart in {}s",
-                            sleep_dur.as_secs_f32(),
-                            DEBOUNCE_SECS
-                        );
-                        schedule_engine_restart(
-                            handle.clone(),
-                            std::sync::Arc::clone(&network_restart_epoch),
-                            "wake",
-                        );
-                    }
-                    SystemEvent::NetworkDown => {
-                        log::info!("[network] NetworkDown — cancelling any pending engine restart");
-                        network_restart_epoch.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                        network_down_at = Some(std::time::SystemTime::now());
-                        // Release Setup DNS so OS-native captive detection on
-                        // the next NetworkUp has a clean State layer to probe.
-                        // macOS-only; Windows/Linux use trait default no-op.
-                        // See docs/claude/dns-override.md.
-                        use crate::engine::{EngineManager, PlatformEngine};
-                        PlatformEngine::on_network_down(&handle);
-                    }
-                    SystemEvent::NetworkUp => {
-                        log::info!("[network] NetworkUp");
-                        // 立即重设 TUN DNS —— 幂等操作,无需防抖。Wi-Fi 切换后系统
-                        // 会把活动接口 DNS 重置回 DHCP 下发的服务器,哪怕后续的
-                        // engine 重启被 MIN_OUTAGE 过滤掉,这一步仍然保证 DNS 继续
-                        // 指向 TUN 网关。
-                        //
-                        // 延迟 1s 再做一次,兜底系统在 NetworkUp 事件之后的"慢一拍"
-                        // DNS 写入(DHCP 续租、IPv6 RA、NetworkManager dispatcher 等)。
-                        use crate::engine::{EngineManager, PlatformEngine};
-                        PlatformEngine::on_network_up(&handle);
-                        let handle_for_retry = handle.clone();
-                        tauri::async_runtime::spawn(async move {
-                            tokio::time::sleep(std::time::Duration::from_secs(1)).await;
-                            PlatformEngine::on_network_up(&handle_for_retry);
-                        });
-                        let down_at = match network_down_at.take() {
-                            Some(t) => t,
-                            // 初始快照就是 Up（应用刚启动时网络正常），忽略
-                            None => continue,
-                        };
-                        let outage = down_at.elapsed().unwrap_or_default();
-                        if outage < MIN_OUTAGE {
-                            log::info!(
-                                "[network] outage {:.1}s < threshold, skipping restart",
-                                outage.as_secs_f32()
-                            );
-                            continue;
-                        }
-                        log::info!(
-                            "[network] outage {:.1}s — scheduling engine restart in {}s",
-                            outage.as_secs_f32(),
-                            DEBOUNCE_SECS
-                        );
-                        // 取消可能被 DidWake 预先排的 wake 重启——epoch 自增一次
-                        // 后新旧两个已排队任务中只有我们刚刚捕获的那个能通过检查。
-                        network_restart_epoch.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                        schedule_engine_restart(
-                            handle.clone(),
-                            std::sync::Arc::clone(&network_restart_epoch),
-                            "network",
-                        );
-                    }
+                    SystemEvent::WillSleep => handler.will_sleep(),
+                    SystemEvent::DidWake => handler.did_wake(),
+                    SystemEvent::NetworkDown => handler.network_down(),
+                    SystemEvent::NetworkUp => handler.network_up(),
                     _ => {}
                 }
             }
@@ -556,6 +620,30 @@ pub(crate) fn spawn_lifecycle_listener(app_handle: &tauri::AppHandle) {
         .expect("failed to spawn lifecycle thread");
 }
 
+/// 生命周期事件监听（Linux）。关机 / 注销由 `RunEvent::Exit` 兜底，
+/// 这里只有网络与睡眠事件。
+#[cfg(target_os = "linux")]
+pub(crate) fn spawn_lifecycle_listener(app_handle: &tauri::AppHandle) {
+    let mut handler = LifecycleHandler::new(app_handle.clone());
+
+    let rx = crate::engine::linux::lifecycle::start();
+
+    std::thread::Builder::new()
+        .name("lifecycle-events".into())
+        .spawn(move || {
+            use crate::engine::linux::lifecycle::LifecycleEvent;
+            for event in rx {
+                match event {
+                    LifecycleEvent::WillSleep => handler.will_sleep(),
+                    LifecycleEvent::DidWake => handler.did_wake(),
+                    LifecycleEvent::NetworkDown => handler.network_down(),
+                    LifecycleEvent::NetworkUp => handler.network_up(),
+                }
+            }
+        })
+        .expect("failed to spawn lifecycle thread");
+}
+
 #[cfg(any(target_os = "windows", target_os = "macos"))]
 fn handle_shutting_down(shutdown_handle: onebox_lifecycle::ShutdownHandle) {
     use crate::engine::cleanup_on_shutdown;
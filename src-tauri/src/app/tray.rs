@@ -0,0 +1,867 @@
+//! Rust-owned system tray.
+//!
+//! Built in `app_setup` and rebuilt whenever `EngineState` changes (plus a
+//! slow periodic refresh so node / subscription edits made in the dashboard
+//! show up), so the tray keeps working when the webview never loaded or was
+//! closed. Labels come from the same `lang/*.json` the frontend uses.
+//!
+//! Menu layout:
+//!
+//!   status line (disabled)        ← EngineState, with failure reason
+//!   Dashboard
+//!   Enable Proxy            [✓]   ← start / stop
+//!   Proxy Mode      ▸ System / Manual / TUN
+//!   Switch Config   ▸ one entry per subscription
+//!   <group>         ▸ members of each Clash `Selector` (while running)
+//!   Copy Env
+//!   Developer       ▸ (developer mode only)
+//!   Quit
+//!
+//! Starting, mode and subscription switches need a freshly merged sing-box
+//! config, and the merger lives in the frontend (`src/config/merger`). When
+//! the main webview is up the tray asks it to re-sync via
+//! `tray-sync-request`; without a webview it (re)starts sing-box directly
+//! with the last generated `config.json`, as long as that config fits the
+//! requested mode.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::Serialize;
+use sqlx::Row;
+use tauri::image::Image;
+use tauri::menu::{CheckMenuItem, Menu, MenuItem, PredefinedMenuItem, Submenu};
+use tauri::tray::{TrayIconBuilder, TrayIconEvent};
+use tauri::{AppHandle, Emitter, Manager, Wry};
+use tauri_plugin_clipboard_manager::ClipboardExt;
+use tauri_plugin_store::{Store, StoreExt};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::core::clash::{ClashClient, Proxy};
+use crate::engine::state_machine::{EngineState, EngineStateCell};
+use crate::engine::ProxyMode;
+
+const TRAY_ID: &str = "main";
+const MAIN_WINDOW: &str = "main";
+pub const EVENT_TRAY_SYNC_REQUEST: &str = "tray-sync-request";
+const REFRESH_INTERVAL: Duration = Duration::from_secs(15);
+const CLASH_TIMEOUT: Duration = Duration::from_secs(1);
+/// Separates group and member in a node item id. Not expected in names.
+const NODE_ID_SEPARATOR: char = '\u{1f}';
+/// Longest failure / degradation reason shown in the status line.
+const MAX_REASON_CHARS: usize = 60;
+
+const SETTINGS_STORE: &str = "settings.json";
+// Same keys the frontend writes (`src/types/definition.ts`, `src/single/store.ts`).
+const LANGUAGE_STORE_KEY: &str = "language";
+const ENABLE_TUN_STORE_KEY: &str = "enable_tun_key";
+const SKIP_SYSTEM_PROXY_STORE_KEY: &str = "skip_system_proxy_key";
+const SELECTED_SUBSCRIPTION_STORE_KEY: &str = "selected_subscription_identifier";
+const DEVELOPER_TOGGLE_STORE_KEY: &str = "developer_toggle_key";
+
+lazy_static::lazy_static! {
+    static ref LANG_EN: HashMap<String, String> =
+        serde_json::from_str(include_str!("../../../lang/en.json")).unwrap_or_default();
+    static ref LANG_ZH: HashMap<String, String> =
+        serde_json::from_str(include_str!("../../../lang/zh.json")).unwrap_or_default();
+    /// Serializes rebuilds so a slow one can't overwrite a newer menu, and
+    /// holds the model the current menu was built from.
+    static ref REFRESH_LOCK: tokio::sync::Mutex<Option<TrayModel>> =
+        tokio::sync::Mutex::new(None);
+}
+
+/// Translate `key` (`{{name}}` placeholders filled from `params`), falling
+/// back to English, then to the key itself — same rules as the frontend `t`.
+fn t(lang: &str, key: &str, params: &[(&str, &str)]) -> String {
+    let table: &HashMap<String, String> = if lang == "zh" { &LANG_ZH } else { &LANG_EN };
+    let mut text = table
+        .get(key)
+        .or_else(|| LANG_EN.get(key))
+        .cloned()
+        .unwrap_or_else(|| key.to_string());
+    for (name, value) in params {
+        text = text.replace(&format!("{{{{{}}}}}", name), value);
+    }
+    text
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TrayAction {
+    Show,
+    Quit,
+    Toggle,
+    CopyEnv,
+    Mode(ProxyMode),
+    Subscription(String),
+    Node { group: String, member: String },
+    OpenAdvanced,
+    Devtools,
+    OpenLogDir,
+    OpenConfigDir,
+}
+
+fn mode_key(mode: &ProxyMode) -> &'static str {
+    match mode {
+        ProxyMode::SystemProxy => "system",
+        ProxyMode::ManualProxy => "manual",
+        ProxyMode::TunProxy => "tun",
+    }
+}
+
+impl TrayAction {
+    fn id(&self) -> String {
+        match self {
+            TrayAction::Show => "show".into(),
+            TrayAction::Quit => "quit".into(),
+            TrayAction::Toggle => "enable".into(),
+            TrayAction::CopyEnv => "copy_proxy".into(),
+            TrayAction::Mode(mode) => format!("mode:{}", mode_key(mode)),
+            TrayAction::Subscription(identifier) => format!("sub:{}", identifier),
+            TrayAction::Node { group, member } => {
+                format!("node:{}{}{}", group, NODE_ID_SEPARATOR, member)
+            }
+            TrayAction::OpenAdvanced => "open_advanced_settings".into(),
+            TrayAction::Devtools => "devtools".into(),
+            TrayAction::OpenLogDir => "open_log_dir".into(),
+            TrayAction::OpenConfigDir => "open_config_dir".into(),
+        }
+    }
+
+    fn parse(id: &str) -> Option<Self> {
+        if let Some(mode) = id.strip_prefix("mode:") {
+            return match mode {
+                "system" => Some(TrayAction::Mode(ProxyMode::SystemProxy)),
+                "manual" => Some(TrayAction::Mode(ProxyMode::ManualProxy)),
+                "tun" => Some(TrayAction::Mode(ProxyMode::TunProxy)),
+                _ => None,
+            };
+        }
+        if let Some(identifier) = id.strip_prefix("sub:") {
+            return Some(TrayAction::Subscription(identifier.to_string()));
+        }
+        if let Some(rest) = id.strip_prefix("node:") {
+            let (group, member) = rest.split_once(NODE_ID_SEPARATOR)?;
+            return Some(TrayAction::Node {
+                group: group.to_string(),
+                member: member.to_string(),
+            });
+        }
+        match id {
+            "show" => Some(TrayAction::Show),
+            "quit" => Some(TrayAction::Quit),
+            "enable" => Some(TrayAction::Toggle),
+            "copy_proxy" => Some(TrayAction::CopyEnv),
+            "open_advanced_settings" => Some(TrayAction::OpenAdvanced),
+            "devtools" => Some(TrayAction::Devtools),
+            "open_log_dir" => Some(TrayAction::OpenLogDir),
+            "open_config_dir" => Some(TrayAction::OpenConfigDir),
+            _ => None,
+        }
+    }
+}
+
+fn mode_label(lang: &str, mode: &ProxyMode) -> String {
+    match mode {
+        ProxyMode::SystemProxy => t(lang, "menu_mode_system", &[]),
+        ProxyMode::ManualProxy => t(lang, "menu_mode_manual", &[]),
+        ProxyMode::TunProxy => t(lang, "menu_mode_tun", &[]),
+    }
+}
+
+fn short_reason(reason: &str) -> String {
+    if reason.chars().count() <= MAX_REASON_CHARS {
+        return reason.to_string();
+    }
+    let cut: String = reason.chars().take(MAX_REASON_CHARS).collect();
+    format!("{}…", cut)
+}
+
+/// `running_mode` is the mode sing-box was started with, if any.
+fn status_label(lang: &str, state: &EngineState, running_mode: Option<&ProxyMode>) -> String {
+    match state {
+        EngineState::Idle { .. } => t(lang, "menu_status_idle", &[]),
+        EngineState::Starting { .. } => t(lang, "menu_status_starting", &[]),
+        EngineState::Stopping { .. } => t(lang, "menu_status_stopping", &[]),
+        EngineState::Running { mode, .. } => {
+            let label = running_mode
+                .map(|m| mode_label(lang, m))
+                .unwrap_or_else(|| mode.clone());
+            t(lang, "menu_status_running", &[("mode", &label)])
+        }
+        EngineState::Degraded { reason, .. } => t(
+            lang,
+            "menu_status_degraded",
+            &[("reason", &short_reason(reason))],
+        ),
+        EngineState::Failed { reason, .. } => t(
+            lang,
+            "menu_status_failed",
+            &[("reason", &short_reason(reason))],
+        ),
+        EngineState::Recovering { attempt, .. } => t(
+            lang,
+            "menu_status_recovering",
+            &[("attempt", &attempt.to_string())],
+        ),
+    }
+}
+
+/// States in which the toggle means "stop".
+fn is_active(state: &EngineState) -> bool {
+    matches!(
+        state,
+        EngineState::Starting { .. }
+            | EngineState::Running { .. }
+            | EngineState::Degraded { .. }
+            | EngineState::Recovering { .. }
+    )
+}
+
+fn settings_store(app: &AppHandle) -> Option<Arc<Store<Wry>>> {
+    // `store()` (not `get_store()`): the tray may run before, or without,
+    // the webview that normally loads settings.json.
+    app.store(SETTINGS_STORE)
+        .map_err(|e| log::warn!("[tray] settings store unavailable: {}", e))
+        .ok()
+}
+
+fn store_bool(store: &Store<Wry>, key: &str) -> bool {
+    store.get(key).and_then(|v| v.as_bool()).unwrap_or(false)
+}
+
+fn store_string(store: &Store<Wry>, key: &str) -> Option<String> {
+    store
+        .get(key)
+        .and_then(|v| v.as_str().map(str::to_string))
+        .filter(|s| !s.is_empty())
+}
+
+/// Mirror of `vpnServiceManager.start` in `src/utils/helper.ts`.
+fn selected_mode(store: &Store<Wry>) -> ProxyMode {
+    if store_bool(store, ENABLE_TUN_STORE_KEY) {
+        ProxyMode::TunProxy
+    } else if store_bool(store, SKIP_SYSTEM_PROXY_STORE_KEY) {
+        ProxyMode::ManualProxy
+    } else {
+        ProxyMode::SystemProxy
+    }
+}
+
+fn config_path(app: &AppHandle) -> Result<String, String> {
+    app.path()
+        .app_config_dir()
+        .map(|dir| dir.join("config.json").to_string_lossy().into_owned())
+        .map_err(|e| e.to_string())
+}
+
+/// Whether an already generated config can be started in `mode`: TUN needs
+/// a `tun` inbound, the proxy modes need the `mixed` one.
+fn config_supports(config: &serde_json::Value, mode: &ProxyMode) -> bool {
+    match mode {
+        ProxyMode::TunProxy => config
+            .get("inbounds")
+            .and_then(|v| v.as_array())
+            .is_some_and(|inbounds| {
+                inbounds
+                    .iter()
+                    .any(|ib| ib.get("type").and_then(|v| v.as_str()) == Some("tun"))
+            }),
+        ProxyMode::SystemProxy | ProxyMode::ManualProxy => {
+            crate::core::mixed_port_from_config(config).is_some()
+        }
+    }
+}
+
+#[derive(PartialEq)]
+struct TrayModel {
+    lang: String,
+    state: EngineState,
+    running_mode: Option<ProxyMode>,
+    mode: ProxyMode,
+    /// (identifier, name)
+    subscriptions: Vec<(String, String)>,
+    selected: Option<String>,
+    groups: Vec<Proxy>,
+    developer: bool,
+    /// Whether the main webview exists; only it can generate a config.
+    dashboard: bool,
+}
+
+/// User-facing `Selector` groups, sorted by name. sing-box's synthetic
+/// `GLOBAL` selector only matters in global mode and goes last.
+fn selector_groups(proxies: HashMap<String, Proxy>) -> Vec<Proxy> {
+    let mut groups: Vec<Proxy> = proxies
+        .into_values()
+        .filter(|p| p.kind == "Selector" && !p.all.is_empty())
+        // The menu shows no delays; without them an unchanged menu
+        // compares equal across refreshes.
+        .map(|p| Proxy {
+            history: Vec::new(),
+            ..p
+        })
+        .collect();
+    groups.sort_by(|a, b| {
+        (a.name == "GLOBAL")
+            .cmp(&(b.name == "GLOBAL"))
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    groups
+}
+
+async fn load_subscriptions(app: &AppHandle) -> Vec<(String, String)> {
+    let Ok(pool) = crate::app::database::sqlite_pool(app).await else {
+        return Vec::new();
+    };
+    match sqlx::query("SELECT identifier, name FROM subscriptions ORDER BY id")
+        .fetch_all(&pool)
+        .await
+    {
+        Ok(rows) => rows
+            .iter()
+            .filter_map(|row| {
+                let identifier: String = row.try_get("identifier").ok()?;
+                let name: Option<String> = row.try_get("name").ok()?;
+                Some((identifier.clone(), name.unwrap_or(identifier)))
+            })
+            .collect(),
+        Err(e) => {
+            log::warn!("[tray] failed to list subscriptions: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+async fn load_model(app: &AppHandle) -> TrayModel {
+    let state = app.state::<EngineStateCell>().snapshot();
+    let store = settings_store(app);
+    let groups = if state.is_running() {
+        ClashClient::for_app(app)
+            .timeout(CLASH_TIMEOUT)
+            .proxies()
+            .await
+            .map(selector_groups)
+            .unwrap_or_default()
+    } else {
+        Vec::new()
+    };
+    let running_mode = crate::core::ProcessManager::acquire()
+        .mode
+        .as_ref()
+        .map(|m| (**m).clone());
+    TrayModel {
+        lang: store
+            .as_deref()
+            .and_then(|s| store_string(s, LANGUAGE_STORE_KEY))
+            .unwrap_or_else(|| "en".into()),
+        running_mode: running_mode.filter(|_| state.is_running()),
+        state,
+        mode: store.as_deref().map(selected_mode).unwrap_or_default(),
+        subscriptions: load_subscriptions(app).await,
+        selected: store
+            .as_deref()
+            .and_then(|s| store_string(s, SELECTED_SUBSCRIPTION_STORE_KEY)),
+        groups,
+        developer: store
+            .as_deref()
+            .is_some_and(|s| store_bool(s, DEVELOPER_TOGGLE_STORE_KEY)),
+        dashboard: app.get_webview_window(MAIN_WINDOW).is_some(),
+    }
+}
+
+fn item(app: &AppHandle, action: TrayAction, text: &str) -> tauri::Result<MenuItem<Wry>> {
+    MenuItem::with_id(app, action.id(), text, true, None::<&str>)
+}
+
+fn check(
+    app: &AppHandle,
+    action: TrayAction,
+    text: &str,
+    checked: bool,
+) -> tauri::Result<CheckMenuItem<Wry>> {
+    CheckMenuItem::with_id(app, action.id(), text, true, checked, None::<&str>)
+}
+
+fn build_menu(app: &AppHandle, model: &TrayModel) -> tauri::Result<Menu<Wry>> {
+    let lang = model.lang.as_str();
+    let menu = Menu::new(app)?;
+
+    let status = status_label(lang, &model.state, model.running_mode.as_ref());
+    menu.append(&MenuItem::with_id(
+        app,
+        "status",
+        status,
+        false,
+        None::<&str>,
+    )?)?;
+    menu.append(&PredefinedMenuItem::separator(app)?)?;
+    menu.append(&item(
+        app,
+        TrayAction::Show,
+        &t(lang, "menu_dashboard", &[]),
+    )?)?;
+    menu.append(&check(
+        app,
+        TrayAction::Toggle,
+        &t(lang, "menu_enable_proxy", &[]),
+        is_active(&model.state),
+    )?)?;
+
+    let modes = Submenu::new(app, t(lang, "menu_mode", &[]), true)?;
+    for mode in [
+        ProxyMode::SystemProxy,
+        ProxyMode::ManualProxy,
+        ProxyMode::TunProxy,
+    ] {
+        let checked = mode == model.mode;
+        let label = mode_label(lang, &mode);
+        modes.append(&check(app, TrayAction::Mode(mode), &label, checked)?)?;
+    }
+    menu.append(&modes)?;
+
+    // Switching means generating another subscription's config, which only
+    // the dashboard's merger can do.
+    let subs = Submenu::new(
+        app,
+        t(lang, "menu_switch_subscription", &[]),
+        model.dashboard && !model.subscriptions.is_empty(),
+    )?;
+    for (identifier, name) in &model.subscriptions {
+        let checked = model.selected.as_deref() == Some(identifier.as_str());
+        subs.append(&check(
+            app,
+            TrayAction::Subscription(identifier.clone()),
+            name,
+            checked,
+        )?)?;
+    }
+    menu.append(&subs)?;
+
+    if !model.groups.is_empty() {
+        menu.append(&PredefinedMenuItem::separator(app)?)?;
+        for group in &model.groups {
+            let label = match &group.now {
+                Some(now) => format!("{}: {}", group.name, now),
+                None => group.name.clone(),
+            };
+            let sub = Submenu::new(app, label, true)?;
+            for member in &group.all {
+                let checked = group.now.as_deref() == Some(member.as_str());
+                sub.append(&check(
+                    app,
+                    TrayAction::Node {
+                        group: group.name.clone(),
+                        member: member.clone(),
+                    },
+                    member,
+                    checked,
+                )?)?;
+            }
+            menu.append(&sub)?;
+        }
+    }
+
+    menu.append(&PredefinedMenuItem::separator(app)?)?;
+    menu.append(&item(
+        app,
+        TrayAction::CopyEnv,
+        &t(lang, "menu_copy_env", &[]),
+    )?)?;
+    if model.developer {
+        let dev = Submenu::new(app, t(lang, "menu_developer", &[]), true)?;
+        dev.append(&item(
+            app,
+            TrayAction::OpenAdvanced,
+            &t(lang, "open_advanced_settings", &[]),
+        )?)?;
+        dev.append(&item(
+            app,
+            TrayAction::Devtools,
+            &t(lang, "menu_devtools", &[]),
+        )?)?;
+        dev.append(&item(
+            app,
+            TrayAction::OpenLogDir,
+            &t(lang, "menu_log_dir", &[]),
+        )?)?;
+        dev.append(&item(
+            app,
+            TrayAction::OpenConfigDir,
+            &t(lang, "menu_config_dir", &[]),
+        )?)?;
+        menu.append(&dev)?;
+    }
+    menu.append(&item(app, TrayAction::Quit, &t(lang, "menu_quit", &[]))?)?;
+    Ok(menu)
+}
+
+fn tray_tooltip(model: &TrayModel) -> String {
+    format!(
+        "OneBox - {}",
+        status_label(&model.lang, &model.state, model.running_mode.as_ref())
+    )
+}
+
+/// Rebuild the tray menu from current state. Nothing is touched when the
+/// state is what the current menu was built from — rebuilding closes a menu
+/// the user has open.
+pub async fn refresh(app: &AppHandle) {
+    let mut last = REFRESH_LOCK.lock().await;
+    let Some(tray) = app.tray_by_id(TRAY_ID) else {
+        return;
+    };
+    let model = load_model(app).await;
+    if last.as_ref() == Some(&model) {
+        return;
+    }
+    let menu = match build_menu(app, &model) {
+        Ok(menu) => menu,
+        Err(e) => {
+            log::warn!("[tray] failed to build menu: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = tray.set_menu(Some(menu)) {
+        log::warn!("[tray] set_menu failed: {}", e);
+        return;
+    }
+    let tooltip = tray_tooltip(&model);
+    if last.as_ref().map(tray_tooltip).as_ref() != Some(&tooltip) {
+        let _ = tray.set_tooltip(Some(tooltip));
+    }
+    *last = Some(model);
+}
+
+fn spawn_refresh(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move { refresh(&app).await });
+}
+
+/// Create the tray icon and keep its menu in sync with the engine.
+pub fn setup_tray(app: &AppHandle) -> tauri::Result<()> {
+    let icon = Image::from_bytes(&crate::commands::shell::get_tray_icon(app.clone()))?;
+    let placeholder = Menu::with_items(
+        app,
+        &[&MenuItem::with_id(
+            app,
+            TrayAction::Show.id(),
+            t("en", "menu_dashboard", &[]),
+            true,
+            None::<&str>,
+        )?],
+    )?;
+    TrayIconBuilder::with_id(TRAY_ID)
+        .icon(icon)
+        .icon_as_template(cfg!(target_os = "macos"))
+        .tooltip("OneBox")
+        .menu(&placeholder)
+        .show_menu_on_left_click(true)
+        .on_tray_icon_event(|tray, event| {
+            // Not delivered on Linux (libappindicator); the periodic
+            // refresh covers it there.
+            if let TrayIconEvent::Enter { .. } = event {
+                spawn_refresh(tray.app_handle());
+            }
+        })
+        .build(app)?;
+
+    let handle = app.clone();
+    let mut rx = app.state::<EngineStateCell>().subscribe();
+    tauri::async_runtime::spawn(async move {
+        refresh(&handle).await;
+        loop {
+            tokio::select! {
+                change = rx.recv() => match change {
+                    Ok(_) | Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => return,
+                },
+                _ = tokio::time::sleep(REFRESH_INTERVAL) => {}
+            }
+            refresh(&handle).await;
+        }
+    });
+    Ok(())
+}
+
+#[derive(Serialize, Clone)]
+struct SyncRequest {
+    /// Stop the running engine before re-syncing and starting.
+    restart: bool,
+}
+
+/// Ask the dashboard to regenerate the config and (re)start. False when
+/// there is no webview to ask.
+fn request_frontend_sync(app: &AppHandle, restart: bool) -> bool {
+    if app.get_webview_window(MAIN_WINDOW).is_none() {
+        return false;
+    }
+    app.emit_to(
+        MAIN_WINDOW,
+        EVENT_TRAY_SYNC_REQUEST,
+        SyncRequest { restart },
+    )
+    .map_err(|e| log::warn!("[tray] sync request failed: {}", e))
+    .is_ok()
+}
+
+/// (Re)start sing-box from Rust with the last generated config.
+async fn start_without_webview(app: &AppHandle, mode: ProxyMode, restart: bool) {
+    let path = match config_path(app) {
+        Ok(path) => path,
+        Err(e) => {
+            log::warn!("[tray] no config dir: {}", e);
+            return;
+        }
+    };
+    let config = std::fs::read_to_string(&path)
+        .ok()
+        .and_then(|text| serde_json::from_str::<serde_json::Value>(&text).ok());
+    if !config.is_some_and(|c| config_supports(&c, &mode)) {
+        log::warn!(
+            "[tray] {} has no inbound for {:?}; open the dashboard to regenerate it",
+            path,
+            mode
+        );
+        return;
+    }
+    if restart {
+        if let Err(e) = crate::core::stop(app.clone()).await {
+            log::warn!("[tray] stop failed: {}", e);
+            return;
+        }
+    }
+    if let Err(e) = crate::core::start(app.clone(), path, mode, None).await {
+        log::warn!("[tray] start failed: {}", e);
+    }
+}
+
+fn copy_env_text(port: u16) -> String {
+    let host = "127.0.0.1";
+    if cfg!(target_os = "windows") {
+        format!(
+            "$env:HTTP_PROXY=\"http://{host}:{port}\"; $env:HTTPS_PROXY=\"http://{host}:{port}\""
+        )
+    } else {
+        format!(
+            "export https_proxy=http://{host}:{port} \n export http_proxy=http://{host}:{port} \n export all_proxy=socks5://{host}:{port}"
+        )
+    }
+}
+
+async fn perform(app: &AppHandle, action: TrayAction) {
+    let state = app.state::<EngineStateCell>().snapshot();
+    let store = settings_store(app);
+    match action {
+        TrayAction::Toggle => {
+            if is_active(&state) {
+                if let Err(e) = crate::core::stop(app.clone()).await {
+                    log::warn!("[tray] stop failed: {}", e);
+                }
+            } else if !request_frontend_sync(app, false) {
+                let mode = store.as_deref().map(selected_mode).unwrap_or_default();
+                start_without_webview(app, mode, false).await;
+            }
+        }
+        TrayAction::Mode(mode) => {
+            if let Some(store) = &store {
+                store.set(
+                    ENABLE_TUN_STORE_KEY,
+                    serde_json::json!(mode == ProxyMode::TunProxy),
+                );
+                if mode != ProxyMode::TunProxy {
+                    store.set(
+                        SKIP_SYSTEM_PROXY_STORE_KEY,
+                        serde_json::json!(mode == ProxyMode::ManualProxy),
+                    );
+                }
+                let _ = store.save();
+            }
+            if is_active(&state) && !request_frontend_sync(app, true) {
+                start_without_webview(app, mode, true).await;
+            }
+        }
+        TrayAction::Subscription(identifier) => {
+            // Stale menu from before the last refresh; see `build_menu`.
+            if app.get_webview_window(MAIN_WINDOW).is_none() {
+                log::warn!(
+                    "[tray] no dashboard to generate the config for {}; not switching",
+                    identifier
+                );
+                return;
+            }
+            if let Some(store) = &store {
+                store.set(
+                    SELECTED_SUBSCRIPTION_STORE_KEY,
+                    serde_json::json!(identifier),
+                );
+                let _ = store.save();
+            }
+            if is_active(&state) {
+                request_frontend_sync(app, true);
+            }
+        }
+        TrayAction::Node { group, member } => {
+            if let Err(e) = ClashClient::for_app(app)
+                .select_proxy(&group, &member)
+                .await
+            {
+                log::warn!("[tray] select {} → {} failed: {}", group, member, e);
+            }
+        }
+        TrayAction::CopyEnv => {
+            let text = copy_env_text(crate::core::mixed_proxy_port(app));
+            if let Err(e) = app.clipboard().write_text(text) {
+                log::warn!("[tray] clipboard write failed: {}", e);
+            }
+        }
+        TrayAction::OpenAdvanced => {
+            crate::commands::shell::create_window(
+                app.clone(),
+                "sing-box-log".into(),
+                "sing-box-log".into(),
+                "Log".into(),
+            )
+            .await;
+        }
+        TrayAction::Devtools => {
+            if app.get_webview_window(MAIN_WINDOW).is_some() {
+                crate::commands::shell::open_devtools(app.clone());
+            }
+        }
+        TrayAction::OpenLogDir | TrayAction::OpenConfigDir => {
+            let dir = if action == TrayAction::OpenLogDir {
+                app.path().app_log_dir()
+            } else {
+                app.path().app_config_dir()
+            };
+            if let Ok(dir) = dir {
+                let _ = crate::commands::shell::open_directory(dir.to_string_lossy().into_owned());
+            }
+        }
+        TrayAction::Show | TrayAction::Quit => {}
+    }
+}
+
+/// `Builder::on_menu_event` entry for tray items.
+pub fn on_menu_event(app: &AppHandle, id: &str) {
+    match TrayAction::parse(id) {
+        Some(TrayAction::Show) => crate::utils::show_dashboard(app.clone()),
+        Some(TrayAction::Quit) => crate::commands::shell::sync_quit(app.clone()),
+        Some(action) => {
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move {
+                perform(&app, action).await;
+                // A clicked check item has flipped itself; rebuild even when
+                // the action changed nothing.
+                REFRESH_LOCK.lock().await.take();
+                refresh(&app).await;
+            });
+        }
+        None => log::warn!("menu item {:?} not handled", id),
+    }
+}
+
+#[cfg(test)]
+mod tray_tests {
+    use super::*;
+
+    #[test]
+    fn action_ids_round_trip() {
+        let actions = [
+            TrayAction::Show,
+            TrayAction::Quit,
+            TrayAction::Toggle,
+            TrayAction::CopyEnv,
+            TrayAction::Mode(ProxyMode::SystemProxy),
+            TrayAction::Mode(ProxyMode::ManualProxy),
+            TrayAction::Mode(ProxyMode::TunProxy),
+            TrayAction::Subscription("a1b2".into()),
+            TrayAction::Node {
+                group: "ExitGateway".into(),
+                member: "HK: 01 (IPLC)".into(),
+            },
+            TrayAction::OpenAdvanced,
+            TrayAction::Devtools,
+            TrayAction::OpenLogDir,
+            TrayAction::OpenConfigDir,
+        ];
+        for action in actions {
+            assert_eq!(TrayAction::parse(&action.id()), Some(action));
+        }
+        assert_eq!(TrayAction::parse("mode:bogus"), None);
+        assert_eq!(TrayAction::parse("node:no-separator"), None);
+        assert_eq!(TrayAction::parse("status"), None);
+    }
+
+    #[test]
+    fn status_labels_use_translations() {
+        let failed = EngineState::Failed {
+            reason: "x".repeat(100),
+            at: 0,
+            epoch: 1,
+        };
+        let label = status_label("en", &failed, None);
+        assert!(label.contains(&format!("{}…", "x".repeat(MAX_REASON_CHARS))));
+        assert!(!label.contains("{{"));
+
+        let running = EngineState::Running {
+            since: 0,
+            epoch: 2,
+            mode: "mixed".into(),
+        };
+        assert_eq!(
+            status_label("zh", &running, Some(&ProxyMode::ManualProxy)),
+            t(
+                "zh",
+                "menu_status_running",
+                &[("mode", &t("zh", "menu_mode_manual", &[]))]
+            )
+        );
+        assert_ne!(
+            t("zh", "menu_quit", &[]),
+            t("en", "menu_quit", &[]),
+            "zh table should load"
+        );
+        assert_eq!(t("fr", "no_such_key", &[]), "no_such_key");
+    }
+
+    #[test]
+    fn config_support_per_mode() {
+        let mixed = serde_json::json!({
+            "inbounds": [{ "type": "mixed", "tag": "mixed", "listen_port": 6789 }]
+        });
+        let tun = serde_json::json!({
+            "inbounds": [{ "type": "tun", "tag": "tun" }]
+        });
+        assert!(config_supports(&mixed, &ProxyMode::SystemProxy));
+        assert!(config_supports(&mixed, &ProxyMode::ManualProxy));
+        assert!(!config_supports(&mixed, &ProxyMode::TunProxy));
+        assert!(config_supports(&tun, &ProxyMode::TunProxy));
+        assert!(!config_supports(&tun, &ProxyMode::SystemProxy));
+    }
+
+    #[test]
+    fn selectors_sorted_with_global_last() {
+        let proxy = |name: &str, kind: &str, all: &[&str]| Proxy {
+            name: name.into(),
+            kind: kind.into(),
+            all: all.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        };
+        let proxies: HashMap<String, Proxy> = [
+            proxy("GLOBAL", "Selector", &["ExitGateway"]),
+            proxy("ExitGateway", "Selector", &["hk", "jp"]),
+            proxy("auto", "URLTest", &["hk", "jp"]),
+            proxy("Empty", "Selector", &[]),
+            proxy("Apple", "Selector", &["direct"]),
+        ]
+        .into_iter()
+        .map(|p| (p.name.clone(), p))
+        .collect();
+        let names: Vec<String> = selector_groups(proxies)
+            .into_iter()
+            .map(|p| p.name)
+            .collect();
+        assert_eq!(names, vec!["Apple", "ExitGateway", "GLOBAL"]);
+    }
+}
@@ -0,0 +1,909 @@
+//! Headless engine control: `one-box engine <start|stop|status|reload>`.
+//!
+//! Intercepted in `lib.rs::run()` the same way `--onebox-tun-helper` is on
+//! Windows — before any GUI setup, so no webview window is ever created.
+//!
+//! `start` runs the engine in the foreground on its own tokio runtime. No
+//! Tauri app is built: an `AppHandle` needs the tao event loop, which on
+//! Linux initialises GTK and so a display server — exactly what an SSH or
+//! CI session lacks. That rules out `core::start` and `EngineManager`
+//! themselves, so paths and `settings.json` are resolved by hand
+//! (`Headless`) and everything around them that doesn't need an
+//! `AppHandle` is shared with the GUI:
+//!
+//!   - preflight: `preflight::validate_headless`, the same structural
+//!     checks plus `sing-box check` as `preflight::validate`
+//!   - state: an `EngineStateCell` driven through `state_machine::apply`
+//!     with the same intents `core::start` / `core::stop` issue
+//!   - journal: those transitions go through `journal::write_changes` into
+//!     the GUI's `engine_state_journal` (when the GUI has created the
+//!     database), tagged with `journal::note_config`'s hash
+//!   - the Linux privileged helper with its DNS override (`start_tun_args`,
+//!     `prepare_dns_override_for`) and the readiness pipeline
+//!     (`readiness::wait_until_ready`)
+//!
+//! Then it parks until it is told to stop:
+//!
+//!   - SIGTERM / SIGINT (Ctrl+C) → stop sing-box (and restore DNS), exit 0
+//!   - SIGHUP                    → preflight, reload, re-probe
+//!   - sing-box exits on its own (crash, failed readiness) → exit 1
+//!
+//! Modes: `manual` (the default) runs the sidecar as the invoking user;
+//! `tun` goes through `onebox-tun-helper` on Linux — directly when run as
+//! root, otherwise via pkexec — and runs the sidecar directly elsewhere,
+//! which then needs an elevated shell. `system` is refused: the system
+//! proxy belongs to a desktop session and its snapshot / restore lives in
+//! the GUI.
+//!
+//! While the foreground process is alive it keeps a small runtime record
+//! (`engine-cli.json` in the app data dir). `stop`, `status` and `reload`
+//! only read that record and signal the recorded PID, so they never touch
+//! GTK / Tauri and work from a plain SSH session.
+//!
+//! Exit codes follow the LSB init-script convention: 0 ok / running,
+//! 1 generic failure, 2 usage error, 3 not running.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::core::ProxyMode;
+use crate::engine::preflight;
+use crate::engine::readiness::{self, ReadinessSettings};
+use crate::engine::state_machine::{self, now_secs, EngineStateCell, Intent};
+
+const RUNTIME_RECORD_FILE: &str = "engine-cli.json";
+/// Where tauri-plugin-store keeps it: relative to the app data dir.
+const SETTINGS_FILE: &str = "settings.json";
+const STATE_POLL_INTERVAL: Duration = Duration::from_millis(200);
+const STOP_WAIT_TIMEOUT: Duration = Duration::from_secs(10);
+
+const EXIT_OK: i32 = 0;
+const EXIT_FAILURE: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+const EXIT_NOT_RUNNING: i32 = 3;
+
+const USAGE: &str = "\
+Usage:
+  one-box engine start [--mode manual|tun] [--config <path>]
+  one-box engine stop
+  one-box engine status [--json]
+  one-box engine reload";
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum EngineCommand {
+    Start {
+        mode: ProxyMode,
+        config: Option<PathBuf>,
+    },
+    Stop,
+    Status {
+        json: bool,
+    },
+    Reload,
+    Usage(Option<String>),
+}
+
+/// Persisted by the foreground `start` process; read by the other verbs.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct RuntimeRecord {
+    pid: u32,
+    mode: ProxyMode,
+    config_path: String,
+    started_at: i64,
+}
+
+/// Parse `argv[1..]`. Returns `None` when the first argument is not the
+/// `engine` subcommand so the caller falls through to the GUI.
+pub(crate) fn parse(args: &[String]) -> Option<EngineCommand> {
+    if args.first().map(String::as_str) != Some("engine") {
+        return None;
+    }
+    let Some(verb) = args.get(1) else {
+        return Some(EngineCommand::Usage(None));
+    };
+    let rest = &args[2..];
+    let command = match verb.as_str() {
+        "start" => parse_start(rest),
+        "stop" if rest.is_empty() => EngineCommand::Stop,
+        "reload" if rest.is_empty() => EngineCommand::Reload,
+        "status" => match rest {
+            [] => EngineCommand::Status { json: false },
+            [flag] if flag == "--json" => EngineCommand::Status { json: true },
+            _ => EngineCommand::Usage(Some(format!("unexpected arguments: {}", rest.join(" ")))),
+        },
+        "help" | "--help" | "-h" => EngineCommand::Usage(None),
+        "stop" | "reload" => {
+            EngineCommand::Usage(Some(format!("unexpected arguments: {}", rest.join(" "))))
+        }
+        other => EngineCommand::Usage(Some(format!("unknown engine command: {}", other))),
+    };
+    Some(command)
+}
+
+fn parse_start(args: &[String]) -> EngineCommand {
+    let mut mode = ProxyMode::ManualProxy;
+    let mut config = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--mode" => {
+                let Some(value) = iter.next() else {
+                    return EngineCommand::Usage(Some("--mode requires a value".into()));
+                };
+                match parse_mode(value) {
+                    Some(ProxyMode::SystemProxy) => {
+                        return EngineCommand::Usage(Some(
+                            "system proxy mode needs the desktop app; use --mode manual or tun"
+                                .into(),
+                        ));
+                    }
+                    Some(m) => mode = m,
+                    None => {
+                        return EngineCommand::Usage(Some(format!("unknown mode: {}", value)));
+                    }
+                }
+            }
+            "--config" => {
+                let Some(value) = iter.next() else {
+                    return EngineCommand::Usage(Some("--config requires a path".into()));
+                };
+                config = Some(PathBuf::from(value));
+            }
+            other => {
+                return EngineCommand::Usage(Some(format!("unexpected argument: {}", other)));
+            }
+        }
+    }
+    EngineCommand::Start { mode, config }
+}
+
+fn parse_mode(value: &str) -> Option<ProxyMode> {
+    match value.to_ascii_lowercase().as_str() {
+        "tun" | "tunproxy" => Some(ProxyMode::TunProxy),
+        "system" | "systemproxy" => Some(ProxyMode::SystemProxy),
+        "manual" | "manualproxy" => Some(ProxyMode::ManualProxy),
+        _ => None,
+    }
+}
+
+/// Execute a parsed engine command and return the process exit code.
+pub(crate) fn run(command: EngineCommand, context: tauri::Context<tauri::Wry>) -> i32 {
+    attach_parent_console();
+    let identifier = &context.config().identifier;
+    let data_dir = app_data_dir(identifier);
+    match command {
+        EngineCommand::Usage(error) => {
+            if let Some(error) = error {
+                eprintln!("error: {}", error);
+            }
+            eprintln!("{}", USAGE);
+            EXIT_USAGE
+        }
+        EngineCommand::Start { mode, config } => run_start(mode, config, identifier),
+        EngineCommand::Stop => run_stop(data_dir.as_deref()),
+        EngineCommand::Status { json } => run_status(data_dir.as_deref(), json),
+        EngineCommand::Reload => run_reload(data_dir.as_deref()),
+    }
+}
+
+/// Release builds use the GUI subsystem on Windows, so stdout/stderr are
+/// detached unless we explicitly re-attach to the invoking console.
+#[cfg(target_os = "windows")]
+fn attach_parent_console() {
+    use windows::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
+    let _ = unsafe { AttachConsole(ATTACH_PARENT_PROCESS) };
+}
+
+#[cfg(not(target_os = "windows"))]
+fn attach_parent_console() {}
+
+/// Mirrors `PathResolver::app_data_dir` without needing an `AppHandle`, so
+/// `stop` / `status` / `reload` can find the record written by `start`.
+fn app_data_dir(identifier: &str) -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join(identifier))
+}
+
+/// Mirrors `PathResolver::app_config_dir`.
+fn app_config_dir(identifier: &str) -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join(identifier))
+}
+
+fn read_record(data_dir: &Path) -> Option<RuntimeRecord> {
+    let text = std::fs::read_to_string(data_dir.join(RUNTIME_RECORD_FILE)).ok()?;
+    serde_json::from_str(&text).ok()
+}
+
+fn write_record(data_dir: &Path, record: &RuntimeRecord) -> std::io::Result<()> {
+    std::fs::create_dir_all(data_dir)?;
+    let text = serde_json::to_string_pretty(record).map_err(std::io::Error::other)?;
+    std::fs::write(data_dir.join(RUNTIME_RECORD_FILE), text)
+}
+
+fn remove_record(data_dir: &Path) {
+    let _ = std::fs::remove_file(data_dir.join(RUNTIME_RECORD_FILE));
+}
+
+/// Record whose foreground process is still alive. A stale record (crashed
+/// or SIGKILLed `start`) is removed on sight.
+fn live_record(data_dir: Option<&Path>) -> Option<RuntimeRecord> {
+    let data_dir = data_dir?;
+    let record = read_record(data_dir)?;
+    if crate::core::pid_is_alive(record.pid) {
+        Some(record)
+    } else {
+        remove_record(data_dir);
+        None
+    }
+}
+
+// ── start ────────────────────────────────────────────────────────────
+
+/// What the GUI gets from `PathResolver` and the settings store.
+struct Headless {
+    config_dir: Option<PathBuf>,
+    data_dir: Option<PathBuf>,
+    settings: serde_json::Value,
+}
+
+impl Headless {
+    fn new(identifier: &str) -> Self {
+        let data_dir = app_data_dir(identifier);
+        let settings = data_dir
+            .as_ref()
+            .and_then(|dir| std::fs::read_to_string(dir.join(SETTINGS_FILE)).ok())
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default();
+        Self {
+            config_dir: app_config_dir(identifier),
+            data_dir,
+            settings,
+        }
+    }
+
+    fn setting<T: DeserializeOwned + Default>(&self, key: &str) -> T {
+        self.settings
+            .get(key)
+            .cloned()
+            .and_then(|value| serde_json::from_value(value).ok())
+            .unwrap_or_default()
+    }
+
+    fn clash_secret(&self) -> Option<String> {
+        self.settings
+            .get(crate::core::clash::CLASH_SECRET_STORE_KEY)
+            .and_then(|v| v.as_str())
+            .filter(|s| !s.is_empty())
+            .map(str::to_string)
+    }
+}
+
+/// Engine `log` output goes to stderr; the GUI routes it through
+/// tauri-plugin-log, which needs an `AppHandle`.
+struct StderrLogger;
+
+impl log::Log for StderrLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::Level::Info
+            && metadata.target().starts_with(env!("CARGO_CRATE_NAME"))
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("[{}] {}", record.level(), record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: StderrLogger = StderrLogger;
+
+fn run_start(mode: ProxyMode, config: Option<PathBuf>, identifier: &str) -> i32 {
+    let headless = Headless::new(identifier);
+    if let Some(record) = live_record(headless.data_dir.as_deref()) {
+        eprintln!(
+            "error: engine already running under pid {} (mode {:?})",
+            record.pid, record.mode
+        );
+        return EXIT_FAILURE;
+    }
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(log::LevelFilter::Info);
+    }
+
+    let runtime = match tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            eprintln!("error: failed to initialise runtime: {}", e);
+            return EXIT_FAILURE;
+        }
+    };
+    let code = runtime.block_on(async {
+        let cell = EngineStateCell::new();
+        let writer = match headless.config_dir.as_deref() {
+            Some(dir) => match crate::app::database::sqlite_pool_at(dir).await {
+                Ok(pool) => Some(tokio::spawn(crate::engine::journal::write_changes(
+                    cell.subscribe(),
+                    move || {
+                        let pool = pool.clone();
+                        async move { Ok(pool) }
+                    },
+                ))),
+                Err(e) => {
+                    log::warn!("[cli] journal disabled: {}", e);
+                    None
+                }
+            },
+            None => None,
+        };
+        let code = drive_engine(&headless, &cell, mode, config).await;
+        // Dropping the cell closes the feed; the writer drains it and exits.
+        drop(cell);
+        if let Some(writer) = writer {
+            let _ = writer.await;
+        }
+        code
+    });
+    if let Some(dir) = headless.data_dir.as_deref() {
+        remove_record(dir);
+    }
+    code
+}
+
+async fn drive_engine(
+    headless: &Headless,
+    cell: &EngineStateCell,
+    mode: ProxyMode,
+    config: Option<PathBuf>,
+) -> i32 {
+    let Some(config_path) = config.or_else(|| {
+        headless
+            .config_dir
+            .as_ref()
+            .map(|dir| dir.join("config.json"))
+    }) else {
+        eprintln!("error: cannot resolve app config dir");
+        return EXIT_FAILURE;
+    };
+    if !config_path.is_file() {
+        eprintln!("error: config file not found: {}", config_path.display());
+        return EXIT_FAILURE;
+    }
+    let config_path = config_path.to_string_lossy().into_owned();
+    if let Err(e) = check_config(&config_path, &mode).await {
+        eprintln!("error: {}", e);
+        return EXIT_FAILURE;
+    }
+
+    if let Some(dir) = headless.data_dir.as_deref() {
+        let record = RuntimeRecord {
+            pid: std::process::id(),
+            mode: mode.clone(),
+            config_path: config_path.clone(),
+            started_at: now_secs(),
+        };
+        if let Err(e) = write_record(dir, &record) {
+            log::warn!("[cli] failed to write runtime record: {}", e);
+        }
+    }
+
+    log::info!("[cli] engine start mode={:?} config={}", mode, config_path);
+    crate::engine::journal::note_config(&config_path);
+    transition(
+        cell,
+        Intent::Start {
+            mode: crate::core::state_mode_label(&mode).into(),
+        },
+    );
+    let mut engine = match Engine::start(headless, mode, config_path).await {
+        Ok(engine) => engine,
+        Err(e) => {
+            eprintln!("error: start failed: {}", e);
+            transition(cell, Intent::Fail { reason: e });
+            return EXIT_FAILURE;
+        }
+    };
+
+    let settings: ReadinessSettings = headless.setting(readiness::READINESS_SETTINGS_KEY);
+    let ready = tokio::select! {
+        ready = readiness::wait_until_ready(
+            &settings,
+            &engine.config_path,
+            engine.is_tun(),
+            headless.clash_secret(),
+        ) => ready,
+        status = engine.child.wait() => Err(format!("sing-box exited during startup ({})", exit_status(status))),
+    };
+    if let Err(reason) = ready {
+        eprintln!("error: engine failed to start: {}", reason);
+        let _ = engine.stop().await;
+        transition(cell, Intent::Fail { reason });
+        return EXIT_FAILURE;
+    }
+    transition(cell, Intent::MarkRunning);
+    println!("engine running (mode {:?})", engine.mode);
+
+    wait_for_shutdown(headless, cell, &mut engine).await
+}
+
+/// `state_machine::apply`, which already logs a rejected intent.
+fn transition(cell: &EngineStateCell, intent: Intent) {
+    let _ = state_machine::apply(cell, intent);
+}
+
+fn exit_status(status: std::io::Result<std::process::ExitStatus>) -> String {
+    match status {
+        Ok(status) => status.to_string(),
+        Err(e) => e.to_string(),
+    }
+}
+
+/// The sing-box this process runs, and what to undo when it stops.
+struct Engine {
+    child: tokio::process::Child,
+    mode: ProxyMode,
+    config_path: String,
+    #[cfg(target_os = "linux")]
+    dns_override: Option<crate::engine::linux::DnsOverride>,
+}
+
+impl Engine {
+    #[cfg_attr(not(target_os = "linux"), allow(unused_variables))]
+    async fn start(
+        headless: &Headless,
+        mode: ProxyMode,
+        config_path: String,
+    ) -> Result<Self, String> {
+        let sidecar = sidecar_path()?;
+
+        #[cfg(target_os = "linux")]
+        if matches!(mode, ProxyMode::TunProxy) {
+            use crate::engine::linux::dns_backend::{LinuxDnsSettings, LINUX_DNS_KEY};
+            let choice = headless.setting::<LinuxDnsSettings>(LINUX_DNS_KEY).backend;
+            let dns_override =
+                match crate::engine::linux::prepare_dns_override_for(choice, &config_path) {
+                    Ok(dns_override) => Some(dns_override),
+                    Err(e) => {
+                        log::warn!("[dns] prepare_dns_override failed: {}", e);
+                        None
+                    }
+                };
+            let args = crate::engine::linux::start_tun_args(
+                sidecar,
+                config_path.clone(),
+                dns_override.as_ref(),
+            );
+            // Root runs the helper itself; anyone else goes through pkexec,
+            // which works over SSH with a text polkit agent or a rule.
+            let mut command = if unsafe { libc::geteuid() } == 0 {
+                let mut command = tokio::process::Command::new(&args[0]);
+                command.args(&args[1..]);
+                command
+            } else {
+                let mut command = tokio::process::Command::new("pkexec");
+                command.args(&args);
+                command
+            };
+            let child = command
+                .spawn()
+                .map_err(|e| format!("spawn failed: {}", e))?;
+            return Ok(Self {
+                child,
+                mode,
+                config_path,
+                dns_override,
+            });
+        }
+
+        let child = tokio::process::Command::new(&sidecar)
+            .args(["run", "-c", &config_path, "--disable-color"])
+            .spawn()
+            .map_err(|e| format!("spawn failed: {}", e))?;
+        Ok(Self {
+            child,
+            mode,
+            config_path,
+            #[cfg(target_os = "linux")]
+            dns_override: None,
+        })
+    }
+
+    fn is_tun(&self) -> bool {
+        matches!(self.mode, ProxyMode::TunProxy)
+    }
+
+    /// Stop sing-box and undo the DNS override. Safe to call after sing-box
+    /// has already exited.
+    async fn stop(&mut self) -> Result<(), String> {
+        #[cfg(target_os = "linux")]
+        if self.is_tun() {
+            // sing-box runs as root: only the helper can stop it, and it
+            // restores DNS in the same call.
+            let dns_override = self.dns_override.take();
+            let pid = self.child.id();
+            let result = tokio::task::spawn_blocking(move || {
+                crate::engine::linux::stop_tun_and_restore_dns(pid, dns_override.as_ref())
+            })
+            .await
+            .map_err(|e| e.to_string())?;
+            let _ = tokio::time::timeout(STOP_WAIT_TIMEOUT, self.child.wait()).await;
+            return result;
+        }
+
+        #[cfg(unix)]
+        if let Some(pid) = self.child.id() {
+            let _ = signal_pid(pid, libc::SIGTERM);
+        }
+        #[cfg(not(unix))]
+        let _ = self.child.start_kill();
+        if tokio::time::timeout(STOP_WAIT_TIMEOUT, self.child.wait())
+            .await
+            .is_err()
+        {
+            self.child.kill().await.map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    /// Reload the config sing-box was started with, then wait for it to
+    /// serve again.
+    #[cfg(unix)]
+    async fn reload(&mut self, headless: &Headless) -> Result<(), String> {
+        check_config(&self.config_path, &self.mode).await?;
+        #[cfg(target_os = "linux")]
+        if self.is_tun() {
+            let pid = self.child.id().ok_or("sing-box is not running")?;
+            tokio::task::spawn_blocking(move || crate::engine::linux::reload_via_helper(pid))
+                .await
+                .map_err(|e| e.to_string())??;
+        }
+        if !cfg!(target_os = "linux") || !self.is_tun() {
+            let pid = self.child.id().ok_or("sing-box is not running")?;
+            signal_pid(pid, libc::SIGHUP).map_err(|e| e.to_string())?;
+        }
+        readiness::verify_reload_with(
+            headless.setting(readiness::READINESS_SETTINGS_KEY),
+            &self.config_path,
+            self.is_tun(),
+            headless.clash_secret(),
+        )
+        .await
+    }
+}
+
+fn sidecar_path() -> Result<String, String> {
+    crate::engine::helper::get_sidecar_path(Path::new("sing-box"))
+        .map_err(|e| format!("sidecar lookup failed: {}", e))
+}
+
+/// The GUI's preflight, with `sing-box check` run from the sidecar path.
+async fn check_config(config_path: &str, mode: &ProxyMode) -> Result<(), String> {
+    let issues = preflight::validate_headless(&sidecar_path()?, config_path, mode).await;
+    if issues.is_empty() {
+        return Ok(());
+    }
+    let issues: Vec<String> = issues
+        .iter()
+        .map(|issue| format!("{}: {}", issue.path, issue.message))
+        .collect();
+    Err(format!("config check failed: {}", issues.join("; ")))
+}
+
+enum Wake {
+    Stop,
+    #[cfg(unix)]
+    Reload,
+    Exited(std::io::Result<std::process::ExitStatus>),
+}
+
+#[cfg(unix)]
+async fn wait_for_shutdown(
+    headless: &Headless,
+    cell: &EngineStateCell,
+    engine: &mut Engine,
+) -> i32 {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let (Ok(mut term), Ok(mut int), Ok(mut hup)) = (
+        signal(SignalKind::terminate()),
+        signal(SignalKind::interrupt()),
+        signal(SignalKind::hangup()),
+    ) else {
+        eprintln!("error: failed to install signal handlers");
+        let _ = engine.stop().await;
+        transition(
+            cell,
+            Intent::Fail {
+                reason: "failed to install signal handlers".into(),
+            },
+        );
+        return EXIT_FAILURE;
+    };
+    loop {
+        let wake = tokio::select! {
+            _ = term.recv() => Wake::Stop,
+            _ = int.recv() => Wake::Stop,
+            _ = hup.recv() => Wake::Reload,
+            status = engine.child.wait() => Wake::Exited(status),
+        };
+        match wake {
+            Wake::Stop => break,
+            Wake::Reload => match engine.reload(headless).await {
+                Ok(()) => println!("configuration reloaded"),
+                Err(e) => eprintln!("error: reload failed: {}", e),
+            },
+            Wake::Exited(status) => return engine_exited(cell, engine, status).await,
+        }
+    }
+    stop_engine(cell, engine).await
+}
+
+#[cfg(not(unix))]
+async fn wait_for_shutdown(
+    _headless: &Headless,
+    cell: &EngineStateCell,
+    engine: &mut Engine,
+) -> i32 {
+    let wake = tokio::select! {
+        _ = tokio::signal::ctrl_c() => Wake::Stop,
+        status = engine.child.wait() => Wake::Exited(status),
+    };
+    match wake {
+        Wake::Stop => stop_engine(cell, engine).await,
+        Wake::Exited(status) => engine_exited(cell, engine, status).await,
+    }
+}
+
+async fn engine_exited(
+    cell: &EngineStateCell,
+    engine: &mut Engine,
+    status: std::io::Result<std::process::ExitStatus>,
+) -> i32 {
+    let reason = format!("sing-box exited ({})", exit_status(status));
+    eprintln!("error: {}", reason);
+    if let Err(e) = engine.stop().await {
+        eprintln!("error: cleanup failed: {}", e);
+    }
+    transition(cell, Intent::Fail { reason });
+    EXIT_FAILURE
+}
+
+async fn stop_engine(cell: &EngineStateCell, engine: &mut Engine) -> i32 {
+    log::info!("[cli] stop requested");
+    transition(cell, Intent::Stop);
+    match engine.stop().await {
+        Ok(()) => {
+            transition(cell, Intent::MarkIdle);
+            println!("engine stopped");
+            EXIT_OK
+        }
+        Err(e) => {
+            eprintln!("error: stop failed: {}", e);
+            transition(
+                cell,
+                Intent::Fail {
+                    reason: format!("stop failed: {}", e),
+                },
+            );
+            EXIT_FAILURE
+        }
+    }
+}
+
+// ── stop / status / reload ───────────────────────────────────────────
+
+fn run_status(data_dir: Option<&Path>, json: bool) -> i32 {
+    let record = live_record(data_dir);
+    if json {
+        let value = serde_json::json!({
+            "running": record.is_some(),
+            "pid": record.as_ref().map(|r| r.pid),
+            "mode": record.as_ref().map(|r| &r.mode),
+            "config_path": record.as_ref().map(|r| &r.config_path),
+            "started_at": record.as_ref().map(|r| r.started_at),
+        });
+        println!("{}", value);
+    } else if let Some(record) = &record {
+        println!("running");
+        println!("  pid:        {}", record.pid);
+        println!("  mode:       {:?}", record.mode);
+        println!("  config:     {}", record.config_path);
+        println!("  started_at: {}", record.started_at);
+    } else {
+        println!("not running");
+    }
+    if record.is_some() {
+        EXIT_OK
+    } else {
+        EXIT_NOT_RUNNING
+    }
+}
+
+#[cfg(unix)]
+fn run_stop(data_dir: Option<&Path>) -> i32 {
+    let Some(record) = live_record(data_dir) else {
+        println!("not running");
+        return EXIT_NOT_RUNNING;
+    };
+    if let Err(e) = signal_pid(record.pid, libc::SIGTERM) {
+        eprintln!("error: failed to signal pid {}: {}", record.pid, e);
+        return EXIT_FAILURE;
+    }
+    let deadline = std::time::Instant::now() + STOP_WAIT_TIMEOUT;
+    while crate::core::pid_is_alive(record.pid) {
+        if std::time::Instant::now() >= deadline {
+            eprintln!(
+                "error: pid {} still alive after {:?}",
+                record.pid, STOP_WAIT_TIMEOUT
+            );
+            return EXIT_FAILURE;
+        }
+        std::thread::sleep(STATE_POLL_INTERVAL);
+    }
+    println!("engine stopped");
+    EXIT_OK
+}
+
+#[cfg(unix)]
+fn run_reload(data_dir: Option<&Path>) -> i32 {
+    let Some(record) = live_record(data_dir) else {
+        println!("not running");
+        return EXIT_NOT_RUNNING;
+    };
+    match signal_pid(record.pid, libc::SIGHUP) {
+        Ok(()) => {
+            println!("reload requested");
+            EXIT_OK
+        }
+        Err(e) => {
+            eprintln!("error: failed to signal pid {}: {}", record.pid, e);
+            EXIT_FAILURE
+        }
+    }
+}
+
+#[cfg(unix)]
+fn signal_pid(pid: u32, signal: i32) -> std::io::Result<()> {
+    if unsafe { libc::kill(pid as i32, signal) } == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+/// Windows has no SIGTERM / SIGHUP to hand to the foreground process;
+/// stop it with Ctrl+C in the console that runs `engine start`.
+#[cfg(not(unix))]
+fn run_stop(data_dir: Option<&Path>) -> i32 {
+    if live_record(data_dir).is_none() {
+        println!("not running");
+        return EXIT_NOT_RUNNING;
+    }
+    eprintln!("error: `engine stop` is not supported on this platform; press Ctrl+C in the `engine start` console");
+    EXIT_FAILURE
+}
+
+#[cfg(not(unix))]
+fn run_reload(data_dir: Option<&Path>) -> i32 {
+    if live_record(data_dir).is_none() {
+        println!("not running");
+        return EXIT_NOT_RUNNING;
+    }
+    eprintln!("error: `engine reload` is not supported on this platform");
+    EXIT_FAILURE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn non_engine_argv_falls_through_to_gui() {
+        assert_eq!(parse(&args(&[])), None);
+        assert_eq!(parse(&args(&["oneoh-networktools://config?data=x"])), None);
+    }
+
+    #[test]
+    fn start_defaults_to_manual_and_default_config() {
+        assert_eq!(
+            parse(&args(&["engine", "start"])),
+            Some(EngineCommand::Start {
+                mode: ProxyMode::ManualProxy,
+                config: None
+            })
+        );
+    }
+
+    #[test]
+    fn start_parses_mode_and_config() {
+        assert_eq!(
+            parse(&args(&[
+                "engine",
+                "start",
+                "--mode",
+                "tun",
+                "--config",
+                "/tmp/c.json"
+            ])),
+            Some(EngineCommand::Start {
+                mode: ProxyMode::TunProxy,
+                config: Some(PathBuf::from("/tmp/c.json"))
+            })
+        );
+        assert_eq!(
+            parse(&args(&["engine", "start", "--mode", "ManualProxy"])),
+            Some(EngineCommand::Start {
+                mode: ProxyMode::ManualProxy,
+                config: None
+            })
+        );
+    }
+
+    #[test]
+    fn bad_arguments_produce_usage() {
+        assert!(matches!(
+            parse(&args(&["engine", "start", "--mode", "bogus"])),
+            Some(EngineCommand::Usage(Some(_)))
+        ));
+        assert!(matches!(
+            parse(&args(&["engine", "start", "--mode", "system"])),
+            Some(EngineCommand::Usage(Some(_)))
+        ));
+        assert!(matches!(
+            parse(&args(&["engine", "start", "--config"])),
+            Some(EngineCommand::Usage(Some(_)))
+        ));
+        assert!(matches!(
+            parse(&args(&["engine", "stop", "now"])),
+            Some(EngineCommand::Usage(Some(_)))
+        ));
+        assert!(matches!(
+            parse(&args(&["engine"])),
+            Some(EngineCommand::Usage(None))
+        ));
+    }
+
+    #[test]
+    fn status_json_flag() {
+        assert_eq!(
+            parse(&args(&["engine", "status", "--json"])),
+            Some(EngineCommand::Status { json: true })
+        );
+    }
+
+    #[test]
+    fn runtime_record_round_trips_and_stale_record_is_dropped() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let record = RuntimeRecord {
+            pid: std::process::id(),
+            mode: ProxyMode::TunProxy,
+            config_path: "/tmp/c.json".into(),
+            started_at: 1,
+        };
+        write_record(tmp.path(), &record).unwrap();
+        let live = live_record(Some(tmp.path())).expect("own pid is alive");
+        assert_eq!(live.pid, record.pid);
+        assert_eq!(live.mode, ProxyMode::TunProxy);
+
+        #[cfg(unix)]
+        {
+            let stale = RuntimeRecord {
+                pid: i32::MAX as u32,
+                ..record
+            };
+            write_record(tmp.path(), &stale).unwrap();
+            assert!(live_record(Some(tmp.path())).is_none());
+            assert!(!tmp.path().join(RUNTIME_RECORD_FILE).exists());
+        }
+    }
+}
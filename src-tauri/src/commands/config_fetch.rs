@@ -1,5 +1,8 @@
 //! Subscription config fetcher with optimal-DNS pinning + CDN accelerator
-//! fallback. Used by the frontend when importing a subscription URL.
+//! fallback. Used by the frontend when importing or refreshing a subscription
+//! URL, and by the background `subscription_refresh` scheduler. Successful
+//! fetches are also recorded in the version history (`config_history`) and
+//! reconcile local usage accounting (`usage::reconcile`).
 //!
 //! Primary path: resolve host against the fastest public DNS
 //! (`commands::dns::get_best_dns_server`), pin the IP into reqwest, GET
@@ -23,6 +26,9 @@ use super::whitelist::{load_whitelist_hashes, KNOWN_HOST_SHA256_LIST};
 // Empty string when not configured.
 const ACCELERATE_URL: &str = env!("ACCELERATE_URL");
 
+/// Provider usage header, reconciled against local metering (`usage`).
+const SUBSCRIPTION_USERINFO_HEADER: &str = "subscription-userinfo";
+
 pub(crate) fn compute_sha256_hex(s: &str) -> String {
     use sha2::{Digest, Sha256};
     let hash = Sha256::digest(s.as_bytes());
@@ -129,11 +135,30 @@ fn collect_headers(headers: &reqwest::header::HeaderMap) -> HashMap<String, Stri
         .collect()
 }
 
+/// Bookkeeping for a successful fetch of `url`: record the version
+/// history, then reconcile usage against the provider header.
+async fn after_fetch(
+    app: &AppHandle,
+    url: &str,
+    data: &serde_json::Value,
+    headers: &HashMap<String, String>,
+) {
+    super::config_history::record_fetch(app, url, data, headers).await;
+    super::usage::reconcile(
+        app,
+        url,
+        headers
+            .get(SUBSCRIPTION_USERINFO_HEADER)
+            .map(String::as_str),
+    )
+    .await;
+}
+
 #[derive(serde::Serialize)]
 pub struct FetchConfigResponse {
-    data: Option<serde_json::Value>,
-    headers: HashMap<String, String>,
-    status: u16,
+    pub(crate) data: Option<serde_json::Value>,
+    pub(crate) headers: HashMap<String, String>,
+    pub(crate) status: u16,
 }
 
 #[tauri::command]
@@ -179,8 +204,13 @@ pub async fn fetch_config_with_optimal_dns(
     let t_dns_probe = Instant::now();
     let app_data = app.state::<AppData>();
     let (dns_server, dns_source) = {
-        let running =
-            crate::core::is_running(app.clone(), app_data.get_clash_secret().unwrap()).await;
+        // Read the engine state directly: the background refresher
+        // (`subscription_refresh`) may call this before the frontend has
+        // ever handed over a Clash secret.
+        let running = app
+            .state::<crate::engine::state_machine::EngineStateCell>()
+            .snapshot()
+            .is_running();
         if running {
             match app_data.get_cached_dns() {
                 Some(d) => (d, "cached"),
@@ -271,6 +301,9 @@ pub async fn fetch_config_with_optimal_dns(
                 t_total.elapsed().as_millis(),
                 url
             );
+            if let Some(data) = &data {
+                after_fetch(&app, &url, data, &headers).await;
+            }
             Ok(FetchConfigResponse {
                 data,
                 headers,
@@ -362,6 +395,9 @@ pub async fn fetch_config_with_optimal_dns(
                             t_total.elapsed().as_millis(),
                             accelerated_url
                         );
+                        if let Some(data) = &data {
+                            after_fetch(&app, &url, data, &headers).await;
+                        }
                         Ok(FetchConfigResponse {
                             data,
                             headers,
@@ -0,0 +1,665 @@
+//! Subscription config version history.
+//!
+//! Every successful fetch (`config_fetch`, from the dashboard or the
+//! background `subscription_refresh`) is recorded in
+//! `subscription_config_versions` before anyone overwrites
+//! `subscription_configs.config_content`, so a bad provider push can be
+//! inspected (`diff_subscription_versions`) and undone
+//! (`rollback_subscription`). The first recorded fetch also snapshots the
+//! content it is about to replace, so rollback works from the very first
+//! refresh after upgrading. A rollback pins the subscription to that
+//! version so the background refresh does not undo it.
+//!
+//! Content is stored canonicalised (`serde_json::Value::to_string`, keys
+//! sorted) so the SHA-256 identifies the config, not its formatting.
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::{Row, SqlitePool};
+use tauri::AppHandle;
+
+use super::config_fetch::compute_sha256_hex;
+use crate::engine::state_machine::now_secs;
+
+/// Versions kept per subscription; older ones are pruned on insert.
+const KEEP_VERSIONS: i64 = 10;
+/// Upper bound on entries returned by one diff.
+const MAX_DIFF_ENTRIES: usize = 500;
+
+async fn latest(pool: &SqlitePool, identifier: &str) -> Result<Option<(i64, String)>, sqlx::Error> {
+    let row = sqlx::query(
+        "SELECT version, sha256 FROM subscription_config_versions \
+         WHERE identifier = ? ORDER BY version DESC LIMIT 1",
+    )
+    .bind(identifier)
+    .fetch_optional(pool)
+    .await?;
+    row.map(|r| Ok((r.try_get("version")?, r.try_get("sha256")?)))
+        .transpose()
+}
+
+async fn insert_version(
+    pool: &SqlitePool,
+    identifier: &str,
+    version: i64,
+    content: &str,
+    headers: Option<&HashMap<String, String>>,
+    fetched_at: i64,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO subscription_config_versions \
+         (identifier, version, sha256, config_content, headers, fetched_at) \
+         VALUES (?, ?, ?, ?, ?, ?)",
+    )
+    .bind(identifier)
+    .bind(version)
+    .bind(compute_sha256_hex(content))
+    .bind(content)
+    .bind(headers.map(|h| serde_json::to_string(h).unwrap_or_default()))
+    .bind(fetched_at)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Seed the history with the currently stored config, dated by the
+/// subscription's `last_update_time`. Returns the seeded version.
+async fn seed_from_current(
+    pool: &SqlitePool,
+    identifier: &str,
+) -> Result<Option<i64>, sqlx::Error> {
+    let row = sqlx::query(
+        "SELECT c.config_content, s.last_update_time FROM subscription_configs c \
+         JOIN subscriptions s ON s.identifier = c.identifier WHERE c.identifier = ?",
+    )
+    .bind(identifier)
+    .fetch_optional(pool)
+    .await?;
+    let Some(row) = row else {
+        return Ok(None);
+    };
+    let content: Option<String> = row.try_get("config_content")?;
+    let Some(current) = content
+        .as_deref()
+        .and_then(|text| serde_json::from_str::<Value>(text).ok())
+        .filter(|v| !v.is_null())
+    else {
+        return Ok(None);
+    };
+    let fetched_at = row
+        .try_get::<Option<i64>, _>("last_update_time")?
+        .map(super::subscription_refresh::last_update_secs)
+        .unwrap_or_else(now_secs);
+    insert_version(pool, identifier, 1, &current.to_string(), None, fetched_at).await?;
+    Ok(Some(1))
+}
+
+/// Record a fetched config for `identifier`. Returns the new version, or
+/// `None` when it matches the latest one (only its fetch time and headers
+/// are refreshed then).
+pub(crate) async fn record_version(
+    pool: &SqlitePool,
+    identifier: &str,
+    data: &Value,
+    headers: &HashMap<String, String>,
+    now: i64,
+) -> Result<Option<i64>, sqlx::Error> {
+    let content = data.to_string();
+    let sha256 = compute_sha256_hex(&content);
+    let latest = match latest(pool, identifier).await? {
+        Some(latest) => Some(latest),
+        None => match seed_from_current(pool, identifier).await? {
+            Some(version) => latest_sha(pool, identifier, version).await?,
+            None => None,
+        },
+    };
+    if let Some((version, latest_sha)) = &latest {
+        if *latest_sha == sha256 {
+            sqlx::query(
+                "UPDATE subscription_config_versions SET headers = ?, fetched_at = ? \
+                 WHERE identifier = ? AND version = ?",
+            )
+            .bind(serde_json::to_string(headers).unwrap_or_default())
+            .bind(now)
+            .bind(identifier)
+            .bind(version)
+            .execute(pool)
+            .await?;
+            return Ok(None);
+        }
+    }
+    let version = latest.map_or(1, |(v, _)| v + 1);
+    insert_version(pool, identifier, version, &content, Some(headers), now).await?;
+    sqlx::query("DELETE FROM subscription_config_versions WHERE identifier = ? AND version <= ?")
+        .bind(identifier)
+        .bind(version - KEEP_VERSIONS)
+        .execute(pool)
+        .await?;
+    Ok(Some(version))
+}
+
+async fn latest_sha(
+    pool: &SqlitePool,
+    identifier: &str,
+    version: i64,
+) -> Result<Option<(i64, String)>, sqlx::Error> {
+    let row = sqlx::query(
+        "SELECT sha256 FROM subscription_config_versions WHERE identifier = ? AND version = ?",
+    )
+    .bind(identifier)
+    .bind(version)
+    .fetch_optional(pool)
+    .await?;
+    row.map(|r| Ok((version, r.try_get("sha256")?))).transpose()
+}
+
+/// Record a successful fetch of `url` for every subscription using it.
+/// Imports of not-yet-saved subscriptions have no row and are skipped.
+/// Errors are logged, never surfaced — a fetch must not fail because of
+/// bookkeeping.
+pub(crate) async fn record_fetch(
+    app: &AppHandle,
+    url: &str,
+    data: &Value,
+    headers: &HashMap<String, String>,
+) {
+    let pool = match crate::app::database::sqlite_pool(app).await {
+        Ok(pool) => pool,
+        Err(e) => {
+            log::warn!("[history] record skipped: {}", e);
+            return;
+        }
+    };
+    let identifiers: Vec<String> =
+        match sqlx::query("SELECT identifier FROM subscriptions WHERE subscription_url = ?")
+            .bind(url)
+            .fetch_all(&pool)
+            .await
+        {
+            Ok(rows) => rows
+                .iter()
+                .filter_map(|r| r.try_get("identifier").ok())
+                .collect(),
+            Err(e) => {
+                log::warn!("[history] lookup failed: {}", e);
+                return;
+            }
+        };
+    for identifier in identifiers {
+        match record_version(&pool, &identifier, data, headers, now_secs()).await {
+            Ok(Some(version)) => {
+                log::info!("[history] {} recorded version {}", identifier, version)
+            }
+            Ok(None) => {}
+            Err(e) => log::warn!("[history] record failed for {}: {}", identifier, e),
+        }
+    }
+}
+
+// ── Diff ─────────────────────────────────────────────────────────────
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct DiffEntry {
+    /// JSONPath of the changed node, e.g. `$.outbounds[?(@.tag=='hk')].server`.
+    pub path: String,
+    pub kind: DiffKind,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+fn key_path(path: &str, key: &str) -> String {
+    let plain = !key.is_empty()
+        && !key.starts_with(|c: char| c.is_ascii_digit())
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if plain {
+        format!("{}.{}", path, key)
+    } else {
+        format!("{}['{}']", path, key.replace('\'', "\\'"))
+    }
+}
+
+/// `Some(tags)` when every element is an object with a unique string
+/// `tag` — sing-box's inbounds / outbounds / rule sets — so elements can
+/// be matched by tag instead of position.
+fn tags(items: &[Value]) -> Option<Vec<&str>> {
+    let tags: Vec<&str> = items
+        .iter()
+        .map(|v| v.get("tag").and_then(Value::as_str))
+        .collect::<Option<_>>()?;
+    let mut unique = tags.clone();
+    unique.sort_unstable();
+    unique.dedup();
+    (unique.len() == tags.len()).then_some(tags)
+}
+
+fn push(
+    out: &mut Vec<DiffEntry>,
+    path: String,
+    kind: DiffKind,
+    before: Option<&Value>,
+    after: Option<&Value>,
+) {
+    out.push(DiffEntry {
+        path,
+        kind,
+        before: before.cloned(),
+        after: after.cloned(),
+    });
+}
+
+fn diff_values(path: &str, before: &Value, after: &Value, out: &mut Vec<DiffEntry>) {
+    if before == after {
+        return;
+    }
+    match (before, after) {
+        (Value::Object(a), Value::Object(b)) => {
+            for (key, old) in a {
+                match b.get(key) {
+                    Some(new) => diff_values(&key_path(path, key), old, new, out),
+                    None => push(out, key_path(path, key), DiffKind::Removed, Some(old), None),
+                }
+            }
+            for (key, new) in b {
+                if !a.contains_key(key) {
+                    push(out, key_path(path, key), DiffKind::Added, None, Some(new));
+                }
+            }
+        }
+        (Value::Array(a), Value::Array(b)) => match (tags(a), tags(b)) {
+            (Some(a_tags), Some(b_tags)) if !a.is_empty() && !b.is_empty() => {
+                let tag_path =
+                    |tag: &str| format!("{}[?(@.tag=='{}')]", path, tag.replace('\'', "\\'"));
+                for (old, tag) in a.iter().zip(&a_tags) {
+                    match b_tags.iter().position(|t| t == tag) {
+                        Some(i) => diff_values(&tag_path(tag), old, &b[i], out),
+                        None => push(out, tag_path(tag), DiffKind::Removed, Some(old), None),
+                    }
+                }
+                for (new, tag) in b.iter().zip(&b_tags) {
+                    if !a_tags.contains(tag) {
+                        push(out, tag_path(tag), DiffKind::Added, None, Some(new));
+                    }
+                }
+            }
+            _ => {
+                for i in 0..a.len().max(b.len()) {
+                    let item_path = format!("{}[{}]", path, i);
+                    match (a.get(i), b.get(i)) {
+                        (Some(old), Some(new)) => diff_values(&item_path, old, new, out),
+                        (Some(old), None) => {
+                            push(out, item_path, DiffKind::Removed, Some(old), None)
+                        }
+                        (None, Some(new)) => push(out, item_path, DiffKind::Added, None, Some(new)),
+                        (None, None) => {}
+                    }
+                }
+            }
+        },
+        _ => push(
+            out,
+            path.to_string(),
+            DiffKind::Changed,
+            Some(before),
+            Some(after),
+        ),
+    }
+}
+
+pub(crate) fn diff(before: &Value, after: &Value) -> Vec<DiffEntry> {
+    let mut out = Vec::new();
+    diff_values("$", before, after, &mut out);
+    out
+}
+
+// ── Commands ─────────────────────────────────────────────────────────
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ConfigVersion {
+    pub version: i64,
+    pub sha256: String,
+    pub fetched_at: i64,
+    pub headers: Option<HashMap<String, String>>,
+    /// Size of the stored content in bytes.
+    pub size: i64,
+    /// Matches what `subscription_configs` holds right now.
+    pub current: bool,
+}
+
+async fn current_content(pool: &SqlitePool, identifier: &str) -> Result<Option<Value>, String> {
+    let row = sqlx::query("SELECT config_content FROM subscription_configs WHERE identifier = ?")
+        .bind(identifier)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(row
+        .and_then(|r| {
+            r.try_get::<Option<String>, _>("config_content")
+                .ok()
+                .flatten()
+        })
+        .and_then(|text| serde_json::from_str(&text).ok()))
+}
+
+async fn version_content(
+    pool: &SqlitePool,
+    identifier: &str,
+    version: i64,
+) -> Result<Value, String> {
+    let row = sqlx::query(
+        "SELECT config_content FROM subscription_config_versions \
+         WHERE identifier = ? AND version = ?",
+    )
+    .bind(identifier)
+    .bind(version)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| e.to_string())?
+    .ok_or_else(|| format!("version {} of {} not found", version, identifier))?;
+    let text: String = row.try_get("config_content").map_err(|e| e.to_string())?;
+    serde_json::from_str(&text).map_err(|e| e.to_string())
+}
+
+/// Recorded versions of one subscription, newest first (content omitted).
+#[tauri::command]
+pub async fn list_subscription_versions(
+    app: AppHandle,
+    identifier: String,
+) -> Result<Vec<ConfigVersion>, String> {
+    let pool = crate::app::database::sqlite_pool(&app).await?;
+    let current_sha = current_content(&pool, &identifier)
+        .await?
+        .map(|v| compute_sha256_hex(&v.to_string()));
+    let rows = sqlx::query(
+        "SELECT version, sha256, fetched_at, headers, LENGTH(config_content) AS size \
+         FROM subscription_config_versions WHERE identifier = ? ORDER BY version DESC",
+    )
+    .bind(&identifier)
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    rows.iter()
+        .map(|row| {
+            let sha256: String = row.try_get("sha256").map_err(|e| e.to_string())?;
+            let headers: Option<String> = row.try_get("headers").map_err(|e| e.to_string())?;
+            Ok(ConfigVersion {
+                version: row.try_get("version").map_err(|e| e.to_string())?,
+                current: current_sha.as_deref() == Some(sha256.as_str()),
+                sha256,
+                fetched_at: row.try_get("fetched_at").map_err(|e| e.to_string())?,
+                headers: headers.and_then(|h| serde_json::from_str(&h).ok()),
+                size: row.try_get("size").map_err(|e| e.to_string())?,
+            })
+        })
+        .collect()
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ConfigDiff {
+    pub from: i64,
+    /// `None` when compared against the currently stored config.
+    pub to: Option<i64>,
+    pub changes: Vec<DiffEntry>,
+    /// More than `MAX_DIFF_ENTRIES` changes; the rest were dropped.
+    pub truncated: bool,
+}
+
+/// Structural diff between two versions; `to: None` compares against the
+/// currently stored config.
+#[tauri::command]
+pub async fn diff_subscription_versions(
+    app: AppHandle,
+    identifier: String,
+    from: i64,
+    to: Option<i64>,
+) -> Result<ConfigDiff, String> {
+    let pool = crate::app::database::sqlite_pool(&app).await?;
+    let before = version_content(&pool, &identifier, from).await?;
+    let after = match to {
+        Some(version) => version_content(&pool, &identifier, version).await?,
+        None => current_content(&pool, &identifier)
+            .await?
+            .ok_or_else(|| format!("{} has no stored config", identifier))?,
+    };
+    let mut changes = diff(&before, &after);
+    let truncated = changes.len() > MAX_DIFF_ENTRIES;
+    changes.truncate(MAX_DIFF_ENTRIES);
+    Ok(ConfigDiff {
+        from,
+        to,
+        changes,
+        truncated,
+    })
+}
+
+/// Record (`Some`) or clear (`None`) the version a subscription is pinned
+/// to; `subscription_refresh` skips pinned subscriptions.
+async fn set_pin(pool: &SqlitePool, identifier: &str, version: Option<i64>) -> Result<(), String> {
+    let result = sqlx::query("UPDATE subscriptions SET pinned_version = ? WHERE identifier = ?")
+        .bind(version)
+        .bind(identifier)
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    if result.rows_affected() == 0 {
+        return Err(format!("subscription {} not found", identifier));
+    }
+    Ok(())
+}
+
+/// Restore a recorded version as the subscription's config, pin it there
+/// (`unpin_subscription` resumes auto-refresh) and, when it is the active
+/// one and sing-box runs, reload onto it before returning
+/// (`subscription_refresh::reload_with_subscription`). A reload that does
+/// not come up is reverted and reported as `CONFIG_RELOAD_REVERTED:…`.
+#[tauri::command]
+pub async fn rollback_subscription(
+    app: AppHandle,
+    identifier: String,
+    version: i64,
+) -> Result<(), String> {
+    let pool = crate::app::database::sqlite_pool(&app).await?;
+    let content = version_content(&pool, &identifier, version)
+        .await?
+        .to_string();
+    let updated =
+        sqlx::query("UPDATE subscription_configs SET config_content = ? WHERE identifier = ?")
+            .bind(&content)
+            .bind(&identifier)
+            .execute(&pool)
+            .await
+            .map_err(|e| e.to_string())?;
+    if updated.rows_affected() == 0 {
+        sqlx::query("INSERT INTO subscription_configs (identifier, config_content) VALUES (?, ?)")
+            .bind(&identifier)
+            .bind(&content)
+            .execute(&pool)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    set_pin(&pool, &identifier, Some(version)).await?;
+    log::info!(
+        "[history] {} rolled back to version {} and pinned",
+        identifier,
+        version
+    );
+    let reload = super::subscription_refresh::is_active_and_running(&app, &identifier);
+    // The reload below replaces the dashboard's re-merge.
+    super::subscription_refresh::emit_refreshed(&app, &identifier, true, false);
+    if reload {
+        super::subscription_refresh::reload_with_subscription(&app, &identifier).await?;
+    }
+    Ok(())
+}
+
+/// Let the background refresh update a subscription pinned by a rollback
+/// again.
+#[tauri::command]
+pub async fn unpin_subscription(app: AppHandle, identifier: String) -> Result<(), String> {
+    let pool = crate::app::database::sqlite_pool(&app).await?;
+    set_pin(&pool, &identifier, None).await?;
+    log::info!("[history] {} unpinned", identifier);
+    Ok(())
+}
+
+#[cfg(test)]
+mod config_history_tests {
+    use super::*;
+    use serde_json::json;
+
+    async fn test_pool() -> SqlitePool {
+        let pool = crate::app::database::test_pool().await;
+        sqlx::query(
+            "INSERT INTO subscriptions (identifier, name, subscription_url, last_update_time) \
+             VALUES ('sub', 'Sub', 'https://example.com/sub', 1700000000000)",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        pool
+    }
+
+    async fn versions(pool: &SqlitePool) -> Vec<(i64, i64)> {
+        sqlx::query(
+            "SELECT version, fetched_at FROM subscription_config_versions \
+             WHERE identifier = 'sub' ORDER BY version",
+        )
+        .fetch_all(pool)
+        .await
+        .unwrap()
+        .iter()
+        .map(|r| (r.get("version"), r.get("fetched_at")))
+        .collect()
+    }
+
+    #[tokio::test]
+    async fn first_record_snapshots_current_config() {
+        let pool = test_pool().await;
+        sqlx::query(
+            "INSERT INTO subscription_configs (identifier, config_content) VALUES ('sub', ?)",
+        )
+        .bind(r#"{"outbounds":[],"log":{"level":"info"}}"#)
+        .execute(&pool)
+        .await
+        .unwrap();
+        let headers = HashMap::new();
+        let v = record_version(&pool, "sub", &json!({"outbounds": [1]}), &headers, 5).await;
+        assert_eq!(v.unwrap(), Some(2));
+        // Seed is dated by last_update_time (ms → s).
+        assert_eq!(versions(&pool).await, vec![(1, 1_700_000_000), (2, 5)]);
+    }
+
+    #[tokio::test]
+    async fn identical_content_only_touches_latest() {
+        let pool = test_pool().await;
+        let headers = HashMap::from([("etag".to_string(), "x".to_string())]);
+        let config = json!({"b": 1, "a": [1, 2]});
+        assert_eq!(
+            record_version(&pool, "sub", &config, &headers, 10)
+                .await
+                .unwrap(),
+            Some(1)
+        );
+        assert_eq!(
+            record_version(&pool, "sub", &config, &headers, 20)
+                .await
+                .unwrap(),
+            None
+        );
+        assert_eq!(versions(&pool).await, vec![(1, 20)]);
+    }
+
+    #[tokio::test]
+    async fn keeps_only_recent_versions() {
+        let pool = test_pool().await;
+        let headers = HashMap::new();
+        for i in 0..(KEEP_VERSIONS + 3) {
+            record_version(&pool, "sub", &json!({ "n": i }), &headers, i)
+                .await
+                .unwrap();
+        }
+        let kept = versions(&pool).await;
+        assert_eq!(kept.len() as i64, KEEP_VERSIONS);
+        assert_eq!(kept.first().unwrap().0, 4);
+        assert_eq!(kept.last().unwrap().0, KEEP_VERSIONS + 3);
+    }
+
+    #[tokio::test]
+    async fn pin_is_recorded_and_cleared() {
+        let pool = test_pool().await;
+        let pinned = || async {
+            sqlx::query_scalar::<_, Option<i64>>(
+                "SELECT pinned_version FROM subscriptions WHERE identifier = 'sub'",
+            )
+            .fetch_one(&pool)
+            .await
+            .unwrap()
+        };
+        assert_eq!(pinned().await, None);
+        set_pin(&pool, "sub", Some(3)).await.unwrap();
+        assert_eq!(pinned().await, Some(3));
+        set_pin(&pool, "sub", None).await.unwrap();
+        assert_eq!(pinned().await, None);
+        assert!(set_pin(&pool, "missing", Some(1)).await.is_err());
+    }
+
+    #[test]
+    fn diff_matches_tagged_elements_by_tag() {
+        let before = json!({
+            "log": {"level": "info"},
+            "outbounds": [
+                {"tag": "hk", "type": "vmess", "server": "1.1.1.1"},
+                {"tag": "jp", "type": "vmess", "server": "2.2.2.2"}
+            ]
+        });
+        let after = json!({
+            "log": {"level": "warn"},
+            "dns": {},
+            "outbounds": [
+                {"tag": "sg", "type": "trojan", "server": "3.3.3.3"},
+                {"tag": "hk", "type": "vmess", "server": "9.9.9.9"}
+            ]
+        });
+        let paths: Vec<(String, DiffKind)> = diff(&before, &after)
+            .into_iter()
+            .map(|e| (e.path, e.kind))
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                ("$.log.level".to_string(), DiffKind::Changed),
+                (
+                    "$.outbounds[?(@.tag=='hk')].server".to_string(),
+                    DiffKind::Changed
+                ),
+                ("$.outbounds[?(@.tag=='jp')]".to_string(), DiffKind::Removed),
+                ("$.outbounds[?(@.tag=='sg')]".to_string(), DiffKind::Added),
+                ("$.dns".to_string(), DiffKind::Added),
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_untagged_arrays_by_index_and_quotes_odd_keys() {
+        let before = json!({"route": {"rules": [{"outbound": "a"}]}, "x-y": 1});
+        let after = json!({"route": {"rules": [{"outbound": "b"}, {"outbound": "c"}]}, "x-y": 2});
+        let entries = diff(&before, &after);
+        let paths: Vec<&str> = entries.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec!["$.route.rules[0].outbound", "$.route.rules[1]", "$['x-y']"]
+        );
+        assert_eq!(entries[0].before, Some(json!("a")));
+        assert_eq!(entries[0].after, Some(json!("b")));
+        assert!(diff(&before, &before).is_empty());
+    }
+}
@@ -5,9 +5,12 @@
 //! the command is tightly coupled to lifecycle/platform state.
 
 pub mod config_fetch;
+pub mod config_history;
 pub mod dns;
 pub mod network;
 pub mod prestart;
 pub mod shell;
+pub mod subscription_refresh;
 pub mod theme;
+pub mod usage;
 pub mod whitelist;
@@ -197,24 +197,36 @@ pub async fn get_captive_redirect_url() -> String {
 
 #[tauri::command]
 pub async fn ping_google(app: tauri::AppHandle) -> bool {
-    let proxy = format!(
-        "http://{}:{}",
-        "127.0.0.1",
-        crate::core::mixed_proxy_port(&app)
-    );
-    let client = reqwest::ClientBuilder::new()
-        .proxy(reqwest::Proxy::all(&proxy).unwrap())
-        .timeout(std::time::Duration::from_secs(10))
-        .build()
-        .unwrap();
+    probe_through_proxy(
+        Some(crate::core::mixed_proxy_port(&app)),
+        "https://www.google.com/generate_204",
+        std::time::Duration::from_secs(10),
+    )
+    .await
+    .is_ok()
+}
 
-    match client
-        .get("https://www.google.com/generate_204")
-        .send()
-        .await
-    {
-        Ok(res) => res.status().is_success(),
-        Err(_) => false,
+/// GET `url` through the local mixed inbound on `proxy_port` (or directly,
+/// e.g. through TUN, when `None`) and return the round-trip latency.
+/// Shared by `ping_google` and the engine health monitor.
+pub(crate) async fn probe_through_proxy(
+    proxy_port: Option<u16>,
+    url: &str,
+    timeout: std::time::Duration,
+) -> Result<std::time::Duration, String> {
+    let mut builder = reqwest::ClientBuilder::new().timeout(timeout);
+    if let Some(port) = proxy_port {
+        let proxy = format!("http://{}:{}", "127.0.0.1", port);
+        builder = builder.proxy(reqwest::Proxy::all(&proxy).map_err(|e| e.to_string())?);
+    }
+    let client = builder.build().map_err(|e| e.to_string())?;
+
+    let started = std::time::Instant::now();
+    let res = client.get(url).send().await.map_err(|e| e.to_string())?;
+    if res.status().is_success() {
+        Ok(started.elapsed())
+    } else {
+        Err(format!("HTTP {}", res.status().as_u16()))
     }
 }
 
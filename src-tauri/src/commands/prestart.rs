@@ -1,14 +1,248 @@
-use serde::Serialize;
+//! Port-conflict resolution before sing-box is spawned.
+//!
+//! A listener on the mixed or Clash API port is only ever killed when it is
+//! verifiably a sing-box OneBox itself spawned: its executable (or argv[0],
+//! when `/proc/<pid>/exe` is unreadable for a root process) is the bundled
+//! sidecar, or it is a process recorded in the PID file at spawn
+//! (`record_spawned_pid`). A recorded pid only counts while the process
+//! behind it still has the recorded start time (and executable, where
+//! readable), so a pid reused after the process exited — or after a reboot
+//! — is never taken for ours. A sing-box whose parent is a recorded process
+//! covers Linux TUN, where the recorded pid is `pkexec`; the parent is
+//! checked the same way and the child itself must be sing-box. Entries are
+//! dropped when the process exits (`forget_spawned_pid`). Anything else is
+//! reported as `ForeignHolder` and left alone; `core::start` can then move
+//! to a free port instead.
+//!
+//! The same file carries the engine session (`engine::runtime_record`), so
+//! there is one record of what OneBox runs.
+
+use serde::{Deserialize, Serialize};
 use std::fmt;
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::Mutex;
 use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+
+use crate::engine::runtime_record::RuntimeRecord;
 
 pub const PORT_OCCUPIED_CANNOT_START: &str = "PORT_OCCUPIED_CANNOT_START";
+/// `PORT_HELD_BY_OTHER_PROCESS:<port>:<process name>` — the port belongs to
+/// something OneBox did not spawn, and OneBox refuses to kill it.
+pub const PORT_HELD_BY_OTHER_PROCESS: &str = "PORT_HELD_BY_OTHER_PROCESS";
+
+/// `PidFile` as JSON.
+const PID_FILE_NAME: &str = "sing-box.pid";
+const PID_FILE_KEEP: usize = 8;
+
+/// Serialises read-modify-write of the PID file: the monitor records pids
+/// while `core::start` records the session.
+static PID_FILE_LOCK: Mutex<()> = Mutex::new(());
 
 #[derive(Serialize)]
 pub struct PrestartCheckResult {
     pub port_occupied: bool,
     pub orphan_pids: Vec<u32>,
+    pub holders: Vec<PortHolder>,
+}
+
+/// A process listening on a port we need, as far as it can be identified.
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct PortHolder {
+    pub pid: u32,
+    pub ppid: Option<u32>,
+    pub exe: Option<String>,
+    pub cmdline: Option<String>,
+    /// Platform start-time token (`process_start_time`).
+    pub started: Option<String>,
+    /// Start-time token of the parent, read only when the parent pid is a
+    /// recorded one.
+    pub parent_started: Option<String>,
+    pub name: String,
+    /// Verifiably a sing-box spawned by OneBox, i.e. safe to kill.
+    pub ours: bool,
+}
+
+/// A process recorded at spawn, with what identified it then.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub(crate) struct SpawnedProcess {
+    pub pid: u32,
+    pub started: String,
+    pub exe: Option<String>,
+}
+
+/// What the PID file holds.
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq)]
+pub(crate) struct PidFile {
+    /// Processes spawned by this install, newest last.
+    #[serde(default)]
+    pub spawned: Vec<SpawnedProcess>,
+    /// The engine session `core::start` brought up; see
+    /// `engine::runtime_record`.
+    #[serde(default)]
+    pub session: Option<RuntimeRecord>,
+}
+
+impl SpawnedProcess {
+    /// Whether `pid` with these properties is still this process. The exe
+    /// is compared only when both sides could read it.
+    pub(crate) fn is(&self, pid: u32, started: Option<&str>, exe: Option<&str>) -> bool {
+        self.pid == pid
+            && started == Some(self.started.as_str())
+            && match (self.exe.as_deref(), exe) {
+                (Some(recorded), Some(exe)) => same_path(Path::new(exe), Path::new(recorded)),
+                _ => true,
+            }
+    }
+
+    /// Whether the process is still running as recorded.
+    fn alive(&self) -> bool {
+        self.is(self.pid, process_start_time(self.pid).as_deref(), None)
+    }
+}
+
+/// What counts as "ours": the sidecar binary and the processes recorded at
+/// spawn.
+#[derive(Default, Debug)]
+pub(crate) struct OwnedProcesses {
+    pub sidecar: Option<PathBuf>,
+    pub spawned: Vec<SpawnedProcess>,
+}
+
+impl OwnedProcesses {
+    pub(crate) fn owns(&self, holder: &PortHolder) -> bool {
+        let started = holder.started.as_deref();
+        if self
+            .spawned
+            .iter()
+            .any(|s| s.is(holder.pid, started, holder.exe.as_deref()))
+        {
+            return true;
+        }
+        // Linux TUN: the recorded process is pkexec and sing-box its child.
+        // The parent must still be the recorded process and the child must
+        // be sing-box — a matching parent pid alone proves nothing.
+        let parent_recorded = holder.ppid.is_some_and(|ppid| {
+            self.spawned
+                .iter()
+                .any(|s| s.is(ppid, holder.parent_started.as_deref(), None))
+        });
+        if parent_recorded && holder.name.starts_with("sing-box") {
+            return true;
+        }
+        let Some(sidecar) = self.sidecar.as_deref() else {
+            return false;
+        };
+        let argv0 = holder
+            .cmdline
+            .as_deref()
+            .and_then(|c| c.split_whitespace().next());
+        holder
+            .exe
+            .as_deref()
+            .into_iter()
+            .chain(argv0)
+            .any(|candidate| same_path(Path::new(candidate), sidecar))
+    }
+}
+
+fn same_path(a: &Path, b: &Path) -> bool {
+    let a = std::fs::canonicalize(a).unwrap_or_else(|_| a.to_path_buf());
+    if cfg!(windows) {
+        a.to_string_lossy()
+            .eq_ignore_ascii_case(&b.to_string_lossy())
+    } else {
+        a == b
+    }
+}
+
+fn pid_file(app: &AppHandle) -> Option<PathBuf> {
+    Some(app.path().app_config_dir().ok()?.join(PID_FILE_NAME))
+}
+
+/// An unreadable file — including the line format of older builds — reads
+/// as empty: those entries can't be verified anyway.
+fn parse_pid_file(text: &str) -> PidFile {
+    serde_json::from_str(text).unwrap_or_default()
+}
+
+fn read_pid_file(path: &Path) -> PidFile {
+    std::fs::read_to_string(path)
+        .map(|text| parse_pid_file(&text))
+        .unwrap_or_default()
+}
+
+fn write_pid_file(path: &Path, file: &mut PidFile) {
+    let skip = file.spawned.len().saturating_sub(PID_FILE_KEEP);
+    file.spawned.drain(..skip);
+    let result = serde_json::to_vec_pretty(file)
+        .map_err(|e| e.to_string())
+        .and_then(|bytes| {
+            crate::engine::config_switch::write_atomic(path, &bytes).map_err(|e| e.to_string())
+        });
+    if let Err(e) = result {
+        log::warn!("[prestart] failed to write {}: {}", path.display(), e);
+    }
+}
+
+/// The PID file as it is now.
+pub(crate) fn pid_file_contents(app: &AppHandle) -> PidFile {
+    let _guard = PID_FILE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    pid_file(app).map(|p| read_pid_file(&p)).unwrap_or_default()
+}
+
+/// Read, change and write back the PID file under `PID_FILE_LOCK`; `update`
+/// returns whether anything changed.
+pub(crate) fn update_pid_file(app: &AppHandle, update: impl FnOnce(&mut PidFile) -> bool) {
+    let Some(path) = pid_file(app) else {
+        return;
+    };
+    let _guard = PID_FILE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut file = read_pid_file(&path);
+    if update(&mut file) {
+        if let Some(dir) = path.parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+        write_pid_file(&path, &mut file);
+    }
+}
+
+/// Record a freshly spawned process in the PID file, dropping entries
+/// whose process is gone and keeping the newest `PID_FILE_KEEP`.
+pub(crate) fn record_spawned_pid(app: &AppHandle, pid: u32) {
+    let process = read_process(pid);
+    let Some(started) = process.started else {
+        log::warn!("[prestart] pid {} exited before it could be recorded", pid);
+        return;
+    };
+    update_pid_file(app, |file| {
+        file.spawned.retain(|s| s.pid != pid && s.alive());
+        file.spawned.push(SpawnedProcess {
+            pid,
+            started,
+            exe: process.exe,
+        });
+        true
+    });
+}
+
+/// Drop `pid` from the PID file once its process has exited.
+pub(crate) fn forget_spawned_pid(app: &AppHandle, pid: u32) {
+    update_pid_file(app, |file| {
+        let before = file.spawned.len();
+        file.spawned.retain(|s| s.pid != pid);
+        file.spawned.len() != before
+    });
+}
+
+pub(crate) fn owned_processes(app: &AppHandle) -> OwnedProcesses {
+    OwnedProcesses {
+        sidecar: crate::engine::helper::get_sidecar_path(Path::new("sing-box"))
+            .ok()
+            .map(|p| std::fs::canonicalize(&p).unwrap_or_else(|_| PathBuf::from(p))),
+        spawned: pid_file_contents(app).spawned,
+    }
 }
 
 #[derive(Serialize)]
@@ -29,6 +263,11 @@ pub(crate) enum PortCleanupError {
     NoKillableProcess {
         port: u16,
     },
+    ForeignHolder {
+        port: u16,
+        pid: u32,
+        name: String,
+    },
     PortStillOccupied {
         port: u16,
         pids: Vec<u32>,
@@ -39,6 +278,9 @@ pub(crate) enum PortCleanupError {
 
 impl PortCleanupError {
     pub(crate) fn start_error(&self) -> String {
+        if let Self::ForeignHolder { port, name, .. } = self {
+            return format!("{}:{}:{}", PORT_HELD_BY_OTHER_PROCESS, port, name);
+        }
         format!(
             "{}:{}: port is occupied and OneBox could not stop the process",
             PORT_OCCUPIED_CANNOT_START,
@@ -50,6 +292,7 @@ impl PortCleanupError {
         match self {
             Self::NoKillableProcess { port } => *port,
             Self::PortStillOccupied { port, .. } => *port,
+            Self::ForeignHolder { port, .. } => *port,
         }
     }
 }
@@ -60,6 +303,9 @@ impl fmt::Display for PortCleanupError {
             Self::NoKillableProcess { port } => {
                 write!(f, "port {port} is occupied but no killable listener PID was found")
             }
+            Self::ForeignHolder { port, pid, name } => {
+                write!(f, "port {port} is held by {name} (pid {pid}), which is not sing-box")
+            }
             Self::PortStillOccupied {
                 port,
                 pids,
@@ -157,7 +403,141 @@ fn find_pids_linux(port: u16) -> Vec<u32> {
     }
 }
 
-fn kill_pid(pid: u32) -> Result<(), String> {
+/// When `pid` started, as a token that differs between two processes that
+/// had the same pid: boot id + start ticks on Linux, the start date on
+/// macOS, the creation FILETIME on Windows.
+fn process_start_time(pid: u32) -> Option<String> {
+    #[cfg(target_os = "linux")]
+    {
+        // starttime is field 22: the 20th after the ')' closing `comm`.
+        let stat = std::fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+        let ticks = stat.rsplit_once(')')?.1.split_whitespace().nth(19)?;
+        let boot_id =
+            std::fs::read_to_string("/proc/sys/kernel/random/boot_id").unwrap_or_default();
+        Some(format!("{}:{}", boot_id.trim(), ticks))
+    }
+    #[cfg(target_os = "macos")]
+    {
+        let out = Command::new("ps")
+            .args(["-p", &pid.to_string(), "-o", "lstart="])
+            .output()
+            .ok()?;
+        Some(String::from_utf8_lossy(&out.stdout).trim().to_string()).filter(|s| !s.is_empty())
+    }
+    #[cfg(target_os = "windows")]
+    {
+        let query = format!(
+            "(Get-CimInstance Win32_Process -Filter 'ProcessId={pid}').CreationDate.ToFileTimeUtc()"
+        );
+        let out = Command::new("powershell")
+            .args(["-NoProfile", "-NonInteractive", "-Command", &query])
+            .output()
+            .ok()?;
+        Some(String::from_utf8_lossy(&out.stdout).trim().to_string())
+            .filter(|s| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()))
+    }
+}
+
+/// Parent, executable, command line and start time of `pid`, best effort —
+/// any of them may be unreadable (other user, exited).
+fn read_process(pid: u32) -> PortHolder {
+    let mut holder = PortHolder {
+        pid,
+        ..Default::default()
+    };
+    #[cfg(target_os = "linux")]
+    {
+        holder.exe = std::fs::read_link(format!("/proc/{pid}/exe"))
+            .ok()
+            .map(|p| p.to_string_lossy().into_owned());
+        holder.cmdline = std::fs::read(format!("/proc/{pid}/cmdline"))
+            .ok()
+            .map(|raw| {
+                String::from_utf8_lossy(&raw)
+                    .split('\0')
+                    .filter(|a| !a.is_empty())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .filter(|c| !c.is_empty());
+        // `comm` is "(name)" followed by the state and then the ppid; the
+        // name itself may contain spaces, so split after the last ')'.
+        holder.ppid = std::fs::read_to_string(format!("/proc/{pid}/stat"))
+            .ok()
+            .and_then(|stat| {
+                let rest = stat.rsplit_once(')')?.1;
+                rest.split_whitespace().nth(1)?.parse().ok()
+            });
+    }
+    #[cfg(target_os = "macos")]
+    {
+        let ps = |field: &str| {
+            Command::new("ps")
+                .args(["-p", &pid.to_string(), "-o", field])
+                .output()
+                .ok()
+                .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+                .filter(|s| !s.is_empty())
+        };
+        holder.ppid = ps("ppid=").and_then(|p| p.parse().ok());
+        holder.exe = ps("comm=");
+        holder.cmdline = ps("args=");
+    }
+    #[cfg(unix)]
+    {
+        holder.started = process_start_time(pid);
+    }
+    #[cfg(target_os = "windows")]
+    {
+        let query = format!(
+            "Get-CimInstance Win32_Process -Filter 'ProcessId={pid}' | ForEach-Object {{ \"ppid=$($_.ParentProcessId)\"; \"start=$($_.CreationDate.ToFileTimeUtc())\"; \"exe=$($_.ExecutablePath)\"; \"cmd=$($_.CommandLine)\" }}"
+        );
+        if let Ok(output) = Command::new("powershell")
+            .args(["-NoProfile", "-NonInteractive", "-Command", &query])
+            .output()
+        {
+            for line in String::from_utf8_lossy(&output.stdout).lines() {
+                match line.split_once('=') {
+                    Some(("ppid", v)) => holder.ppid = v.trim().parse().ok(),
+                    Some(("start", v)) if !v.trim().is_empty() => {
+                        holder.started = Some(v.trim().into())
+                    }
+                    Some(("exe", v)) if !v.trim().is_empty() => holder.exe = Some(v.trim().into()),
+                    Some(("cmd", v)) if !v.trim().is_empty() => {
+                        holder.cmdline = Some(v.trim().into())
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+    holder
+}
+
+/// Identify the process behind `pid` and whether it is ours.
+pub(crate) fn describe_holder(pid: u32, owned: &OwnedProcesses) -> PortHolder {
+    let mut holder = read_process(pid);
+    holder.parent_started = holder
+        .ppid
+        .filter(|ppid| owned.spawned.iter().any(|s| s.pid == *ppid))
+        .and_then(process_start_time);
+    holder.name = holder
+        .exe
+        .as_deref()
+        .or_else(|| {
+            holder
+                .cmdline
+                .as_deref()
+                .and_then(|c| c.split_whitespace().next())
+        })
+        .and_then(|p| Path::new(p).file_name())
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| format!("pid {}", pid));
+    holder.ours = owned.owns(&holder);
+    holder
+}
+
+pub(crate) fn kill_pid(pid: u32) -> Result<(), String> {
     #[cfg(target_os = "windows")]
     {
         let output = Command::new("taskkill")
@@ -183,7 +563,13 @@ fn kill_pid(pid: u32) -> Result<(), String> {
     }
 }
 
-pub(crate) fn ensure_port_available(port: u16) -> Result<PortCleanupResult, PortCleanupError> {
+/// Free `port` by killing its listeners — only if every one of them is
+/// ours (`OwnedProcesses::owns`). Otherwise nothing is killed and the first
+/// foreign listener comes back as `ForeignHolder`.
+pub(crate) fn ensure_port_available(
+    port: u16,
+    owned: &OwnedProcesses,
+) -> Result<PortCleanupResult, PortCleanupError> {
     if !crate::core::probe_port_listening(port) {
         return Ok(PortCleanupResult {
             killed_pids: vec![],
@@ -195,6 +581,25 @@ pub(crate) fn ensure_port_available(port: u16) -> Result<PortCleanupResult, Port
     if pids.is_empty() {
         return Err(PortCleanupError::NoKillableProcess { port });
     }
+    for pid in &pids {
+        let holder = describe_holder(*pid, owned);
+        log::info!(
+            "[prestart] :{} held by pid={} ppid={:?} exe={:?} cmdline={:?} ours={}",
+            port,
+            holder.pid,
+            holder.ppid,
+            holder.exe,
+            holder.cmdline,
+            holder.ours
+        );
+        if !holder.ours {
+            return Err(PortCleanupError::ForeignHolder {
+                port,
+                pid: holder.pid,
+                name: holder.name,
+            });
+        }
+    }
 
     let mut killed_pids = Vec::new();
     let mut kill_errors = Vec::new();
@@ -240,21 +645,29 @@ pub fn prestart_check(app: tauri::AppHandle, port: Option<u16>) -> PrestartCheck
     } else {
         vec![]
     };
+    let owned = owned_processes(&app);
+    let holders: Vec<PortHolder> = orphan_pids
+        .iter()
+        .map(|pid| describe_holder(*pid, &owned))
+        .collect();
     log::info!(
-        "[prestart] check: port={} port_occupied={} orphan_pids={:?}",
+        "[prestart] check: port={} port_occupied={} orphan_pids={:?} holders={:?}",
         port,
         port_occupied,
-        orphan_pids
+        orphan_pids,
+        holders
     );
     PrestartCheckResult {
         port_occupied,
         orphan_pids,
+        holders,
     }
 }
 
 #[tauri::command]
 pub fn kill_orphans(app: tauri::AppHandle, port: Option<u16>) -> KillOrphansResult {
     let port = port.unwrap_or_else(|| crate::core::mixed_proxy_port(&app));
+    let owned = owned_processes(&app);
     let check = prestart_check(app, Some(port));
 
     if !check.port_occupied {
@@ -276,14 +689,15 @@ pub fn kill_orphans(app: tauri::AppHandle, port: Option<u16>) -> KillOrphansResu
         };
     }
 
-    let cleanup = ensure_port_available(port);
+    let cleanup = ensure_port_available(port, &owned);
     let (killed_pids, port_released, error_message) = match cleanup {
         Ok(result) => (result.killed_pids, result.port_released, None),
         Err(e) => {
             log::warn!("[prestart] kill_orphans failed: {}", e);
             let killed_pids = match &e {
                 PortCleanupError::PortStillOccupied { killed_pids, .. } => killed_pids.clone(),
-                PortCleanupError::NoKillableProcess { .. } => Vec::new(),
+                PortCleanupError::NoKillableProcess { .. }
+                | PortCleanupError::ForeignHolder { .. } => Vec::new(),
             };
             (killed_pids, false, Some(e.start_error()))
         }
@@ -313,8 +727,12 @@ pub fn kill_orphans(app: tauri::AppHandle, port: Option<u16>) -> KillOrphansResu
 
 #[cfg(test)]
 mod tests {
-    use super::ensure_port_available;
+    use super::{
+        ensure_port_available, parse_pid_file, OwnedProcesses, PidFile, PortCleanupError,
+        PortHolder, SpawnedProcess,
+    };
     use std::net::TcpListener;
+    use std::path::PathBuf;
 
     /// A port nobody listens on must be reported free without killing
     /// anything — the idempotent no-op path the start guard depends on.
@@ -327,8 +745,138 @@ mod tests {
             listener.local_addr().expect("local_addr").port()
         };
 
-        let result = ensure_port_available(port).expect("free port must be Ok");
+        let result =
+            ensure_port_available(port, &OwnedProcesses::default()).expect("free port must be Ok");
         assert!(result.killed_pids.is_empty(), "nothing should be killed");
         assert!(result.port_released, "free port must read as released");
     }
+
+    fn owned() -> OwnedProcesses {
+        OwnedProcesses {
+            sidecar: Some(PathBuf::from("/opt/OneBox/sing-box")),
+            spawned: vec![SpawnedProcess {
+                pid: 4242,
+                started: "boot-a:1000".into(),
+                exe: Some("/usr/bin/pkexec".into()),
+            }],
+        }
+    }
+
+    fn holder(pid: u32, ppid: Option<u32>, exe: Option<&str>, cmdline: Option<&str>) -> PortHolder {
+        PortHolder {
+            pid,
+            ppid,
+            exe: exe.map(str::to_string),
+            cmdline: cmdline.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    fn started(
+        mut holder: PortHolder,
+        started: &str,
+        parent: Option<&str>,
+        name: &str,
+    ) -> PortHolder {
+        holder.started = Some(started.into());
+        holder.parent_started = parent.map(str::to_string);
+        holder.name = name.into();
+        holder
+    }
+
+    #[test]
+    fn recorded_process_or_its_sing_box_child_is_ours() {
+        assert!(owned().owns(&started(
+            holder(4242, None, None, None),
+            "boot-a:1000",
+            None,
+            "pkexec"
+        )));
+        // Linux TUN: the recorded pid is pkexec, sing-box is its child.
+        assert!(owned().owns(&started(
+            holder(5000, Some(4242), None, Some("sing-box run")),
+            "boot-a:1001",
+            Some("boot-a:1000"),
+            "sing-box"
+        )));
+    }
+
+    #[test]
+    fn a_reused_pid_is_not_ours() {
+        // Same pid, different start time: after a reboot or a wrap-around.
+        assert!(!owned().owns(&started(
+            holder(4242, None, None, None),
+            "boot-b:1000",
+            None,
+            "postgres"
+        )));
+        // Same pid and start time but another executable.
+        assert!(!owned().owns(&started(
+            holder(4242, None, Some("/usr/bin/postgres"), None),
+            "boot-a:1000",
+            None,
+            "postgres"
+        )));
+        // No start time readable: never trusted on the pid alone.
+        assert!(!owned().owns(&holder(4242, None, None, None)));
+        // The parent pid matches but the parent was reused...
+        assert!(!owned().owns(&started(
+            holder(5000, Some(4242), None, Some("sing-box run")),
+            "boot-b:2000",
+            Some("boot-b:1000"),
+            "sing-box"
+        )));
+        // ...or the child of the recorded parent is not sing-box.
+        assert!(!owned().owns(&started(
+            holder(5000, Some(4242), Some("/usr/bin/node"), None),
+            "boot-a:1001",
+            Some("boot-a:1000"),
+            "node"
+        )));
+    }
+
+    #[test]
+    fn pid_file_round_trips_and_old_lines_are_dropped() {
+        let file = PidFile {
+            spawned: owned().spawned,
+            session: None,
+        };
+        let text = serde_json::to_string(&file).unwrap();
+        assert_eq!(parse_pid_file(&text), file);
+        assert_eq!(parse_pid_file("4242\tboot-a:1000\t\n"), PidFile::default());
+    }
+
+    #[test]
+    fn sidecar_binary_is_ours_even_without_a_pid_record() {
+        assert!(owned().owns(&holder(7, Some(1), Some("/opt/OneBox/sing-box"), None)));
+        // Root-owned process: exe unreadable, argv[0] still visible.
+        assert!(owned().owns(&holder(
+            7,
+            Some(1),
+            None,
+            Some("/opt/OneBox/sing-box run -c /tmp/config.json")
+        )));
+    }
+
+    #[test]
+    fn anything_else_is_foreign() {
+        assert!(!owned().owns(&holder(
+            9,
+            Some(1),
+            Some("/usr/bin/sing-box"),
+            Some("/usr/bin/sing-box run")
+        )));
+        assert!(!owned().owns(&holder(9, Some(1), Some("/usr/bin/node"), None)));
+        assert!(!OwnedProcesses::default().owns(&holder(9, None, None, None)));
+    }
+
+    #[test]
+    fn foreign_holder_error_names_the_process() {
+        let err = PortCleanupError::ForeignHolder {
+            port: 9191,
+            pid: 42,
+            name: "node".into(),
+        };
+        assert_eq!(err.start_error(), "PORT_HELD_BY_OTHER_PROCESS:9191:node");
+    }
 }
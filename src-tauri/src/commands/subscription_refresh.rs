@@ -0,0 +1,747 @@
+//! Background subscription auto-refresh.
+//!
+//! Every `TICK` the scheduler looks for subscriptions whose
+//! `last_update_time` is older than their interval (`subscriptions.
+//! refresh_interval`, falling back to the global default in
+//! `settings.json`), re-fetches them through
+//! `config_fetch::fetch_config_with_optimal_dns` and writes the result
+//! back the same way the frontend's `updateSubscription` does. The
+//! `subscription-userinfo` header updates traffic / expiry here, and
+//! `fetch_config_with_optimal_dns` itself reconciles local usage.
+//!
+//! A subscription rolled back with `config_history::rollback_subscription`
+//! is pinned (`subscriptions.pinned_version`) and skipped until
+//! `unpin_subscription` clears it.
+//!
+//! Failures back off exponentially: a transport error (offline laptop,
+//! captive portal) pauses the whole pass, a provider error only delays
+//! that one subscription.
+//!
+//! If the active subscription's content changed while sing-box runs, the
+//! dashboard is asked (via `subscription-refreshed`) to re-merge
+//! `config.json` and call `reload_config` — the merger lives in the
+//! frontend, so reloading from Rust alone would reload the stale file.
+//! With no dashboard open, `reload_with_subscription` swaps the server
+//! outbounds — the only part of the merged config that comes from the
+//! subscription — into the running config and reloads that instead.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{Row, SqlitePool};
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_store::StoreExt;
+
+use crate::engine::config_switch;
+use crate::engine::state_machine::EngineStateCell;
+
+const SETTINGS_STORE: &str = "settings.json";
+const AUTO_REFRESH_SETTINGS_KEY: &str = "subscription_auto_refresh";
+// Same keys the frontend writes (`src/types/definition.ts`).
+const SELECTED_SUBSCRIPTION_STORE_KEY: &str = "selected_subscription_identifier";
+const USER_AGENT_STORE_KEY: &str = "user_agent_key";
+const DEFAULT_OFFICIAL_WEBSITE: &str = "https://sing-box.net";
+
+/// How often the scheduler wakes up to look for due subscriptions.
+const TICK: Duration = Duration::from_secs(60);
+/// Delay before the first pass, so startup traffic settles first.
+const STARTUP_DELAY: Duration = Duration::from_secs(30);
+/// Lower bound for any interval, global or per subscription.
+pub const MIN_INTERVAL_SECS: u64 = 15 * 60;
+const BACKOFF_BASE_SECS: i64 = 60;
+const BACKOFF_MAX_SECS: i64 = 60 * 60;
+
+pub const EVENT_SUBSCRIPTION_REFRESHED: &str = "subscription-refreshed";
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct AutoRefreshSettings {
+    pub enabled: bool,
+    /// Default interval for subscriptions without their own.
+    pub interval_secs: u64,
+}
+
+impl Default for AutoRefreshSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_secs: 12 * 3600,
+        }
+    }
+}
+
+fn load_settings(app: &AppHandle) -> AutoRefreshSettings {
+    app.get_store(SETTINGS_STORE)
+        .and_then(|store| store.get(AUTO_REFRESH_SETTINGS_KEY))
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default()
+}
+
+// ── Scheduling ───────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Backoff {
+    failures: u32,
+    retry_at: i64,
+}
+
+impl Backoff {
+    fn after_failure(previous: Option<Backoff>, now: i64) -> Backoff {
+        let failures = previous.map_or(0, |b| b.failures).saturating_add(1);
+        let delay = BACKOFF_BASE_SECS
+            .saturating_mul(1 << (failures - 1).min(16))
+            .min(BACKOFF_MAX_SECS);
+        Backoff {
+            failures,
+            retry_at: now + delay,
+        }
+    }
+}
+
+/// Retry bookkeeping; in memory only, a restart starts fresh.
+#[derive(Default)]
+struct Scheduler {
+    /// Transport failures — the network itself is unusable.
+    offline: Option<Backoff>,
+    /// Failed attempts per identifier, whatever the cause — so one broken
+    /// subscription can't keep tripping the global pause for the rest.
+    failing: HashMap<String, Backoff>,
+}
+
+impl Scheduler {
+    fn is_due(&self, sub: &DueCandidate, default_interval: u64, now: i64) -> bool {
+        let interval = match sub.refresh_interval {
+            Some(0) => return false,
+            Some(secs) if secs > 0 => (secs as u64).max(MIN_INTERVAL_SECS),
+            _ => default_interval.max(MIN_INTERVAL_SECS),
+        };
+        if let Some(backoff) = self.failing.get(&sub.identifier) {
+            return now >= backoff.retry_at;
+        }
+        now - last_update_secs(sub.last_update_time) >= interval as i64
+    }
+
+    fn paused(&self, now: i64) -> bool {
+        self.offline.is_some_and(|b| now < b.retry_at)
+    }
+
+    fn network_failed(&mut self, now: i64) -> Backoff {
+        let backoff = Backoff::after_failure(self.offline, now);
+        self.offline = Some(backoff);
+        backoff
+    }
+
+    fn subscription_failed(&mut self, identifier: &str, now: i64) -> Backoff {
+        let backoff = Backoff::after_failure(self.failing.get(identifier).copied(), now);
+        self.failing.insert(identifier.to_string(), backoff);
+        backoff
+    }
+
+    fn succeeded(&mut self, identifier: &str) {
+        self.offline = None;
+        self.failing.remove(identifier);
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref SCHEDULER: Mutex<Scheduler> = Mutex::new(Scheduler::default());
+}
+
+/// The frontend writes `Date.now()` (ms); the column default is
+/// `strftime('%s')` (s). Normalise to seconds.
+pub(crate) fn last_update_secs(raw: i64) -> i64 {
+    if raw > 100_000_000_000 {
+        raw / 1000
+    } else {
+        raw
+    }
+}
+
+// ── Refresh ──────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone)]
+struct DueCandidate {
+    identifier: String,
+    url: String,
+    last_update_time: i64,
+    refresh_interval: Option<i64>,
+}
+
+async fn candidates(pool: &SqlitePool) -> Result<Vec<DueCandidate>, sqlx::Error> {
+    let rows = sqlx::query(
+        "SELECT identifier, subscription_url, last_update_time, refresh_interval \
+         FROM subscriptions WHERE pinned_version IS NULL ORDER BY last_update_time",
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .iter()
+        .filter_map(|row| {
+            let url: Option<String> = row.try_get("subscription_url").ok()?;
+            Some(DueCandidate {
+                identifier: row.try_get("identifier").ok()?,
+                // Local files are re-read by the frontend, not fetched.
+                url: url.filter(|u| {
+                    url::Url::parse(u)
+                        .is_ok_and(|p| matches!(p.scheme(), "http" | "https") && p.has_host())
+                })?,
+                last_update_time: row
+                    .try_get::<Option<i64>, _>("last_update_time")
+                    .ok()
+                    .flatten()
+                    .unwrap_or(0),
+                refresh_interval: row.try_get("refresh_interval").ok().flatten(),
+            })
+        })
+        .collect())
+}
+
+enum RefreshError {
+    /// Could not reach anything; retrying other subscriptions is pointless.
+    Network(String),
+    /// The provider answered, but not with a usable config.
+    Provider(String),
+}
+
+/// `Ok(true)` when the stored config content changed.
+async fn refresh_one(
+    app: &AppHandle,
+    pool: &SqlitePool,
+    sub: &DueCandidate,
+    user_agent: &str,
+) -> Result<bool, RefreshError> {
+    let response = super::config_fetch::fetch_config_with_optimal_dns(
+        app.clone(),
+        sub.url.clone(),
+        user_agent.to_string(),
+    )
+    .await
+    .map_err(RefreshError::Network)?;
+    let data = match response.data {
+        Some(data) if response.status == 200 => data,
+        _ => {
+            return Err(RefreshError::Provider(format!(
+                "HTTP {} without a usable config",
+                response.status
+            )))
+        }
+    };
+    let db_err = |e: sqlx::Error| RefreshError::Provider(format!("database: {}", e));
+
+    let official_website = response
+        .headers
+        .get("official-website")
+        .map(String::as_str)
+        .unwrap_or(DEFAULT_OFFICIAL_WEBSITE);
+    let now_ms = chrono::Local::now().timestamp_millis();
+    match response
+        .headers
+        .get("subscription-userinfo")
+        .and_then(|h| super::usage::parse_userinfo(h))
+    {
+        Some(info) => {
+            // Same units as the frontend: bytes, expire in ms.
+            sqlx::query(
+                "UPDATE subscriptions SET official_website = ?, used_traffic = ?, \
+                 total_traffic = ?, expire_time = ?, last_update_time = ? WHERE identifier = ?",
+            )
+            .bind(official_website)
+            .bind((info.upload + info.download) as i64)
+            .bind(info.total as i64)
+            .bind(info.expire.saturating_mul(1000))
+            .bind(now_ms)
+            .bind(&sub.identifier)
+            .execute(pool)
+            .await
+            .map_err(db_err)?;
+        }
+        None => {
+            sqlx::query(
+                "UPDATE subscriptions SET official_website = ?, last_update_time = ? \
+                 WHERE identifier = ?",
+            )
+            .bind(official_website)
+            .bind(now_ms)
+            .bind(&sub.identifier)
+            .execute(pool)
+            .await
+            .map_err(db_err)?;
+        }
+    }
+
+    let stored: Option<String> =
+        sqlx::query("SELECT config_content FROM subscription_configs WHERE identifier = ?")
+            .bind(&sub.identifier)
+            .fetch_optional(pool)
+            .await
+            .map_err(db_err)?
+            .and_then(|row| row.try_get("config_content").ok());
+    // Compare parsed values: key order of the stored text is the
+    // frontend's `JSON.stringify`, not ours.
+    let unchanged = stored
+        .as_deref()
+        .and_then(|text| serde_json::from_str::<serde_json::Value>(text).ok())
+        .is_some_and(|old| old == data);
+    if unchanged {
+        return Ok(false);
+    }
+    let content = data.to_string();
+    let updated =
+        sqlx::query("UPDATE subscription_configs SET config_content = ? WHERE identifier = ?")
+            .bind(&content)
+            .bind(&sub.identifier)
+            .execute(pool)
+            .await
+            .map_err(db_err)?;
+    if updated.rows_affected() == 0 {
+        sqlx::query("INSERT INTO subscription_configs (identifier, config_content) VALUES (?, ?)")
+            .bind(&sub.identifier)
+            .bind(&content)
+            .execute(pool)
+            .await
+            .map_err(db_err)?;
+    }
+    Ok(true)
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct SubscriptionRefreshed {
+    pub identifier: String,
+    pub changed: bool,
+    /// The active config changed while running: re-merge and reload.
+    pub reload: bool,
+}
+
+fn selected_subscription(app: &AppHandle) -> Option<String> {
+    app.get_store(SETTINGS_STORE)
+        .and_then(|store| store.get(SELECTED_SUBSCRIPTION_STORE_KEY))
+        .and_then(|v| v.as_str().map(str::to_string))
+}
+
+/// Whether `identifier` is the selected subscription and sing-box runs on it.
+pub(crate) fn is_active_and_running(app: &AppHandle, identifier: &str) -> bool {
+    selected_subscription(app).as_deref() == Some(identifier)
+        && app.state::<EngineStateCell>().snapshot().is_running()
+}
+
+/// Server outbounds as `updateVPNServerConfigFromDB` picks them from a
+/// subscription: everything but groups, `direct`, `block` and `dns`.
+fn is_server(outbound: &Value) -> bool {
+    !matches!(
+        outbound.get("type").and_then(Value::as_str),
+        Some("selector" | "urltest" | "direct" | "block" | "dns")
+    )
+}
+
+/// Replace the servers in a merged config with `subscription`'s, the way
+/// `updateVPNServerConfigFromDB` (`src/config/merger/helper.ts`) adds them
+/// to a template: appended to `outbounds`, listed in the selector (index 1)
+/// and urltest (index 2) groups, duplicate tags dropped. The servers being
+/// replaced are the ones the urltest group lists.
+fn splice_servers(config: &mut Value, subscription: &Value) -> Result<(), String> {
+    const SELECTOR: usize = 1;
+    const URLTEST: usize = 2;
+    let servers = subscription
+        .get("outbounds")
+        .and_then(Value::as_array)
+        .ok_or("subscription_config_missing")?;
+    let outbounds = config
+        .get_mut("outbounds")
+        .and_then(Value::as_array_mut)
+        .filter(|outbounds| outbounds.len() > URLTEST)
+        .ok_or("config has no selector / urltest outbound groups")?;
+    let members = |outbounds: &mut Vec<Value>, index: usize| -> Result<Vec<Value>, String> {
+        outbounds[index]
+            .get_mut("outbounds")
+            .and_then(Value::as_array_mut)
+            .map(std::mem::take)
+            .ok_or_else(|| format!("outbound group #{} has no members", index))
+    };
+    let old = members(outbounds, URLTEST)?;
+    let mut selector = members(outbounds, SELECTOR)?;
+    outbounds.retain(|ob| !(is_server(ob) && ob.get("tag").is_some_and(|tag| old.contains(tag))));
+    selector.retain(|tag| !old.contains(tag));
+
+    let mut urltest = Vec::new();
+    for server in servers.iter().filter(|ob| is_server(ob)) {
+        let Some(tag) = server.get("tag").filter(|tag| !urltest.contains(*tag)) else {
+            continue;
+        };
+        let mut server = server.clone();
+        server["domain_resolver"] = "system".into();
+        selector.push(tag.clone());
+        urltest.push(tag.clone());
+        outbounds.push(server);
+    }
+    outbounds[SELECTOR]["outbounds"] = Value::Array(selector);
+    outbounds[URLTEST]["outbounds"] = Value::Array(urltest);
+    Ok(())
+}
+
+/// Stage the running config with `identifier`'s stored servers spliced in
+/// and reload onto it (`core::reload_config`, which reverts to the
+/// last-known-good config if the result doesn't come up).
+pub(crate) async fn reload_with_subscription(
+    app: &AppHandle,
+    identifier: &str,
+) -> Result<String, String> {
+    let live = crate::core::ProcessManager::acquire()
+        .config_path
+        .as_ref()
+        .map(|path| (**path).clone())
+        .ok_or("No running config path found")?;
+    let pool = crate::app::database::sqlite_pool(app).await?;
+    let content: Option<String> =
+        sqlx::query("SELECT config_content FROM subscription_configs WHERE identifier = ?")
+            .bind(identifier)
+            .fetch_optional(&pool)
+            .await
+            .map_err(|e| e.to_string())?
+            .and_then(|row| row.try_get("config_content").ok());
+    let subscription: Value = serde_json::from_str(&content.ok_or("subscription_config_missing")?)
+        .map_err(|e| e.to_string())?;
+    let mut config: Value = std::fs::read_to_string(&live)
+        .map_err(|e| format!("failed to read {}: {}", live, e))
+        .and_then(|text| serde_json::from_str(&text).map_err(|e| e.to_string()))?;
+    splice_servers(&mut config, &subscription)?;
+    let staging = config_switch::staging_path(&live);
+    config_switch::write_atomic(&staging, config.to_string().as_bytes())
+        .map_err(|e| format!("failed to write {}: {}", staging.display(), e))?;
+    crate::core::reload_config(app.clone()).await
+}
+
+/// Tell the dashboard a subscription's stored config was rewritten
+/// (`changed`) or merely re-checked; asks for a re-merge + reload when it
+/// is the active one and sing-box is running. Without a dashboard the
+/// reload happens here (`reload_with_subscription`).
+pub(crate) async fn notify_refreshed(app: &AppHandle, identifier: &str, changed: bool) {
+    let mut reload = changed && is_active_and_running(app, identifier);
+    if reload && app.get_webview_window("main").is_none() {
+        match reload_with_subscription(app, identifier).await {
+            Ok(_) => log::info!(
+                "[sub-refresh] reloaded onto the refreshed servers of {}",
+                identifier
+            ),
+            Err(e) => log::warn!("[sub-refresh] reload for {} failed: {}", identifier, e),
+        }
+        reload = false;
+    }
+    emit_refreshed(app, identifier, changed, reload);
+}
+
+pub(crate) fn emit_refreshed(app: &AppHandle, identifier: &str, changed: bool, reload: bool) {
+    let _ = app.emit(
+        EVENT_SUBSCRIPTION_REFRESHED,
+        SubscriptionRefreshed {
+            identifier: identifier.to_string(),
+            changed,
+            reload,
+        },
+    );
+}
+
+async fn run_pass(app: &AppHandle, settings: &AutoRefreshSettings) {
+    let now = chrono::Local::now().timestamp();
+    if SCHEDULER
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .paused(now)
+    {
+        return;
+    }
+    let pool = match crate::app::database::sqlite_pool(app).await {
+        Ok(pool) => pool,
+        Err(e) => {
+            log::debug!("[sub-refresh] skipped: {}", e);
+            return;
+        }
+    };
+    let due: Vec<DueCandidate> = match candidates(&pool).await {
+        Ok(all) => {
+            let scheduler = SCHEDULER.lock().unwrap_or_else(|e| e.into_inner());
+            all.into_iter()
+                .filter(|sub| scheduler.is_due(sub, settings.interval_secs, now))
+                .collect()
+        }
+        Err(e) => {
+            log::warn!("[sub-refresh] failed to list subscriptions: {}", e);
+            return;
+        }
+    };
+    if due.is_empty() {
+        return;
+    }
+    let user_agent = user_agent(app).await;
+    for sub in due {
+        log::info!("[sub-refresh] refreshing {}", sub.identifier);
+        let result = refresh_one(app, &pool, &sub, &user_agent).await;
+        let now = chrono::Local::now().timestamp();
+        match result {
+            Ok(changed) => {
+                SCHEDULER
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .succeeded(&sub.identifier);
+                log::info!(
+                    "[sub-refresh] {} refreshed changed={}",
+                    sub.identifier,
+                    changed
+                );
+                notify_refreshed(app, &sub.identifier, changed).await;
+            }
+            Err(RefreshError::Network(e)) => {
+                let backoff = {
+                    let mut scheduler = SCHEDULER.lock().unwrap_or_else(|e| e.into_inner());
+                    scheduler.subscription_failed(&sub.identifier, now);
+                    scheduler.network_failed(now)
+                };
+                log::warn!(
+                    "[sub-refresh] {} unreachable ({}); pausing for {}s",
+                    sub.identifier,
+                    e,
+                    backoff.retry_at - now
+                );
+                return;
+            }
+            Err(RefreshError::Provider(e)) => {
+                let backoff = SCHEDULER
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .subscription_failed(&sub.identifier, now);
+                log::warn!(
+                    "[sub-refresh] {} failed ({}); retry #{} in {}s",
+                    sub.identifier,
+                    e,
+                    backoff.failures,
+                    backoff.retry_at - now
+                );
+            }
+        }
+    }
+}
+
+/// Call once during app setup.
+pub fn spawn_subscription_refresh_task(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(STARTUP_DELAY).await;
+        loop {
+            let settings = load_settings(&app);
+            if settings.enabled {
+                run_pass(&app, &settings).await;
+            }
+            tokio::time::sleep(TICK).await;
+        }
+    });
+}
+
+// ── User-Agent ───────────────────────────────────────────────────────
+
+static SING_BOX_VERSION: tokio::sync::OnceCell<String> = tokio::sync::OnceCell::const_new();
+
+/// `sing-box version` prints `sing-box version 1.13.13` on its first line.
+fn parse_sing_box_version(output: &str) -> Option<String> {
+    output
+        .lines()
+        .next()?
+        .trim()
+        .strip_prefix("sing-box version ")
+        .map(|v| v.trim().to_string())
+}
+
+/// Mirror of `getSingBoxUserAgent` in `src/utils/helper.ts`, so providers
+/// see the same client whether the dashboard or the scheduler fetches.
+async fn user_agent(app: &AppHandle) -> String {
+    let custom = app
+        .get_store(SETTINGS_STORE)
+        .and_then(|store| store.get(USER_AGENT_STORE_KEY))
+        .and_then(|v| v.as_str().map(|s| s.trim().to_string()))
+        .filter(|s| !s.is_empty() && s != "default");
+    if let Some(ua) = custom {
+        return ua;
+    }
+    let sing_box = SING_BOX_VERSION
+        .get_or_init(|| async {
+            crate::commands::shell::version(app.clone())
+                .await
+                .ok()
+                .and_then(|out| parse_sing_box_version(&out))
+                .unwrap_or_else(|| "unknown".into())
+        })
+        .await;
+    let os_type = tauri_plugin_os::type_().to_string();
+    let prefix = match os_type.as_str() {
+        "linux" => "SFL",
+        "macos" => "SFM",
+        _ => "SFW",
+    };
+    format!(
+        "{}/{} ({} {} {}; sing-box {}; language {})",
+        prefix,
+        app.package_info().version,
+        os_type,
+        tauri_plugin_os::arch(),
+        tauri_plugin_os::version(),
+        sing_box,
+        tauri_plugin_os::locale().unwrap_or_else(|| "en-US".into()),
+    )
+}
+
+// ── Commands ─────────────────────────────────────────────────────────
+
+/// Set one subscription's auto-refresh interval. `None` follows the
+/// global default, `Some(0)` disables auto-refresh for it.
+#[tauri::command]
+pub async fn set_subscription_refresh_interval(
+    app: AppHandle,
+    identifier: String,
+    interval_secs: Option<u64>,
+) -> Result<(), String> {
+    if let Some(secs) = interval_secs {
+        if secs != 0 && secs < MIN_INTERVAL_SECS {
+            return Err(format!(
+                "interval must be 0 or at least {}s",
+                MIN_INTERVAL_SECS
+            ));
+        }
+    }
+    let pool = crate::app::database::sqlite_pool(&app).await?;
+    let result = sqlx::query("UPDATE subscriptions SET refresh_interval = ? WHERE identifier = ?")
+        .bind(interval_secs.map(|s| s as i64))
+        .bind(&identifier)
+        .execute(&pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    if result.rows_affected() == 0 {
+        return Err(format!("subscription {} not found", identifier));
+    }
+    SCHEDULER
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .failing
+        .remove(&identifier);
+    Ok(())
+}
+
+#[cfg(test)]
+mod subscription_refresh_tests {
+    use super::*;
+
+    fn candidate(last_update_time: i64, refresh_interval: Option<i64>) -> DueCandidate {
+        DueCandidate {
+            identifier: "a".into(),
+            url: "https://example.com/sub".into(),
+            last_update_time,
+            refresh_interval,
+        }
+    }
+
+    #[test]
+    fn due_after_interval_in_seconds_or_millis() {
+        let scheduler = Scheduler::default();
+        let now = 1_700_000_000;
+        let hour = 3600;
+        // Seconds (column default) and milliseconds (frontend) agree.
+        assert!(!scheduler.is_due(&candidate(now - hour, None), 2 * 3600, now));
+        assert!(scheduler.is_due(&candidate(now - 3 * hour, None), 2 * 3600, now));
+        assert!(!scheduler.is_due(&candidate((now - hour) * 1000, None), 2 * 3600, now));
+        assert!(scheduler.is_due(&candidate((now - 3 * hour) * 1000, None), 2 * 3600, now));
+    }
+
+    #[test]
+    fn per_subscription_interval_overrides_default() {
+        let scheduler = Scheduler::default();
+        let now = 1_700_000_000;
+        let sub = candidate(now - 2 * 3600, Some(3600));
+        assert!(scheduler.is_due(&sub, 24 * 3600, now));
+        // 0 disables; tiny values are clamped to the minimum.
+        assert!(!scheduler.is_due(&candidate(0, Some(0)), 60, now));
+        assert!(!scheduler.is_due(&candidate(now - 60, Some(1)), 60, now));
+        assert!(scheduler.is_due(&candidate(now - MIN_INTERVAL_SECS as i64, Some(1)), 60, now));
+    }
+
+    #[test]
+    fn provider_failures_back_off_per_subscription() {
+        let mut scheduler = Scheduler::default();
+        let now = 1_700_000_000;
+        let sub = candidate(0, None);
+        let first = scheduler.subscription_failed("a", now);
+        assert_eq!(first.retry_at, now + BACKOFF_BASE_SECS);
+        assert!(!scheduler.is_due(&sub, 3600, now + 1));
+        assert!(scheduler.is_due(&sub, 3600, first.retry_at));
+        let second = scheduler.subscription_failed("a", now);
+        assert_eq!(second.retry_at, now + 2 * BACKOFF_BASE_SECS);
+        for _ in 0..20 {
+            scheduler.subscription_failed("a", now);
+        }
+        assert_eq!(scheduler.failing["a"].retry_at, now + BACKOFF_MAX_SECS);
+        scheduler.succeeded("a");
+        assert!(scheduler.is_due(&sub, 3600, now));
+    }
+
+    #[test]
+    fn network_failures_pause_the_pass() {
+        let mut scheduler = Scheduler::default();
+        let now = 1_700_000_000;
+        assert!(!scheduler.paused(now));
+        let first = scheduler.network_failed(now);
+        assert!(scheduler.paused(now + 1));
+        assert!(!scheduler.paused(first.retry_at));
+        let second = scheduler.network_failed(first.retry_at);
+        assert_eq!(second.failures, 2);
+        assert_eq!(second.retry_at, first.retry_at + 2 * BACKOFF_BASE_SECS);
+        scheduler.succeeded("a");
+        assert!(!scheduler.paused(first.retry_at));
+    }
+
+    #[test]
+    fn parses_sing_box_version_output() {
+        let out = "sing-box version 1.13.13\n\nEnvironment: go1.24.1 linux/amd64\n";
+        assert_eq!(parse_sing_box_version(out).as_deref(), Some("1.13.13"));
+        assert_eq!(parse_sing_box_version("garbage"), None);
+    }
+
+    #[test]
+    fn splice_replaces_only_the_subscription_servers() {
+        let mut config = serde_json::json!({ "outbounds": [
+            { "type": "direct", "tag": "direct" },
+            { "type": "selector", "tag": "ExitGateway", "outbounds": ["auto", "hk-01", "jp-01"] },
+            { "type": "urltest", "tag": "auto", "outbounds": ["hk-01", "jp-01"] },
+            { "type": "trojan", "tag": "hk-01", "domain_resolver": "system" },
+            { "type": "vmess", "tag": "jp-01", "domain_resolver": "system" },
+        ]});
+        let subscription = serde_json::json!({ "outbounds": [
+            { "type": "selector", "tag": "proxy", "outbounds": ["sg-01"] },
+            { "type": "direct", "tag": "direct" },
+            { "type": "trojan", "tag": "sg-01" },
+            { "type": "trojan", "tag": "sg-01" },
+            { "type": "dns", "tag": "dns-out" },
+        ]});
+        splice_servers(&mut config, &subscription).unwrap();
+        assert_eq!(
+            config["outbounds"],
+            serde_json::json!([
+                { "type": "direct", "tag": "direct" },
+                { "type": "selector", "tag": "ExitGateway", "outbounds": ["auto", "sg-01"] },
+                { "type": "urltest", "tag": "auto", "outbounds": ["sg-01"] },
+                { "type": "trojan", "tag": "sg-01", "domain_resolver": "system" },
+            ])
+        );
+        assert_eq!(
+            splice_servers(&mut config, &serde_json::json!({})).unwrap_err(),
+            "subscription_config_missing"
+        );
+    }
+
+    #[test]
+    fn settings_default_when_fields_missing() {
+        let s: AutoRefreshSettings = serde_json::from_value(serde_json::json!({
+            "interval_secs": 3600
+        }))
+        .unwrap();
+        assert!(s.enabled);
+        assert_eq!(s.interval_secs, 3600);
+    }
+}
@@ -0,0 +1,675 @@
+//! Local per-subscription usage accounting.
+//!
+//! `core::traffic` reports each engine session's running byte totals here
+//! (`note_session_totals`); the deltas are attributed to the subscription
+//! that was selected when the session started and flushed every
+//! `FLUSH_INTERVAL` into daily buckets (`subscription_usage_daily`,
+//! migration v3 in `app::database`).
+//!
+//! Provider numbers only arrive with a refresh: `fetch_config_with_optimal_dns`
+//! hands the `subscription-userinfo` header to `reconcile`, which snapshots
+//! it next to the local total at that moment (`subscription_usage_sync`).
+//! Between refreshes the estimate is `provider_used + local bytes since the
+//! snapshot`; alerts fire when that approaches `total_traffic`, or when
+//! `expire_time` is near.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, SqlitePool};
+use tauri::{AppHandle, Emitter};
+use tauri_plugin_store::StoreExt;
+
+use crate::engine::state_machine::now_secs;
+
+const SETTINGS_STORE: &str = "settings.json";
+/// Same key the frontend writes (`SSI_STORE_KEY` in `src/types/definition.ts`).
+const SELECTED_SUBSCRIPTION_STORE_KEY: &str = "selected_subscription_identifier";
+const USAGE_ALERT_SETTINGS_KEY: &str = "usage_alerts";
+const FLUSH_INTERVAL: Duration = Duration::from_secs(30);
+const DEFAULT_QUERY_DAYS: u32 = 30;
+pub const EVENT_USAGE_ALERT: &str = "subscription-usage-alert";
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct UsageAlertSettings {
+    pub enabled: bool,
+    /// Alert once estimated usage reaches this share of `total_traffic`.
+    pub traffic_ratio: f64,
+    /// Alert once the subscription expires within this many days.
+    pub expire_within_days: u64,
+}
+
+impl Default for UsageAlertSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            traffic_ratio: 0.9,
+            expire_within_days: 3,
+        }
+    }
+}
+
+fn load_alert_settings(app: &AppHandle) -> UsageAlertSettings {
+    app.get_store(SETTINGS_STORE)
+        .and_then(|store| store.get(USAGE_ALERT_SETTINGS_KEY))
+        .and_then(|value| serde_json::from_value(value).ok())
+        .unwrap_or_default()
+}
+
+fn selected_subscription(app: &AppHandle) -> Option<String> {
+    app.get_store(SETTINGS_STORE)
+        .and_then(|store| store.get(SELECTED_SUBSCRIPTION_STORE_KEY))
+        .and_then(|v| v.as_str().map(str::to_string))
+        .filter(|s| !s.is_empty())
+}
+
+// ── Metering ─────────────────────────────────────────────────────────
+
+/// Turns cumulative per-session totals into per-(subscription, day) deltas.
+#[derive(Default)]
+struct Meter {
+    epoch: u64,
+    identifier: Option<String>,
+    last_upload: u64,
+    last_download: u64,
+    /// Not yet flushed: (identifier, day) → (upload, download).
+    pending: HashMap<(String, String), (u64, u64)>,
+}
+
+impl Meter {
+    /// `identifier` is only consulted when a new session shows up, so a
+    /// subscription switch mid-session doesn't split its bytes.
+    fn record(
+        &mut self,
+        epoch: u64,
+        identifier: impl FnOnce() -> Option<String>,
+        upload: u64,
+        download: u64,
+        day: &str,
+    ) {
+        if epoch != self.epoch {
+            self.epoch = epoch;
+            self.identifier = identifier();
+            self.last_upload = 0;
+            self.last_download = 0;
+        }
+        let up = upload.saturating_sub(self.last_upload);
+        let down = download.saturating_sub(self.last_download);
+        self.last_upload = self.last_upload.max(upload);
+        self.last_download = self.last_download.max(download);
+        let Some(identifier) = &self.identifier else {
+            return;
+        };
+        if up == 0 && down == 0 {
+            return;
+        }
+        let slot = self
+            .pending
+            .entry((identifier.clone(), day.to_string()))
+            .or_default();
+        slot.0 += up;
+        slot.1 += down;
+    }
+}
+
+static METER: Mutex<Option<Meter>> = Mutex::new(None);
+
+/// Called by `core::traffic` with each sample's session totals.
+pub(crate) fn note_session_totals(app: &AppHandle, epoch: u64, upload: u64, download: u64) {
+    let mut guard = METER.lock().unwrap_or_else(|e| e.into_inner());
+    guard.get_or_insert_with(Meter::default).record(
+        epoch,
+        || selected_subscription(app),
+        upload,
+        download,
+        &today(),
+    );
+}
+
+fn take_pending() -> HashMap<(String, String), (u64, u64)> {
+    METER
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_mut()
+        .map(|m| std::mem::take(&mut m.pending))
+        .unwrap_or_default()
+}
+
+/// Put back what a failed flush couldn't write.
+fn restore_pending(pending: HashMap<(String, String), (u64, u64)>) {
+    let mut guard = METER.lock().unwrap_or_else(|e| e.into_inner());
+    let meter = guard.get_or_insert_with(Meter::default);
+    for (key, (up, down)) in pending {
+        let slot = meter.pending.entry(key).or_default();
+        slot.0 += up;
+        slot.1 += down;
+    }
+}
+
+async fn add_daily(
+    pool: &SqlitePool,
+    identifier: &str,
+    day: &str,
+    upload: u64,
+    download: u64,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO subscription_usage_daily (identifier, day, upload, download) \
+         VALUES (?, ?, ?, ?) \
+         ON CONFLICT(identifier, day) DO UPDATE SET \
+         upload = upload + excluded.upload, download = download + excluded.download",
+    )
+    .bind(identifier)
+    .bind(day)
+    .bind(upload as i64)
+    .bind(download as i64)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub fn spawn_usage_meter(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(FLUSH_INTERVAL).await;
+            let pending = take_pending();
+            if pending.is_empty() {
+                continue;
+            }
+            let pool = match crate::app::database::sqlite_pool(&app).await {
+                Ok(pool) => pool,
+                Err(e) => {
+                    log::warn!("[usage] db unavailable, keeping counters: {}", e);
+                    restore_pending(pending);
+                    continue;
+                }
+            };
+            let mut touched = HashSet::new();
+            let mut failed = HashMap::new();
+            for ((identifier, day), (up, down)) in pending {
+                match add_daily(&pool, &identifier, &day, up, down).await {
+                    Ok(()) => {
+                        touched.insert(identifier);
+                    }
+                    Err(e) => {
+                        log::warn!("[usage] flush failed for {}: {}", identifier, e);
+                        failed.insert((identifier, day), (up, down));
+                    }
+                }
+            }
+            restore_pending(failed);
+            for identifier in touched {
+                check_alerts(&app, &pool, &identifier).await;
+            }
+        }
+    });
+}
+
+// ── Reconciliation ───────────────────────────────────────────────────
+
+/// Parsed `subscription-userinfo`
+/// (`upload=1; download=2; total=3; expire=4`). Missing fields are 0.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct ProviderInfo {
+    pub(crate) upload: u64,
+    pub(crate) download: u64,
+    pub(crate) total: u64,
+    pub(crate) expire: i64,
+}
+
+pub(crate) fn parse_userinfo(header: &str) -> Option<ProviderInfo> {
+    let mut info = ProviderInfo::default();
+    let mut seen = false;
+    for part in header.split(';') {
+        let Some((key, value)) = part.split_once('=') else {
+            continue;
+        };
+        // Some providers send floats ("1.5e9"); truncate rather than drop.
+        let value = value.trim();
+        let number = value
+            .parse::<u64>()
+            .ok()
+            .or_else(|| value.parse::<f64>().ok().map(|f| f.max(0.0) as u64));
+        let Some(number) = number else {
+            continue;
+        };
+        match key.trim() {
+            "upload" => info.upload = number,
+            "download" => info.download = number,
+            "total" => info.total = number,
+            "expire" => info.expire = number as i64,
+            _ => continue,
+        }
+        seen = true;
+    }
+    seen.then_some(info)
+}
+
+async fn local_total(pool: &SqlitePool, identifier: &str) -> Result<i64, sqlx::Error> {
+    sqlx::query(
+        "SELECT COALESCE(SUM(upload + download), 0) AS total \
+         FROM subscription_usage_daily WHERE identifier = ?",
+    )
+    .bind(identifier)
+    .fetch_one(pool)
+    .await?
+    .try_get("total")
+}
+
+async fn store_sync(
+    pool: &SqlitePool,
+    identifier: &str,
+    info: &ProviderInfo,
+    now: i64,
+) -> Result<(), sqlx::Error> {
+    let local = local_total(pool, identifier).await?;
+    let previous = sqlx::query(
+        "SELECT provider_used, local_total FROM subscription_usage_sync WHERE identifier = ?",
+    )
+    .bind(identifier)
+    .fetch_optional(pool)
+    .await?;
+    let provider_used = (info.upload + info.download) as i64;
+    if let Some(previous) = previous {
+        let provider_delta = provider_used - previous.try_get::<i64, _>("provider_used")?;
+        let local_delta = local - previous.try_get::<i64, _>("local_total")?;
+        log::info!(
+            "[usage] reconcile {}: provider +{} bytes, local +{} bytes since last refresh",
+            identifier,
+            provider_delta,
+            local_delta
+        );
+    }
+    sqlx::query(
+        "INSERT INTO subscription_usage_sync \
+         (identifier, provider_used, provider_total, expire_time, local_total, synced_at) \
+         VALUES (?, ?, ?, ?, ?, ?) \
+         ON CONFLICT(identifier) DO UPDATE SET \
+         provider_used = excluded.provider_used, provider_total = excluded.provider_total, \
+         expire_time = excluded.expire_time, local_total = excluded.local_total, \
+         synced_at = excluded.synced_at",
+    )
+    .bind(identifier)
+    .bind(provider_used)
+    .bind(info.total as i64)
+    .bind(info.expire)
+    .bind(local)
+    .bind(now)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Snapshot the provider's numbers for the subscription fetched from `url`.
+/// Called after a successful fetch; imports of not-yet-saved subscriptions
+/// (no matching row) are skipped. Errors are logged, never surfaced — a
+/// refresh must not fail because of accounting.
+pub(crate) async fn reconcile(app: &AppHandle, url: &str, userinfo: Option<&str>) {
+    let Some(info) = userinfo.and_then(parse_userinfo) else {
+        return;
+    };
+    let pool = match crate::app::database::sqlite_pool(app).await {
+        Ok(pool) => pool,
+        Err(e) => {
+            log::warn!("[usage] reconcile skipped: {}", e);
+            return;
+        }
+    };
+    let identifiers: Vec<String> =
+        match sqlx::query("SELECT identifier FROM subscriptions WHERE subscription_url = ?")
+            .bind(url)
+            .fetch_all(&pool)
+            .await
+        {
+            Ok(rows) => rows
+                .iter()
+                .filter_map(|r| r.try_get("identifier").ok())
+                .collect(),
+            Err(e) => {
+                log::warn!("[usage] reconcile lookup failed: {}", e);
+                return;
+            }
+        };
+    for identifier in identifiers {
+        if let Err(e) = store_sync(&pool, &identifier, &info, now_secs()).await {
+            log::warn!("[usage] reconcile failed for {}: {}", identifier, e);
+            continue;
+        }
+        forget_alerts(&identifier);
+        check_alerts(app, &pool, &identifier).await;
+    }
+}
+
+// ── Queries & alerts ─────────────────────────────────────────────────
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct DailyUsage {
+    pub day: String,
+    pub upload: i64,
+    pub download: i64,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SubscriptionUsage {
+    pub identifier: String,
+    /// Newest first.
+    pub daily: Vec<DailyUsage>,
+    /// Everything metered locally for this subscription.
+    pub local_total: i64,
+    /// From the last reconcile, or the `subscriptions` row if there was none.
+    pub provider_used: i64,
+    pub provider_total: i64,
+    pub expire_time: i64,
+    /// `provider_used` plus what was metered locally since it was reported.
+    pub estimated_used: i64,
+    pub synced_at: Option<i64>,
+}
+
+async fn usage(
+    pool: &SqlitePool,
+    identifier: &str,
+    days: u32,
+) -> Result<SubscriptionUsage, sqlx::Error> {
+    let daily = sqlx::query(
+        "SELECT day, upload, download FROM subscription_usage_daily \
+         WHERE identifier = ? ORDER BY day DESC LIMIT ?",
+    )
+    .bind(identifier)
+    .bind(days as i64)
+    .fetch_all(pool)
+    .await?
+    .iter()
+    .map(|row| {
+        Ok(DailyUsage {
+            day: row.try_get("day")?,
+            upload: row.try_get("upload")?,
+            download: row.try_get("download")?,
+        })
+    })
+    .collect::<Result<Vec<_>, sqlx::Error>>()?;
+    let local = local_total(pool, identifier).await?;
+
+    let sync = sqlx::query(
+        "SELECT provider_used, provider_total, expire_time, local_total, synced_at \
+         FROM subscription_usage_sync WHERE identifier = ?",
+    )
+    .bind(identifier)
+    .fetch_optional(pool)
+    .await?;
+    let (provider_used, provider_total, expire_time, local_at_sync, synced_at) = match sync {
+        Some(row) => (
+            row.try_get("provider_used")?,
+            row.try_get("provider_total")?,
+            row.try_get("expire_time")?,
+            row.try_get("local_total")?,
+            Some(row.try_get("synced_at")?),
+        ),
+        None => {
+            let row = sqlx::query(
+                "SELECT used_traffic, total_traffic, expire_time \
+                 FROM subscriptions WHERE identifier = ?",
+            )
+            .bind(identifier)
+            .fetch_optional(pool)
+            .await?;
+            match row {
+                Some(row) => (
+                    row.try_get::<Option<i64>, _>("used_traffic")?.unwrap_or(0),
+                    row.try_get::<Option<i64>, _>("total_traffic")?.unwrap_or(0),
+                    row.try_get::<Option<i64>, _>("expire_time")?.unwrap_or(0),
+                    // No snapshot: assume the provider number already
+                    // includes everything metered so far.
+                    local,
+                    None,
+                ),
+                None => (0, 0, 0, local, None),
+            }
+        }
+    };
+
+    Ok(SubscriptionUsage {
+        identifier: identifier.to_string(),
+        daily,
+        local_total: local,
+        provider_used,
+        provider_total,
+        expire_time,
+        estimated_used: provider_used + (local - local_at_sync).max(0),
+        synced_at,
+    })
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum AlertKind {
+    Traffic,
+    Expire,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct UsageAlert {
+    pub identifier: String,
+    pub kind: AlertKind,
+    pub estimated_used: i64,
+    pub total_traffic: i64,
+    pub expire_time: i64,
+}
+
+fn due_alerts(
+    settings: &UsageAlertSettings,
+    usage: &SubscriptionUsage,
+    now: i64,
+) -> Vec<AlertKind> {
+    let mut due = Vec::new();
+    if !settings.enabled {
+        return due;
+    }
+    // The schema default `total_traffic = 1` means "unknown", not 1 byte.
+    if usage.provider_total > 1
+        && usage.estimated_used as f64 >= usage.provider_total as f64 * settings.traffic_ratio
+    {
+        due.push(AlertKind::Traffic);
+    }
+    if usage.expire_time > 0
+        && usage.expire_time - now <= (settings.expire_within_days * 86400) as i64
+    {
+        due.push(AlertKind::Expire);
+    }
+    due
+}
+
+lazy_static::lazy_static! {
+    /// Alerts already raised, so each fires once per reconcile.
+    static ref RAISED: Mutex<HashSet<(String, AlertKind)>> = Mutex::new(HashSet::new());
+}
+
+fn forget_alerts(identifier: &str) {
+    RAISED
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .retain(|(id, _)| id != identifier);
+}
+
+async fn check_alerts(app: &AppHandle, pool: &SqlitePool, identifier: &str) {
+    let usage = match usage(pool, identifier, 0).await {
+        Ok(usage) => usage,
+        Err(e) => {
+            log::warn!("[usage] alert check failed for {}: {}", identifier, e);
+            return;
+        }
+    };
+    for kind in due_alerts(&load_alert_settings(app), &usage, now_secs()) {
+        let fresh = RAISED
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert((identifier.to_string(), kind));
+        if !fresh {
+            continue;
+        }
+        log::warn!(
+            "[usage] {:?} alert for {}: used≈{} total={} expire={}",
+            kind,
+            identifier,
+            usage.estimated_used,
+            usage.provider_total,
+            usage.expire_time
+        );
+        let _ = app.emit(
+            EVENT_USAGE_ALERT,
+            UsageAlert {
+                identifier: identifier.to_string(),
+                kind,
+                estimated_used: usage.estimated_used,
+                total_traffic: usage.provider_total,
+                expire_time: usage.expire_time,
+            },
+        );
+    }
+}
+
+fn today() -> String {
+    chrono::Local::now().format("%Y-%m-%d").to_string()
+}
+
+/// Local accounting for one subscription; `days` limits the daily buckets
+/// returned (default 30), totals always cover everything.
+#[tauri::command]
+pub async fn get_subscription_usage(
+    app: AppHandle,
+    identifier: String,
+    days: Option<u32>,
+) -> Result<SubscriptionUsage, String> {
+    let pool = crate::app::database::sqlite_pool(&app).await?;
+    usage(&pool, &identifier, days.unwrap_or(DEFAULT_QUERY_DAYS))
+        .await
+        .map_err(|e| format!("usage query failed: {}", e))
+}
+
+#[cfg(test)]
+mod usage_tests {
+    use super::*;
+    use crate::app::database::test_pool;
+
+    #[test]
+    fn meter_turns_session_totals_into_deltas() {
+        let mut meter = Meter::default();
+        let sub = || Some("sub-a".to_string());
+        meter.record(2, sub, 100, 1000, "2026-01-01");
+        meter.record(2, || panic!("not a new session"), 150, 1500, "2026-01-01");
+        meter.record(2, sub, 160, 1600, "2026-01-02");
+        // New session: totals restart at zero.
+        meter.record(5, || Some("sub-b".into()), 10, 20, "2026-01-02");
+
+        assert_eq!(
+            meter.pending[&("sub-a".into(), "2026-01-01".into())],
+            (150, 1500)
+        );
+        assert_eq!(
+            meter.pending[&("sub-a".into(), "2026-01-02".into())],
+            (10, 100)
+        );
+        assert_eq!(
+            meter.pending[&("sub-b".into(), "2026-01-02".into())],
+            (10, 20)
+        );
+    }
+
+    #[test]
+    fn meter_skips_sessions_without_subscription() {
+        let mut meter = Meter::default();
+        meter.record(1, || None, 100, 100, "2026-01-01");
+        assert!(meter.pending.is_empty());
+    }
+
+    #[test]
+    fn parses_userinfo_header() {
+        assert_eq!(
+            parse_userinfo("upload=1; download=2; total=1073741824; expire=1700000000"),
+            Some(ProviderInfo {
+                upload: 1,
+                download: 2,
+                total: 1073741824,
+                expire: 1700000000
+            })
+        );
+        assert_eq!(
+            parse_userinfo("upload=0;download=1.5e3;total=10"),
+            Some(ProviderInfo {
+                download: 1500,
+                total: 10,
+                ..Default::default()
+            })
+        );
+        assert_eq!(parse_userinfo(""), None);
+        assert_eq!(parse_userinfo("garbage"), None);
+    }
+
+    #[test]
+    fn alerts_on_traffic_ratio_and_expiry() {
+        let settings = UsageAlertSettings::default();
+        let mut usage = SubscriptionUsage {
+            identifier: "x".into(),
+            daily: vec![],
+            local_total: 0,
+            provider_used: 0,
+            provider_total: 1000,
+            expire_time: 0,
+            estimated_used: 899,
+            synced_at: None,
+        };
+        assert!(due_alerts(&settings, &usage, 0).is_empty());
+        usage.estimated_used = 900;
+        assert_eq!(due_alerts(&settings, &usage, 0), vec![AlertKind::Traffic]);
+        usage.provider_total = 1;
+        assert!(due_alerts(&settings, &usage, 0).is_empty());
+        usage.expire_time = 10 * 86400;
+        assert!(due_alerts(&settings, &usage, 0).is_empty());
+        assert_eq!(
+            due_alerts(&settings, &usage, 8 * 86400),
+            vec![AlertKind::Expire]
+        );
+    }
+
+    #[tokio::test]
+    async fn estimate_adds_local_bytes_since_reconcile() {
+        let pool = test_pool().await;
+        sqlx::query(
+            "INSERT INTO subscriptions (identifier, subscription_url, used_traffic, total_traffic) \
+             VALUES ('sub-a', 'https://p.example/sub', 500, 10000)",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        add_daily(&pool, "sub-a", "2026-01-01", 100, 200)
+            .await
+            .unwrap();
+        // Before any reconcile the subscriptions row is the provider view.
+        let before = usage(&pool, "sub-a", 30).await.unwrap();
+        assert_eq!((before.provider_used, before.estimated_used), (500, 500));
+
+        let info = parse_userinfo("upload=300; download=400; total=5000; expire=99").unwrap();
+        store_sync(&pool, "sub-a", &info, 42).await.unwrap();
+        add_daily(&pool, "sub-a", "2026-01-01", 10, 20)
+            .await
+            .unwrap();
+        add_daily(&pool, "sub-a", "2026-01-02", 1, 2).await.unwrap();
+
+        let after = usage(&pool, "sub-a", 30).await.unwrap();
+        assert_eq!(after.local_total, 333);
+        assert_eq!(after.provider_used, 700);
+        assert_eq!(after.provider_total, 5000);
+        assert_eq!(after.expire_time, 99);
+        assert_eq!(after.estimated_used, 700 + 33);
+        assert_eq!(after.synced_at, Some(42));
+        assert_eq!(after.daily[0].day, "2026-01-02");
+        assert_eq!((after.daily[1].upload, after.daily[1].download), (110, 220));
+
+        let one_day = usage(&pool, "sub-a", 1).await.unwrap();
+        assert_eq!(one_day.daily.len(), 1);
+        assert_eq!(one_day.local_total, 333);
+    }
+}
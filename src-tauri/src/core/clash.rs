@@ -0,0 +1,658 @@
+//! Typed client for sing-box's Clash API (external controller).
+//!
+//! Rust-side counterpart of `src/utils/clash-api.ts`, so readiness, the
+//! health monitor, the tray and the CLI can query the engine without a
+//! webview. Covers `/version`, `/configs`, `/proxies`, `/proxies/{name}`,
+//! `/proxies/{name}/delay`, `/connections`, and the chunked `/traffic` and
+//! `/logs` streams.
+//!
+//! Requests never go through the system proxy (`no_proxy()`, same reason as
+//! `build_no_redirect_client`): with an external proxy configured, loopback
+//! requests would otherwise be routed into it and fail.
+//!
+//! Not every endpoint has a Rust caller yet; the surface mirrors the API so
+//! new consumers don't each grow their own ad-hoc requests. Those without
+//! one are marked `allow(dead_code)` outside tests, item by item.
+
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_http::reqwest::{self, Method, RequestBuilder, Response};
+use tauri_plugin_store::StoreExt;
+
+const SETTINGS_STORE: &str = "settings.json";
+/// Same key the frontend writes in `src/single/store.ts`.
+pub(crate) const CLASH_SECRET_STORE_KEY: &str = "clash_api_secret_key";
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Secret for the running engine: the one `core::is_running` stashed in
+/// `AppData`, falling back to the persisted store value.
+pub(crate) fn clash_secret(app: &AppHandle) -> Option<String> {
+    app.state::<crate::app::state::AppData>()
+        .get_clash_secret()
+        .or_else(|| {
+            app.get_store(SETTINGS_STORE)
+                .and_then(|store| store.get(CLASH_SECRET_STORE_KEY))
+                .and_then(|v| v.as_str().map(str::to_string))
+        })
+        .filter(|s| !s.is_empty())
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct Version {
+    pub version: String,
+    pub premium: bool,
+    pub meta: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Default)]
+#[serde(default, rename_all = "kebab-case")]
+#[cfg_attr(not(test), allow(dead_code))]
+pub struct Configs {
+    pub port: u16,
+    pub socks_port: u16,
+    pub redir_port: u16,
+    pub tproxy_port: u16,
+    pub mixed_port: u16,
+    pub allow_lan: bool,
+    pub bind_address: String,
+    pub mode: String,
+    pub mode_list: Vec<String>,
+    pub log_level: String,
+    pub ipv6: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct DelayHistory {
+    pub time: String,
+    pub delay: u64,
+}
+
+/// One outbound or outbound group from `/proxies`.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct Proxy {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub udp: bool,
+    /// Selected member, for `Selector` / `URLTest` groups.
+    pub now: Option<String>,
+    /// Members, for groups.
+    pub all: Vec<String>,
+    pub history: Vec<DelayHistory>,
+}
+
+#[derive(Deserialize)]
+struct ProxiesResponse {
+    #[serde(default)]
+    proxies: HashMap<String, Proxy>,
+}
+
+#[derive(Deserialize)]
+#[cfg_attr(not(test), allow(dead_code))]
+struct DelayResponse {
+    delay: u64,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct ConnectionMetadata {
+    pub network: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(rename = "sourceIP")]
+    pub source_ip: String,
+    #[serde(rename = "destinationIP")]
+    pub destination_ip: String,
+    #[serde(rename = "sourcePort")]
+    pub source_port: String,
+    #[serde(rename = "destinationPort")]
+    pub destination_port: String,
+    pub host: String,
+    #[serde(rename = "dnsMode")]
+    pub dns_mode: String,
+    #[serde(rename = "processPath")]
+    pub process_path: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Connection {
+    pub id: String,
+    pub metadata: ConnectionMetadata,
+    pub upload: u64,
+    pub download: u64,
+    pub start: String,
+    pub chains: Vec<String>,
+    pub rule: String,
+    pub rule_payload: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Connections {
+    pub download_total: u64,
+    pub upload_total: u64,
+    /// `null` when there are none.
+    #[serde(deserialize_with = "null_as_default")]
+    pub connections: Vec<Connection>,
+}
+
+fn null_as_default<'de, D, T>(d: D) -> Result<T, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Default + Deserialize<'de>,
+{
+    Ok(Option::<T>::deserialize(d)?.unwrap_or_default())
+}
+
+/// One `/traffic` sample: bytes/s over the last second.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(default)]
+pub struct Traffic {
+    pub up: u64,
+    pub down: u64,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Default)]
+#[serde(default)]
+#[cfg_attr(not(test), allow(dead_code))]
+pub struct LogLine {
+    /// `trace` … `error`, as sent in the `type` field.
+    #[serde(rename = "type")]
+    pub level: String,
+    pub payload: String,
+}
+
+/// Newline-delimited JSON stream (`/traffic`, `/logs`). Each `next()`
+/// yields one decoded line; `None` once the server closes the stream.
+pub struct ClashStream<T> {
+    response: Response,
+    buf: Vec<u8>,
+    _item: PhantomData<T>,
+}
+
+impl<T: DeserializeOwned> ClashStream<T> {
+    fn new(response: Response) -> Self {
+        Self {
+            response,
+            buf: Vec::new(),
+            _item: PhantomData,
+        }
+    }
+
+    pub async fn next(&mut self) -> Option<Result<T, String>> {
+        loop {
+            if let Some(pos) = self.buf.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = self.buf.drain(..=pos).collect();
+                let line = &line[..line.len() - 1];
+                if line.iter().all(u8::is_ascii_whitespace) {
+                    continue;
+                }
+                return Some(serde_json::from_slice(line).map_err(|e| e.to_string()));
+            }
+            match self.response.chunk().await {
+                Ok(Some(chunk)) => self.buf.extend_from_slice(&chunk),
+                Ok(None) => {
+                    // Trailing line without a newline.
+                    if self.buf.iter().all(u8::is_ascii_whitespace) {
+                        return None;
+                    }
+                    let line = std::mem::take(&mut self.buf);
+                    return Some(serde_json::from_slice(&line).map_err(|e| e.to_string()));
+                }
+                Err(e) => return Some(Err(e.to_string())),
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ClashClient {
+    base_url: String,
+    secret: Option<String>,
+    timeout: Duration,
+}
+
+impl ClashClient {
+    /// Client for the controller on `127.0.0.1:port`.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn new(port: u16, secret: Option<String>) -> Self {
+        Self::with_base_url(format!("http://127.0.0.1:{}", port), secret)
+    }
+
+    pub fn with_base_url(base_url: impl Into<String>, secret: Option<String>) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            secret: secret.filter(|s| !s.is_empty()),
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Client for the app's running engine, at the controller address of
+    /// the active config.
+    pub fn for_app(app: &AppHandle) -> Self {
+        let (host, port) = super::clash_api_addr(app);
+        Self::with_base_url(format!("http://{}:{}", host, port), clash_secret(app))
+    }
+
+    /// Per-request timeout for the unary calls. Streams are not bounded by
+    /// it once the response headers arrive.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    fn url(&self, segments: &[&str]) -> Result<String, String> {
+        let mut url = url::Url::parse(&self.base_url).map_err(|e| e.to_string())?;
+        url.path_segments_mut()
+            .map_err(|_| format!("invalid Clash API base url: {}", self.base_url))?
+            .pop_if_empty()
+            .extend(segments);
+        Ok(url.to_string())
+    }
+
+    fn request(
+        &self,
+        method: Method,
+        segments: &[&str],
+        timeout: Option<Duration>,
+    ) -> Result<RequestBuilder, String> {
+        let mut builder = reqwest::Client::builder().no_proxy();
+        if let Some(timeout) = timeout {
+            builder = builder.timeout(timeout);
+        }
+        let client = builder.build().map_err(|e| e.to_string())?;
+        let mut req = client.request(method, self.url(segments)?);
+        if let Some(secret) = &self.secret {
+            req = req.bearer_auth(secret);
+        }
+        Ok(req)
+    }
+
+    async fn send(req: RequestBuilder) -> Result<Response, String> {
+        let res = req.send().await.map_err(|e| e.to_string())?;
+        let status = res.status();
+        if status.is_success() {
+            return Ok(res);
+        }
+        let body = res.text().await.unwrap_or_default();
+        let message = serde_json::from_str::<serde_json::Value>(&body)
+            .ok()
+            .and_then(|v| v.get("message")?.as_str().map(str::to_string))
+            .unwrap_or(body);
+        if message.trim().is_empty() {
+            Err(format!("HTTP {}", status.as_u16()))
+        } else {
+            Err(format!("HTTP {}: {}", status.as_u16(), message.trim()))
+        }
+    }
+
+    async fn get_json<T: DeserializeOwned>(&self, segments: &[&str]) -> Result<T, String> {
+        let res = Self::send(self.request(Method::GET, segments, Some(self.timeout))?).await?;
+        let bytes = res.bytes().await.map_err(|e| e.to_string())?;
+        serde_json::from_slice(&bytes).map_err(|e| e.to_string())
+    }
+
+    async fn send_json(
+        &self,
+        method: Method,
+        segments: &[&str],
+        body: &serde_json::Value,
+    ) -> Result<(), String> {
+        let req = self
+            .request(method, segments, Some(self.timeout))?
+            .header("Content-Type", "application/json")
+            .body(body.to_string());
+        Self::send(req).await.map(|_| ())
+    }
+
+    pub async fn version(&self) -> Result<Version, String> {
+        self.get_json(&["version"]).await
+    }
+
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub async fn configs(&self) -> Result<Configs, String> {
+        self.get_json(&["configs"]).await
+    }
+
+    /// `PATCH /configs` — only the keys present in `patch` change
+    /// (e.g. `{"mode": "global"}`).
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub async fn patch_configs(&self, patch: &serde_json::Value) -> Result<(), String> {
+        self.send_json(Method::PATCH, &["configs"], patch).await
+    }
+
+    pub async fn proxies(&self) -> Result<HashMap<String, Proxy>, String> {
+        self.get_json::<ProxiesResponse>(&["proxies"])
+            .await
+            .map(|r| r.proxies)
+    }
+
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub async fn proxy(&self, name: &str) -> Result<Proxy, String> {
+        self.get_json(&["proxies", name]).await
+    }
+
+    /// Switch the selected member of a `Selector` group.
+    pub async fn select_proxy(&self, group: &str, name: &str) -> Result<(), String> {
+        self.send_json(
+            Method::PUT,
+            &["proxies", group],
+            &serde_json::json!({ "name": name }),
+        )
+        .await
+    }
+
+    /// Have sing-box measure `name` against `url`; returns the delay in ms.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub async fn proxy_delay(
+        &self,
+        name: &str,
+        url: &str,
+        timeout: Duration,
+    ) -> Result<u64, String> {
+        let req = self
+            .request(
+                Method::GET,
+                &["proxies", name, "delay"],
+                // sing-box answers 504 itself once `timeout` elapses; allow
+                // a little slack on top for the HTTP round-trip.
+                Some(timeout + self.timeout),
+            )?
+            .query(&[
+                ("url", url.to_string()),
+                ("timeout", timeout.as_millis().to_string()),
+            ]);
+        let res = Self::send(req).await?;
+        let bytes = res.bytes().await.map_err(|e| e.to_string())?;
+        serde_json::from_slice::<DelayResponse>(&bytes)
+            .map(|r| r.delay)
+            .map_err(|e| e.to_string())
+    }
+
+    pub async fn connections(&self) -> Result<Connections, String> {
+        self.get_json(&["connections"]).await
+    }
+
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub async fn close_connections(&self) -> Result<(), String> {
+        let req = self.request(Method::DELETE, &["connections"], Some(self.timeout))?;
+        Self::send(req).await.map(|_| ())
+    }
+
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub async fn close_connection(&self, id: &str) -> Result<(), String> {
+        let req = self.request(Method::DELETE, &["connections", id], Some(self.timeout))?;
+        Self::send(req).await.map(|_| ())
+    }
+
+    /// `/traffic` — one sample per second until the stream is dropped.
+    pub async fn traffic(&self) -> Result<ClashStream<Traffic>, String> {
+        self.stream(&["traffic"], None).await
+    }
+
+    /// `/logs`, optionally filtered to `level` and above.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub async fn logs(&self, level: Option<&str>) -> Result<ClashStream<LogLine>, String> {
+        self.stream(&["logs"], level.map(|l| ("level", l))).await
+    }
+
+    async fn stream<T: DeserializeOwned>(
+        &self,
+        segments: &[&str],
+        query: Option<(&str, &str)>,
+    ) -> Result<ClashStream<T>, String> {
+        let mut req = self.request(Method::GET, segments, None)?;
+        if let Some(query) = query {
+            req = req.query(&[query]);
+        }
+        // Bound only the connect + headers phase; the body is unbounded.
+        let res = tokio::time::timeout(self.timeout, Self::send(req))
+            .await
+            .map_err(|_| format!("timed out after {:?}", self.timeout))??;
+        Ok(ClashStream::new(res))
+    }
+}
+
+#[cfg(test)]
+mod clash_tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Minimal HTTP/1.1 stub: records each request line + auth header and
+    /// answers from `route(method, path)` → (status, body chunks).
+    /// Chunks are written with a short pause between them and the
+    /// connection is closed afterwards, which is how the streams end.
+    async fn stub<F>(route: F) -> (String, Arc<Mutex<Vec<String>>>)
+    where
+        F: Fn(&str, &str) -> (u16, Vec<String>) + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let log = seen.clone();
+        let route = Arc::new(route);
+        tokio::spawn(async move {
+            loop {
+                let Ok((mut sock, _)) = listener.accept().await else {
+                    return;
+                };
+                let route = route.clone();
+                let log = log.clone();
+                tokio::spawn(async move {
+                    let mut head = Vec::new();
+                    let mut byte = [0u8; 1];
+                    while !head.ends_with(b"\r\n\r\n") {
+                        if sock.read(&mut byte).await.unwrap_or(0) == 0 {
+                            return;
+                        }
+                        head.push(byte[0]);
+                    }
+                    let head = String::from_utf8_lossy(&head).to_string();
+                    let mut lines = head.lines();
+                    let request_line = lines.next().unwrap_or_default().to_string();
+                    let auth = lines
+                        .find_map(|l| {
+                            l.strip_prefix("authorization: ")
+                                .or_else(|| l.strip_prefix("Authorization: "))
+                        })
+                        .unwrap_or("-")
+                        .to_string();
+                    let length: usize = head
+                        .lines()
+                        .find_map(|l| {
+                            l.to_ascii_lowercase()
+                                .strip_prefix("content-length: ")
+                                .map(str::to_string)
+                        })
+                        .and_then(|v| v.trim().parse().ok())
+                        .unwrap_or(0);
+                    let mut body = vec![0u8; length];
+                    sock.read_exact(&mut body).await.unwrap();
+                    log.lock().unwrap().push(format!(
+                        "{} auth={} body={}",
+                        request_line,
+                        auth,
+                        String::from_utf8_lossy(&body)
+                    ));
+
+                    let mut parts = request_line.split(' ');
+                    let method = parts.next().unwrap_or_default();
+                    let path = parts.next().unwrap_or_default();
+                    let (status, chunks) = route(method, path);
+                    let header = format!(
+                        "HTTP/1.1 {} X\r\nContent-Type: application/json\r\nConnection: close\r\n\r\n",
+                        status
+                    );
+                    sock.write_all(header.as_bytes()).await.unwrap();
+                    for chunk in chunks {
+                        sock.write_all(chunk.as_bytes()).await.unwrap();
+                        sock.flush().await.unwrap();
+                        tokio::time::sleep(Duration::from_millis(20)).await;
+                    }
+                    let _ = sock.shutdown().await;
+                });
+            }
+        });
+        (base, seen)
+    }
+
+    fn one(body: &str) -> (u16, Vec<String>) {
+        (200, vec![body.to_string()])
+    }
+
+    #[tokio::test]
+    async fn unary_endpoints_decode_and_send_secret() {
+        let (base, seen) = stub(|method, path| match (method, path) {
+            ("GET", "/version") => one(r#"{"version":"sing-box 1.11.0","premium":true,"meta":true}"#),
+            ("GET", "/configs") => one(r#"{"mode":"rule","mode-list":["rule","global"],"log-level":"info","mixed-port":0}"#),
+            ("PATCH", "/configs") => (204, vec![]),
+            ("GET", "/proxies") => one(
+                r#"{"proxies":{"ExitGateway":{"type":"Selector","name":"ExitGateway","udp":true,"now":"hk-01","all":["hk-01","jp-01"],"history":[]},"hk-01":{"type":"Trojan","name":"hk-01","history":[{"time":"t","delay":88}]}}}"#,
+            ),
+            ("GET", "/proxies/hk%2001") => one(r#"{"type":"Trojan","name":"hk 01"}"#),
+            ("PUT", "/proxies/ExitGateway") => (204, vec![]),
+            (_, p) if p.starts_with("/proxies/hk-01/delay?") => one(r#"{"delay":123}"#),
+            (_, p) if p.starts_with("/proxies/dead/delay?") => (504, vec![r#"{"message":"Timeout"}"#.into()]),
+            ("GET", "/connections") => one(
+                r#"{"downloadTotal":10,"uploadTotal":5,"connections":[{"id":"c1","metadata":{"network":"tcp","type":"tun/tun-in","sourceIP":"172.19.0.1","destinationPort":"443","host":"example.com","processPath":"/usr/bin/curl"},"upload":1,"download":2,"chains":["hk-01","ExitGateway"],"rule":"final","rulePayload":""}]}"#,
+            ),
+            ("DELETE", "/connections") | ("DELETE", "/connections/c1") => (204, vec![]),
+            _ => (404, vec![]),
+        })
+        .await;
+        let client = ClashClient::with_base_url(base.clone(), Some("s3cret".into()));
+
+        assert_eq!(client.version().await.unwrap().version, "sing-box 1.11.0");
+        let configs = client.configs().await.unwrap();
+        assert_eq!(configs.mode, "rule");
+        assert_eq!(configs.mode_list, vec!["rule", "global"]);
+        client
+            .patch_configs(&serde_json::json!({ "mode": "global" }))
+            .await
+            .unwrap();
+
+        let proxies = client.proxies().await.unwrap();
+        assert_eq!(proxies["ExitGateway"].now.as_deref(), Some("hk-01"));
+        assert_eq!(proxies["ExitGateway"].kind, "Selector");
+        assert_eq!(proxies["hk-01"].history[0].delay, 88);
+        assert_eq!(client.proxy("hk 01").await.unwrap().name, "hk 01");
+        client.select_proxy("ExitGateway", "jp-01").await.unwrap();
+
+        let delay = client
+            .proxy_delay(
+                "hk-01",
+                "https://www.gstatic.com/generate_204",
+                Duration::from_millis(800),
+            )
+            .await
+            .unwrap();
+        assert_eq!(delay, 123);
+        let err = client
+            .proxy_delay("dead", "https://x", Duration::from_millis(800))
+            .await
+            .unwrap_err();
+        assert_eq!(err, "HTTP 504: Timeout");
+
+        let conns = client.connections().await.unwrap();
+        assert_eq!(conns.download_total, 10);
+        let c = &conns.connections[0];
+        assert_eq!(c.metadata.source_ip, "172.19.0.1");
+        assert_eq!(c.metadata.process_path, "/usr/bin/curl");
+        assert_eq!(c.chains, vec!["hk-01", "ExitGateway"]);
+        client.close_connection("c1").await.unwrap();
+        client.close_connections().await.unwrap();
+
+        let seen = seen.lock().unwrap().clone();
+        assert!(seen.iter().all(|l| l.contains("auth=Bearer s3cret")));
+        assert!(seen
+            .iter()
+            .any(|l| l.starts_with("PATCH /configs") && l.ends_with(r#"body={"mode":"global"}"#)));
+        assert!(seen
+            .iter()
+            .any(|l| l.starts_with("PUT /proxies/ExitGateway")
+                && l.ends_with(r#"body={"name":"jp-01"}"#)));
+        assert!(seen.iter().any(|l| l.contains(
+            "/proxies/hk-01/delay?url=https%3A%2F%2Fwww.gstatic.com%2Fgenerate_204&timeout=800"
+        )));
+
+        let anonymous = ClashClient::with_base_url(base, Some(String::new()));
+        anonymous.version().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn null_connections_decode_as_empty() {
+        let (base, _) =
+            stub(|_, _| one(r#"{"downloadTotal":0,"uploadTotal":0,"connections":null}"#)).await;
+        let conns = ClashClient::with_base_url(base, None)
+            .connections()
+            .await
+            .unwrap();
+        assert!(conns.connections.is_empty());
+    }
+
+    #[tokio::test]
+    async fn streams_split_lines_across_chunks() {
+        let (base, seen) = stub(|_, path| match path {
+            "/traffic" => (
+                200,
+                vec![
+                    "{\"up\":1,\"down\":2}\n{\"up\":3,".into(),
+                    "\"down\":4}\n\n".into(),
+                    "{\"up\":5,\"down\":6}".into(),
+                ],
+            ),
+            _ => (
+                200,
+                vec!["{\"type\":\"warning\",\"payload\":\"dial failed\"}\n".into()],
+            ),
+        })
+        .await;
+        let client = ClashClient::with_base_url(base, None);
+
+        let mut traffic = client.traffic().await.unwrap();
+        let mut samples = Vec::new();
+        while let Some(sample) = traffic.next().await {
+            samples.push(sample.unwrap());
+        }
+        assert_eq!(
+            samples,
+            vec![
+                Traffic { up: 1, down: 2 },
+                Traffic { up: 3, down: 4 },
+                Traffic { up: 5, down: 6 }
+            ]
+        );
+
+        let mut logs = client.logs(Some("warning")).await.unwrap();
+        let line = logs.next().await.unwrap().unwrap();
+        assert_eq!(line.level, "warning");
+        assert_eq!(line.payload, "dial failed");
+        assert!(logs.next().await.is_none());
+        assert!(seen
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|l| l.starts_with("GET /logs?level=warning")));
+    }
+
+    #[tokio::test]
+    async fn closed_port_is_an_error() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+        let client = ClashClient::new(port, None).timeout(Duration::from_millis(300));
+        assert!(client.version().await.is_err());
+        assert!(client.traffic().await.is_err());
+    }
+}
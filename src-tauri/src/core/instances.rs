@@ -0,0 +1,547 @@
+//! Keyed registry of side-by-side sing-box instances.
+//!
+//! Every instance's process lives in its own `ProcessManager` slot, keyed
+//! by instance key (`ProcessManager::slot`). The `default` instance is the
+//! one the dashboard drives through `start` / `stop` / `reload_config`: its
+//! slot is `PROCESS_MANAGER`, its state the app-wide `EngineStateCell`, and
+//! it owns the system proxy, TUN and DNS override. Nothing about it changes
+//! here.
+//!
+//! Any other key names an extra, isolated ManualProxy instance — e.g. a
+//! second subscription on its own mixed port next to a TUN session, for
+//! comparing routes. Each one gets:
+//!
+//!   - its own mixed and Clash API ports, read from its config and refused
+//!     if they collide with the default instance or another entry;
+//!   - its own working directory (`<app config>/instances/<key>`), so
+//!     `cache.db` and rule-set caches are not shared;
+//!   - its own daily-rotated log under `<app log>/instances/<key>`;
+//!   - its own `EngineStateCell`, driven through the same state machine and
+//!     published as `engine-instance-state` `{ key, state }`;
+//!   - the same sing-box monitor (`monitor::spawn_monitor`) and readiness
+//!     pipeline (`readiness::wait_until_ready`) as the default instance.
+//!
+//! Extra instances never touch system-wide settings and are killed when
+//! the app exits.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use lazy_static::lazy_static;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_shell::process::TerminatedPayload;
+use tauri_plugin_shell::ShellExt;
+use tokio::sync::broadcast::error::RecvError;
+
+use super::log::prepare_singbox_log_dir;
+use super::monitor::{spawn_monitor, MonitorOwner};
+use super::{ProcessManager, ProxyMode};
+use crate::engine::readiness;
+use crate::engine::state_machine::{apply, EngineState, EngineStateCell, Intent};
+
+pub const DEFAULT_INSTANCE: &str = "default";
+pub const EVENT_INSTANCE_STATE: &str = "engine-instance-state";
+
+const MAX_KEY_LEN: usize = 32;
+
+/// What an extra instance has beyond its `ProcessManager` slot.
+struct Instance {
+    mixed_port: u16,
+    clash_port: u16,
+    log_path: Option<PathBuf>,
+    state: Arc<EngineStateCell>,
+    slot: Arc<Mutex<ProcessManager>>,
+}
+
+lazy_static! {
+    static ref INSTANCES: Mutex<HashMap<String, Instance>> = Mutex::new(HashMap::new());
+}
+
+fn registry() -> std::sync::MutexGuard<'static, HashMap<String, Instance>> {
+    INSTANCES.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct InstanceInfo {
+    pub key: String,
+    pub mode: Option<ProxyMode>,
+    pub config_path: Option<String>,
+    pub mixed_port: Option<u16>,
+    pub clash_port: u16,
+    pub log_path: Option<String>,
+    pub state: EngineState,
+}
+
+#[derive(Serialize, Clone)]
+struct InstanceStatePayload<'a> {
+    key: &'a str,
+    state: &'a EngineState,
+}
+
+fn publish(app: &AppHandle, key: &str, state: &EngineState) {
+    if let Err(e) = app.emit(EVENT_INSTANCE_STATE, InstanceStatePayload { key, state }) {
+        log::error!("[instance] emit {} failed: {}", EVENT_INSTANCE_STATE, e);
+    }
+}
+
+/// Apply `intent` to `key`'s cell and publish the result. A cell that has
+/// since been replaced by a newer start under the same key is ignored.
+fn drive(app: &AppHandle, key: &str, cell: &Arc<EngineStateCell>, intent: Intent) {
+    let current = registry()
+        .get(key)
+        .is_some_and(|inst| Arc::ptr_eq(&inst.state, cell));
+    if !current {
+        return;
+    }
+    if let Ok(state) = apply(cell, intent) {
+        publish(app, key, &state);
+    }
+}
+
+fn validate_key(key: &str) -> Result<(), String> {
+    if key == DEFAULT_INSTANCE {
+        return Err(format!(
+            "\"{}\" is the main engine; use start/stop for it",
+            DEFAULT_INSTANCE
+        ));
+    }
+    let valid = !key.is_empty()
+        && key.len() <= MAX_KEY_LEN
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(format!(
+            "instance key must be 1-{} characters of [A-Za-z0-9_-]",
+            MAX_KEY_LEN
+        ));
+    }
+    Ok(())
+}
+
+/// Why `config` would touch system-wide settings, if it would: a `tun`
+/// inbound routes the whole machine, `set_system_proxy` rewrites the OS
+/// proxy. Preflight in ManualProxy mode lets both through.
+fn system_wide_setting(config: &serde_json::Value) -> Option<String> {
+    let inbounds = config.get("inbounds")?.as_array()?;
+    inbounds.iter().find_map(|inbound| {
+        let tag = inbound.get("tag").and_then(|v| v.as_str()).unwrap_or("?");
+        if inbound.get("type").and_then(|v| v.as_str()) == Some("tun") {
+            Some(format!("inbound \"{}\" is a tun inbound", tag))
+        } else if inbound.get("set_system_proxy").and_then(|v| v.as_bool()) == Some(true) {
+            Some(format!("inbound \"{}\" sets the system proxy", tag))
+        } else {
+            None
+        }
+    })
+}
+
+/// Mixed port, Clash API port and Clash API secret of an instance config.
+/// Preflight has already guaranteed both ports are present.
+fn instance_ports(config: &serde_json::Value) -> Option<(u16, u16, Option<String>)> {
+    let mixed = super::mixed_port_from_config(config)?;
+    let (_, clash) = super::clash_api_addr_from_config(config)?;
+    let secret = config
+        .pointer("/experimental/clash_api/secret")
+        .and_then(serde_json::Value::as_str)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string);
+    Some((mixed, clash, secret))
+}
+
+/// First of `wanted` already claimed in `taken` (`(owner, port)` pairs),
+/// or two of `wanted` colliding with each other.
+fn port_conflict(wanted: &[u16], taken: &[(String, u16)]) -> Option<(String, u16)> {
+    for (i, port) in wanted.iter().enumerate() {
+        if wanted[..i].contains(port) {
+            return Some(("itself".into(), *port));
+        }
+        if let Some((owner, _)) = taken.iter().find(|(_, p)| p == port) {
+            return Some((owner.clone(), *port));
+        }
+    }
+    None
+}
+
+/// Start `key` on the ManualProxy config at `path`. Restarting a key that
+/// is not running replaces its previous entry.
+#[tauri::command]
+pub async fn start_instance(app: AppHandle, key: String, path: String) -> Result<(), String> {
+    validate_key(&key)?;
+    let path = std::fs::canonicalize(&path)
+        .map_err(|e| format!("failed to resolve {}: {}", path, e))?
+        .to_string_lossy()
+        .into_owned();
+    let config: serde_json::Value = std::fs::read_to_string(&path)
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .ok_or_else(|| format!("failed to read {}", path))?;
+    if let Some(reason) = system_wide_setting(&config) {
+        return Err(format!(
+            "instances never touch system-wide settings: {}",
+            reason
+        ));
+    }
+    crate::engine::preflight::validate(&app, &path, &ProxyMode::ManualProxy).await?;
+    let (mixed_port, clash_port, secret) =
+        instance_ports(&config).ok_or_else(|| "instance config has no ports".to_string())?;
+
+    let work_dir = app
+        .path()
+        .app_config_dir()
+        .map_err(|e| e.to_string())?
+        .join("instances")
+        .join(&key);
+    std::fs::create_dir_all(&work_dir)
+        .map_err(|e| format!("failed to create {}: {}", work_dir.display(), e))?;
+    let log_path = app.path().app_log_dir().ok().and_then(|dir| {
+        prepare_singbox_log_dir(&dir.join("instances").join(&key))
+            .map_err(|e| log::warn!("[instance] {key}: prepare log dir failed: {e}"))
+            .ok()
+    });
+
+    let cell = Arc::new(EngineStateCell::new());
+    let (rx, pid, slot) = {
+        let mut instances = registry();
+        let running = instances.get(&key).is_some_and(|existing| {
+            ProcessManager::lock(&existing.slot).child.is_some()
+                || matches!(
+                    existing.state.snapshot(),
+                    EngineState::Starting { .. } | EngineState::Running { .. }
+                )
+        });
+        if running {
+            return Err(format!("INSTANCE_ALREADY_RUNNING:{}", key));
+        }
+        // The default instance's ports stay reserved even while it is
+        // stopped, so it can always be started again.
+        let mut taken = vec![
+            (DEFAULT_INSTANCE.to_string(), super::mixed_proxy_port(&app)),
+            (DEFAULT_INSTANCE.to_string(), super::clash_api_port(&app)),
+        ];
+        for (other, inst) in instances.iter().filter(|(k, _)| **k != key) {
+            taken.push((other.clone(), inst.mixed_port));
+            taken.push((other.clone(), inst.clash_port));
+        }
+        if let Some((owner, port)) = port_conflict(&[mixed_port, clash_port], &taken) {
+            return Err(format!(
+                "INSTANCE_PORT_CONFLICT:{}: port {} is already used by {}",
+                key, port, owner
+            ));
+        }
+        // Unlike the default instance, never evict a foreign listener.
+        let clash_host = super::clash_api_addr_from_config(&config)
+            .map(|(host, _)| host)
+            .unwrap_or_else(|| "127.0.0.1".into());
+        for (host, port) in [("127.0.0.1", mixed_port), (clash_host.as_str(), clash_port)] {
+            if super::probe_listening(host, port) {
+                return Err(format!(
+                    "{}:{}",
+                    crate::commands::prestart::PORT_OCCUPIED_CANNOT_START,
+                    port
+                ));
+            }
+        }
+
+        let (rx, child) = app
+            .shell()
+            .sidecar("sing-box")
+            .map_err(|e| format!("sidecar lookup failed: {}", e))?
+            .args([
+                "run",
+                "-c",
+                &path,
+                "-D",
+                &work_dir.to_string_lossy(),
+                "--disable-color",
+            ])
+            .spawn()
+            .map_err(|e| format!("spawn failed: {}", e))?;
+        let pid = child.pid();
+        log::info!("[instance] {key}: spawned pid={pid} mixed=:{mixed_port} clash=:{clash_port}");
+        let slot = ProcessManager::slot(&key);
+        {
+            let mut manager = ProcessManager::lock(&slot);
+            manager.reset();
+            manager.child = Some(child);
+            manager.mode = Some(Arc::new(ProxyMode::ManualProxy));
+            manager.config_path = Some(Arc::new(path.clone()));
+        }
+        if let Ok(state) = apply(
+            &cell,
+            Intent::Start {
+                mode: "mixed".into(),
+            },
+        ) {
+            publish(&app, &key, &state);
+        }
+        instances.insert(
+            key.clone(),
+            Instance {
+                mixed_port,
+                clash_port,
+                log_path: log_path.clone(),
+                state: cell.clone(),
+                slot: slot.clone(),
+            },
+        );
+        (rx, pid, slot)
+    };
+
+    let singbox_log = log_path.and_then(|p| {
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(p)
+            .ok()
+    });
+    spawn_monitor(
+        app.clone(),
+        rx,
+        pid,
+        singbox_log,
+        MonitorOwner::Instance {
+            key: key.clone(),
+            cell: cell.clone(),
+            slot: slot.clone(),
+        },
+    );
+    spawn_readiness(app, key, cell, slot, path, secret);
+    Ok(())
+}
+
+/// The instance's sing-box exited: called by its monitor.
+pub(crate) fn handle_termination(
+    app: &AppHandle,
+    key: &str,
+    cell: &Arc<EngineStateCell>,
+    slot: &Mutex<ProcessManager>,
+    payload: TerminatedPayload,
+) {
+    log::info!("[instance] {key}: terminated code={:?}", payload.code);
+    let stopping = {
+        let mut manager = ProcessManager::lock(slot);
+        let stopping = manager.is_stopping;
+        manager.reset();
+        stopping
+    };
+    let intent = match cell.snapshot() {
+        // Killed after a failed readiness check; keep that reason.
+        EngineState::Failed { .. } => return,
+        EngineState::Stopping { .. } => Intent::MarkIdle,
+        _ if stopping => Intent::MarkIdle,
+        _ => Intent::Fail {
+            reason: format!("sing-box exited (code {:?})", payload.code),
+        },
+    };
+    drive(app, key, cell, intent);
+}
+
+/// Run the shared readiness pipeline against the instance's config, until
+/// it passes, times out, or the instance leaves Starting.
+fn spawn_readiness(
+    app: AppHandle,
+    key: String,
+    cell: Arc<EngineStateCell>,
+    slot: Arc<Mutex<ProcessManager>>,
+    config_path: String,
+    secret: Option<String>,
+) {
+    let mut changes = cell.subscribe();
+    tokio::spawn(async move {
+        if !matches!(cell.snapshot(), EngineState::Starting { .. }) {
+            return;
+        }
+        let settings = readiness::load_settings(&app);
+        let left_starting = async {
+            loop {
+                match changes.recv().await {
+                    Ok(change) if !matches!(change.to, EngineState::Starting { .. }) => return,
+                    Err(RecvError::Closed) => return,
+                    _ => {}
+                }
+            }
+        };
+        let ready = tokio::select! {
+            ready = readiness::wait_until_ready(&settings, &config_path, false, secret) => ready,
+            _ = left_starting => return,
+        };
+        match ready {
+            Ok(()) => {
+                log::info!("[instance] {key}: ready");
+                drive(&app, &key, &cell, Intent::MarkRunning);
+            }
+            Err(reason) => {
+                log::warn!("[instance] {key}: {reason}");
+                drive(&app, &key, &cell, Intent::Fail { reason });
+                let child = ProcessManager::lock(&slot).child.take();
+                if let Some(child) = child {
+                    let _ = child.kill();
+                }
+            }
+        }
+    });
+}
+
+/// Stop `key` and drop it from the registry.
+#[tauri::command]
+pub async fn stop_instance(app: AppHandle, key: String) -> Result<(), String> {
+    validate_key(&key)?;
+    let Some(inst) = registry().remove(&key) else {
+        return Err(format!("INSTANCE_NOT_FOUND:{}", key));
+    };
+    ProcessManager::remove_slot(&key);
+    if inst.state.snapshot().is_running() {
+        let _ = apply(&inst.state, Intent::Stop);
+    }
+    let child = {
+        let mut manager = ProcessManager::lock(&inst.slot);
+        manager.is_stopping = true;
+        manager.child.take()
+    };
+    if let Some(child) = child {
+        child
+            .kill()
+            .map_err(|e| format!("failed to stop instance {}: {}", key, e))?;
+    }
+    if let Ok(state) = apply(&inst.state, Intent::MarkIdle) {
+        publish(&app, &key, &state);
+    }
+    log::info!("[instance] {key}: stopped");
+    Ok(())
+}
+
+/// Every known instance, `default` first.
+#[tauri::command]
+pub fn list_instances(app: AppHandle) -> Vec<InstanceInfo> {
+    let (mode, config_path) = {
+        let manager = ProcessManager::acquire();
+        (
+            manager.mode.as_ref().map(|m| (**m).clone()),
+            manager.config_path.as_ref().map(|p| (**p).clone()),
+        )
+    };
+    let mut list = vec![InstanceInfo {
+        key: DEFAULT_INSTANCE.into(),
+        mixed_port: (mode.is_some() && mode != Some(ProxyMode::TunProxy))
+            .then(|| super::mixed_proxy_port(&app)),
+        mode,
+        config_path,
+        clash_port: super::clash_api_port(&app),
+        log_path: app.path().app_log_dir().ok().map(|dir| {
+            dir.join(format!("sing-box-{}.log", super::log::today_date_string()))
+                .to_string_lossy()
+                .into_owned()
+        }),
+        state: app.state::<EngineStateCell>().snapshot(),
+    }];
+    let instances = registry();
+    let mut keys: Vec<&String> = instances.keys().collect();
+    keys.sort();
+    list.extend(keys.into_iter().map(|key| {
+        let inst = &instances[key];
+        let manager = ProcessManager::lock(&inst.slot);
+        InstanceInfo {
+            key: key.clone(),
+            mode: manager.mode.as_ref().map(|m| (**m).clone()),
+            config_path: manager.config_path.as_ref().map(|p| (**p).clone()),
+            mixed_port: Some(inst.mixed_port),
+            clash_port: inst.clash_port,
+            log_path: inst
+                .log_path
+                .as_ref()
+                .map(|p| p.to_string_lossy().into_owned()),
+            state: inst.state.snapshot(),
+        }
+    }));
+    list
+}
+
+/// Kill every extra instance. Called from the exit path.
+pub fn stop_all() {
+    for (key, inst) in registry().drain() {
+        ProcessManager::remove_slot(&key);
+        let child = {
+            let mut manager = ProcessManager::lock(&inst.slot);
+            manager.is_stopping = true;
+            manager.child.take()
+        };
+        if let Some(child) = child {
+            log::info!("[instance] {key}: killed on exit");
+            let _ = child.kill();
+        }
+    }
+}
+
+#[cfg(test)]
+mod instances_tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn default_and_malformed_keys_are_rejected() {
+        assert!(validate_key(DEFAULT_INSTANCE).is_err());
+        assert!(validate_key("").is_err());
+        assert!(validate_key("../etc").is_err());
+        assert!(validate_key(&"a".repeat(MAX_KEY_LEN + 1)).is_err());
+        assert!(validate_key("route-test_2").is_ok());
+    }
+
+    #[test]
+    fn ports_and_secret_come_from_the_config() {
+        let config = json!({
+            "inbounds": [{ "type": "mixed", "tag": "mixed", "listen_port": 7890 }],
+            "experimental": { "clash_api": {
+                "external_controller": "127.0.0.1:19090",
+                "secret": "s3cret"
+            }}
+        });
+        assert_eq!(
+            instance_ports(&config),
+            Some((7890, 19090, Some("s3cret".into())))
+        );
+    }
+
+    #[test]
+    fn tun_inbounds_are_rejected() {
+        let config = json!({ "inbounds": [
+            { "type": "mixed", "tag": "mixed", "listen_port": 7890 },
+            { "type": "tun", "tag": "tun-in" }
+        ]});
+        assert_eq!(
+            system_wide_setting(&config).as_deref(),
+            Some("inbound \"tun-in\" is a tun inbound")
+        );
+    }
+
+    #[test]
+    fn set_system_proxy_is_rejected() {
+        let config = json!({ "inbounds": [
+            { "type": "mixed", "tag": "mixed", "listen_port": 7890, "set_system_proxy": true }
+        ]});
+        assert_eq!(
+            system_wide_setting(&config).as_deref(),
+            Some("inbound \"mixed\" sets the system proxy")
+        );
+        let manual = json!({ "inbounds": [
+            { "type": "mixed", "tag": "mixed", "listen_port": 7890, "set_system_proxy": false }
+        ]});
+        assert_eq!(system_wide_setting(&manual), None);
+    }
+
+    #[test]
+    fn conflicts_name_the_owner() {
+        let taken = vec![("default".to_string(), 6789), ("default".to_string(), 9191)];
+        assert_eq!(port_conflict(&[7890, 19090], &taken), None);
+        assert_eq!(
+            port_conflict(&[7890, 9191], &taken),
+            Some(("default".into(), 9191))
+        );
+        assert_eq!(
+            port_conflict(&[7890, 7890], &taken),
+            Some(("itself".into(), 7890))
+        );
+    }
+}
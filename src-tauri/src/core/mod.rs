@@ -1,19 +1,24 @@
+pub(crate) mod clash;
+pub mod instances;
 mod log;
 pub(crate) mod monitor;
+pub mod traffic;
 
 pub use self::log::cleanup_old_onebox_logs;
 #[cfg(target_os = "macos")]
 pub(crate) use self::log::resolve_singbox_log_path;
 
 use lazy_static::lazy_static;
+use std::collections::HashMap;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex, OnceLock};
 use std::time::{Duration, Instant};
 use tauri::{AppHandle, Manager};
 
 use crate::app::state::AppData;
+use crate::commands::prestart::{OwnedProcesses, PortCleanupError};
 use crate::engine::state_machine::{transition, EngineState, EngineStateCell, Intent};
-use crate::engine::{readiness, EVENT_STATUS_CHANGED};
+use crate::engine::{config_switch, preflight, readiness, runtime_record, EVENT_STATUS_CHANGED};
 use crate::engine::{EngineManager, PlatformEngine};
 use tauri::Emitter;
 use tauri_plugin_shell::process::CommandChild;
@@ -38,9 +43,9 @@ pub(crate) fn next_action_token() -> u64 {
 }
 
 /// Wall-clock of the previous `reload_config` entry. The frontend
-/// (`vpnServiceManager.reload`) does NOT serialize calls, so two
-/// quickly-successive config edits dispatch two `pkill -HUP` without
-/// any interlock. A short delta here flags that situation.
+/// (`vpnServiceManager.reload`) does NOT serialize calls; the backend
+/// queues them (`config_switch::queue`), and a short delta here flags
+/// that the queue is being exercised.
 fn note_reload_entry() -> Option<Duration> {
     static LAST: OnceLock<Mutex<Option<Instant>>> = OnceLock::new();
     let slot = LAST.get_or_init(|| Mutex::new(None));
@@ -52,22 +57,57 @@ fn note_reload_entry() -> Option<Duration> {
 
 pub(crate) const DEFAULT_MIXED_PROXY_PORT: u16 = 6789;
 
-/// Clash API / external-controller port. Hardcoded to match
-/// `src/config/merger/main.ts` ("127.0.0.1:9191"); single source of truth on
-/// the Rust side so the start-guard and the readiness prober agree on it.
-pub(crate) const CLASH_API_PORT: u16 = 9191;
+/// Clash API / external-controller port used when the active config does
+/// not name one. Matches `DEFAULT_CLASH_API_PORT` in
+/// `src/types/definition.ts`; the merger writes the user's choice into
+/// `experimental.clash_api.external_controller`.
+pub(crate) const DEFAULT_CLASH_API_PORT: u16 = 9191;
+
+/// The generated `config.json` in the app config dir, parsed.
+fn active_config(app: &AppHandle) -> Option<serde_json::Value> {
+    let config_path = app.path().app_config_dir().ok()?.join("config.json");
+    let text = std::fs::read_to_string(config_path).ok()?;
+    serde_json::from_str(&text).ok()
+}
 
 pub(crate) fn mixed_proxy_port(app: &AppHandle) -> u16 {
-    let Ok(config_dir) = app.path().app_config_dir() else {
-        return DEFAULT_MIXED_PROXY_PORT;
-    };
-    let config_path = config_dir.join("config.json");
-    let Ok(text) = std::fs::read_to_string(config_path) else {
-        return DEFAULT_MIXED_PROXY_PORT;
-    };
-    let Ok(json) = serde_json::from_str::<serde_json::Value>(&text) else {
-        return DEFAULT_MIXED_PROXY_PORT;
+    active_config(app)
+        .as_ref()
+        .and_then(mixed_port_from_config)
+        .unwrap_or(DEFAULT_MIXED_PROXY_PORT)
+}
+
+/// `(host, port)` to reach the Clash API of the active config. A wildcard
+/// listen address is reached over loopback.
+pub(crate) fn clash_api_addr(app: &AppHandle) -> (String, u16) {
+    active_config(app)
+        .as_ref()
+        .and_then(clash_api_addr_from_config)
+        .unwrap_or_else(|| ("127.0.0.1".into(), DEFAULT_CLASH_API_PORT))
+}
+
+pub(crate) fn clash_api_port(app: &AppHandle) -> u16 {
+    clash_api_addr(app).1
+}
+
+/// `experimental.clash_api.external_controller` of a parsed sing-box
+/// config as a connectable `(host, port)`.
+pub(crate) fn clash_api_addr_from_config(json: &serde_json::Value) -> Option<(String, u16)> {
+    let addr = json
+        .pointer("/experimental/clash_api/external_controller")?
+        .as_str()?;
+    let (host, port) = addr.rsplit_once(':')?;
+    let port = port.parse::<u16>().ok().filter(|port| *port > 0)?;
+    let host = match host {
+        "" | "0.0.0.0" | "[::]" => "127.0.0.1",
+        other => other,
     };
+    Some((host.to_string(), port))
+}
+
+/// `listen_port` of the `type: "mixed", tag: "mixed"` inbound in a parsed
+/// sing-box config, if there is one.
+pub(crate) fn mixed_port_from_config(json: &serde_json::Value) -> Option<u16> {
     json.get("inbounds")
         .and_then(|v| v.as_array())
         .and_then(|inbounds| {
@@ -83,18 +123,34 @@ pub(crate) fn mixed_proxy_port(app: &AppHandle) -> u16 {
                     .filter(|port| *port > 0)
             })
         })
-        .unwrap_or(DEFAULT_MIXED_PROXY_PORT)
 }
 
 /// Best-effort check: is *something* already listening on
 /// 127.0.0.1:<mixed port> right now? A successful connect
 /// means the port is bound — used as a pre-flight before spawning a
-/// fresh sing-box and as a post-flight after `pkill -HUP` to detect
+/// fresh sing-box and as a post-flight after the SIGHUP to detect
 /// a failed rebind.
 pub(crate) fn probe_port_listening(port: u16) -> bool {
-    use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpStream};
-    let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port);
-    TcpStream::connect_timeout(&addr, Duration::from_millis(100)).is_ok()
+    probe_listening("127.0.0.1", port)
+}
+
+/// `probe_port_listening` against the host a listener was configured on —
+/// the Clash API may sit on `[::1]` or a LAN address rather than 127.0.0.1.
+pub(crate) fn probe_listening(host: &str, port: u16) -> bool {
+    std::net::TcpStream::connect_timeout(&probe_addr(host, port), Duration::from_millis(100))
+        .is_ok()
+}
+
+/// `host` as `clash_api_addr_from_config` returns it (IPv6 bracketed); a
+/// name that isn't an address literal is probed on loopback.
+fn probe_addr(host: &str, port: u16) -> std::net::SocketAddr {
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+    let ip = host
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse()
+        .unwrap_or(IpAddr::V4(Ipv4Addr::LOCALHOST));
+    SocketAddr::new(ip, port)
 }
 
 /// `kill(pid, 0)` probe — returns true if the PID still refers to a
@@ -124,7 +180,7 @@ pub(crate) fn sigterm_target_already_exited(raw_os_error: Option<i32>) -> bool {
 /// child_pid_alive, mode)`.
 fn pm_snapshot() -> (Option<u32>, Option<bool>, Option<ProxyMode>) {
     let mgr = ProcessManager::acquire();
-    let pid = mgr.child.as_ref().map(|c| c.pid());
+    let pid = mgr.child.as_ref().map(|c| c.pid()).or(mgr.adopted_pid);
     let alive = pid.map(pid_is_alive);
     let mode = mgr.mode.as_ref().map(|m| (**m).clone());
     (pid, alive, mode)
@@ -137,25 +193,74 @@ fn pm_snapshot() -> (Option<u32>, Option<bool>, Option<ProxyMode>) {
 // to work.
 pub use crate::engine::ProxyMode;
 
+/// `mode` label of `EngineState` for a proxy mode.
+pub(crate) fn state_mode_label(mode: &ProxyMode) -> &'static str {
+    match mode {
+        ProxyMode::TunProxy => "tun",
+        ProxyMode::SystemProxy | ProxyMode::ManualProxy => "mixed",
+    }
+}
+
 pub(crate) struct ProcessManager {
     pub(crate) child: Option<CommandChild>,
+    /// Sidecar pid adopted from a previous GUI session
+    /// (`engine::runtime_record`). There is no `CommandChild` for it, so stop
+    /// signals the pid directly.
+    pub(crate) adopted_pid: Option<u32>,
     pub(crate) mode: Option<Arc<ProxyMode>>,
     pub(crate) config_path: Option<Arc<String>>,
     pub(crate) is_stopping: bool,
 }
 
 impl ProcessManager {
+    fn idle() -> Self {
+        ProcessManager {
+            child: None,
+            adopted_pid: None,
+            mode: None,
+            config_path: None,
+            is_stopping: false,
+        }
+    }
+
     /// Lock the global PROCESS_MANAGER, recovering from poison.
     pub(crate) fn acquire() -> std::sync::MutexGuard<'static, ProcessManager> {
         PROCESS_MANAGER.lock().unwrap_or_else(|e| e.into_inner())
     }
 
+    /// The slot of instance `key` (`core::instances`), created idle on first
+    /// use. `default` is `PROCESS_MANAGER` itself.
+    pub(crate) fn slot(key: &str) -> Arc<Mutex<ProcessManager>> {
+        PROCESS_MANAGERS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(key.to_string())
+            .or_insert_with(|| Arc::new(Mutex::new(ProcessManager::idle())))
+            .clone()
+    }
+
+    /// Drop the slot of a side-by-side instance; `default` always stays.
+    pub(crate) fn remove_slot(key: &str) {
+        if key != instances::DEFAULT_INSTANCE {
+            PROCESS_MANAGERS
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .remove(key);
+        }
+    }
+
+    /// Lock a slot returned by `slot`, recovering from poison.
+    pub(crate) fn lock(slot: &Mutex<ProcessManager>) -> std::sync::MutexGuard<'_, ProcessManager> {
+        slot.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
     /// Reset to idle defaults. Platform engines are expected to have
     /// already torn down their own private state (macOS bypass-router
     /// watchdog, Linux DNS-override stash, …) via `stop` or
     /// `on_process_terminated` before this runs.
     pub(crate) fn reset(&mut self) {
         self.child = None;
+        self.adopted_pid = None;
         self.mode = None;
         self.config_path = None;
         self.is_stopping = false;
@@ -164,12 +269,13 @@ impl ProcessManager {
 
 lazy_static! {
     pub(crate) static ref PROCESS_MANAGER: Arc<Mutex<ProcessManager>> =
-        Arc::new(Mutex::new(ProcessManager {
-            child: None,
-            mode: None,
-            config_path: None,
-            is_stopping: false,
-        }));
+        Arc::new(Mutex::new(ProcessManager::idle()));
+    /// Every instance's slot by key, `default` → `PROCESS_MANAGER`.
+    static ref PROCESS_MANAGERS: Mutex<HashMap<String, Arc<Mutex<ProcessManager>>>> =
+        Mutex::new(HashMap::from([(
+            instances::DEFAULT_INSTANCE.to_string(),
+            PROCESS_MANAGER.clone(),
+        )]));
 }
 
 // ── Start-time port guard ─────────────────────────────────────────────
@@ -177,41 +283,43 @@ lazy_static! {
 /// Ports that must be free before spawning sing-box: the mixed proxy port and
 /// the clash API / external-controller port. Deduped so a configuration where
 /// the two coincide only triggers a single cleanup pass.
-fn ports_to_free(mixed_port: u16) -> Vec<u16> {
+fn ports_to_free(mixed_port: u16, clash_port: u16) -> Vec<u16> {
     let mut ports = vec![mixed_port];
-    if mixed_port != CLASH_API_PORT {
-        ports.push(CLASH_API_PORT);
+    if mixed_port != clash_port {
+        ports.push(clash_port);
     }
     ports
 }
 
 /// Free `port` before spawning sing-box on it. Idempotent: a silent no-op when
 /// the port is already free. Otherwise kills the current listener (user-mode
-/// SIGKILL via `ensure_port_available`) and waits for the socket to release.
+/// SIGKILL via `ensure_port_available`) and waits for the socket to release —
+/// but only when the listener is a sing-box OneBox spawned; anything else
+/// comes back as `ForeignHolder` untouched.
 ///
-/// Returns the `PORT_OCCUPIED_CANNOT_START:<port>` error string when a holder
-/// survives cleanup — e.g. a root-owned orphan that user-mode signals cannot
-/// evict. Surfacing it here turns a silent 20s readiness timeout into an
-/// actionable, logged failure.
-async fn ensure_port_free_for_spawn(action: u64, port: u16) -> Result<(), String> {
-    if !probe_port_listening(port) {
+/// A holder that survives cleanup — e.g. a root-owned orphan that user-mode
+/// signals cannot evict — is surfaced here as an actionable, logged failure
+/// instead of a silent 20s readiness timeout.
+async fn ensure_port_free_for_spawn(
+    action: u64,
+    host: &str,
+    port: u16,
+    owned: Arc<OwnedProcesses>,
+) -> Result<(), PortCleanupError> {
+    if !probe_listening(host, port) {
         return Ok(());
     }
     ::log::warn!(
         "[start] action={action} :{port} already has a listener on entry — previous sing-box still bound?"
     );
     let cleanup = tokio::task::spawn_blocking(move || {
-        crate::commands::prestart::ensure_port_available(port)
+        crate::commands::prestart::ensure_port_available(port, &owned)
     })
     .await
-    .map_err(|e| {
-        format!(
-            "{}:{}: port cleanup join error: {}",
-            crate::commands::prestart::PORT_OCCUPIED_CANNOT_START,
-            port,
-            e
-        )
-    })?;
+    .unwrap_or_else(|e| {
+        ::log::error!("[start] action={action} port cleanup join error: {}", e);
+        Err(PortCleanupError::NoKillableProcess { port })
+    });
     match cleanup {
         Ok(result) => {
             if result.killed_pids.is_empty() {
@@ -226,31 +334,237 @@ async fn ensure_port_free_for_spawn(action: u64, port: u16) -> Result<(), String
         }
         Err(e) => {
             ::log::error!("[start] action={action} prestart port cleanup failed: {}", e);
-            Err(e.start_error())
+            Err(e)
         }
     }
 }
 
+// ── Port fallback ─────────────────────────────────────────────────────
+//
+// When a port we need belongs to some other program, `start` can move to a
+// free port instead of failing: the runtime config is rewritten in place
+// and `port-fallback` tells the dashboard so it can persist the new port
+// for the next merge. Opt-in per call (`port_fallback`) or always via
+// `settings.json` → `port_conflict_fallback`.
+
+pub const EVENT_PORT_FALLBACK: &str = "port-fallback";
+const PORT_FALLBACK_SETTING: &str = "port_conflict_fallback";
+const PORT_FALLBACK_SEARCH: u16 = 100;
+
+#[derive(serde::Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum PortRole {
+    Mixed,
+    ClashApi,
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+struct PortFallback {
+    role: PortRole,
+    from: u16,
+    to: u16,
+}
+
+fn port_fallback_enabled(app: &AppHandle, requested: Option<bool>) -> bool {
+    use tauri_plugin_store::StoreExt;
+    requested.unwrap_or_else(|| {
+        app.get_store("settings.json")
+            .and_then(|store| store.get(PORT_FALLBACK_SETTING))
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false)
+    })
+}
+
+/// First port above `port` that nothing listens on and we can bind,
+/// skipping `avoid`; an OS-assigned port if that range is exhausted.
+fn free_port_near(port: u16, avoid: &[u16]) -> Option<u16> {
+    let bindable = |p: u16| std::net::TcpListener::bind(("127.0.0.1", p)).is_ok();
+    (1..=PORT_FALLBACK_SEARCH)
+        .filter_map(|offset| port.checked_add(offset))
+        .find(|p| !avoid.contains(p) && !probe_port_listening(*p) && bindable(*p))
+        .or_else(|| {
+            std::net::TcpListener::bind("127.0.0.1:0")
+                .and_then(|l| l.local_addr())
+                .map(|addr| addr.port())
+                .ok()
+        })
+}
+
+/// Point the mixed inbound or the Clash API controller of `config` at
+/// `port`. `false` when the config has no such entry.
+fn set_config_port(config: &mut serde_json::Value, role: PortRole, port: u16) -> bool {
+    match role {
+        PortRole::Mixed => config
+            .get_mut("inbounds")
+            .and_then(|v| v.as_array_mut())
+            .and_then(|inbounds| {
+                inbounds.iter_mut().find(|ib| {
+                    ib.get("type").and_then(|v| v.as_str()) == Some("mixed")
+                        && ib.get("tag").and_then(|v| v.as_str()) == Some("mixed")
+                })
+            })
+            .map(|ib| ib["listen_port"] = serde_json::json!(port))
+            .is_some(),
+        PortRole::ClashApi => {
+            let Some(addr) = config.pointer_mut("/experimental/clash_api/external_controller")
+            else {
+                return false;
+            };
+            let host = addr
+                .as_str()
+                .and_then(|a| a.rsplit_once(':'))
+                .map(|(host, _)| host.to_string())
+                .unwrap_or_else(|| "127.0.0.1".into());
+            *addr = serde_json::json!(format!("{}:{}", host, port));
+            true
+        }
+    }
+}
+
+/// Move `role` from `from` to a free port in the config at `path`.
+fn fall_back_to_free_port(
+    app: &AppHandle,
+    action: u64,
+    path: &str,
+    role: PortRole,
+    from: u16,
+    avoid: &[u16],
+) -> Result<u16, String> {
+    let to = free_port_near(from, avoid).ok_or_else(|| format!("no free port near {}", from))?;
+    let mut config: serde_json::Value = std::fs::read_to_string(path)
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .ok_or_else(|| format!("failed to read {}", path))?;
+    if !set_config_port(&mut config, role, to) {
+        return Err(format!("{:?} port not found in {}", role, path));
+    }
+    let text = serde_json::to_string_pretty(&config).map_err(|e| e.to_string())?;
+    config_switch::write_atomic(std::path::Path::new(path), text.as_bytes())
+        .map_err(|e| format!("failed to write {}: {}", path, e))?;
+    ::log::warn!(
+        "[start] action={action} {:?} port :{from} is held by another program, moved to :{to}",
+        role
+    );
+    let _ = app.emit(EVENT_PORT_FALLBACK, PortFallback { role, from, to });
+    Ok(to)
+}
+
 // ── Tauri Commands ────────────────────────────────────────────────────
 
+/// `port_fallback`: when a port is held by a program OneBox did not spawn,
+/// move to a free port instead of failing (`None` defers to the
+/// `port_conflict_fallback` setting).
 #[tauri::command]
-pub async fn start(app: tauri::AppHandle, path: String, mode: ProxyMode) -> Result<(), String> {
+pub async fn start(
+    app: tauri::AppHandle,
+    path: String,
+    mode: ProxyMode,
+    port_fallback: Option<bool>,
+) -> Result<(), String> {
+    start_with(app, path, mode, port_fallback, StartConfig::Staged).await
+}
+
+/// Re-run start for `engine::recovery` after a crash. A candidate staged
+/// since the crash is left alone: recovery restores what was running, it
+/// does not roll a new config out.
+pub(crate) async fn restart_after_crash(
+    app: tauri::AppHandle,
+    path: String,
+    mode: ProxyMode,
+) -> Result<(), String> {
+    start_with(app, path, mode, None, StartConfig::LiveOrLastGood).await
+}
+
+/// Which config a start may run.
+enum StartConfig {
+    /// A staged candidate is validated and promoted; otherwise the live one.
+    Staged,
+    /// Only the live config, or the last-known-good when the live one no
+    /// longer validates.
+    LiveOrLastGood,
+}
+
+/// Validate the live config, falling back to the last-known-good.
+async fn validate_live_or_last_good(
+    app: &AppHandle,
+    action: u64,
+    path: &str,
+    mode: &ProxyMode,
+) -> Result<(), String> {
+    let Err(e) = preflight::validate(app, path, mode).await else {
+        return Ok(());
+    };
+    match config_switch::revert(path) {
+        Ok(true) => {
+            ::log::warn!("[start] action={action} live config invalid, restored last-known-good");
+            preflight::validate(app, path, mode).await
+        }
+        Ok(false) => Err(e),
+        Err(revert_err) => {
+            ::log::warn!("[start] action={action} {}", revert_err);
+            Err(e)
+        }
+    }
+}
+
+async fn start_with(
+    app: tauri::AppHandle,
+    path: String,
+    mode: ProxyMode,
+    port_fallback: Option<bool>,
+    config: StartConfig,
+) -> Result<(), String> {
     let action = next_action_token();
+    // Reject a broken config before touching ports, state or the system
+    // proxy, so the UI gets field-level errors instead of a readiness timeout.
+    // A freshly merged config is validated in staging, then promoted.
+    let validated = match config {
+        StartConfig::Staged => match config_switch::promote(&app, &path, &mode).await {
+            Ok(true) => Ok(()),
+            Ok(false) => preflight::validate(&app, &path, &mode).await,
+            Err(e) => Err(e),
+        },
+        StartConfig::LiveOrLastGood => validate_live_or_last_good(&app, action, &path, &mode).await,
+    };
+    if let Err(e) = validated {
+        ::log::warn!("[start] action={action} rejected by preflight");
+        return Err(e);
+    }
     let (pm_pid, pm_alive, pm_mode) = pm_snapshot();
+    // Ports are read after promotion: a staged config may move them.
     let mixed_port = mixed_proxy_port(&app);
     let port_listening = probe_port_listening(mixed_port);
-    let clash_listening = probe_port_listening(CLASH_API_PORT);
+    let (clash_host, clash_port) = clash_api_addr(&app);
+    let clash_listening = probe_listening(&clash_host, clash_port);
     let cur_state_kind = app.state::<EngineStateCell>().snapshot().kind();
     ::log::info!(
-        "[start] action={action} mode={:?} state={} pm_child_pid={:?} pm_child_alive={:?} pm_mode={:?} :{mixed_port}_listener={} :{CLASH_API_PORT}_listener={}",
+        "[start] action={action} mode={:?} state={} pm_child_pid={:?} pm_child_alive={:?} pm_mode={:?} :{mixed_port}_listener={} :{clash_port}_listener={}",
         mode, cur_state_kind, pm_pid, pm_alive, pm_mode, port_listening, clash_listening
     );
     // A listener on either the mixed proxy port or the clash API port on entry
     // is enough to explain a subsequent EADDRINUSE in sing-box stderr — free
     // both before spawning. `ensure_port_free_for_spawn` is idempotent and
     // logs per-port, so an unoccupied port is a silent no-op.
-    for port in ports_to_free(mixed_port) {
-        ensure_port_free_for_spawn(action, port).await?;
+    let owned = Arc::new(crate::commands::prestart::owned_processes(&app));
+    let fallback = port_fallback_enabled(&app, port_fallback);
+    for port in ports_to_free(mixed_port, clash_port) {
+        let host = if port == clash_port {
+            clash_host.as_str()
+        } else {
+            "127.0.0.1"
+        };
+        match ensure_port_free_for_spawn(action, host, port, owned.clone()).await {
+            Ok(()) => {}
+            Err(PortCleanupError::ForeignHolder { .. }) if fallback => {
+                let role = if port == mixed_port {
+                    PortRole::Mixed
+                } else {
+                    PortRole::ClashApi
+                };
+                fall_back_to_free_port(&app, action, &path, role, port, &[mixed_port, clash_port])?;
+            }
+            Err(e) => return Err(e.start_error()),
+        }
     }
     if matches!(pm_alive, Some(true)) {
         ::log::warn!(
@@ -261,7 +575,10 @@ pub async fn start(app: tauri::AppHandle, path: String, mode: ProxyMode) -> Resu
 
     {
         let cur = app.state::<EngineStateCell>().snapshot();
-        if !matches!(cur, EngineState::Idle { .. } | EngineState::Failed { .. }) {
+        if !matches!(
+            cur,
+            EngineState::Idle { .. } | EngineState::Failed { .. } | EngineState::Recovering { .. }
+        ) {
             ::log::warn!(
                 "[start] action={action} engine in {} state, forcing MarkIdle before restart",
                 cur.kind()
@@ -269,14 +586,11 @@ pub async fn start(app: tauri::AppHandle, path: String, mode: ProxyMode) -> Resu
             let _ = transition(&app, Intent::MarkIdle);
         }
     }
-    let mode_label = match mode {
-        ProxyMode::TunProxy => "tun",
-        ProxyMode::SystemProxy | ProxyMode::ManualProxy => "mixed",
-    };
+    crate::engine::journal::note_config(&path);
     if let Err(e) = transition(
         &app,
         Intent::Start {
-            mode: mode_label.into(),
+            mode: state_mode_label(&mode).into(),
         },
     ) {
         return Err(format!("state transition rejected: {}", e));
@@ -287,7 +601,7 @@ pub async fn start(app: tauri::AppHandle, path: String, mode: ProxyMode) -> Resu
     // watchdogs, and ProcessManager seeding live inside the platform engine.
     // core just drives state-machine transitions and hands off to the
     // readiness prober once the spawn call returns.
-    if let Err(e) = PlatformEngine::start(&app, mode.clone(), path, start_epoch).await {
+    if let Err(e) = PlatformEngine::start(&app, mode.clone(), path.clone(), start_epoch).await {
         ::log::error!(
             "[start] action={action} PlatformEngine::start failed: {}",
             e
@@ -297,9 +611,14 @@ pub async fn start(app: tauri::AppHandle, path: String, mode: ProxyMode) -> Resu
         // it did set up so we don't leak a half-started engine.
         let _ = PlatformEngine::stop(&app).await;
         ProcessManager::acquire().reset();
-        let _ = transition(&app, Intent::Fail { reason: e.clone() });
+        runtime_record::clear(&app);
+        if let Some(reason) = crate::engine::recovery::handle_failure(&app, e.clone(), None, None)
+        {
+            let _ = transition(&app, Intent::Fail { reason });
+        }
         return Err(e);
     }
+    runtime_record::note_started(&app, &mode, &path, start_epoch);
 
     // Platform-specific settle window before readiness probing — TUN
     // round-trips through the privileged companion, SystemProxy just
@@ -325,13 +644,16 @@ pub async fn stop(app: tauri::AppHandle) -> Result<(), String> {
         cur_state_kind, pm_pid, pm_alive, pm_mode, is_stopping_before
     );
 
+    // A user stop ends any crash-recovery incident, including a pending
+    // attempt (Recovering → Idle bumps the epoch the attempt task checks).
+    crate::engine::recovery::reset();
     {
         let cur = app.state::<EngineStateCell>().snapshot();
         match cur {
-            EngineState::Running { .. } => {
+            EngineState::Running { .. } | EngineState::Degraded { .. } => {
                 let _ = transition(&app, Intent::Stop);
             }
-            EngineState::Starting { .. } => {
+            EngineState::Starting { .. } | EngineState::Recovering { .. } => {
                 let _ = transition(&app, Intent::MarkIdle);
             }
             _ => {}
@@ -371,9 +693,10 @@ pub async fn stop(app: tauri::AppHandle) -> Result<(), String> {
     // and leave the child untrackable and unkillable.
     if !matches!(
         post_stop_state,
-        EngineState::Starting { .. } | EngineState::Running { .. }
+        EngineState::Starting { .. } | EngineState::Running { .. } | EngineState::Degraded { .. }
     ) {
         ProcessManager::acquire().reset();
+        runtime_record::clear(&app);
     }
 
     // Post-stop port probe: `PlatformEngine::stop` only SIGTERMs + sleeps
@@ -414,7 +737,7 @@ pub async fn is_running(app: AppHandle, secret: String) -> bool {
     let app_data = app.state::<AppData>();
     app_data.set_clash_secret(Some(secret));
     let state = app.state::<EngineStateCell>().snapshot();
-    matches!(state, EngineState::Running { .. })
+    state.is_running()
 }
 
 #[tauri::command]
@@ -430,7 +753,7 @@ pub fn clear_engine_error(app: AppHandle) {
     }
 }
 
-#[cfg(any(target_os = "macos", target_os = "windows"))]
+#[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
 pub fn get_running_config() -> Option<(ProxyMode, String)> {
     let manager = ProcessManager::acquire();
     match (manager.mode.as_ref(), manager.config_path.as_ref()) {
@@ -456,7 +779,7 @@ pub async fn reload_config(app: tauri::AppHandle) -> Result<String, String> {
     if let Some(delta) = since_last {
         if delta.as_millis() < 2000 {
             ::log::warn!(
-                "[reload] action={action} back-to-back: only {}ms since last reload — frontend is not serializing",
+                "[reload] action={action} back-to-back: only {}ms since last reload — will queue",
                 delta.as_millis()
             );
         }
@@ -469,41 +792,72 @@ pub async fn reload_config(app: tauri::AppHandle) -> Result<String, String> {
 
     #[cfg(any(unix, target_os = "windows"))]
     {
-        let needs_proxy_reset = {
+        let _queue = config_switch::queue(action).await;
+        let (running_mode, running_path) = {
             let manager = ProcessManager::acquire();
-            match manager.mode.as_ref().map(|m| m.as_ref()) {
-                Some(ProxyMode::TunProxy) => false,
-                Some(ProxyMode::SystemProxy) => true,
-                Some(ProxyMode::ManualProxy) => false,
-                None => {
-                    ::log::warn!("[reload] action={action} rejected: no running process");
-                    return Err("No running process found".to_string());
-                }
+            (
+                manager.mode.as_ref().map(|m| (**m).clone()),
+                manager.config_path.as_ref().map(|p| (**p).clone()),
+            )
+        };
+        let needs_proxy_reset = match running_mode.as_ref() {
+            Some(ProxyMode::TunProxy) => false,
+            Some(ProxyMode::SystemProxy) => true,
+            Some(ProxyMode::ManualProxy) => false,
+            None => {
+                ::log::warn!("[reload] action={action} rejected: no running process");
+                return Err("No running process found".to_string());
             }
         };
 
+        let (Some(mode), Some(path)) = (running_mode, running_path) else {
+            ::log::warn!("[reload] action={action} rejected: no running config path");
+            return Err("No running config path found".to_string());
+        };
+        let is_tun = matches!(mode, ProxyMode::TunProxy);
+
+        // Keep the running engine on its current config if the new one
+        // would not load. Nothing staged means the live file was edited
+        // directly; it is checked in place.
+        let validated = match config_switch::promote(&app, &path, &mode).await {
+            Ok(true) => Ok(()),
+            Ok(false) => preflight::validate(&app, &path, &mode).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = validated {
+            ::log::warn!("[reload] action={action} rejected by preflight; engine left untouched");
+            return Err(e);
+        }
         ::log::info!("[reload] action={action} dispatching PlatformEngine::restart");
-        PlatformEngine::restart(&app).await?;
+        if let Err(e) = PlatformEngine::restart(&app).await {
+            // SIGHUP never went out; keep the file matching the engine.
+            let _ = config_switch::revert(&path);
+            return Err(e);
+        }
 
-        if needs_proxy_reset {
-            ::log::info!(
-                "[reload] action={action} SystemProxy — sleeping 500ms for sing-box rebind, then re-apply proxy"
-            );
-            tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
-            let still_listening = probe_port_listening(mixed_port);
-            if !still_listening {
-                // Decisive signal: SIGHUP was sent, 500ms later the port
-                // has no listener. Either (a) sing-box's Close()+create()
-                // cycle hasn't rebound yet (bind failed, Close took too
-                // long, log.Fatal exit), or (b) sing-box died outright.
-                ::log::warn!(
-                    "[reload] action={action} :{mixed_port} NOT listening 500ms after SIGHUP — sing-box rebind FAILED or process died"
-                );
-            } else {
-                ::log::info!(
-                    "[reload] action={action} :{mixed_port} listener up 500ms after SIGHUP"
-                );
+        if let Err(reason) = readiness::verify_reload(&app, &path, is_tun).await {
+            ::log::warn!("[reload] action={action} {reason}; reverting to last-known-good config");
+            let reverted = revert_failed_reload(&app, action, &path, is_tun, &reason).await;
+            if reverted && needs_proxy_reset {
+                if let Err(e) = crate::engine::apply_system_proxy(&app).await {
+                    ::log::error!(
+                        "[reload] action={action} re-apply system proxy failed: {}",
+                        e
+                    );
+                }
             }
+            return Err(format!(
+                "{}:{}",
+                config_switch::CONFIG_RELOAD_REVERTED,
+                reason
+            ));
+        }
+        ::log::info!("[reload] action={action} :{mixed_port} serving on the new config");
+        if let Err(e) = config_switch::commit(&path) {
+            ::log::warn!("[reload] action={action} failed to record last-known-good config: {e}");
+        }
+
+        if needs_proxy_reset {
             if let Err(e) = crate::engine::apply_system_proxy(&app).await {
                 ::log::error!(
                     "[reload] action={action} re-apply system proxy failed: {}",
@@ -524,21 +878,135 @@ pub async fn reload_config(app: tauri::AppHandle) -> Result<String, String> {
     }
 }
 
+/// Put the last-known-good config back and reload onto it. Returns whether
+/// the engine came back; if it did not (or there was nothing to revert to),
+/// the engine is marked failed so the UI stops showing it as running.
+#[cfg(any(unix, target_os = "windows"))]
+async fn revert_failed_reload(
+    app: &AppHandle,
+    action: u64,
+    path: &str,
+    is_tun: bool,
+    reason: &str,
+) -> bool {
+    let outcome = match config_switch::revert(path) {
+        Ok(true) => match PlatformEngine::restart(app).await {
+            Ok(()) => readiness::verify_reload(app, path, is_tun).await,
+            Err(e) => Err(e),
+        },
+        Ok(false) => Err("no last-known-good config to revert to".to_string()),
+        Err(e) => Err(e),
+    };
+    match outcome {
+        Ok(()) => {
+            ::log::info!("[reload] action={action} reverted to last-known-good config");
+            true
+        }
+        Err(e) => {
+            ::log::error!("[reload] action={action} revert failed: {e}");
+            let _ = transition(
+                app,
+                Intent::Fail {
+                    reason: format!("reload failed ({reason}); revert failed ({e})"),
+                },
+            );
+            false
+        }
+    }
+}
+
 // ── Tests ─────────────────────────────────────────────────────────────
 
 #[cfg(test)]
 mod port_guard_tests {
-    use super::{ports_to_free, CLASH_API_PORT};
+    use super::{
+        clash_api_addr_from_config, free_port_near, mixed_port_from_config, ports_to_free,
+        probe_addr, probe_listening, set_config_port, PortRole, DEFAULT_CLASH_API_PORT,
+    };
 
     #[test]
     fn distinct_ports_yield_both_in_order() {
-        assert_eq!(ports_to_free(6661), vec![6661, CLASH_API_PORT]);
-        assert_eq!(ports_to_free(6789), vec![6789, CLASH_API_PORT]);
+        assert_eq!(
+            ports_to_free(6661, DEFAULT_CLASH_API_PORT),
+            vec![6661, DEFAULT_CLASH_API_PORT]
+        );
+        assert_eq!(ports_to_free(6789, 19090), vec![6789, 19090]);
     }
 
     #[test]
     fn coinciding_port_is_deduped() {
-        assert_eq!(ports_to_free(CLASH_API_PORT), vec![CLASH_API_PORT]);
+        assert_eq!(
+            ports_to_free(DEFAULT_CLASH_API_PORT, DEFAULT_CLASH_API_PORT),
+            vec![DEFAULT_CLASH_API_PORT]
+        );
+    }
+
+    #[test]
+    fn clash_api_addr_follows_the_config() {
+        let with = |addr: &str| serde_json::json!({ "experimental": { "clash_api": { "external_controller": addr } } });
+        assert_eq!(
+            clash_api_addr_from_config(&with("127.0.0.1:19090")),
+            Some(("127.0.0.1".into(), 19090))
+        );
+        assert_eq!(
+            clash_api_addr_from_config(&with("0.0.0.0:9191")),
+            Some(("127.0.0.1".into(), 9191))
+        );
+        assert_eq!(
+            clash_api_addr_from_config(&with("[::1]:9191")),
+            Some(("[::1]".into(), 9191))
+        );
+        assert_eq!(clash_api_addr_from_config(&with("127.0.0.1")), None);
+        assert_eq!(clash_api_addr_from_config(&serde_json::json!({})), None);
+    }
+
+    #[test]
+    fn probes_go_to_the_configured_host() {
+        let v6 = std::net::SocketAddr::from((std::net::Ipv6Addr::LOCALHOST, 9191));
+        assert_eq!(probe_addr("[::1]", 9191), v6);
+        let v4 = std::net::SocketAddr::from(([127, 0, 0, 1], 9191));
+        assert_eq!(probe_addr("127.0.0.1", 9191), v4);
+        assert_eq!(probe_addr("localhost", 9191), v4);
+
+        let listener = match std::net::TcpListener::bind("[::1]:0") {
+            Ok(listener) => listener,
+            // No IPv6 loopback on this host.
+            Err(_) => return,
+        };
+        let port = listener.local_addr().unwrap().port();
+        assert!(probe_listening("[::1]", port));
+    }
+
+    #[test]
+    fn fallback_rewrites_only_the_conflicting_port() {
+        let mut config = serde_json::json!({
+            "inbounds": [
+                { "type": "tun", "tag": "tun" },
+                { "type": "mixed", "tag": "mixed", "listen_port": 6789 }
+            ],
+            "experimental": { "clash_api": { "external_controller": "127.0.0.1:9191" } }
+        });
+        assert!(set_config_port(&mut config, PortRole::Mixed, 6790));
+        assert_eq!(mixed_port_from_config(&config), Some(6790));
+        assert!(set_config_port(&mut config, PortRole::ClashApi, 9192));
+        assert_eq!(
+            clash_api_addr_from_config(&config),
+            Some(("127.0.0.1".into(), 9192))
+        );
+        assert!(!set_config_port(
+            &mut serde_json::json!({}),
+            PortRole::Mixed,
+            1
+        ));
+    }
+
+    #[test]
+    fn free_port_skips_busy_and_avoided_ports() {
+        let busy = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = busy.local_addr().unwrap().port();
+        let found = free_port_near(base.saturating_sub(1), &[base.saturating_sub(1)]).unwrap();
+        assert_ne!(found, base);
+        assert_ne!(found, base.saturating_sub(1));
     }
 }
 
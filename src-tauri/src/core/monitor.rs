@@ -1,6 +1,7 @@
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use tauri::Emitter;
 use tauri::Manager;
+use tauri_plugin_shell::process::{CommandEvent, TerminatedPayload};
 
 use crate::app::state::{AppData, LogType};
 use crate::engine::state_machine::{transition, EngineState, EngineStateCell, Intent};
@@ -9,6 +10,22 @@ use crate::engine::{EngineManager, PlatformEngine, EVENT_STATUS_CHANGED};
 use super::log::{create_singbox_log_writer, write_singbox_log};
 use super::{ProcessManager, ProxyMode};
 
+/// Whose sing-box a monitor watches, i.e. where its exit is handled.
+pub(crate) enum MonitorOwner {
+    /// The default engine: app-wide state machine, proxy / TUN teardown and
+    /// crash recovery (`handle_process_termination`).
+    Default {
+        mode: Arc<ProxyMode>,
+        spawn_epoch: u64,
+    },
+    /// A side-by-side instance (`core::instances`).
+    Instance {
+        key: String,
+        cell: Arc<EngineStateCell>,
+        slot: Arc<Mutex<ProcessManager>>,
+    },
+}
+
 /// Spawn the sing-box stdout/stderr monitor as a tokio task.
 /// Routes output to log file + frontend events, and handles termination.
 ///
@@ -19,53 +36,84 @@ use super::{ProcessManager, ProxyMode};
 /// bind-error / spawn entries can be correlated across the full log.
 pub(crate) fn spawn_process_monitor(
     app: tauri::AppHandle,
-    mut rx: tauri::async_runtime::Receiver<tauri_plugin_shell::process::CommandEvent>,
+    rx: tauri::async_runtime::Receiver<CommandEvent>,
     mode: Arc<ProxyMode>,
     child_pid: u32,
     spawn_epoch: u64,
 ) {
-    let mut singbox_log = create_singbox_log_writer(&app);
-    let spawn_at = std::time::Instant::now();
-    log::info!(
-        "[sing-box] monitor attached pid={} mode={:?}",
+    let singbox_log = create_singbox_log_writer(&app);
+    spawn_monitor(
+        app,
+        rx,
         child_pid,
-        mode
+        singbox_log,
+        MonitorOwner::Default { mode, spawn_epoch },
     );
+}
+
+/// The monitor for any owner. `singbox_log` is the file sing-box output is
+/// appended to; only the default engine's stderr also reaches the
+/// dashboard log.
+pub(crate) fn spawn_monitor(
+    app: tauri::AppHandle,
+    mut rx: tauri::async_runtime::Receiver<CommandEvent>,
+    child_pid: u32,
+    mut singbox_log: Option<std::fs::File>,
+    owner: MonitorOwner,
+) {
+    crate::commands::prestart::record_spawned_pid(&app, child_pid);
+    let spawn_at = std::time::Instant::now();
+    match &owner {
+        MonitorOwner::Default { mode, .. } => log::info!(
+            "[sing-box] monitor attached pid={} mode={:?}",
+            child_pid,
+            mode
+        ),
+        MonitorOwner::Instance { key, .. } => log::info!(
+            "[sing-box] monitor attached pid={} instance={}",
+            child_pid,
+            key
+        ),
+    }
+    let to_dashboard = matches!(owner, MonitorOwner::Default { .. });
     tokio::spawn(async move {
         let mut terminated = false;
         let app_status_data = app.state::<AppData>();
 
         while let Some(event) = rx.recv().await {
             if terminated {
-                if let tauri_plugin_shell::process::CommandEvent::Stdout(line)
-                | tauri_plugin_shell::process::CommandEvent::Stderr(line) = event
-                {
+                if let CommandEvent::Stdout(line) | CommandEvent::Stderr(line) = event {
                     let line_str = String::from_utf8_lossy(&line);
                     write_singbox_log(&mut singbox_log, &line_str);
                 }
                 continue;
             }
             match event {
-                tauri_plugin_shell::process::CommandEvent::Stdout(line) => {
+                CommandEvent::Stdout(line) => {
                     log::debug!("[sing-box-event] pid={} Stdout", child_pid);
                     let line_str = String::from_utf8_lossy(&line);
                     write_singbox_log(&mut singbox_log, &line_str);
                 }
-                tauri_plugin_shell::process::CommandEvent::Stderr(line) => {
+                CommandEvent::Stderr(line) => {
                     log::debug!("[sing-box-event] pid={} Stderr", child_pid);
                     let line_str = String::from_utf8_lossy(&line);
                     write_singbox_log(&mut singbox_log, &line_str);
                     scan_stderr_for_bind_error(child_pid, &line_str);
-                    app_status_data.write(line_str.to_string(), LogType::Info);
+                    if to_dashboard {
+                        app_status_data.write(line_str.to_string(), LogType::Info);
+                    }
                 }
-                tauri_plugin_shell::process::CommandEvent::Error(err) => {
+                CommandEvent::Error(err) => {
                     log::debug!("[sing-box-event] pid={} Error", child_pid);
                     log::error!("[sing-box] pid={} process error: {}", child_pid, err);
                     write_singbox_log(&mut singbox_log, &format!("[ERROR] {}", err));
-                    app_status_data.write(err.to_string(), LogType::Error);
+                    if to_dashboard {
+                        app_status_data.write(err.to_string(), LogType::Error);
+                    }
                 }
-                tauri_plugin_shell::process::CommandEvent::Terminated(payload) => {
+                CommandEvent::Terminated(payload) => {
                     terminated = true;
+                    crate::commands::prestart::forget_spawned_pid(&app, child_pid);
                     let runtime = spawn_at.elapsed();
                     log::info!(
                         "[sing-box] pid={} terminated runtime={:.2}s code={:?} signal={:?}",
@@ -74,35 +122,15 @@ pub(crate) fn spawn_process_monitor(
                         payload.code,
                         payload.signal
                     );
-                    #[allow(unused_variables)]
-                    let adjusted_payload = {
-                        #[cfg(target_os = "windows")]
-                        {
-                            let is_stopping = {
-                                let manager = ProcessManager::acquire();
-                                manager.is_stopping
-                            };
-                            if is_stopping && payload.code == Some(1) {
-                                log::info!(
-                                    "[monitor] windows code remap applied orig_code=1 new_code=0 is_stopping=true pid={}",
-                                    child_pid
-                                );
-                                tauri_plugin_shell::process::TerminatedPayload {
-                                    code: Some(0),
-                                    signal: payload.signal,
-                                }
-                            } else {
-                                log::debug!(
-                                    "[monitor] windows code remap not applied pid={} is_stopping={} code={:?}",
-                                    child_pid, is_stopping, payload.code
-                                );
-                                payload
-                            }
+                    match &owner {
+                        MonitorOwner::Default { mode, spawn_epoch } => {
+                            let payload = remap_stop_exit_code(child_pid, payload);
+                            handle_process_termination(&app, mode, payload, *spawn_epoch).await;
+                        }
+                        MonitorOwner::Instance { key, cell, slot } => {
+                            super::instances::handle_termination(&app, key, cell, slot, payload);
                         }
-                        #[cfg(not(target_os = "windows"))]
-                        payload
-                    };
-                    handle_process_termination(&app, &mode, adjusted_payload, spawn_epoch).await;
+                    }
                 }
                 _ => {
                     log::debug!("[sing-box-event] pid={} other event received", child_pid);
@@ -112,6 +140,36 @@ pub(crate) fn spawn_process_monitor(
     });
 }
 
+/// Windows reports a requested stop of the default engine as code 1;
+/// count it as the clean exit it is.
+#[cfg_attr(not(target_os = "windows"), allow(unused_variables))]
+fn remap_stop_exit_code(child_pid: u32, payload: TerminatedPayload) -> TerminatedPayload {
+    #[cfg(target_os = "windows")]
+    {
+        let is_stopping = {
+            let manager = ProcessManager::acquire();
+            manager.is_stopping
+        };
+        if is_stopping && payload.code == Some(1) {
+            log::info!(
+                "[monitor] windows code remap applied orig_code=1 new_code=0 is_stopping=true pid={}",
+                child_pid
+            );
+            return TerminatedPayload {
+                code: Some(0),
+                signal: payload.signal,
+            };
+        }
+        log::debug!(
+            "[monitor] windows code remap not applied pid={} is_stopping={} code={:?}",
+            child_pid,
+            is_stopping,
+            payload.code
+        );
+    }
+    payload
+}
+
 /// Sing-box emits `listen tcp 127.0.0.1:6789: bind: address already in
 /// use` (or the platform's localized equivalent) on stderr when its
 /// Mixed inbound's `listenConfig.Listen()` returns EADDRINUSE. The raw
@@ -145,7 +203,7 @@ pub(crate) fn epoch_guard_stale(spawn_epoch: u64, current_epoch: u64) -> bool {
 pub(crate) async fn handle_process_termination(
     app_handle: &tauri::AppHandle,
     process_mode: &Arc<ProxyMode>,
-    payload: tauri_plugin_shell::process::TerminatedPayload,
+    payload: TerminatedPayload,
     spawn_epoch: u64,
 ) {
     let current_epoch = app_handle.state::<EngineStateCell>().snapshot().epoch();
@@ -173,17 +231,20 @@ pub(crate) async fn handle_process_termination(
     // reset ProcessManager yet — the platform's on_process_terminated hook
     // below may need to read teardown state (e.g. Linux dns_override) that
     // lives there.
-    let (pm_pid, manager_mode, matches, is_stopping) = {
+    let (pm_pid, manager_mode, manager_config, matches, is_stopping) = {
         let manager = ProcessManager::acquire();
         let pm_pid = manager.child.as_ref().map(|c| c.pid());
         let manager_mode = manager.mode.as_ref().map(|m| (**m).clone());
+        // Captured before the reset below so crash recovery can restart
+        // with the same config.
+        let manager_config = manager.config_path.as_ref().map(|p| (**p).clone());
         let matches = manager
             .mode
             .as_ref()
             .map(|m| **m == **process_mode)
             .unwrap_or(false);
         let is_stopping = manager.is_stopping;
-        (pm_pid, manager_mode, matches, is_stopping)
+        (pm_pid, manager_mode, manager_config, matches, is_stopping)
     };
     let engine_state = app_handle
         .state::<crate::engine::state_machine::EngineStateCell>()
@@ -226,6 +287,7 @@ pub(crate) async fn handle_process_termination(
         // it needed, reset ProcessManager. The old `reset()` return value is
         // ignored — dns_override consumption is a platform concern.
         ProcessManager::acquire().reset();
+        crate::engine::runtime_record::clear(app_handle);
     }
 
     if let Err(e) = app_handle.emit(EVENT_STATUS_CHANGED, payload.clone()) {
@@ -242,7 +304,9 @@ pub(crate) async fn handle_process_termination(
             );
             let _ = transition(app_handle, Intent::MarkIdle);
         }
-        EngineState::Running { .. } | EngineState::Starting { .. } => {
+        EngineState::Running { .. }
+        | EngineState::Degraded { .. }
+        | EngineState::Starting { .. } => {
             let code = payload.code.unwrap_or(-1);
             if code == 0 {
                 log::info!(
@@ -251,17 +315,41 @@ pub(crate) async fn handle_process_termination(
                 );
                 let _ = transition(app_handle, Intent::MarkIdle);
             } else {
-                log::info!(
-                    "[monitor] intent=Fail reason=unexpected_exit engine_state={} code={}",
-                    cur.kind(),
-                    code
-                );
-                let _ = transition(
-                    app_handle,
-                    Intent::Fail {
-                        reason: format!("sing-box exited unexpectedly (code={})", code),
-                    },
-                );
+                let reason = format!("sing-box exited unexpectedly (code={})", code);
+                let running_since = match cur {
+                    // Degraded `since` understates uptime, which only makes
+                    // the recovery cooldown more conservative.
+                    EngineState::Running { since, .. } | EngineState::Degraded { since, .. } => {
+                        Some(since)
+                    }
+                    _ => None,
+                };
+                let target = manager_mode.clone().zip(manager_config.clone());
+                let fail_reason = if is_stale || !matches {
+                    Some(reason)
+                } else {
+                    crate::engine::recovery::handle_failure(
+                        app_handle,
+                        reason,
+                        running_since,
+                        target,
+                    )
+                };
+                match fail_reason {
+                    Some(reason) => {
+                        log::info!(
+                            "[monitor] intent=Fail reason=unexpected_exit engine_state={} code={}",
+                            cur.kind(),
+                            code
+                        );
+                        let _ = transition(app_handle, Intent::Fail { reason });
+                    }
+                    None => log::info!(
+                        "[monitor] intent=Recover reason=unexpected_exit engine_state={} code={}",
+                        cur.kind(),
+                        code
+                    ),
+                }
             }
         }
         _ => {
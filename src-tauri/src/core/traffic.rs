@@ -0,0 +1,388 @@
+//! Live traffic statistics, aggregated on the Rust side.
+//!
+//! While the engine is Running (or Degraded) one task follows the Clash API:
+//! every `/traffic` sample (one per second) updates the up/down rate, and a
+//! `/connections` poll alongside it folds per-connection byte counters into
+//! per-outbound and per-process totals. Counters of connections that closed
+//! since the previous poll are kept, so totals only grow for the whole
+//! engine session; the next start begins a new session at zero.
+//!
+//! Each sample is pushed as a compact `traffic-stats` event (top entries
+//! only); `get_traffic_snapshot` returns the full picture on demand.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::broadcast::{self, error::TryRecvError};
+
+use super::clash::{ClashClient, Connection, Traffic};
+use crate::engine::state_machine::{now_secs, EngineStateCell, StateChange};
+
+pub const EVENT_TRAFFIC_STATS: &str = "traffic-stats";
+/// Rows per breakdown carried by the event; the command returns all.
+const EVENT_TOP_N: usize = 8;
+const IDLE_POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// No `/traffic` line for this long means the stream is wedged: reconnect.
+const STREAM_STALL_TIMEOUT: Duration = Duration::from_secs(5);
+const CONNECTIONS_TIMEOUT: Duration = Duration::from_secs(1);
+/// Label for connections without a process path (e.g. system proxy mode
+/// on platforms where sing-box can't resolve the owner).
+const UNKNOWN_PROCESS: &str = "unknown";
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct UsageEntry {
+    pub name: String,
+    pub upload: u64,
+    pub download: u64,
+}
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct TrafficSnapshot {
+    /// Engine epoch this session started at; 0 when no session is tracked.
+    pub epoch: u64,
+    /// Unix seconds of the most recent sample.
+    pub at: i64,
+    /// Bytes/s over the last second.
+    pub up_rate: u64,
+    pub down_rate: u64,
+    /// Bytes since the session started.
+    pub upload_total: u64,
+    pub download_total: u64,
+    pub active_connections: usize,
+    /// Sorted by upload + download, largest first.
+    pub by_outbound: Vec<UsageEntry>,
+    pub by_process: Vec<UsageEntry>,
+}
+
+struct LiveConnection {
+    outbound: String,
+    process: String,
+    upload: u64,
+    download: u64,
+}
+
+/// Folds successive `/connections` polls into session totals.
+#[derive(Default)]
+struct Aggregator {
+    epoch: u64,
+    at: i64,
+    rate: Traffic,
+    live: HashMap<String, LiveConnection>,
+    /// Bytes of connections that have since closed.
+    closed_outbound: HashMap<String, (u64, u64)>,
+    closed_process: HashMap<String, (u64, u64)>,
+}
+
+/// The leaf outbound that actually carried the connection. sing-box lists
+/// the chain leaf-first (`["hk-01", "ExitGateway"]`).
+fn outbound_of(conn: &Connection) -> String {
+    conn.chains
+        .first()
+        .cloned()
+        .unwrap_or_else(|| "direct".into())
+}
+
+fn process_of(conn: &Connection) -> String {
+    let path = conn.metadata.process_path.trim();
+    if path.is_empty() {
+        return UNKNOWN_PROCESS.into();
+    }
+    path.rsplit(['/', '\\']).next().unwrap_or(path).to_string()
+}
+
+fn add(map: &mut HashMap<String, (u64, u64)>, key: &str, upload: u64, download: u64) {
+    let slot = map.entry(key.to_string()).or_default();
+    slot.0 += upload;
+    slot.1 += download;
+}
+
+fn sorted(map: HashMap<String, (u64, u64)>) -> Vec<UsageEntry> {
+    let mut entries: Vec<UsageEntry> = map
+        .into_iter()
+        .map(|(name, (upload, download))| UsageEntry {
+            name,
+            upload,
+            download,
+        })
+        .collect();
+    entries.sort_by(|a, b| {
+        (b.upload + b.download)
+            .cmp(&(a.upload + a.download))
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    entries
+}
+
+impl Aggregator {
+    fn new(epoch: u64) -> Self {
+        Self {
+            epoch,
+            ..Default::default()
+        }
+    }
+
+    fn record_rate(&mut self, rate: Traffic, at: i64) {
+        self.rate = rate;
+        self.at = at;
+    }
+
+    fn record_connections(&mut self, conns: &[Connection]) {
+        let mut next = HashMap::with_capacity(conns.len());
+        for conn in conns {
+            next.insert(
+                conn.id.clone(),
+                LiveConnection {
+                    outbound: outbound_of(conn),
+                    process: process_of(conn),
+                    upload: conn.upload,
+                    download: conn.download,
+                },
+            );
+        }
+        for (id, gone) in self.live.drain() {
+            if !next.contains_key(&id) {
+                add(
+                    &mut self.closed_outbound,
+                    &gone.outbound,
+                    gone.upload,
+                    gone.download,
+                );
+                add(
+                    &mut self.closed_process,
+                    &gone.process,
+                    gone.upload,
+                    gone.download,
+                );
+            }
+        }
+        self.live = next;
+    }
+
+    fn snapshot(&self, top_n: Option<usize>) -> TrafficSnapshot {
+        let mut outbound = self.closed_outbound.clone();
+        let mut process = self.closed_process.clone();
+        for conn in self.live.values() {
+            add(&mut outbound, &conn.outbound, conn.upload, conn.download);
+            add(&mut process, &conn.process, conn.upload, conn.download);
+        }
+        let (upload_total, download_total) = outbound
+            .values()
+            .fold((0, 0), |(u, d), (cu, cd)| (u + cu, d + cd));
+        let mut by_outbound = sorted(outbound);
+        let mut by_process = sorted(process);
+        if let Some(n) = top_n {
+            by_outbound.truncate(n);
+            by_process.truncate(n);
+        }
+        TrafficSnapshot {
+            epoch: self.epoch,
+            at: self.at,
+            up_rate: self.rate.up,
+            down_rate: self.rate.down,
+            upload_total,
+            download_total,
+            active_connections: self.live.len(),
+            by_outbound,
+            by_process,
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref AGGREGATOR: Mutex<Option<Aggregator>> = Mutex::new(None);
+}
+
+/// Current session, or the one that just ended (kept until the next start
+/// so the last totals stay readable after a stop).
+fn with_aggregator<R>(f: impl FnOnce(&mut Option<Aggregator>) -> R) -> R {
+    f(&mut AGGREGATOR.lock().unwrap_or_else(|e| e.into_inner()))
+}
+
+/// Epoch of the current state if the engine is Running/Degraded.
+fn running_epoch(app: &AppHandle) -> Option<u64> {
+    let state = app.state::<EngineStateCell>().snapshot();
+    state.is_running().then(|| state.epoch())
+}
+
+/// Drain pending transitions; true if any of them left the running states.
+/// Running ↔ Degraded flips bump the epoch without restarting sing-box, so
+/// the epoch alone can't tell a new session from the same one. A lagged
+/// receiver may have missed a restart: treat that as a new session too.
+fn session_ended(rx: &mut broadcast::Receiver<StateChange>) -> bool {
+    let mut ended = false;
+    loop {
+        match rx.try_recv() {
+            Ok(change) => ended |= !change.to.is_running(),
+            Err(TryRecvError::Lagged(_)) => ended = true,
+            Err(TryRecvError::Empty) | Err(TryRecvError::Closed) => return ended,
+        }
+    }
+}
+
+pub fn spawn_traffic_stats(app: AppHandle) {
+    let mut rx = app.state::<EngineStateCell>().subscribe();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            session_ended(&mut rx);
+            let Some(epoch) = running_epoch(&app) else {
+                tokio::time::sleep(IDLE_POLL_INTERVAL).await;
+                continue;
+            };
+            with_aggregator(|agg| *agg = Some(Aggregator::new(epoch)));
+            follow_session(&app, &mut rx).await;
+        }
+    });
+}
+
+/// Follow `/traffic` until the session ends. Stream errors just reconnect;
+/// the aggregator survives them.
+async fn follow_session(app: &AppHandle, rx: &mut broadcast::Receiver<StateChange>) {
+    loop {
+        if session_ended(rx) {
+            return;
+        }
+        let client = ClashClient::for_app(app);
+        let mut stream = match client.traffic().await {
+            Ok(stream) => stream,
+            Err(e) => {
+                log::debug!("[traffic] /traffic unavailable: {}", e);
+                tokio::time::sleep(IDLE_POLL_INTERVAL).await;
+                continue;
+            }
+        };
+        loop {
+            let sample = match tokio::time::timeout(STREAM_STALL_TIMEOUT, stream.next()).await {
+                Ok(Some(Ok(sample))) => sample,
+                Ok(Some(Err(e))) => {
+                    log::debug!("[traffic] bad /traffic line: {}", e);
+                    continue;
+                }
+                Ok(None) | Err(_) => break,
+            };
+            if session_ended(rx) {
+                return;
+            }
+            let conns = client
+                .clone()
+                .timeout(CONNECTIONS_TIMEOUT)
+                .connections()
+                .await;
+            let snapshot = with_aggregator(|agg| {
+                let agg = agg.as_mut()?;
+                agg.record_rate(sample, now_secs());
+                if let Ok(conns) = &conns {
+                    agg.record_connections(&conns.connections);
+                }
+                Some(agg.snapshot(Some(EVENT_TOP_N)))
+            });
+            if let Some(snapshot) = snapshot {
+                crate::commands::usage::note_session_totals(
+                    app,
+                    snapshot.epoch,
+                    snapshot.upload_total,
+                    snapshot.download_total,
+                );
+                let _ = app.emit(EVENT_TRAFFIC_STATS, snapshot);
+            }
+        }
+    }
+}
+
+/// Full breakdown for the current (or most recent) engine session. Rates
+/// read as zero once the engine is no longer running.
+#[tauri::command]
+pub fn get_traffic_snapshot(app: AppHandle) -> TrafficSnapshot {
+    let running = running_epoch(&app).is_some();
+    with_aggregator(|agg| {
+        let mut snapshot = agg
+            .as_ref()
+            .map(|agg| agg.snapshot(None))
+            .unwrap_or_default();
+        if !running {
+            snapshot.up_rate = 0;
+            snapshot.down_rate = 0;
+            snapshot.active_connections = 0;
+        }
+        snapshot
+    })
+}
+
+#[cfg(test)]
+mod traffic_tests {
+    use super::*;
+    use crate::core::clash::ConnectionMetadata;
+
+    fn conn(id: &str, chain: &str, process: &str, upload: u64, download: u64) -> Connection {
+        Connection {
+            id: id.into(),
+            chains: vec![chain.into(), "ExitGateway".into()],
+            metadata: ConnectionMetadata {
+                process_path: process.into(),
+                ..Default::default()
+            },
+            upload,
+            download,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn closed_connections_keep_their_bytes() {
+        let mut agg = Aggregator::new(3);
+        agg.record_connections(&[
+            conn("a", "hk-01", "/usr/bin/curl", 10, 100),
+            conn("b", "jp-01", "C:\\Apps\\chrome.exe", 5, 50),
+        ]);
+        // `a` grew, `b` closed, `c` opened.
+        agg.record_connections(&[
+            conn("a", "hk-01", "/usr/bin/curl", 20, 200),
+            conn("c", "hk-01", "", 1, 1),
+        ]);
+        let snap = agg.snapshot(None);
+        assert_eq!(snap.epoch, 3);
+        assert_eq!(snap.active_connections, 2);
+        assert_eq!((snap.upload_total, snap.download_total), (26, 251));
+        assert_eq!(
+            snap.by_outbound,
+            vec![
+                UsageEntry {
+                    name: "hk-01".into(),
+                    upload: 21,
+                    download: 201
+                },
+                UsageEntry {
+                    name: "jp-01".into(),
+                    upload: 5,
+                    download: 50
+                },
+            ]
+        );
+        let processes: Vec<&str> = snap.by_process.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(processes, vec!["curl", "chrome.exe", UNKNOWN_PROCESS]);
+    }
+
+    #[test]
+    fn event_snapshot_is_truncated_but_totals_are_not() {
+        let mut agg = Aggregator::new(1);
+        let conns: Vec<Connection> = (0..12)
+            .map(|i| conn(&i.to_string(), &format!("node-{}", i), "/bin/app", i, 0))
+            .collect();
+        agg.record_connections(&conns);
+        agg.record_rate(Traffic { up: 7, down: 9 }, 42);
+        let snap = agg.snapshot(Some(EVENT_TOP_N));
+        assert_eq!(snap.by_outbound.len(), EVENT_TOP_N);
+        assert_eq!(snap.by_outbound[0].name, "node-11");
+        assert_eq!(snap.upload_total, (0..12).sum::<u64>());
+        assert_eq!((snap.up_rate, snap.down_rate, snap.at), (7, 9, 42));
+    }
+
+    #[test]
+    fn connection_without_chain_counts_as_direct() {
+        let mut c = conn("x", "", "", 1, 2);
+        c.chains.clear();
+        assert_eq!(outbound_of(&c), "direct");
+    }
+}
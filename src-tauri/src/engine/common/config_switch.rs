@@ -0,0 +1,166 @@
+//! Atomic config switch for `reload_config`.
+//!
+//! The dashboard used to regenerate `config.json` in place and then ask for
+//! a reload. sing-box re-reads that same path on SIGHUP, so a half-written
+//! or broken file took the running engine down with no way back. The
+//! merger now writes `config.staging.json` next to it instead, and a reload
+//! goes through this module:
+//!
+//!   1. `queue()` — reloads run one at a time, in arrival order (tokio's
+//!      `Mutex` is FIFO-fair). Back-to-back edits no longer race two
+//!      SIGHUPs against each other.
+//!   2. `promote()` — the staged candidate is validated where it is and
+//!      only a candidate that passes is renamed over the live path, so
+//!      sing-box never observes a partial write and the live file is never
+//!      touched by a rejected one. `start` promotes the same way.
+//!   3. the caller sends the SIGHUP and waits for readiness
+//!      (`readiness::verify_reload`).
+//!   4. `commit()` on success copies the live config to
+//!      `config.last-good.json`; `revert()` on failure puts that copy back
+//!      so the caller can reload once more onto it.
+//!
+//! The last-known-good copy is also refreshed whenever a fresh start passes
+//! readiness, so the first reload of a session already has something to
+//! fall back to.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use tauri::AppHandle;
+use tokio::sync::{Mutex, MutexGuard};
+
+use crate::engine::ProxyMode;
+
+pub const STAGING_FILE_NAME: &str = "config.staging.json";
+pub const LAST_GOOD_FILE_NAME: &str = "config.last-good.json";
+
+/// Error prefix when a reload did not come up and the last-known-good
+/// config was put back: `CONFIG_RELOAD_REVERTED:<reason>`.
+pub const CONFIG_RELOAD_REVERTED: &str = "CONFIG_RELOAD_REVERTED";
+
+static QUEUE: Mutex<()> = Mutex::const_new(());
+static WAITING: AtomicUsize = AtomicUsize::new(0);
+
+/// Serializes reloads. Hold the guard for the whole stage → reload → verify
+/// → commit/revert cycle.
+pub async fn queue(action: u64) -> MutexGuard<'static, ()> {
+    let ahead = WAITING.fetch_add(1, Ordering::SeqCst);
+    if ahead > 0 {
+        log::info!("[reload] action={action} queued behind {ahead} reload(s)");
+    }
+    let guard = QUEUE.lock().await;
+    WAITING.fetch_sub(1, Ordering::SeqCst);
+    guard
+}
+
+fn sibling(live: &Path, name: &str) -> PathBuf {
+    live.with_file_name(name)
+}
+
+pub fn staging_path(live: &str) -> PathBuf {
+    sibling(Path::new(live), STAGING_FILE_NAME)
+}
+
+pub fn last_good_path(live: &str) -> PathBuf {
+    sibling(Path::new(live), LAST_GOOD_FILE_NAME)
+}
+
+/// Write via a temp file + rename so readers see either the old or the new
+/// content, never a torn one.
+pub(crate) fn write_atomic(target: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    let mut tmp = target.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    std::fs::write(&tmp, bytes)?;
+    std::fs::rename(&tmp, target)
+}
+
+/// Validate the candidate staged next to `live` and rename it over `live`.
+/// `Ok(false)` when nothing is staged. A candidate that fails validation is
+/// deleted and `live` stays as it was.
+pub async fn promote(app: &AppHandle, live: &str, mode: &ProxyMode) -> Result<bool, String> {
+    let staging = staging_path(live);
+    if !staging.is_file() {
+        return Ok(false);
+    }
+    if let Err(e) = super::preflight::validate(app, &staging.to_string_lossy(), mode).await {
+        let _ = std::fs::remove_file(&staging);
+        return Err(e);
+    }
+    std::fs::rename(&staging, live)
+        .map_err(|e| format!("failed to promote {} to {}: {}", staging.display(), live, e))?;
+    Ok(true)
+}
+
+/// Record the live config as last-known-good.
+pub fn commit(live: &str) -> Result<(), String> {
+    let bytes = std::fs::read(live).map_err(|e| format!("failed to read {}: {}", live, e))?;
+    let target = last_good_path(live);
+    write_atomic(&target, &bytes)
+        .map_err(|e| format!("failed to write {}: {}", target.display(), e))
+}
+
+/// Put the last-known-good config back onto the live path. `Ok(false)` when
+/// there is nothing to revert to.
+pub fn revert(live: &str) -> Result<bool, String> {
+    let source = last_good_path(live);
+    let bytes = match std::fs::read(&source) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(format!("failed to read {}: {}", source.display(), e)),
+    };
+    write_atomic(Path::new(live), &bytes)
+        .map_err(|e| format!("failed to write {}: {}", live, e))?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod config_switch_tests {
+    use super::*;
+
+    fn temp_live(name: &str) -> (PathBuf, String) {
+        let dir = std::env::temp_dir().join(format!(
+            "onebox_config_switch_{}_{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let live = dir.join("config.json");
+        (dir, live.to_string_lossy().into_owned())
+    }
+
+    #[test]
+    fn side_files_sit_next_to_the_live_config() {
+        let live = "/tmp/onebox/config.json";
+        assert_eq!(
+            staging_path(live),
+            PathBuf::from("/tmp/onebox/config.staging.json")
+        );
+        assert_eq!(
+            last_good_path(live),
+            PathBuf::from("/tmp/onebox/config.last-good.json")
+        );
+    }
+
+    #[test]
+    fn revert_without_last_good_is_a_no_op() {
+        let (dir, live) = temp_live("no_last_good");
+        std::fs::write(&live, "new").unwrap();
+        assert_eq!(revert(&live), Ok(false));
+        assert_eq!(std::fs::read_to_string(&live).unwrap(), "new");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn commit_then_revert_restores_the_committed_config() {
+        let (dir, live) = temp_live("round_trip");
+        std::fs::write(&live, "good").unwrap();
+        commit(&live).unwrap();
+        std::fs::write(&live, "bad").unwrap();
+        assert_eq!(revert(&live), Ok(true));
+        assert_eq!(std::fs::read_to_string(&live).unwrap(), "good");
+        assert!(!Path::new(&format!("{}.tmp", live)).exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
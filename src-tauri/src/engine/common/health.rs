@@ -0,0 +1,302 @@
+//! Health monitor — watches a Running engine for stalls the process monitor
+//! can't see (dead upstream, blackholed outbound, DNS wedged).
+//!
+//! One long-lived task, spawned from `app_setup`. While the engine is
+//! Running or Degraded it periodically fetches `probe_url` through the
+//! mixed inbound (directly in TUN mode when there is no mixed inbound) and
+//! keeps a sliding window of the results:
+//!
+//!   - `failure_threshold` failures, or a median latency above
+//!     `latency_threshold_ms`, within the last `window_size` probes
+//!     → `transition(Degrade { reason })`
+//!   - `recovery_successes` consecutive fast successes while Degraded
+//!     → `transition(MarkHealthy)`
+//!
+//! The window is dropped whenever the engine leaves Running/Degraded, so a
+//! restarted session starts clean. Configured via `settings.json` →
+//! `engine_health` (see `HealthSettings`).
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use serde::Deserialize;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_store::StoreExt;
+
+use super::state_machine::{transition, EngineState, EngineStateCell, Intent};
+
+const SETTINGS_STORE: &str = "settings.json";
+const HEALTH_SETTINGS_KEY: &str = "engine_health";
+/// How often to re-check the state (and settings) while not probing.
+const IDLE_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct HealthSettings {
+    pub enabled: bool,
+    pub interval_secs: u64,
+    pub probe_url: String,
+    pub probe_timeout_ms: u64,
+    /// Number of most recent probes considered.
+    pub window_size: usize,
+    /// Failures within the window that mark the engine Degraded.
+    pub failure_threshold: usize,
+    /// Median latency (successful probes) above this marks it Degraded.
+    pub latency_threshold_ms: u64,
+    /// Consecutive healthy probes needed to leave Degraded.
+    pub recovery_successes: usize,
+}
+
+impl Default for HealthSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_secs: 30,
+            probe_url: "https://www.google.com/generate_204".into(),
+            probe_timeout_ms: 5000,
+            window_size: 5,
+            failure_threshold: 3,
+            latency_threshold_ms: 3000,
+            recovery_successes: 2,
+        }
+    }
+}
+
+fn load_settings(app: &AppHandle) -> HealthSettings {
+    app.get_store(SETTINGS_STORE)
+        .and_then(|store| store.get(HEALTH_SETTINGS_KEY))
+        .and_then(|value| serde_json::from_value(value).ok())
+        .unwrap_or_default()
+}
+
+#[derive(Debug, PartialEq)]
+enum Verdict {
+    Healthy,
+    Degraded(String),
+    /// Not enough evidence either way yet; keep the current state.
+    Undecided,
+}
+
+/// Sliding window of probe outcomes: `Ok(latency)` or `Err(detail)`.
+#[derive(Default)]
+struct HealthWindow {
+    samples: VecDeque<Result<Duration, String>>,
+}
+
+impl HealthWindow {
+    fn record(&mut self, settings: &HealthSettings, sample: Result<Duration, String>) {
+        self.samples.push_back(sample);
+        while self.samples.len() > settings.window_size.max(1) {
+            self.samples.pop_front();
+        }
+    }
+
+    fn clear(&mut self) {
+        self.samples.clear();
+    }
+
+    fn verdict(&self, settings: &HealthSettings) -> Verdict {
+        let latency_limit = Duration::from_millis(settings.latency_threshold_ms);
+        let failures: Vec<&String> = self
+            .samples
+            .iter()
+            .filter_map(|s| s.as_ref().err())
+            .collect();
+        if !failures.is_empty() && failures.len() >= settings.failure_threshold.max(1) {
+            return Verdict::Degraded(format!(
+                "{}/{} health probes failed (last: {})",
+                failures.len(),
+                self.samples.len(),
+                failures[failures.len() - 1]
+            ));
+        }
+
+        let mut latencies: Vec<Duration> = self
+            .samples
+            .iter()
+            .filter_map(|s| s.as_ref().ok().copied())
+            .collect();
+        if latencies.len() >= settings.failure_threshold.max(1) {
+            latencies.sort();
+            let median = latencies[latencies.len() / 2];
+            if median > latency_limit {
+                return Verdict::Degraded(format!(
+                    "median probe latency {}ms exceeds {}ms",
+                    median.as_millis(),
+                    settings.latency_threshold_ms
+                ));
+            }
+        }
+
+        let need = settings.recovery_successes.max(1);
+        let recent_ok = self
+            .samples
+            .iter()
+            .rev()
+            .take_while(|s| matches!(s, Ok(latency) if *latency <= latency_limit))
+            .count();
+        if recent_ok >= need {
+            Verdict::Healthy
+        } else {
+            Verdict::Undecided
+        }
+    }
+}
+
+/// Where to send probes for the running engine: through the mixed inbound
+/// if the config has one, otherwise directly (TUN routes it) in TUN mode.
+/// `None` means there is no way to probe this session.
+fn probe_route(state: &EngineState) -> Option<Option<u16>> {
+    let config_path = crate::core::ProcessManager::acquire()
+        .config_path
+        .as_ref()
+        .map(|p| (**p).clone());
+    let mixed_port = config_path
+        .and_then(|p| std::fs::read_to_string(p).ok())
+        .and_then(|text| serde_json::from_str::<serde_json::Value>(&text).ok())
+        .and_then(|json| crate::core::mixed_port_from_config(&json));
+    match (mixed_port, state.mode()) {
+        (Some(port), _) => Some(Some(port)),
+        (None, Some("tun")) => Some(None),
+        _ => None,
+    }
+}
+
+pub fn spawn_health_monitor(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut window = HealthWindow::default();
+        loop {
+            let settings = load_settings(&app);
+            let snap = app.state::<EngineStateCell>().snapshot();
+            if !settings.enabled || !snap.is_running() {
+                window.clear();
+                tokio::time::sleep(IDLE_POLL_INTERVAL).await;
+                continue;
+            }
+            let Some(route) = probe_route(&snap) else {
+                window.clear();
+                tokio::time::sleep(IDLE_POLL_INTERVAL).await;
+                continue;
+            };
+
+            let sample = crate::commands::network::probe_through_proxy(
+                route,
+                &settings.probe_url,
+                Duration::from_millis(settings.probe_timeout_ms),
+            )
+            .await;
+            if let Err(e) = &sample {
+                log::info!("[health] probe failed: {}", e);
+            }
+
+            // The session may have been stopped / restarted while probing.
+            let now = app.state::<EngineStateCell>().snapshot();
+            if now.epoch() != snap.epoch() && !(now.is_running() && window_survives(&snap, &now)) {
+                window.clear();
+                continue;
+            }
+            window.record(&settings, sample);
+
+            match (&now, window.verdict(&settings)) {
+                (EngineState::Running { .. }, Verdict::Degraded(reason)) => {
+                    log::warn!("[health] degraded: {}", reason);
+                    let _ = transition(&app, Intent::Degrade { reason });
+                }
+                (EngineState::Degraded { .. }, Verdict::Healthy) => {
+                    log::info!("[health] probes recovered");
+                    let _ = transition(&app, Intent::MarkHealthy);
+                }
+                _ => {}
+            }
+
+            tokio::time::sleep(Duration::from_secs(settings.interval_secs.max(1))).await;
+        }
+    });
+}
+
+/// Running ↔ Degraded flips bump the epoch but are the same session; only
+/// those may keep the window across an epoch change.
+fn window_survives(before: &EngineState, after: &EngineState) -> bool {
+    before.is_running() && after.is_running() && after.epoch() == before.epoch() + 1
+}
+
+#[cfg(test)]
+mod health_tests {
+    use super::*;
+
+    fn ms(v: u64) -> Result<Duration, String> {
+        Ok(Duration::from_millis(v))
+    }
+
+    fn fail() -> Result<Duration, String> {
+        Err("timeout".into())
+    }
+
+    #[test]
+    fn failures_within_window_degrade() {
+        let settings = HealthSettings::default();
+        let mut w = HealthWindow::default();
+        w.record(&settings, fail());
+        w.record(&settings, ms(100));
+        w.record(&settings, fail());
+        assert_eq!(w.verdict(&settings), Verdict::Undecided);
+        w.record(&settings, fail());
+        assert!(matches!(w.verdict(&settings), Verdict::Degraded(r) if r.starts_with("3/4")));
+    }
+
+    #[test]
+    fn failures_age_out_of_window() {
+        let settings = HealthSettings {
+            window_size: 3,
+            ..Default::default()
+        };
+        let mut w = HealthWindow::default();
+        for s in [fail(), fail(), ms(100), ms(100), ms(100)] {
+            w.record(&settings, s);
+        }
+        assert_eq!(w.verdict(&settings), Verdict::Healthy);
+    }
+
+    #[test]
+    fn slow_median_degrades() {
+        let settings = HealthSettings::default();
+        let mut w = HealthWindow::default();
+        for s in [ms(4000), ms(100), ms(5000)] {
+            w.record(&settings, s);
+        }
+        assert!(matches!(w.verdict(&settings), Verdict::Degraded(r) if r.contains("latency")));
+    }
+
+    #[test]
+    fn recovery_needs_consecutive_fast_successes() {
+        let settings = HealthSettings::default();
+        let mut w = HealthWindow::default();
+        w.record(&settings, fail());
+        w.record(&settings, ms(100));
+        assert_eq!(w.verdict(&settings), Verdict::Undecided);
+        w.record(&settings, ms(120));
+        assert_eq!(w.verdict(&settings), Verdict::Healthy);
+    }
+
+    #[test]
+    fn degraded_flip_keeps_window() {
+        let running = EngineState::Running {
+            since: 0,
+            epoch: 4,
+            mode: "tun".into(),
+        };
+        let degraded = EngineState::Degraded {
+            since: 0,
+            reason: "x".into(),
+            epoch: 5,
+            mode: "tun".into(),
+        };
+        let restarted = EngineState::Running {
+            since: 0,
+            epoch: 8,
+            mode: "tun".into(),
+        };
+        assert!(window_survives(&running, &degraded));
+        assert!(!window_survives(&running, &restarted));
+    }
+}
@@ -18,6 +18,25 @@ pub fn get_sidecar_path(program: &Path) -> Result<String, anyhow::Error> {
     }
 }
 
+/// 运行一个探测命令，返回其 stdout；启动失败或退出码非零时返回 None。
+/// Windows 上不弹出控制台窗口（netsh / powershell 否则会闪一下）。
+pub(crate) fn command_stdout(program: &str, args: &[&str]) -> Option<String> {
+    let mut command = std::process::Command::new(program);
+    command.args(args);
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        use winapi::um::winbase::CREATE_NO_WINDOW;
+
+        command.creation_flags(CREATE_NO_WINDOW);
+    }
+    let output = command.output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
 /// ZH: 从 sing-box 配置文件里解析出 TUN inbound 的首个 IPv4 网关地址。
 ///     例如 `"172.19.0.1/30"` → `"172.19.0.1"`。找不到返回 None。
 ///     用于把系统 DNS 指向该地址，强制 OS 的 DNS 查询必走 TUN 被 hijack-dns 捕获。
@@ -27,6 +46,11 @@ pub fn get_sidecar_path(program: &Path) -> Result<String, anyhow::Error> {
 pub fn extract_tun_gateway_from_config(config_path: &str) -> Option<String> {
     let content = fs::read_to_string(config_path).ok()?;
     let v: serde_json::Value = serde_json::from_str(&content).ok()?;
+    tun_gateway_from_value(&v)
+}
+
+/// `extract_tun_gateway_from_config` on an already parsed config.
+pub fn tun_gateway_from_value(v: &serde_json::Value) -> Option<String> {
     let inbounds = v.get("inbounds")?.as_array()?;
     for inb in inbounds {
         if inb.get("type").and_then(serde_json::Value::as_str) != Some("tun") {
@@ -0,0 +1,336 @@
+//! Durable journal of engine state transitions.
+//!
+//! `state_machine::transition` publishes every committed change on
+//! `EngineStateCell::subscribe`; the writer task spawned here appends each
+//! one to the `engine_state_journal` table (migration v2 in
+//! `app::database`). `get_engine_journal` reads it back with filters, so
+//! "how often did TUN drop yesterday and why" is a query, not a log grep.
+
+use std::future::Future;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use sqlx::{QueryBuilder, Row, Sqlite, SqlitePool};
+use tauri::{AppHandle, Manager};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use super::state_machine::{EngineState, EngineStateCell, StateChange};
+
+const DEFAULT_QUERY_LIMIT: u32 = 200;
+const MAX_QUERY_LIMIT: u32 = 5000;
+
+lazy_static::lazy_static! {
+    /// sha256 of the config most recently handed to `core::start`. Attached
+    /// to every journal row so a regression can be tied to a config change.
+    static ref CONFIG_HASH: Mutex<Option<String>> = Mutex::new(None);
+}
+
+/// Hash the config at `path` and remember it for subsequent journal rows.
+/// Called by `core::start` before the Starting transition.
+pub fn note_config(path: &str) {
+    let hash = std::fs::read_to_string(path)
+        .ok()
+        .map(|content| crate::commands::config_fetch::compute_sha256_hex(&content));
+    *CONFIG_HASH.lock().unwrap_or_else(|e| e.into_inner()) = hash;
+}
+
+pub(crate) fn current_config_hash() -> Option<String> {
+    CONFIG_HASH
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct JournalEntry {
+    pub id: i64,
+    pub at: i64,
+    pub from_kind: String,
+    pub to_kind: String,
+    pub mode: Option<String>,
+    pub epoch: i64,
+    pub reason: Option<String>,
+    pub config_hash: Option<String>,
+    pub trigger: String,
+}
+
+/// All fields optional; absent fields don't constrain the query. Results
+/// are newest first.
+#[derive(Deserialize, Debug, Default)]
+pub struct JournalFilter {
+    /// Inclusive lower bound, Unix seconds.
+    pub since: Option<i64>,
+    /// Exclusive upper bound, Unix seconds.
+    pub until: Option<i64>,
+    /// Matches the post-transition state (`failed`, `running`, ...).
+    pub kind: Option<String>,
+    /// `user` / `watchdog` / `network-up` / `wake` / `engine` / `recovery` /
+    /// `adopt`.
+    pub trigger: Option<String>,
+    /// `tun` / `mixed`.
+    pub mode: Option<String>,
+    pub limit: Option<u32>,
+}
+
+/// Subscribe to state changes and persist them. Lives for the app lifetime.
+pub fn spawn_journal_writer(app: AppHandle) {
+    let rx = app.state::<EngineStateCell>().subscribe();
+    tauri::async_runtime::spawn(write_changes(rx, move || {
+        let app = app.clone();
+        async move { crate::app::database::sqlite_pool(&app).await }
+    }));
+}
+
+/// Append every change on `rx` until its cell is dropped. `pool` is asked
+/// for the pool per entry, so a database that isn't up yet only costs the
+/// entries written before it is. The headless CLI drives its own cell
+/// through this too.
+pub(crate) async fn write_changes<F, Fut>(mut rx: broadcast::Receiver<StateChange>, pool: F)
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<SqlitePool, String>>,
+{
+    loop {
+        let change = match rx.recv().await {
+            Ok(change) => change,
+            Err(RecvError::Lagged(n)) => {
+                log::warn!("[journal] lagged, {} transitions not recorded", n);
+                continue;
+            }
+            Err(RecvError::Closed) => return,
+        };
+        let pool = match pool().await {
+            Ok(pool) => pool,
+            Err(e) => {
+                log::warn!("[journal] db unavailable, dropping entry: {}", e);
+                continue;
+            }
+        };
+        if let Err(e) = insert(&pool, &change).await {
+            log::warn!("[journal] insert failed: {}", e);
+        }
+    }
+}
+
+fn state_reason(state: &EngineState) -> Option<&str> {
+    match state {
+        EngineState::Failed { reason, .. }
+        | EngineState::Degraded { reason, .. }
+        | EngineState::Recovering { reason, .. } => Some(reason.as_str()),
+        _ => None,
+    }
+}
+
+async fn insert(pool: &SqlitePool, change: &StateChange) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO engine_state_journal \
+         (at, from_kind, to_kind, mode, epoch, reason, config_hash, trigger_source) \
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(change.at)
+    .bind(change.from.kind())
+    .bind(change.to.kind())
+    // Failed / Idle carry no mode; leaving one is still a drop of that mode.
+    .bind(change.to.mode().or(change.from.mode()))
+    .bind(change.to.epoch() as i64)
+    .bind(state_reason(&change.to))
+    .bind(change.config_hash.as_deref())
+    .bind(change.trigger.as_str())
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+async fn query(
+    pool: &SqlitePool,
+    filter: &JournalFilter,
+) -> Result<Vec<JournalEntry>, sqlx::Error> {
+    let mut qb: QueryBuilder<Sqlite> = QueryBuilder::new(
+        "SELECT id, at, from_kind, to_kind, mode, epoch, reason, config_hash, trigger_source \
+         FROM engine_state_journal WHERE 1 = 1",
+    );
+    if let Some(since) = filter.since {
+        qb.push(" AND at >= ").push_bind(since);
+    }
+    if let Some(until) = filter.until {
+        qb.push(" AND at < ").push_bind(until);
+    }
+    if let Some(kind) = &filter.kind {
+        qb.push(" AND to_kind = ").push_bind(kind.clone());
+    }
+    if let Some(trigger) = &filter.trigger {
+        qb.push(" AND trigger_source = ").push_bind(trigger.clone());
+    }
+    if let Some(mode) = &filter.mode {
+        qb.push(" AND mode = ").push_bind(mode.clone());
+    }
+    let limit = filter
+        .limit
+        .unwrap_or(DEFAULT_QUERY_LIMIT)
+        .min(MAX_QUERY_LIMIT);
+    qb.push(" ORDER BY id DESC LIMIT ").push_bind(limit as i64);
+
+    let rows = qb.build().fetch_all(pool).await?;
+    rows.iter()
+        .map(|row| {
+            Ok(JournalEntry {
+                id: row.try_get("id")?,
+                at: row.try_get("at")?,
+                from_kind: row.try_get("from_kind")?,
+                to_kind: row.try_get("to_kind")?,
+                mode: row.try_get("mode")?,
+                epoch: row.try_get("epoch")?,
+                reason: row.try_get("reason")?,
+                config_hash: row.try_get("config_hash")?,
+                trigger: row.try_get("trigger_source")?,
+            })
+        })
+        .collect()
+}
+
+#[tauri::command]
+pub async fn get_engine_journal(
+    app: AppHandle,
+    filter: Option<JournalFilter>,
+) -> Result<Vec<JournalEntry>, String> {
+    let pool = crate::app::database::sqlite_pool(&app).await?;
+    query(&pool, &filter.unwrap_or_default())
+        .await
+        .map_err(|e| format!("journal query failed: {}", e))
+}
+
+#[cfg(test)]
+mod journal_tests {
+    use super::*;
+    use crate::app::database::test_pool;
+    use crate::engine::state_machine::Trigger;
+
+    fn change(from: EngineState, to: EngineState, trigger: Trigger, at: i64) -> StateChange {
+        StateChange {
+            from,
+            to,
+            trigger,
+            at,
+            config_hash: Some("abc".into()),
+        }
+    }
+
+    #[tokio::test]
+    async fn insert_and_filter() {
+        let pool = test_pool().await;
+        let running = EngineState::Running {
+            since: 0,
+            epoch: 2,
+            mode: "tun".into(),
+        };
+        let failed = EngineState::Failed {
+            reason: "sing-box exited unexpectedly (code=1)".into(),
+            at: 0,
+            epoch: 3,
+        };
+        insert(
+            &pool,
+            &change(
+                EngineState::Starting {
+                    since: 0,
+                    epoch: 1,
+                    mode: "tun".into(),
+                },
+                running.clone(),
+                Trigger::Wake,
+                100,
+            ),
+        )
+        .await
+        .unwrap();
+        insert(&pool, &change(running, failed, Trigger::Engine, 200))
+            .await
+            .unwrap();
+
+        let all = query(&pool, &JournalFilter::default()).await.unwrap();
+        assert_eq!(all.len(), 2);
+        // Newest first.
+        assert_eq!(all[0].to_kind, "failed");
+        // Stamped with the transition's time, not the insert's.
+        assert_eq!(all[0].at, 200);
+        assert_eq!(all[0].trigger, "engine");
+        assert_eq!(
+            all[0].reason.as_deref(),
+            Some("sing-box exited unexpectedly (code=1)")
+        );
+        // Failed has no mode of its own; the row keeps the one it left.
+        assert_eq!(all[0].mode.as_deref(), Some("tun"));
+        assert_eq!(all[1].mode.as_deref(), Some("tun"));
+        assert_eq!(all[1].config_hash.as_deref(), Some("abc"));
+
+        let failures = query(
+            &pool,
+            &JournalFilter {
+                kind: Some("failed".into()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(failures.len(), 1);
+
+        let tun_failures = query(
+            &pool,
+            &JournalFilter {
+                kind: Some("failed".into()),
+                mode: Some("tun".into()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(tun_failures.len(), 1);
+        let mixed_failures = query(
+            &pool,
+            &JournalFilter {
+                kind: Some("failed".into()),
+                mode: Some("mixed".into()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert!(mixed_failures.is_empty());
+
+        let wake = query(
+            &pool,
+            &JournalFilter {
+                trigger: Some("wake".into()),
+                mode: Some("tun".into()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(wake.len(), 1);
+        assert_eq!(wake[0].epoch, 2);
+
+        let recent = query(
+            &pool,
+            &JournalFilter {
+                since: Some(150),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].to_kind, "failed");
+
+        let limited = query(
+            &pool,
+            &JournalFilter {
+                limit: Some(1),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(limited.len(), 1);
+    }
+}
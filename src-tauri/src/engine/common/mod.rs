@@ -1,9 +1,23 @@
-//! Cross-platform engine primitives: sidecar path resolution, readiness
-//! probing, the lifecycle state machine, and the system-proxy wrapper.
+//! Cross-platform engine primitives: sidecar path resolution, pre-flight
+//! config validation, the atomic config switch, readiness probing, health
+//! monitoring, crash recovery, the lifecycle state machine and its journal,
+//! the runtime record used to adopt or clean up orphans, the user bypass
+//! list, the PAC server, the prior-proxy snapshot and the system-proxy
+//! wrapper.
 //! These don't depend on any one OS and are shared by all three
 //! `EngineManager` implementations.
 
+pub mod config_switch;
+pub mod health;
 pub mod helper;
+pub mod journal;
+pub mod pac;
+pub mod preflight;
+pub mod proxy_bypass;
+pub mod proxy_guard;
+pub mod proxy_snapshot;
 pub mod readiness;
+pub mod recovery;
+pub mod runtime_record;
 pub mod state_machine;
 pub(crate) mod sysproxy;
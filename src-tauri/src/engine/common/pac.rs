@@ -0,0 +1,614 @@
+//! PAC strategy for the system proxy.
+//!
+//! With `system_proxy_pac.enabled` in `settings.json`, `set_system_proxy`
+//! stops setting a static host:port proxy. Instead OneBox serves a generated
+//! `proxy.pac` from a loopback HTTP listener and points the OS auto-config
+//! URL at it. Apps that ignore static proxies but honor PAC get proxied, and
+//! traffic the script sends `DIRECT` skips the local hop entirely.
+//!
+//! The script's `DIRECT` list is the bypass list in effect
+//! (`proxy_bypass::effective_entries`: the per-OS base, user entries and
+//! matching network rules). With `use_route_rules`, the active sing-box
+//! config adds the `domain` / `domain_suffix` / `ip_cidr` items of route
+//! rules whose outbound is a `direct` one, including inline rule-sets they
+//! reference. Binary or remote rule-sets can't be read here and are skipped.
+//! sing-box routes by first match, so the rules are walked in order: an
+//! item an earlier non-direct rule could also match is left to the proxy,
+//! and nothing is taken past a non-direct rule whose conditions can't be
+//! read (ports, processes, remote rule-sets, a catch-all).
+//!
+//! The URL carries a `?v=` revision bumped on every regeneration, since
+//! Windows and macOS cache a PAC by URL.
+
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::AppHandle;
+use tauri_plugin_store::StoreExt;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+use super::proxy_bypass::{network_address, BypassEntry};
+use crate::core::ProcessManager;
+
+const SETTINGS_STORE: &str = "settings.json";
+const PAC_SETTINGS_KEY: &str = "system_proxy_pac";
+pub const PAC_PATH: &str = "/proxy.pac";
+const PROXY_HOST: &str = "127.0.0.1";
+/// Largest request head we read before answering.
+const MAX_REQUEST_HEAD: usize = 8 * 1024;
+/// How long a client gets to send its request head; a stalled connection
+/// must not pin a task for the life of the listener.
+const REQUEST_READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct PacSettings {
+    pub enabled: bool,
+    /// Listener port; `0` picks a free one.
+    pub port: u16,
+    /// Also send the active config's `direct` route rules `DIRECT`.
+    pub use_route_rules: bool,
+}
+
+impl Default for PacSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 0,
+            use_route_rules: true,
+        }
+    }
+}
+
+pub(crate) fn load_settings(app: &AppHandle) -> PacSettings {
+    app.get_store(SETTINGS_STORE)
+        .and_then(|store| store.get(PAC_SETTINGS_KEY))
+        .and_then(|value| serde_json::from_value(value).ok())
+        .unwrap_or_default()
+}
+
+// ── Script ────────────────────────────────────────────────────────────
+
+fn json<T: Serialize>(value: &T) -> String {
+    serde_json::to_string(value).unwrap_or_else(|_| "[]".into())
+}
+
+fn netmask(prefix: u8) -> Ipv4Addr {
+    Ipv4Addr::from(u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0))
+}
+
+/// The PAC script: `DIRECT` for `entries`, the Mixed inbound otherwise.
+/// IPv4 ranges are only tested against IP-literal hosts — `isInNet` on a
+/// name would block on a DNS lookup. IPv6 ranges have no portable PAC
+/// test and are left out.
+pub fn script(proxy_port: u16, entries: &[BypassEntry]) -> String {
+    let mut hosts = Vec::new();
+    let mut suffixes = Vec::new();
+    let mut nets = Vec::new();
+    let mut plain = false;
+    for entry in entries {
+        match entry {
+            BypassEntry::Host(host) => hosts.push(host.clone()),
+            BypassEntry::DomainSuffix(domain) => suffixes.push(format!(".{}", domain)),
+            BypassEntry::Ip(ip) => hosts.push(ip.to_string()),
+            BypassEntry::Cidr(IpAddr::V4(ip), prefix) => {
+                nets.push([ip.to_string(), netmask(*prefix).to_string()])
+            }
+            BypassEntry::Cidr(IpAddr::V6(_), _) => {}
+            BypassEntry::Local => plain = true,
+        }
+    }
+    format!(
+        r#"// Generated by OneBox. Edits are overwritten.
+var PROXY = "PROXY {host}:{port}";
+var DIRECT_PLAIN = {plain};
+var DIRECT_HOSTS = {hosts};
+var DIRECT_SUFFIXES = {suffixes};
+var DIRECT_NETS = {nets};
+
+function FindProxyForURL(url, host) {{
+  host = host.toLowerCase();
+  if (host.charAt(0) == "[") host = host.substring(1, host.length - 1);
+  if (DIRECT_PLAIN && isPlainHostName(host)) return "DIRECT";
+  var i;
+  for (i = 0; i < DIRECT_HOSTS.length; i++) {{
+    if (host == DIRECT_HOSTS[i]) return "DIRECT";
+  }}
+  for (i = 0; i < DIRECT_SUFFIXES.length; i++) {{
+    if (dnsDomainIs(host, DIRECT_SUFFIXES[i])) return "DIRECT";
+  }}
+  if (/^\d+\.\d+\.\d+\.\d+$/.test(host)) {{
+    for (i = 0; i < DIRECT_NETS.length; i++) {{
+      if (isInNet(host, DIRECT_NETS[i][0], DIRECT_NETS[i][1])) return "DIRECT";
+    }}
+  }}
+  return PROXY;
+}}
+"#,
+        host = PROXY_HOST,
+        port = proxy_port,
+        plain = plain,
+        hosts = json(&hosts),
+        suffixes = json(&suffixes),
+        nets = json(&nets),
+    )
+}
+
+/// The items a route rule matches on, including those of the inline
+/// rule-sets it references. `None` when the rule has conditions the PAC
+/// can't express (ports, processes, …) — for a direct rule, taking only its
+/// domains would send too much `DIRECT`.
+fn rule_items(rule: &Value, inline_sets: &HashMap<&str, &Vec<Value>>) -> Option<Vec<BypassEntry>> {
+    let mut items = headless_items(rule)?;
+    for tag in string_list(rule.get("rule_set")) {
+        for headless in inline_sets.get(tag.as_str())?.iter() {
+            items.extend(headless_items(headless)?);
+        }
+    }
+    Some(items)
+}
+
+/// Rule keys the PAC can reproduce; any other condition disqualifies a rule.
+const HANDLED_RULE_KEYS: [&str; 6] = [
+    "outbound",
+    "action",
+    "domain",
+    "domain_suffix",
+    "ip_cidr",
+    "rule_set",
+];
+
+/// `domain` / `domain_suffix` / `ip_cidr` of one (headless) rule; `None`
+/// if it has any condition outside `HANDLED_RULE_KEYS`.
+fn headless_items(rule: &Value) -> Option<Vec<BypassEntry>> {
+    let obj = rule.as_object()?;
+    if obj.keys().any(|k| !HANDLED_RULE_KEYS.contains(&k.as_str())) {
+        return None;
+    }
+    let mut items = Vec::new();
+    for domain in string_list(obj.get("domain")) {
+        items.push(BypassEntry::parse(&domain).ok()?);
+    }
+    for suffix in string_list(obj.get("domain_suffix")) {
+        // sing-box: `example.com` also matches the apex, `.example.com` doesn't.
+        let (apex, domain) = match suffix.strip_prefix('.') {
+            Some(domain) => (false, domain),
+            None => (true, suffix.as_str()),
+        };
+        match BypassEntry::parse(domain).ok()? {
+            BypassEntry::Host(host) => {
+                if apex {
+                    items.push(BypassEntry::Host(host.clone()));
+                }
+                items.push(BypassEntry::DomainSuffix(host));
+            }
+            _ => return None,
+        }
+    }
+    for cidr in string_list(obj.get("ip_cidr")) {
+        items.push(BypassEntry::parse(&cidr).ok()?);
+    }
+    Some(items)
+}
+
+/// sing-box accepts a single string wherever it takes a list.
+fn string_list(value: Option<&Value>) -> Vec<String> {
+    match value {
+        Some(Value::String(s)) => vec![s.clone()],
+        Some(Value::Array(items)) => items
+            .iter()
+            .filter_map(Value::as_str)
+            .map(str::to_string)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn is_subdomain(host: &str, domain: &str) -> bool {
+    host.strip_suffix(domain)
+        .is_some_and(|rest| rest.ends_with('.'))
+}
+
+fn net(entry: &BypassEntry) -> Option<(IpAddr, u8)> {
+    match entry {
+        BypassEntry::Ip(ip @ IpAddr::V4(_)) => Some((*ip, 32)),
+        BypassEntry::Ip(ip @ IpAddr::V6(_)) => Some((*ip, 128)),
+        BypassEntry::Cidr(ip, prefix) => Some((*ip, *prefix)),
+        _ => None,
+    }
+}
+
+/// Whether a destination the PAC sends `DIRECT` for `direct` could also
+/// match `earlier`. A name can resolve into any range, but an IP literal
+/// never matches a domain condition.
+fn could_overlap(direct: &BypassEntry, earlier: &BypassEntry) -> bool {
+    use BypassEntry::*;
+    match (direct, earlier) {
+        (Host(a), Host(b)) => a == b,
+        (Host(host), DomainSuffix(domain)) | (DomainSuffix(domain), Host(host)) => {
+            is_subdomain(host, domain)
+        }
+        (DomainSuffix(a), DomainSuffix(b)) => a == b || is_subdomain(a, b) || is_subdomain(b, a),
+        (Local, Host(host)) | (Host(host), Local) => !host.contains('.'),
+        (Local, DomainSuffix(_)) | (DomainSuffix(_), Local) => false,
+        (Ip(_) | Cidr(..), Host(_) | DomainSuffix(_) | Local) => false,
+        _ => match (net(direct), net(earlier)) {
+            (Some((a, a_prefix)), Some((b, b_prefix))) => {
+                let prefix = a_prefix.min(b_prefix);
+                a.is_ipv4() == b.is_ipv4()
+                    && network_address(a, prefix) == network_address(b, prefix)
+            }
+            _ => true,
+        },
+    }
+}
+
+/// `DIRECT` entries derived from the route rules of a sing-box config.
+/// A rule whose `protocol` is just `dns` (sniffed) — e.g. the usual
+/// `{"protocol":"dns","action":"hijack-dns"}` or a legacy `dns-out` rule.
+fn matches_only_dns(rule: &Value) -> bool {
+    match rule.get("protocol") {
+        Some(Value::String(p)) => p == "dns",
+        Some(Value::Array(ps)) => !ps.is_empty() && ps.iter().all(|p| p == "dns"),
+        _ => false,
+    }
+}
+
+pub fn route_direct_entries(config: &Value) -> Vec<BypassEntry> {
+    let direct_tags: HashSet<&str> = config
+        .get("outbounds")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter(|o| o.get("type").and_then(Value::as_str) == Some("direct"))
+        .filter_map(|o| o.get("tag").and_then(Value::as_str))
+        .collect();
+    let route = config.get("route");
+    let inline_sets = route
+        .and_then(|r| r.get("rule_set"))
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter(|set| set.get("type").and_then(Value::as_str) == Some("inline"))
+        .filter_map(|set| {
+            let tag = set.get("tag")?.as_str()?;
+            let rules = set.get("rules")?.as_array()?;
+            Some((tag, rules))
+        })
+        .collect();
+    let mut entries = Vec::new();
+    // What earlier non-direct rules match; it wins over any later rule.
+    let mut shadowed: Vec<BypassEntry> = Vec::new();
+    for rule in route
+        .and_then(|r| r.get("rules"))
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+    {
+        let action = rule
+            .get("action")
+            .and_then(Value::as_str)
+            .unwrap_or("route");
+        if matches!(action, "sniff" | "resolve" | "route-options") {
+            // Matching carries on past these.
+            continue;
+        }
+        if action == "hijack-dns" || matches_only_dns(rule) {
+            // Only DNS queries stop here; the HTTP a PAC routes never does.
+            continue;
+        }
+        let direct = action == "route"
+            && rule
+                .get("outbound")
+                .and_then(Value::as_str)
+                .is_some_and(|tag| direct_tags.contains(tag));
+        match rule_items(rule, &inline_sets) {
+            Some(items) if direct => {
+                for item in items {
+                    if !shadowed.iter().any(|earlier| could_overlap(&item, earlier))
+                        && !entries.contains(&item)
+                    {
+                        entries.push(item);
+                    }
+                }
+            }
+            // A direct rule we can't express only sends less `DIRECT`.
+            None if direct => {}
+            Some(items) if !items.is_empty() => shadowed.extend(items),
+            // Could match anything from here on.
+            _ => break,
+        }
+    }
+    entries
+}
+
+/// The script for the current bypass list (and route rules, if enabled).
+pub(crate) async fn current_script(app: &AppHandle, proxy_port: u16) -> String {
+    let mut entries = super::proxy_bypass::effective_entries(app).await;
+    if load_settings(app).use_route_rules {
+        let config_path = ProcessManager::acquire().config_path.clone();
+        let config = config_path
+            .and_then(|path| std::fs::read_to_string(path.as_str()).ok())
+            .and_then(|text| serde_json::from_str::<Value>(&text).ok());
+        if let Some(config) = config {
+            for entry in route_direct_entries(&config) {
+                if !entries.contains(&entry) {
+                    entries.push(entry);
+                }
+            }
+        }
+    }
+    script(proxy_port, &entries)
+}
+
+// ── Listener ──────────────────────────────────────────────────────────
+
+struct PacServer {
+    port: u16,
+    script: Arc<RwLock<String>>,
+    revision: u64,
+    task: tauri::async_runtime::JoinHandle<()>,
+}
+
+static SERVER: Mutex<Option<PacServer>> = Mutex::new(None);
+
+fn response(request_head: &str, script: &str) -> String {
+    let path = request_head
+        .lines()
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .unwrap_or("");
+    let path = path.split('?').next().unwrap_or("");
+    if path == PAC_PATH {
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/x-ns-proxy-autoconfig\r\nContent-Length: {}\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n{}",
+            script.len(),
+            script
+        )
+    } else {
+        "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".into()
+    }
+}
+
+async fn serve_one(mut stream: tokio::net::TcpStream, script: Arc<RwLock<String>>) {
+    let mut head = Vec::new();
+    let mut buf = [0u8; 1024];
+    let read_head = async {
+        while !head.windows(4).any(|w| w == b"\r\n\r\n") && head.len() < MAX_REQUEST_HEAD {
+            match stream.read(&mut buf).await {
+                Ok(0) | Err(_) => break,
+                Ok(n) => head.extend_from_slice(&buf[..n]),
+            }
+        }
+    };
+    if tokio::time::timeout(REQUEST_READ_TIMEOUT, read_head)
+        .await
+        .is_err()
+    {
+        return;
+    }
+    let body = script.read().map(|s| s.clone()).unwrap_or_default();
+    let reply = response(&String::from_utf8_lossy(&head), &body);
+    let _ = stream.write_all(reply.as_bytes()).await;
+    let _ = stream.shutdown().await;
+}
+
+/// Serve `script`, starting the listener if needed, and return the URL to
+/// hand to the OS (revision bumped).
+pub(crate) async fn serve(app: &AppHandle, script: String) -> Result<String, String> {
+    {
+        let mut guard = SERVER.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(server) = guard.as_mut() {
+            if !server.task.inner().is_finished() {
+                *server.script.write().unwrap_or_else(|e| e.into_inner()) = script;
+                server.revision += 1;
+                return Ok(pac_url(server.port, server.revision));
+            }
+        }
+    }
+
+    let port = load_settings(app).port;
+    let listener = TcpListener::bind((PROXY_HOST, port))
+        .await
+        .map_err(|e| format!("PAC listener on {}:{}: {}", PROXY_HOST, port, e))?;
+    let port = listener.local_addr().map_err(|e| e.to_string())?.port();
+    let shared = Arc::new(RwLock::new(script));
+    let for_task = Arc::clone(&shared);
+    let task = tauri::async_runtime::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    tauri::async_runtime::spawn(serve_one(stream, Arc::clone(&for_task)));
+                }
+                Err(e) => log::warn!("[pac] accept failed: {}", e),
+            }
+        }
+    });
+    log::info!("[pac] serving {} on {}:{}", PAC_PATH, PROXY_HOST, port);
+
+    let mut guard = SERVER.lock().unwrap_or_else(|e| e.into_inner());
+    let revision = 1;
+    if let Some(old) = guard.replace(PacServer {
+        port,
+        script: shared,
+        revision,
+        task,
+    }) {
+        old.task.abort();
+    }
+    Ok(pac_url(port, revision))
+}
+
+fn pac_url(port: u16, revision: u64) -> String {
+    format!("http://{}:{}{}?v={}", PROXY_HOST, port, PAC_PATH, revision)
+}
+
+/// Whether `url` is one of ours — the clear path only turns off an
+/// auto-config URL OneBox set, and `proxy_guard` tells ours from a foreign
+/// one.
+pub(crate) fn is_onebox_url(url: &str) -> bool {
+    url.strip_prefix(&format!("http://{}:", PROXY_HOST))
+        .and_then(|rest| rest.split_once('/'))
+        .is_some_and(|(port, path)| {
+            port.parse::<u16>().is_ok() && path.split('?').next() == Some(&PAC_PATH[1..])
+        })
+}
+
+/// Whether the listener is up, i.e. the OS may currently point at it.
+/// Unused on Linux, whose clear path restores captured settings.
+#[cfg_attr(target_os = "linux", allow(dead_code))]
+pub(crate) fn is_serving() -> bool {
+    SERVER.lock().unwrap_or_else(|e| e.into_inner()).is_some()
+}
+
+/// Stop the listener. Safe to call when it isn't running.
+pub(crate) fn stop() {
+    let server = SERVER.lock().unwrap_or_else(|e| e.into_inner()).take();
+    if let Some(server) = server {
+        server.task.abort();
+        log::info!("[pac] listener on port {} stopped", server.port);
+    }
+}
+
+/// The script `set_system_proxy` would serve right now.
+#[tauri::command]
+pub async fn preview_pac_script(app: AppHandle) -> String {
+    current_script(&app, crate::core::mixed_proxy_port(&app)).await
+}
+
+#[cfg(test)]
+mod pac_tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn script_lists_each_kind_of_entry() {
+        let entries: Vec<BypassEntry> = [
+            "localhost",
+            "*.corp.lan",
+            "10.0.0.0/8",
+            "fd00::/8",
+            "<local>",
+        ]
+        .iter()
+        .map(|e| BypassEntry::parse(e).unwrap())
+        .collect();
+        let pac = script(7890, &entries);
+        assert!(pac.contains(r#"var PROXY = "PROXY 127.0.0.1:7890";"#));
+        assert!(pac.contains("var DIRECT_PLAIN = true;"));
+        assert!(pac.contains(r#"var DIRECT_HOSTS = ["localhost"];"#));
+        assert!(pac.contains(r#"var DIRECT_SUFFIXES = [".corp.lan"];"#));
+        assert!(pac.contains(r#"var DIRECT_NETS = [["10.0.0.0","255.0.0.0"]];"#));
+        assert!(pac.contains("function FindProxyForURL(url, host)"));
+    }
+
+    #[test]
+    fn route_rules_to_direct_outbounds_become_entries() {
+        let config = json!({
+            "outbounds": [
+                { "type": "direct", "tag": "direct" },
+                { "type": "vless", "tag": "proxy" }
+            ],
+            "route": {
+                "rule_set": [
+                    { "type": "inline", "tag": "lan", "rules": [{ "ip_cidr": ["192.168.0.0/16"] }] },
+                    { "type": "remote", "tag": "geosite-cn", "url": "https://example.com/cn.srs" }
+                ],
+                "rules": [
+                    { "domain_suffix": ["example.cn", ".internal"], "outbound": "direct" },
+                    { "rule_set": "lan", "outbound": "direct" },
+                    { "rule_set": "geosite-cn", "outbound": "direct" },
+                    { "domain": "pinned.example", "port": 443, "outbound": "direct" },
+                    { "domain": "blocked.example", "outbound": "proxy" }
+                ]
+            }
+        });
+        assert_eq!(
+            route_direct_entries(&config),
+            vec![
+                BypassEntry::Host("example.cn".into()),
+                BypassEntry::DomainSuffix("example.cn".into()),
+                BypassEntry::DomainSuffix("internal".into()),
+                BypassEntry::Cidr("192.168.0.0".parse().unwrap(), 16),
+            ]
+        );
+    }
+
+    #[test]
+    fn route_rules_after_a_matching_non_direct_rule_are_shadowed() {
+        let config = json!({
+            "outbounds": [
+                { "type": "direct", "tag": "direct" },
+                { "type": "vless", "tag": "proxy" }
+            ],
+            "route": {
+                "rules": [
+                    { "action": "sniff" },
+                    { "domain_suffix": "ads.example.cn", "action": "reject" },
+                    { "domain_suffix": ["example.cn", "example.org"], "outbound": "direct" },
+                    { "ip_cidr": "10.8.0.0/16", "outbound": "proxy" },
+                    { "ip_cidr": ["10.0.0.0/8", "192.168.0.0/16"], "outbound": "direct" },
+                    { "domain": "late.example", "outbound": "direct" },
+                    { "port": 22, "outbound": "proxy" },
+                    { "ip_cidr": "172.16.0.0/12", "outbound": "direct" }
+                ]
+            }
+        });
+        // `*.example.cn` could hit the reject rule; `10.0.0.0/8` contains
+        // 10.8/16 and `late.example` could resolve into it; nothing is
+        // taken past the port rule.
+        assert_eq!(
+            route_direct_entries(&config),
+            vec![
+                BypassEntry::Host("example.cn".into()),
+                BypassEntry::Host("example.org".into()),
+                BypassEntry::DomainSuffix("example.org".into()),
+                BypassEntry::Cidr("192.168.0.0".parse().unwrap(), 16),
+            ]
+        );
+    }
+
+    #[test]
+    fn dns_hijack_and_sniff_rules_do_not_shadow() {
+        let config = json!({
+            "outbounds": [
+                { "type": "direct", "tag": "direct" },
+                { "type": "dns", "tag": "dns-out" }
+            ],
+            "route": {
+                "rules": [
+                    { "protocol": "dns", "action": "hijack-dns" },
+                    { "inbound": "tun-in", "action": "sniff" },
+                    { "port": 53, "action": "hijack-dns" },
+                    { "protocol": ["dns"], "outbound": "dns-out" },
+                    { "domain_suffix": "example.cn", "outbound": "direct" }
+                ]
+            }
+        });
+        assert_eq!(
+            route_direct_entries(&config),
+            vec![
+                BypassEntry::Host("example.cn".into()),
+                BypassEntry::DomainSuffix("example.cn".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn listener_answers_only_the_pac_path() {
+        let ok = response("GET /proxy.pac?v=3 HTTP/1.1\r\nHost: x\r\n\r\n", "js");
+        assert!(ok.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(ok.contains("Content-Length: 2\r\n"));
+        assert!(ok.ends_with("\r\n\r\njs"));
+        assert!(response("GET / HTTP/1.1\r\n\r\n", "js").starts_with("HTTP/1.1 404"));
+
+        assert!(is_onebox_url("http://127.0.0.1:51234/proxy.pac?v=7"));
+        assert!(!is_onebox_url("http://wpad.corp/proxy.pac"));
+        assert!(!is_onebox_url("http://127.0.0.1:51234/other.pac"));
+    }
+}
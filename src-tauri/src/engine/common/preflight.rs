@@ -0,0 +1,444 @@
+//! Pre-flight config validation, run by `core::start`,
+//! `core::reload_config` and the headless `engine start` / `reload`
+//! (`validate_headless`) before anything is spawned or signalled.
+//!
+//! Without it a malformed `config.json` only surfaces as a readiness
+//! timeout or a stderr line in the monitor. Two layers:
+//!
+//!   1. Structural checks in Rust for what OneBox itself depends on: the
+//!      `mixed` inbound (system proxy, readiness, copy-env), a Clash API
+//!      controller with its own port, and in TUN mode a `tun` inbound
+//!      whose gateway `helper::tun_gateway_from_value` can parse (DNS
+//!      override target).
+//!   2. `sing-box check -c <path>` through the bundled sidecar, which
+//!      catches everything sing-box itself would reject.
+//!
+//! Failures are returned as `CONFIG_INVALID:<json>` where `<json>` is a
+//! list of `ConfigIssue { path, message, source }`, `path` being a
+//! JSONPath (`$.inbounds[0].listen_port`) the UI can point at. If the
+//! sidecar can't be run at all the check is skipped with a warning —
+//! validation must never be the reason a good config doesn't start.
+
+use std::time::Duration;
+
+use serde::Serialize;
+use serde_json::Value;
+use tauri::AppHandle;
+use tauri_plugin_shell::ShellExt;
+
+use crate::engine::ProxyMode;
+
+pub const CONFIG_INVALID: &str = "CONFIG_INVALID";
+const CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum IssueSource {
+    /// OneBox's own structural checks.
+    Structure,
+    /// `sing-box check`.
+    SingBox,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ConfigIssue {
+    /// JSONPath of the offending node; `$` when it can't be narrowed down.
+    pub path: String,
+    pub message: String,
+    pub source: IssueSource,
+}
+
+impl ConfigIssue {
+    fn structure(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            message: message.into(),
+            source: IssueSource::Structure,
+        }
+    }
+}
+
+/// The error string handed back to the frontend.
+pub fn format_issues(issues: &[ConfigIssue]) -> String {
+    format_coded(CONFIG_INVALID, issues)
+}
+
+/// `<code>:<json issues>`, the shape of every validation error the
+/// frontend parses (`CONFIG_INVALID`, `proxy_bypass::PROXY_BYPASS_INVALID`).
+pub fn format_coded<T: Serialize>(code: &str, issues: &[T]) -> String {
+    format!(
+        "{}:{}",
+        code,
+        serde_json::to_string(issues).unwrap_or_else(|_| "[]".into())
+    )
+}
+
+fn inbound_index(config: &Value, kind: &str, tag: Option<&str>) -> Option<usize> {
+    config.get("inbounds")?.as_array()?.iter().position(|ib| {
+        ib.get("type").and_then(Value::as_str) == Some(kind)
+            && tag.is_none_or(|t| ib.get("tag").and_then(Value::as_str) == Some(t))
+    })
+}
+
+/// Checks for what OneBox relies on beyond sing-box's own schema.
+pub fn check_structure(config: &Value, mode: &ProxyMode) -> Vec<ConfigIssue> {
+    let mut issues = Vec::new();
+    if !config.is_object() {
+        issues.push(ConfigIssue::structure("$", "config must be a JSON object"));
+        return issues;
+    }
+
+    match config.get("inbounds") {
+        Some(Value::Array(_)) => {}
+        Some(_) => issues.push(ConfigIssue::structure(
+            "$.inbounds",
+            "inbounds must be an array",
+        )),
+        None => issues.push(ConfigIssue::structure("$.inbounds", "inbounds is missing")),
+    }
+
+    match inbound_index(config, "mixed", Some("mixed")) {
+        None => issues.push(ConfigIssue::structure(
+            "$.inbounds",
+            "no inbound with type \"mixed\" and tag \"mixed\"",
+        )),
+        Some(i) => {
+            if crate::core::mixed_port_from_config(config).is_none() {
+                issues.push(ConfigIssue::structure(
+                    format!("$.inbounds[{}].listen_port", i),
+                    "mixed inbound needs a listen_port between 1 and 65535",
+                ));
+            }
+        }
+    }
+
+    let controller_path = "$.experimental.clash_api.external_controller";
+    let controller = config
+        .pointer("/experimental/clash_api/external_controller")
+        .and_then(Value::as_str);
+    match controller {
+        None => issues.push(ConfigIssue::structure(
+            controller_path,
+            "Clash API controller is not configured",
+        )),
+        Some(addr) => match crate::core::clash_api_addr_from_config(config) {
+            None => issues.push(ConfigIssue::structure(
+                controller_path,
+                format!(
+                    "Clash API controller {:?} needs a port between 1 and 65535",
+                    addr
+                ),
+            )),
+            Some((_, port)) if crate::core::mixed_port_from_config(config) == Some(port) => issues
+                .push(ConfigIssue::structure(
+                    controller_path,
+                    format!(
+                        "Clash API controller port {} collides with the mixed inbound",
+                        port
+                    ),
+                )),
+            Some(_) => {}
+        },
+    }
+
+    if *mode == ProxyMode::TunProxy {
+        match inbound_index(config, "tun", None) {
+            None => issues.push(ConfigIssue::structure(
+                "$.inbounds",
+                "TUN mode needs an inbound with type \"tun\"",
+            )),
+            Some(i) => {
+                if super::helper::tun_gateway_from_value(config).is_none() {
+                    issues.push(ConfigIssue::structure(
+                        format!("$.inbounds[{}].address", i),
+                        "tun inbound needs an IPv4 address such as \"172.19.0.1/30\"",
+                    ));
+                }
+            }
+        }
+    }
+    issues
+}
+
+fn strip_ansi(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            // CSI: ESC [ params final-byte
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Turn `sing-box check` output into one issue. sing-box reports decode
+/// errors as `FATAL[0000] decode config at <file>: outbounds[2].server:
+/// <reason>`; the field path is lifted into JSONPath when present.
+fn parse_check_output(output: &str) -> ConfigIssue {
+    let text = strip_ansi(output);
+    let line = text
+        .lines()
+        .map(str::trim)
+        .find(|l| !l.is_empty())
+        .unwrap_or("sing-box check failed");
+    // Drop the `FATAL[0000] ` level prefix.
+    let message = match line.split_once("] ") {
+        Some((level, rest)) if level.len() <= 16 && level.contains('[') => rest,
+        _ => line,
+    };
+    let mut path = "$".to_string();
+    if let Some(rest) = message
+        .split_once("decode config at ")
+        .and_then(|(_, rest)| rest.split_once(": "))
+        .map(|(_, rest)| rest)
+    {
+        if let Some((field, _)) = rest.split_once(": ") {
+            let is_path = !field.is_empty()
+                && field
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '[' | ']'));
+            if is_path {
+                path = format!("$.{}", field);
+            }
+        }
+    }
+    ConfigIssue {
+        path,
+        message: message.to_string(),
+        source: IssueSource::SingBox,
+    }
+}
+
+async fn sing_box_check(app: &AppHandle, config_path: &str) -> Option<ConfigIssue> {
+    let command = match app.shell().sidecar("sing-box") {
+        Ok(command) => command.args(["check", "-c", config_path]),
+        Err(e) => {
+            log::warn!(
+                "[preflight] sing-box sidecar unavailable, skipping check: {}",
+                e
+            );
+            return None;
+        }
+    };
+    let outcome = tokio::time::timeout(CHECK_TIMEOUT, command.output()).await;
+    check_outcome(outcome.map(|r| r.map(|o| (o.status.success(), o.stdout, o.stderr))))
+}
+
+/// `sing_box_check` for callers without an `AppHandle` (the headless CLI),
+/// given the resolved sidecar path.
+async fn sidecar_check(sidecar: &str, config_path: &str) -> Option<ConfigIssue> {
+    let command = tokio::process::Command::new(sidecar)
+        .args(["check", "-c", config_path])
+        .output();
+    let outcome = tokio::time::timeout(CHECK_TIMEOUT, command).await;
+    check_outcome(outcome.map(|r| r.map(|o| (o.status.success(), o.stdout, o.stderr))))
+}
+
+/// `(success, stdout, stderr)` of a finished `sing-box check`.
+type CheckOutput = (bool, Vec<u8>, Vec<u8>);
+
+/// A `sing-box check` run, or why it didn't run.
+fn check_outcome<E: std::fmt::Display>(
+    outcome: Result<Result<CheckOutput, E>, tokio::time::error::Elapsed>,
+) -> Option<ConfigIssue> {
+    match outcome {
+        Ok(Ok((true, _, _))) => None,
+        Ok(Ok((false, stdout, stderr))) => {
+            let stderr = String::from_utf8_lossy(&stderr);
+            let stdout = String::from_utf8_lossy(&stdout);
+            Some(parse_check_output(if stderr.trim().is_empty() {
+                &stdout
+            } else {
+                &stderr
+            }))
+        }
+        Ok(Err(e)) => {
+            log::warn!("[preflight] sing-box check could not run, skipping: {}", e);
+            None
+        }
+        Err(_) => {
+            log::warn!(
+                "[preflight] sing-box check timed out after {:?}, skipping",
+                CHECK_TIMEOUT
+            );
+            None
+        }
+    }
+}
+
+/// Both layers against `config_path`; `check` is the `sing-box check`
+/// layer, only awaited once the JSON parses.
+async fn collect_issues(
+    config_path: &str,
+    mode: &ProxyMode,
+    check: impl std::future::Future<Output = Option<ConfigIssue>>,
+) -> Vec<ConfigIssue> {
+    let issues = match std::fs::read_to_string(config_path) {
+        Err(e) => vec![ConfigIssue::structure(
+            "$",
+            format!("cannot read {}: {}", config_path, e),
+        )],
+        Ok(text) => match serde_json::from_str::<Value>(&text) {
+            Err(e) => vec![ConfigIssue::structure(
+                "$",
+                format!(
+                    "invalid JSON at line {} column {}: {}",
+                    e.line(),
+                    e.column(),
+                    e
+                ),
+            )],
+            Ok(config) => {
+                let mut issues = check_structure(&config, mode);
+                issues.extend(check.await);
+                issues
+            }
+        },
+    };
+    for issue in &issues {
+        log::warn!(
+            "[preflight] {} ({:?}): {}",
+            issue.path,
+            issue.source,
+            issue.message
+        );
+    }
+    issues
+}
+
+/// Validate `config_path` for `mode`. `Err` carries `format_issues`.
+pub async fn validate(app: &AppHandle, config_path: &str, mode: &ProxyMode) -> Result<(), String> {
+    let issues = collect_issues(config_path, mode, sing_box_check(app, config_path)).await;
+    if issues.is_empty() {
+        Ok(())
+    } else {
+        Err(format_issues(&issues))
+    }
+}
+
+/// `validate` for the headless CLI: same checks, with `sing-box check` run
+/// from `sidecar`. Returns the issues themselves so they can be printed.
+pub async fn validate_headless(
+    sidecar: &str,
+    config_path: &str,
+    mode: &ProxyMode,
+) -> Vec<ConfigIssue> {
+    collect_issues(config_path, mode, sidecar_check(sidecar, config_path)).await
+}
+
+#[cfg(test)]
+mod preflight_tests {
+    use super::*;
+    use serde_json::json;
+
+    fn valid() -> Value {
+        json!({
+            "inbounds": [
+                {"type": "tun", "tag": "tun", "address": ["172.19.0.1/30", "fdfe:dcba:9876::1/126"]},
+                {"type": "mixed", "tag": "mixed", "listen": "127.0.0.1", "listen_port": 6789}
+            ],
+            "experimental": {"clash_api": {"external_controller": "127.0.0.1:9191"}}
+        })
+    }
+
+    #[test]
+    fn controller_port_is_free_but_not_shared_with_mixed() {
+        let mut config = valid();
+        config["experimental"]["clash_api"]["external_controller"] = json!("127.0.0.1:19191");
+        assert!(check_structure(&config, &ProxyMode::ManualProxy).is_empty());
+        config["experimental"]["clash_api"]["external_controller"] = json!("127.0.0.1:6789");
+        assert_eq!(
+            paths(&check_structure(&config, &ProxyMode::ManualProxy)),
+            vec!["$.experimental.clash_api.external_controller"]
+        );
+    }
+
+    fn paths(issues: &[ConfigIssue]) -> Vec<&str> {
+        issues.iter().map(|i| i.path.as_str()).collect()
+    }
+
+    #[test]
+    fn valid_config_passes_in_every_mode() {
+        for mode in [
+            ProxyMode::SystemProxy,
+            ProxyMode::ManualProxy,
+            ProxyMode::TunProxy,
+        ] {
+            assert!(check_structure(&valid(), &mode).is_empty(), "{:?}", mode);
+        }
+    }
+
+    #[test]
+    fn reports_paths_of_broken_fields() {
+        let mut config = valid();
+        config["inbounds"][1]["listen_port"] = json!(0);
+        config["inbounds"][0]["address"] = json!(["fdfe:dcba:9876::1/126"]);
+        config["experimental"]["clash_api"]["external_controller"] = json!("[::1]");
+        let issues = check_structure(&config, &ProxyMode::TunProxy);
+        assert_eq!(
+            paths(&issues),
+            vec![
+                "$.inbounds[1].listen_port",
+                "$.experimental.clash_api.external_controller",
+                "$.inbounds[0].address",
+            ]
+        );
+        // The tun inbound only matters in TUN mode.
+        assert_eq!(check_structure(&config, &ProxyMode::SystemProxy).len(), 2);
+    }
+
+    #[test]
+    fn reports_missing_sections() {
+        let issues = check_structure(&json!({}), &ProxyMode::TunProxy);
+        assert_eq!(
+            paths(&issues),
+            vec![
+                "$.inbounds",
+                "$.inbounds",
+                "$.experimental.clash_api.external_controller",
+                "$.inbounds",
+            ]
+        );
+        assert_eq!(
+            paths(&check_structure(&json!([]), &ProxyMode::SystemProxy)),
+            vec!["$"]
+        );
+    }
+
+    #[test]
+    fn parses_sing_box_decode_errors() {
+        let issue = parse_check_output(
+            "\u{1b}[31mFATAL\u{1b}[0m[0000] decode config at /tmp/config.json: outbounds[2].server_port: json: cannot unmarshal string into Go value of type uint16\n",
+        );
+        assert_eq!(issue.path, "$.outbounds[2].server_port");
+        assert_eq!(issue.source, IssueSource::SingBox);
+        assert!(issue
+            .message
+            .starts_with("decode config at /tmp/config.json"));
+
+        let issue =
+            parse_check_output("FATAL[0000] initialize outbound[3]: unknown outbound type: foo");
+        assert_eq!(issue.path, "$");
+        assert_eq!(
+            issue.message,
+            "initialize outbound[3]: unknown outbound type: foo"
+        );
+    }
+
+    #[test]
+    fn error_string_round_trips() {
+        let issues = vec![ConfigIssue::structure("$.inbounds", "missing")];
+        let text = format_issues(&issues);
+        let json = text.strip_prefix("CONFIG_INVALID:").unwrap();
+        let parsed: Value = serde_json::from_str(json).unwrap();
+        assert_eq!(parsed[0]["path"], "$.inbounds");
+        assert_eq!(parsed[0]["source"], "structure");
+    }
+}
@@ -0,0 +1,787 @@
+//! User-editable system-proxy bypass list.
+//!
+//! `sysproxy::DEFAULT_BYPASS` stays the per-OS base; on top of it the user
+//! can add entries in `settings.json` under `proxy_bypass`:
+//!
+//! ```json
+//! {
+//!   "entries": ["*.corp.example.com", "10.20.0.0/16", "intranet"],
+//!   "networks": [
+//!     { "ssid": "Office", "entries": ["*.office.lan"] },
+//!     { "gateway": "192.168.50.1", "entries": ["nas"] }
+//!   ]
+//! }
+//! ```
+//!
+//! Entries use one platform-neutral syntax — host, `*.domain` (or
+//! `.domain`), IP, CIDR, `a.b.*`, `<local>` — and `render` turns them into
+//! what each OS proxy setting understands: comma-separated globs and CIDRs
+//! on macOS and Linux, semicolon-separated wildcards on Windows, which has
+//! no CIDR and gets octet-aligned `10.20.*` patterns instead. A `networks`
+//! rule applies while its `ssid` and/or `gateway` match the network the
+//! system is on when the proxy is set.
+//!
+//! `set_proxy_bypass` validates before saving and fails with
+//! `PROXY_BYPASS_INVALID:<json>` (a list of `BypassIssue { path, message }`);
+//! `preview_proxy_bypass` shows the final string for saved or draft settings.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+use tauri_plugin_store::StoreExt;
+
+use super::helper::command_stdout;
+use super::preflight::format_coded;
+use crate::core::ProcessManager;
+use crate::engine::ProxyMode;
+
+pub const PROXY_BYPASS_INVALID: &str = "PROXY_BYPASS_INVALID";
+const SETTINGS_STORE: &str = "settings.json";
+const PROXY_BYPASS_KEY: &str = "proxy_bypass";
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct ProxyBypassSettings {
+    /// Applied on every network.
+    pub entries: Vec<String>,
+    pub networks: Vec<NetworkBypass>,
+}
+
+/// Extra entries for one network. At least one of `ssid` / `gateway` must
+/// be set; when both are, both must match.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct NetworkBypass {
+    pub ssid: Option<String>,
+    pub gateway: Option<String>,
+    pub entries: Vec<String>,
+}
+
+/// The network the system is on, as far as it could be detected.
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct CurrentNetwork {
+    pub ssid: Option<String>,
+    pub gateway: Option<IpAddr>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct BypassIssue {
+    /// Path into `ProxyBypassSettings`, e.g. `$.networks[0].entries[2]`.
+    pub path: String,
+    pub message: String,
+}
+
+impl BypassIssue {
+    fn new(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Platform {
+    Macos,
+    Linux,
+    Windows,
+}
+
+impl Platform {
+    pub fn current() -> Self {
+        if cfg!(target_os = "windows") {
+            Platform::Windows
+        } else if cfg!(target_os = "macos") {
+            Platform::Macos
+        } else {
+            Platform::Linux
+        }
+    }
+
+    fn separator(self) -> char {
+        match self {
+            Platform::Windows => ';',
+            Platform::Macos | Platform::Linux => ',',
+        }
+    }
+}
+
+/// One parsed bypass entry.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BypassEntry {
+    Host(String),
+    /// `*.example.com`; stored without the `*.`.
+    DomainSuffix(String),
+    Ip(IpAddr),
+    /// Network address (host bits cleared) and prefix length.
+    Cidr(IpAddr, u8),
+    /// Plain host names without a dot.
+    Local,
+}
+
+fn is_hostname(s: &str) -> bool {
+    s.len() <= 253
+        && s.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+}
+
+pub(super) fn network_address(ip: IpAddr, prefix: u8) -> IpAddr {
+    match ip {
+        IpAddr::V4(v4) => {
+            let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+            IpAddr::V4(Ipv4Addr::from(u32::from(v4) & mask))
+        }
+        IpAddr::V6(v6) => {
+            let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+            IpAddr::V6(Ipv6Addr::from(u128::from(v6) & mask))
+        }
+    }
+}
+
+/// `192.168.*` → `192.168.0.0/16`.
+fn parse_octet_wildcard(s: &str) -> Option<BypassEntry> {
+    let head = s.strip_suffix(".*")?;
+    let octets = head
+        .split('.')
+        .map(|o| o.parse::<u8>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    if octets.is_empty() || octets.len() > 3 {
+        return None;
+    }
+    let mut full = [0u8; 4];
+    full[..octets.len()].copy_from_slice(&octets);
+    Some(BypassEntry::Cidr(
+        IpAddr::V4(Ipv4Addr::from(full)),
+        octets.len() as u8 * 8,
+    ))
+}
+
+impl BypassEntry {
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        let s = raw.trim();
+        if s.is_empty() {
+            return Err("empty entry".into());
+        }
+        if s.contains(|c: char| c.is_whitespace() || c == ',' || c == ';') {
+            return Err("one entry per item: no spaces, commas or semicolons".into());
+        }
+        if s.eq_ignore_ascii_case("<local>") {
+            return Ok(BypassEntry::Local);
+        }
+        if let Some((addr, prefix)) = s.split_once('/') {
+            let ip: IpAddr = addr
+                .parse()
+                .map_err(|_| format!("{} is not an IP address", addr))?;
+            let max = if ip.is_ipv4() { 32 } else { 128 };
+            let prefix = prefix
+                .parse::<u8>()
+                .ok()
+                .filter(|p| (1..=max).contains(p))
+                .ok_or_else(|| format!("prefix length must be 1-{}", max))?;
+            return Ok(BypassEntry::Cidr(network_address(ip, prefix), prefix));
+        }
+        if let Some(entry) = parse_octet_wildcard(s) {
+            return Ok(entry);
+        }
+        let unbracketed = s
+            .strip_prefix('[')
+            .and_then(|rest| rest.strip_suffix(']'))
+            .unwrap_or(s);
+        if let Ok(ip) = unbracketed.parse::<IpAddr>() {
+            return Ok(BypassEntry::Ip(ip));
+        }
+        let (is_suffix, host) = match s.strip_prefix("*.").or_else(|| s.strip_prefix('.')) {
+            Some(rest) => (true, rest),
+            None => (false, s),
+        };
+        if !is_hostname(host) {
+            return Err(format!(
+                "{} is not a host name, *.domain, IP address or CIDR",
+                s
+            ));
+        }
+        let host = host.to_ascii_lowercase();
+        Ok(if is_suffix {
+            BypassEntry::DomainSuffix(host)
+        } else {
+            BypassEntry::Host(host)
+        })
+    }
+}
+
+/// Windows has no CIDR in ProxyOverride: widen the prefix to the next
+/// octet boundary and list one `a.b.*` pattern per covered block, e.g.
+/// `172.16.0.0/12` → `172.16.*` … `172.31.*`.
+fn octet_wildcards(ip: Ipv4Addr, prefix: u8) -> Vec<String> {
+    let fixed = prefix.div_ceil(8) as u32;
+    let count = 1u32 << (fixed * 8 - prefix as u32);
+    let step = 1u32 << (32 - fixed * 8);
+    (0..count)
+        .map(|i| {
+            let octets = Ipv4Addr::from(u32::from(ip) + i * step).octets();
+            let mut parts: Vec<String> =
+                octets[..fixed as usize].iter().map(u8::to_string).collect();
+            if fixed < 4 {
+                parts.push("*".into());
+            }
+            parts.join(".")
+        })
+        .collect()
+}
+
+/// `entry` in `platform` syntax; `Err` when the platform can't express it.
+pub fn render(entry: &BypassEntry, platform: Platform) -> Result<Vec<String>, String> {
+    match (entry, platform) {
+        (BypassEntry::Host(host), _) => Ok(vec![host.clone()]),
+        (BypassEntry::DomainSuffix(domain), _) => Ok(vec![format!("*.{}", domain)]),
+        (BypassEntry::Ip(ip), _) => Ok(vec![ip.to_string()]),
+        (BypassEntry::Local, Platform::Linux) => {
+            Err("<local> is not supported by the Linux proxy settings".into())
+        }
+        (BypassEntry::Local, _) => Ok(vec!["<local>".into()]),
+        (BypassEntry::Cidr(ip, prefix), Platform::Macos | Platform::Linux) => {
+            Ok(vec![format!("{}/{}", ip, prefix)])
+        }
+        (BypassEntry::Cidr(IpAddr::V4(ip), prefix), Platform::Windows) => {
+            Ok(octet_wildcards(*ip, *prefix))
+        }
+        (BypassEntry::Cidr(IpAddr::V6(_), _), Platform::Windows) => {
+            Err("IPv6 ranges are not supported by the Windows proxy settings".into())
+        }
+    }
+}
+
+fn rule_matches(rule: &NetworkBypass, network: &CurrentNetwork) -> bool {
+    let gateway = rule
+        .gateway
+        .as_deref()
+        .map(|g| g.trim().parse::<IpAddr>().ok());
+    (rule.ssid.is_some() || gateway.is_some())
+        && rule
+            .ssid
+            .as_deref()
+            .is_none_or(|ssid| network.ssid.as_deref() == Some(ssid))
+        && gateway.is_none_or(|gw| gw.is_some() && gw == network.gateway)
+}
+
+/// Every problem in `settings`, whichever network it is for.
+pub fn validate(settings: &ProxyBypassSettings) -> Vec<BypassIssue> {
+    let mut issues = Vec::new();
+    let mut check_entries = |prefix: &str, entries: &[String]| {
+        for (i, raw) in entries.iter().enumerate() {
+            if let Err(e) = BypassEntry::parse(raw) {
+                issues.push(BypassIssue::new(format!("{}.entries[{}]", prefix, i), e));
+            }
+        }
+    };
+    check_entries("$", &settings.entries);
+    for (i, rule) in settings.networks.iter().enumerate() {
+        check_entries(&format!("$.networks[{}]", i), &rule.entries);
+    }
+    for (i, rule) in settings.networks.iter().enumerate() {
+        let ssid = rule.ssid.as_deref().map(str::trim);
+        let gateway = rule.gateway.as_deref().map(str::trim);
+        if ssid.is_none_or(str::is_empty) && gateway.is_none_or(str::is_empty) {
+            issues.push(BypassIssue::new(
+                format!("$.networks[{}]", i),
+                "needs an ssid or a gateway to match",
+            ));
+        }
+        if let Some(gw) = gateway.filter(|g| !g.is_empty()) {
+            if gw.parse::<IpAddr>().is_err() {
+                issues.push(BypassIssue::new(
+                    format!("$.networks[{}].gateway", i),
+                    format!("{} is not an IP address", gw),
+                ));
+            }
+        }
+    }
+    issues
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct ResolvedBypass {
+    pub platform: Platform,
+    /// What goes to the OS proxy setting.
+    pub bypass: String,
+    /// `None` when no per-network rule exists and detection was skipped.
+    pub network: Option<CurrentNetwork>,
+    /// Indices of the `networks` rules that applied.
+    pub matched_networks: Vec<usize>,
+    /// Invalid entries, and valid ones `platform` can't express; all left out.
+    pub issues: Vec<BypassIssue>,
+}
+
+/// User entries in effect on `network`, with their settings path, plus the
+/// indices of the `networks` rules that matched.
+fn applicable<'a>(
+    settings: &'a ProxyBypassSettings,
+    network: Option<&CurrentNetwork>,
+) -> (Vec<(String, &'a str)>, Vec<usize>) {
+    let mut raw: Vec<(String, &str)> = settings
+        .entries
+        .iter()
+        .enumerate()
+        .map(|(i, e)| (format!("$.entries[{}]", i), e.as_str()))
+        .collect();
+    let mut matched_networks = Vec::new();
+    if let Some(current) = network {
+        for (n, rule) in settings.networks.iter().enumerate() {
+            if !rule_matches(rule, current) {
+                continue;
+            }
+            matched_networks.push(n);
+            raw.extend(
+                rule.entries
+                    .iter()
+                    .enumerate()
+                    .map(|(i, e)| (format!("$.networks[{}].entries[{}]", n, i), e.as_str())),
+            );
+        }
+    }
+    (raw, matched_networks)
+}
+
+/// `base` (platform syntax) plus the applicable user entries, deduplicated.
+pub fn resolve(
+    settings: &ProxyBypassSettings,
+    base: &str,
+    platform: Platform,
+    network: Option<CurrentNetwork>,
+) -> ResolvedBypass {
+    let mut items: Vec<String> = base
+        .split(platform.separator())
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect();
+    let mut issues = Vec::new();
+    let (raw, matched_networks) = applicable(settings, network.as_ref());
+    for (path, raw) in raw {
+        match BypassEntry::parse(raw).and_then(|entry| render(&entry, platform)) {
+            Ok(rendered) => {
+                for item in rendered {
+                    if !items.contains(&item) {
+                        items.push(item);
+                    }
+                }
+            }
+            Err(e) => issues.push(BypassIssue::new(path, e)),
+        }
+    }
+    ResolvedBypass {
+        platform,
+        bypass: items.join(&platform.separator().to_string()),
+        network,
+        matched_networks,
+        issues,
+    }
+}
+
+/// Same selection as `resolve`, but parsed instead of rendered — for
+/// consumers with their own syntax (the PAC script). Invalid entries are
+/// dropped; `resolve` is where they get reported.
+pub fn entries(
+    settings: &ProxyBypassSettings,
+    base: &str,
+    platform: Platform,
+    network: Option<&CurrentNetwork>,
+) -> Vec<BypassEntry> {
+    let (raw, _) = applicable(settings, network);
+    let mut parsed: Vec<BypassEntry> = Vec::new();
+    let base = base.split(platform.separator()).filter(|s| !s.is_empty());
+    for entry in base
+        .chain(raw.into_iter().map(|(_, raw)| raw))
+        .filter_map(|raw| BypassEntry::parse(raw).ok())
+    {
+        if !parsed.contains(&entry) {
+            parsed.push(entry);
+        }
+    }
+    parsed
+}
+
+fn load_settings(app: &AppHandle) -> ProxyBypassSettings {
+    app.get_store(SETTINGS_STORE)
+        .and_then(|store| store.get(PROXY_BYPASS_KEY))
+        .and_then(|value| serde_json::from_value(value).ok())
+        .unwrap_or_default()
+}
+
+/// `current_network` shells out (`nmcli`, `route`, `netsh`), so it runs on
+/// the blocking pool; skipped when no rule is scoped to a network.
+async fn detect_network(settings: &ProxyBypassSettings) -> Option<CurrentNetwork> {
+    if settings.networks.is_empty() {
+        return None;
+    }
+    match tauri::async_runtime::spawn_blocking(current_network).await {
+        Ok(network) => Some(network),
+        Err(e) => {
+            log::warn!("[sysproxy] network detection failed: {}", e);
+            None
+        }
+    }
+}
+
+async fn resolve_current(settings: &ProxyBypassSettings) -> ResolvedBypass {
+    let network = detect_network(settings).await;
+    resolve(
+        settings,
+        super::sysproxy::DEFAULT_BYPASS,
+        Platform::current(),
+        network,
+    )
+}
+
+/// Bypass string for `sysproxy::set_system_proxy`.
+pub(crate) async fn system_bypass(app: &AppHandle) -> String {
+    let resolved = resolve_current(&load_settings(app)).await;
+    for issue in &resolved.issues {
+        log::warn!(
+            "[sysproxy] bypass entry {} skipped: {}",
+            issue.path,
+            issue.message
+        );
+    }
+    resolved.bypass
+}
+
+/// Parsed bypass entries in effect right now, base list included.
+pub(crate) async fn effective_entries(app: &AppHandle) -> Vec<BypassEntry> {
+    let settings = load_settings(app);
+    let network = detect_network(&settings).await;
+    entries(
+        &settings,
+        super::sysproxy::DEFAULT_BYPASS,
+        Platform::current(),
+        network.as_ref(),
+    )
+}
+
+/// Re-apply the system proxy so per-network rules follow a network switch.
+/// No-op unless the engine drives the system proxy and a rule exists.
+pub(crate) async fn refresh_for_network_change(app: &AppHandle) {
+    let is_system_proxy = matches!(
+        ProcessManager::acquire().mode.as_deref(),
+        Some(ProxyMode::SystemProxy)
+    );
+    if !is_system_proxy || load_settings(app).networks.is_empty() {
+        return;
+    }
+    if let Err(e) = super::sysproxy::set_system_proxy(app).await {
+        log::warn!("[sysproxy] re-apply after network change failed: {}", e);
+    }
+}
+
+/// Final bypass string for the saved settings, or for `settings` when the
+/// UI wants to preview a draft before saving it.
+#[tauri::command]
+pub async fn preview_proxy_bypass(
+    app: AppHandle,
+    settings: Option<ProxyBypassSettings>,
+) -> ResolvedBypass {
+    let settings = settings.unwrap_or_else(|| load_settings(&app));
+    resolve_current(&settings).await
+}
+
+/// Validate and save the bypass settings; the running system proxy picks
+/// them up immediately.
+#[tauri::command]
+pub async fn set_proxy_bypass(
+    app: AppHandle,
+    settings: ProxyBypassSettings,
+) -> Result<ResolvedBypass, String> {
+    let issues = validate(&settings);
+    if !issues.is_empty() {
+        return Err(format_coded(PROXY_BYPASS_INVALID, &issues));
+    }
+    let store = app
+        .get_store(SETTINGS_STORE)
+        .ok_or("settings store unavailable")?;
+    store.set(
+        PROXY_BYPASS_KEY,
+        serde_json::to_value(&settings).map_err(|e| e.to_string())?,
+    );
+    store.save().map_err(|e| e.to_string())?;
+
+    let is_system_proxy = matches!(
+        ProcessManager::acquire().mode.as_deref(),
+        Some(ProxyMode::SystemProxy)
+    );
+    if is_system_proxy {
+        super::sysproxy::set_system_proxy(&app)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(resolve_current(&settings).await)
+}
+
+// ── Network detection ─────────────────────────────────────────────────
+
+/// `ip route show default` → `default via 192.168.1.1 dev wlp2s0 …`.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_ip_route_gateway(out: &str) -> Option<IpAddr> {
+    out.lines().find_map(|line| {
+        let mut words = line.split_whitespace();
+        words.find(|w| *w == "via")?;
+        words.next()?.parse().ok()
+    })
+}
+
+/// `nmcli -t -f active,ssid dev wifi` → `yes:Office` (colons in the SSID
+/// are escaped as `\:`).
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_nmcli_ssid(out: &str) -> Option<String> {
+    out.lines()
+        .find_map(|line| line.strip_prefix("yes:"))
+        .map(|ssid| ssid.replace("\\:", ":"))
+        .filter(|ssid| !ssid.is_empty())
+}
+
+/// `route -n get default` → `gateway: 192.168.1.1` / `interface: en0`.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn parse_route_get_field<'a>(out: &'a str, field: &str) -> Option<&'a str> {
+    out.lines().find_map(|line| {
+        let (key, value) = line.trim().split_once(':')?;
+        (key.trim() == field).then(|| value.trim())
+    })
+}
+
+/// `netsh wlan show interfaces` → `    SSID                   : Office`
+/// (and a separate `BSSID` line that must not match).
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+fn parse_netsh_ssid(out: &str) -> Option<String> {
+    out.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        (key.trim() == "SSID")
+            .then(|| value.trim().to_string())
+            .filter(|s| !s.is_empty())
+    })
+}
+
+/// Best effort: either part may be missing (wired, no NetworkManager, …).
+/// Blocks on child processes — call through `detect_network`.
+fn current_network() -> CurrentNetwork {
+    #[cfg(target_os = "linux")]
+    {
+        CurrentNetwork {
+            ssid: command_stdout("nmcli", &["-t", "-f", "active,ssid", "dev", "wifi"])
+                .and_then(|out| parse_nmcli_ssid(&out)),
+            gateway: command_stdout("ip", &["route", "show", "default"])
+                .and_then(|out| parse_ip_route_gateway(&out)),
+        }
+    }
+    #[cfg(target_os = "macos")]
+    {
+        let route = command_stdout("route", &["-n", "get", "default"]).unwrap_or_default();
+        let gateway = parse_route_get_field(&route, "gateway").and_then(|g| g.parse().ok());
+        let ssid = parse_route_get_field(&route, "interface")
+            .and_then(|iface| command_stdout("networksetup", &["-getairportnetwork", iface]))
+            .and_then(|out| {
+                out.trim()
+                    .strip_prefix("Current Wi-Fi Network:")
+                    .map(|s| s.trim().to_string())
+            })
+            .filter(|s| !s.is_empty());
+        CurrentNetwork { ssid, gateway }
+    }
+    #[cfg(target_os = "windows")]
+    {
+        CurrentNetwork {
+            ssid: command_stdout("netsh", &["wlan", "show", "interfaces"])
+                .and_then(|out| parse_netsh_ssid(&out)),
+            gateway: command_stdout(
+                "powershell",
+                &[
+                    "-NoProfile",
+                    "-NonInteractive",
+                    "-Command",
+                    "(Get-NetRoute -DestinationPrefix '0.0.0.0/0' | Sort-Object RouteMetric | Select-Object -First 1).NextHop",
+                ],
+            )
+            .and_then(|out| out.trim().parse().ok()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod proxy_bypass_tests {
+    use super::*;
+
+    fn entries(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn entries_normalize_to_one_syntax() {
+        assert_eq!(
+            BypassEntry::parse(" .Corp.Example.com "),
+            Ok(BypassEntry::DomainSuffix("corp.example.com".into()))
+        );
+        assert_eq!(
+            BypassEntry::parse("10.20.3.4/16"),
+            Ok(BypassEntry::Cidr("10.20.0.0".parse().unwrap(), 16))
+        );
+        assert_eq!(
+            BypassEntry::parse("192.168.*"),
+            Ok(BypassEntry::Cidr("192.168.0.0".parse().unwrap(), 16))
+        );
+        assert_eq!(
+            BypassEntry::parse("[fd00::1]"),
+            Ok(BypassEntry::Ip("fd00::1".parse().unwrap()))
+        );
+        assert_eq!(BypassEntry::parse("<LOCAL>"), Ok(BypassEntry::Local));
+        for bad in [
+            "",
+            "a b",
+            "a,b",
+            "10.0.0.0/33",
+            "10.0.0.0/0",
+            "foo_bar",
+            "*.",
+            "x.*.y",
+        ] {
+            assert!(
+                BypassEntry::parse(bad).is_err(),
+                "{bad:?} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn translator_speaks_each_platform() {
+        let cidr = BypassEntry::parse("172.16.0.0/12").unwrap();
+        assert_eq!(
+            render(&cidr, Platform::Macos),
+            Ok(vec!["172.16.0.0/12".to_string()])
+        );
+        assert_eq!(
+            render(&cidr, Platform::Linux),
+            Ok(vec!["172.16.0.0/12".to_string()])
+        );
+        let windows = render(&cidr, Platform::Windows).unwrap();
+        assert_eq!(windows.len(), 16);
+        assert_eq!(windows.first().unwrap(), "172.16.*");
+        assert_eq!(windows.last().unwrap(), "172.31.*");
+
+        let small = BypassEntry::parse("10.1.2.0/31").unwrap();
+        assert_eq!(
+            render(&small, Platform::Windows),
+            Ok(vec!["10.1.2.0".to_string(), "10.1.2.1".to_string()])
+        );
+        let suffix = BypassEntry::parse("*.corp.lan").unwrap();
+        assert_eq!(
+            render(&suffix, Platform::Windows),
+            Ok(vec!["*.corp.lan".to_string()])
+        );
+        assert!(render(&BypassEntry::Local, Platform::Linux).is_err());
+        assert!(render(&BypassEntry::parse("fd00::/8").unwrap(), Platform::Windows).is_err());
+    }
+
+    #[test]
+    fn resolve_appends_deduplicated_entries_with_the_platform_separator() {
+        let settings = ProxyBypassSettings {
+            entries: entries(&["intranet", "192.168.0.0/16", "fd00::/8"]),
+            networks: vec![],
+        };
+        let windows = resolve(&settings, "localhost;192.168.*", Platform::Windows, None);
+        assert_eq!(windows.bypass, "localhost;192.168.*;intranet");
+        assert_eq!(windows.issues.len(), 1);
+        assert_eq!(windows.issues[0].path, "$.entries[2]");
+
+        let linux = resolve(&settings, "localhost,::1", Platform::Linux, None);
+        assert_eq!(
+            linux.bypass,
+            "localhost,::1,intranet,192.168.0.0/16,fd00::/8"
+        );
+        assert!(linux.issues.is_empty());
+    }
+
+    #[test]
+    fn network_rules_apply_only_on_their_network() {
+        let settings = ProxyBypassSettings {
+            entries: vec![],
+            networks: vec![
+                NetworkBypass {
+                    ssid: Some("Office".into()),
+                    gateway: None,
+                    entries: entries(&["*.office.lan"]),
+                },
+                NetworkBypass {
+                    ssid: None,
+                    gateway: Some("192.168.50.1".into()),
+                    entries: entries(&["nas"]),
+                },
+            ],
+        };
+        let office = CurrentNetwork {
+            ssid: Some("Office".into()),
+            gateway: Some("10.0.0.1".parse().unwrap()),
+        };
+        let resolved = resolve(&settings, "localhost", Platform::Macos, Some(office));
+        assert_eq!(resolved.bypass, "localhost,*.office.lan");
+        assert_eq!(resolved.matched_networks, vec![0]);
+
+        let home = CurrentNetwork {
+            ssid: None,
+            gateway: Some("192.168.50.1".parse().unwrap()),
+        };
+        let resolved = resolve(&settings, "localhost", Platform::Macos, Some(home));
+        assert_eq!(resolved.bypass, "localhost,nas");
+    }
+
+    #[test]
+    fn validation_reports_paths() {
+        let settings = ProxyBypassSettings {
+            entries: entries(&["ok.example", "bad entry"]),
+            networks: vec![
+                NetworkBypass {
+                    ssid: None,
+                    gateway: None,
+                    entries: entries(&["10.0.0.0/99"]),
+                },
+                NetworkBypass {
+                    ssid: None,
+                    gateway: Some("router".into()),
+                    entries: vec![],
+                },
+            ],
+        };
+        let paths: Vec<String> = validate(&settings).into_iter().map(|i| i.path).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "$.entries[1]",
+                "$.networks[0].entries[0]",
+                "$.networks[0]",
+                "$.networks[1].gateway",
+            ]
+        );
+        assert!(format_coded::<BypassIssue>(PROXY_BYPASS_INVALID, &[])
+            .starts_with("PROXY_BYPASS_INVALID:"));
+    }
+
+    #[test]
+    fn network_detection_parsers() {
+        assert_eq!(
+            parse_ip_route_gateway("default via 192.168.1.1 dev wlp2s0 proto dhcp metric 600\n"),
+            Some("192.168.1.1".parse().unwrap())
+        );
+        assert_eq!(
+            parse_nmcli_ssid("no:Neighbour\nyes:Cafe\\:Guest\n"),
+            Some("Cafe:Guest".into())
+        );
+        let route =
+            "   route to: default\ndestination: default\n    gateway: 10.0.0.1\n  interface: en0\n";
+        assert_eq!(parse_route_get_field(route, "gateway"), Some("10.0.0.1"));
+        assert_eq!(parse_route_get_field(route, "interface"), Some("en0"));
+        let netsh = "    Name                   : Wi-Fi\n    SSID                   : Office\n    BSSID                  : aa:bb:cc:dd:ee:ff\n";
+        assert_eq!(parse_netsh_ssid(netsh), Some("Office".into()));
+    }
+}
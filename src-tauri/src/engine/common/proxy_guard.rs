@@ -0,0 +1,445 @@
+//! System proxy drift guard — notices when something else rewrites the OS
+//! proxy while OneBox runs in SystemProxy mode.
+//!
+//! Corporate agents and other VPN clients routinely overwrite the system
+//! proxy; until now OneBox kept reporting "running" while traffic bypassed
+//! it. `sysproxy::set_system_proxy` records what it applied (`note_applied`)
+//! and the clear path forgets it (`note_cleared`). One long-lived task,
+//! spawned from `app_setup`, compares that against the live setting:
+//!
+//!   - every `interval_secs` while a proxy is applied and the engine runs
+//!   - right away when a native change source fires: the SCDynamicStore
+//!     `State:/Network/Global/Proxies` key on macOS
+//!     (`engine::macos::proxy_watcher`), the `Internet Settings` registry
+//!     key on Windows (`engine::windows::proxy_watcher`). Linux polls only.
+//!
+//! On divergence it either re-applies (`action: "reapply"`, at most
+//! `max_reapplies` times a minute, so OneBox doesn't fight a policy agent
+//! forever) or only reports it (`action: "warn"`, the default). Either way
+//! the drift is logged and emitted as `system-proxy-drift`
+//! (`EVENT_PROXY_DRIFT`). Configured via `settings.json` →
+//! `system_proxy_guard` (see `ProxyGuardSettings`).
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_store::StoreExt;
+use tokio::sync::Notify;
+
+use super::proxy_snapshot::{points_at_onebox, ProxySnapshot};
+use super::state_machine::EngineStateCell;
+use crate::engine::EVENT_TAURI_LOG;
+
+pub const EVENT_PROXY_DRIFT: &str = "system-proxy-drift";
+
+const SETTINGS_STORE: &str = "settings.json";
+const PROXY_GUARD_SETTINGS_KEY: &str = "system_proxy_guard";
+/// How often to re-check the state (and settings) while not watching.
+const IDLE_POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// Our own write fires the native change sources too, and the OS may still
+/// be mid-update; a read this soon after `note_applied` proves nothing.
+const SETTLE_TIME: Duration = Duration::from_secs(3);
+/// Let a burst of change notifications (one per key written) finish.
+const NUDGE_DEBOUNCE: Duration = Duration::from_millis(500);
+const REAPPLY_WINDOW: Duration = Duration::from_secs(60);
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DriftAction {
+    /// Point the OS back at OneBox.
+    Reapply,
+    /// Log and emit the drift, leave the setting alone.
+    #[default]
+    Warn,
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct ProxyGuardSettings {
+    pub enabled: bool,
+    pub action: DriftAction,
+    pub interval_secs: u64,
+    /// Re-applies allowed per minute before falling back to warnings.
+    pub max_reapplies: usize,
+}
+
+impl Default for ProxyGuardSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            action: DriftAction::Warn,
+            interval_secs: 10,
+            max_reapplies: 3,
+        }
+    }
+}
+
+fn load_settings(app: &AppHandle) -> ProxyGuardSettings {
+    app.get_store(SETTINGS_STORE)
+        .and_then(|store| store.get(PROXY_GUARD_SETTINGS_KEY))
+        .and_then(|value| serde_json::from_value(value).ok())
+        .unwrap_or_default()
+}
+
+/// What `set_system_proxy` last pointed the OS at.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Expected {
+    Static { port: u16 },
+    Pac { url: String, port: u16 },
+}
+
+#[derive(Clone)]
+struct Applied {
+    /// Bumped by every apply and clear, so a check that raced one of them
+    /// can tell its reading is stale.
+    generation: u64,
+    expected: Expected,
+    at: Instant,
+}
+
+struct Slot {
+    generation: u64,
+    applied: Option<Applied>,
+}
+
+static SLOT: Mutex<Slot> = Mutex::new(Slot {
+    generation: 0,
+    applied: None,
+});
+static NUDGE: Notify = Notify::const_new();
+/// Held by a re-apply and by `clear_system_proxy`, so a re-apply can't land
+/// after the engine stop already cleared the proxy.
+static APPLY_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+pub(crate) fn note_applied(expected: Expected) {
+    let mut slot = SLOT.lock().unwrap_or_else(|e| e.into_inner());
+    slot.generation += 1;
+    slot.applied = Some(Applied {
+        generation: slot.generation,
+        expected,
+        at: Instant::now(),
+    });
+}
+
+pub(crate) fn note_cleared() {
+    let mut slot = SLOT.lock().unwrap_or_else(|e| e.into_inner());
+    slot.generation += 1;
+    slot.applied = None;
+}
+
+fn current() -> Option<Applied> {
+    SLOT.lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .applied
+        .clone()
+}
+
+fn is_current(generation: u64) -> bool {
+    current().is_some_and(|applied| applied.generation == generation)
+}
+
+pub(crate) async fn exclusive() -> tokio::sync::MutexGuard<'static, ()> {
+    APPLY_LOCK.lock().await
+}
+
+/// Ask the guard to check now. Called from the native change sources.
+#[cfg_attr(target_os = "linux", allow(dead_code))]
+pub(crate) fn nudge() {
+    NUDGE.notify_one();
+}
+
+/// How `live` diverges from what OneBox applied, `None` if it doesn't.
+pub(crate) fn drift(expected: &Expected, live: &ProxySnapshot) -> Option<String> {
+    let foreign_static = live.enable && !points_at_onebox(&live.host, live.port, expected.port());
+    match expected {
+        Expected::Static { port } => {
+            if live.pac_enable && !super::pac::is_onebox_url(&live.pac_url) {
+                Some(format!(
+                    "an auto-config URL was turned on: {}",
+                    live.pac_url
+                ))
+            } else if !live.enable {
+                Some("the system proxy was turned off".into())
+            } else if foreign_static {
+                Some(format!(
+                    "the system proxy points at {}:{} instead of 127.0.0.1:{}",
+                    live.host, live.port, port
+                ))
+            } else {
+                None
+            }
+        }
+        Expected::Pac { url, .. } => {
+            if !live.pac_enable {
+                Some("the auto-config URL was turned off".into())
+            } else if live.pac_url != *url {
+                Some(format!(
+                    "the auto-config URL was changed to {}",
+                    live.pac_url
+                ))
+            } else if foreign_static {
+                Some(format!(
+                    "a static proxy {}:{} was turned on",
+                    live.host, live.port
+                ))
+            } else {
+                None
+            }
+        }
+    }
+}
+
+impl Expected {
+    fn port(&self) -> u16 {
+        match self {
+            Expected::Static { port } | Expected::Pac { port, .. } => *port,
+        }
+    }
+}
+
+/// Timestamps of recent re-applies, for the per-minute cap.
+#[derive(Default)]
+struct ReapplyBudget {
+    recent: VecDeque<Instant>,
+}
+
+impl ReapplyBudget {
+    fn take(&mut self, now: Instant, max: usize) -> bool {
+        while self
+            .recent
+            .front()
+            .is_some_and(|at| now.duration_since(*at) >= REAPPLY_WINDOW)
+        {
+            self.recent.pop_front();
+        }
+        if self.recent.len() >= max {
+            return false;
+        }
+        self.recent.push_back(now);
+        true
+    }
+}
+
+/// What `check` did about a drift; the `action` of `system-proxy-drift`.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum DriftOutcome {
+    Reapplied,
+    ReapplyFailed,
+    Warned,
+    /// Out of re-applies for this minute.
+    GaveUp,
+}
+
+#[derive(Serialize, Clone, Debug)]
+struct ProxyDrift {
+    reason: String,
+    action: DriftOutcome,
+}
+
+#[cfg(target_os = "linux")]
+async fn read_live(app: &AppHandle) -> anyhow::Result<Option<ProxySnapshot>> {
+    use crate::engine::linux::desktop_proxy;
+    let settings = desktop_proxy::load_settings(app);
+    tauri::async_runtime::spawn_blocking(move || desktop_proxy::read_live(&settings))
+        .await
+        .map_err(|e| anyhow::anyhow!("{}", e))?
+}
+
+#[cfg(not(target_os = "linux"))]
+async fn read_live(_app: &AppHandle) -> anyhow::Result<Option<ProxySnapshot>> {
+    tauri::async_runtime::spawn_blocking(|| super::proxy_snapshot::read_live().map(Some))
+        .await
+        .map_err(|e| anyhow::anyhow!("{}", e))?
+}
+
+/// Re-apply unless the proxy was cleared or re-applied since `generation`.
+async fn reapply(app: &AppHandle, generation: u64) -> Option<Result<(), String>> {
+    let _exclusive = exclusive().await;
+    if !is_current(generation) {
+        return None;
+    }
+    Some(
+        super::sysproxy::set_system_proxy(app)
+            .await
+            .map_err(|e| e.to_string()),
+    )
+}
+
+async fn wait(interval: Duration) {
+    tokio::select! {
+        _ = tokio::time::sleep(interval) => {}
+        _ = NUDGE.notified() => tokio::time::sleep(NUDGE_DEBOUNCE).await,
+    }
+}
+
+pub fn spawn_proxy_guard(app: AppHandle) {
+    #[cfg(target_os = "macos")]
+    crate::engine::macos::proxy_watcher::ensure_started();
+    #[cfg(target_os = "windows")]
+    crate::engine::windows::proxy_watcher::ensure_started();
+
+    tauri::async_runtime::spawn(async move {
+        let mut budget = ReapplyBudget::default();
+        // Last drift reported without fixing it; repeated only when it changes.
+        let mut reported: Option<String> = None;
+        loop {
+            let settings = load_settings(&app);
+            let running = app.state::<EngineStateCell>().snapshot().is_running();
+            let applied = current().filter(|_| settings.enabled && running);
+            let Some(applied) = applied else {
+                reported = None;
+                wait(IDLE_POLL_INTERVAL).await;
+                continue;
+            };
+            if applied.at.elapsed() >= SETTLE_TIME {
+                check(&app, &settings, &applied, &mut budget, &mut reported).await;
+            }
+            wait(Duration::from_secs(settings.interval_secs.max(1))).await;
+        }
+    });
+}
+
+async fn check(
+    app: &AppHandle,
+    settings: &ProxyGuardSettings,
+    applied: &Applied,
+    budget: &mut ReapplyBudget,
+    reported: &mut Option<String>,
+) {
+    let live = match read_live(app).await {
+        Ok(Some(live)) => live,
+        Ok(None) => return,
+        Err(e) => {
+            log::debug!("[proxy-guard] could not read the system proxy: {}", e);
+            return;
+        }
+    };
+    let Some(reason) = drift(&applied.expected, &live) else {
+        *reported = None;
+        return;
+    };
+    // Applied or cleared while reading: the reading is about the old state.
+    if !is_current(applied.generation) {
+        return;
+    }
+
+    let action = match settings.action {
+        DriftAction::Warn => DriftOutcome::Warned,
+        DriftAction::Reapply if !budget.take(Instant::now(), settings.max_reapplies) => {
+            DriftOutcome::GaveUp
+        }
+        DriftAction::Reapply => match reapply(app, applied.generation).await {
+            None => return,
+            Some(Ok(())) => DriftOutcome::Reapplied,
+            Some(Err(e)) => {
+                log::warn!("[proxy-guard] re-apply failed: {}", e);
+                DriftOutcome::ReapplyFailed
+            }
+        },
+    };
+    let fixed = action == DriftOutcome::Reapplied;
+    if !fixed && reported.as_deref() == Some(reason.as_str()) {
+        return;
+    }
+    *reported = (!fixed).then(|| reason.clone());
+
+    let msg = match action {
+        DriftOutcome::Reapplied => {
+            format!("System proxy changed externally ({}); re-applied", reason)
+        }
+        DriftOutcome::GaveUp => format!(
+            "System proxy changed externally ({}); not re-applying again within a minute",
+            reason
+        ),
+        DriftOutcome::Warned | DriftOutcome::ReapplyFailed => {
+            format!("System proxy changed externally: {}", reason)
+        }
+    };
+    log::warn!("[proxy-guard] {}", msg);
+    let _ = app.emit(EVENT_TAURI_LOG, (1, msg));
+    let _ = app.emit(EVENT_PROXY_DRIFT, ProxyDrift { reason, action });
+}
+
+#[cfg(test)]
+mod proxy_guard_tests {
+    use super::*;
+
+    fn live(enable: bool, host: &str, port: u16, pac_enable: bool, pac_url: &str) -> ProxySnapshot {
+        ProxySnapshot {
+            enable,
+            host: host.into(),
+            port,
+            pac_enable,
+            pac_url: pac_url.into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn static_proxy_drift_is_detected() {
+        let expected = Expected::Static { port: 7890 };
+        assert_eq!(
+            drift(&expected, &live(true, "127.0.0.1", 7890, false, "")),
+            None
+        );
+        assert_eq!(
+            drift(&expected, &live(false, "127.0.0.1", 7890, false, "")).as_deref(),
+            Some("the system proxy was turned off")
+        );
+        assert!(drift(&expected, &live(true, "proxy.corp", 3128, false, ""))
+            .is_some_and(|r| r.contains("proxy.corp:3128")));
+        assert!(drift(
+            &expected,
+            &live(true, "127.0.0.1", 7890, true, "http://wpad.corp/wpad.dat")
+        )
+        .is_some_and(|r| r.contains("wpad.corp")));
+    }
+
+    #[test]
+    fn pac_drift_is_detected() {
+        let url = "http://127.0.0.1:51234/proxy.pac?v=1";
+        let expected = Expected::Pac {
+            url: url.into(),
+            port: 7890,
+        };
+        assert_eq!(drift(&expected, &live(false, "", 0, true, url)), None);
+        assert!(drift(&expected, &live(false, "", 0, false, url)).is_some());
+        assert!(drift(
+            &expected,
+            &live(false, "", 0, true, "http://wpad.corp/wpad.dat")
+        )
+        .is_some());
+        assert!(drift(&expected, &live(true, "proxy.corp", 3128, true, url)).is_some());
+    }
+
+    #[test]
+    fn reapplies_are_capped_per_minute() {
+        let mut budget = ReapplyBudget::default();
+        let start = Instant::now();
+        assert!(budget.take(start, 2));
+        assert!(budget.take(start + Duration::from_secs(10), 2));
+        assert!(!budget.take(start + Duration::from_secs(20), 2));
+        assert!(budget.take(start + Duration::from_secs(61), 2));
+        assert!(!ReapplyBudget::default().take(start, 0));
+    }
+
+    #[test]
+    fn drift_event_keeps_its_action_names() {
+        let event = ProxyDrift {
+            reason: "x".into(),
+            action: DriftOutcome::ReapplyFailed,
+        };
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            serde_json::json!({ "reason": "x", "action": "reapply_failed" })
+        );
+        assert_eq!(
+            serde_json::to_value(DriftOutcome::GaveUp).unwrap(),
+            "gave_up"
+        );
+    }
+}
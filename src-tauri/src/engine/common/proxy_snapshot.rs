@@ -0,0 +1,241 @@
+//! The user's own system proxy, captured before OneBox replaces it.
+//!
+//! Clearing used to flip `enable` off, which wiped a corporate proxy or PAC
+//! the user had before OneBox started. Now the first `set_system_proxy` of a
+//! session captures the full prior state — host, port, bypass, enable flag,
+//! PAC URL and its enable flag — into `system-proxy-restore.json` in the app
+//! data dir, and the clear path writes exactly that back. The file outlives
+//! a crash, so the next launch (`sysproxy::recover_stale_proxy`) can still
+//! put things back.
+//!
+//! macOS and Windows read and write through `onebox_sysproxy_rs`; Linux has
+//! its own capture in `engine::linux::desktop_proxy`, which covers more than
+//! one desktop and the command-line tools; there only the snapshot type and
+//! `app_data_dir` are used, and the restore-file helpers are compiled out.
+
+#[cfg(not(target_os = "linux"))]
+use std::path::Path;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+/// Mirrors `PathResolver::app_data_dir` like `cli.rs` does — the shutdown
+/// clear runs without an `AppHandle`.
+const APP_IDENTIFIER: &str = "cloud.oneoh.onebox";
+pub const RESTORE_FILE_NAME: &str = "system-proxy-restore.json";
+
+/// The app data dir, for code that has no `AppHandle` to ask.
+pub(crate) fn app_data_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join(APP_IDENTIFIER))
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ProxySnapshot {
+    pub enable: bool,
+    pub host: String,
+    pub port: u16,
+    pub bypass: String,
+    pub pac_enable: bool,
+    pub pac_url: String,
+    pub captured_at: i64,
+}
+
+/// Whether `host:port` is a OneBox proxy — loopback on the port OneBox
+/// listens on.
+pub fn points_at_onebox(host: &str, port: u16, onebox_port: u16) -> bool {
+    port == onebox_port && matches!(host, "127.0.0.1" | "localhost" | "::1" | "[::1]")
+}
+
+impl ProxySnapshot {
+    /// What to remember given the live settings. A proxy that already points
+    /// at OneBox is a leftover of a session that died without a snapshot,
+    /// not the user's — remember it as off.
+    pub fn from_live(
+        live: ProxySnapshot,
+        onebox_port: u16,
+        is_onebox_pac: impl Fn(&str) -> bool,
+    ) -> Self {
+        let mut snapshot = live;
+        if points_at_onebox(&snapshot.host, snapshot.port, onebox_port) {
+            snapshot.enable = false;
+        }
+        if is_onebox_pac(&snapshot.pac_url) {
+            snapshot.pac_enable = false;
+        }
+        snapshot
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn restore_path() -> Option<PathBuf> {
+    app_data_dir().map(|dir| dir.join(RESTORE_FILE_NAME))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn read(path: &Path) -> Option<ProxySnapshot> {
+    let text = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&text).ok()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn exists() -> bool {
+    restore_path().is_some_and(|path| path.exists())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn load() -> Option<ProxySnapshot> {
+    read(&restore_path()?)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn discard() {
+    if let Some(path) = restore_path() {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+/// Persist `snapshot` unless one is already on disk — the first capture of
+/// a session is the one that predates OneBox.
+pub(crate) fn save_once(snapshot: &ProxySnapshot) -> anyhow::Result<()> {
+    let path = restore_path().ok_or_else(|| anyhow::anyhow!("no data directory"))?;
+    if path.exists() {
+        return Ok(());
+    }
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let text = serde_json::to_string_pretty(snapshot)?;
+    super::config_switch::write_atomic(&path, text.as_bytes())
+        .map_err(|e| anyhow::anyhow!("failed to write {}: {}", path.display(), e))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) use platform::read_live;
+
+#[cfg(not(target_os = "linux"))]
+mod platform {
+    use super::ProxySnapshot;
+
+    /// Read the live static + PAC settings.
+    pub(crate) fn read_live() -> anyhow::Result<ProxySnapshot> {
+        let sys = onebox_sysproxy_rs::Sysproxy::get_system_proxy()
+            .map_err(|e| anyhow::anyhow!("Sysproxy::get_system_proxy failed: {}", e))?;
+        let auto = onebox_sysproxy_rs::Autoproxy::get_auto_proxy()
+            .map_err(|e| anyhow::anyhow!("Autoproxy::get_auto_proxy failed: {}", e))?;
+        Ok(ProxySnapshot {
+            enable: sys.enable,
+            host: sys.host,
+            port: sys.port,
+            bypass: sys.bypass,
+            pac_enable: auto.enable,
+            pac_url: auto.url,
+            captured_at: chrono::Local::now().timestamp(),
+        })
+    }
+
+    /// Write `snapshot` back. With `enable == false` the crate only turns the
+    /// proxy off, so host/port the user had disabled are left as they were.
+    pub(crate) fn write(snapshot: &ProxySnapshot) -> anyhow::Result<()> {
+        onebox_sysproxy_rs::Sysproxy {
+            enable: snapshot.enable,
+            host: snapshot.host.clone(),
+            port: snapshot.port,
+            bypass: snapshot.bypass.clone(),
+        }
+        .set_system_proxy()
+        .map_err(|e| anyhow::anyhow!("Sysproxy::set_system_proxy failed: {}", e))?;
+        onebox_sysproxy_rs::Autoproxy {
+            enable: snapshot.pac_enable,
+            url: snapshot.pac_url.clone(),
+        }
+        .set_auto_proxy()
+        .map_err(|e| anyhow::anyhow!("Autoproxy::set_auto_proxy failed: {}", e))
+    }
+}
+
+/// Capture the live settings unless a snapshot is already on disk. Failing
+/// to read them is logged, not fatal: the clear path then falls back to
+/// just turning OneBox's proxy off.
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn capture(onebox_port: u16) {
+    if exists() {
+        return;
+    }
+    let live = match platform::read_live() {
+        Ok(live) => live,
+        Err(e) => {
+            log::warn!("[sysproxy] could not capture the prior proxy: {}", e);
+            return;
+        }
+    };
+    let snapshot = ProxySnapshot::from_live(live, onebox_port, super::pac::is_onebox_url);
+    match save_once(&snapshot) {
+        Ok(()) => log::info!(
+            "[sysproxy] captured prior proxy: enable={} {}:{} pac_enable={} {:?}",
+            snapshot.enable,
+            snapshot.host,
+            snapshot.port,
+            snapshot.pac_enable,
+            snapshot.pac_url
+        ),
+        Err(e) => log::warn!("[sysproxy] could not save the prior proxy: {}", e),
+    }
+}
+
+/// Write the captured settings back and drop the file. `Ok(false)` when
+/// there was nothing captured.
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn restore() -> anyhow::Result<bool> {
+    let Some(snapshot) = load() else {
+        discard();
+        return Ok(false);
+    };
+    platform::write(&snapshot)?;
+    discard();
+    log::info!(
+        "[sysproxy] restored prior proxy: enable={} {}:{} pac_enable={}",
+        snapshot.enable,
+        snapshot.host,
+        snapshot.port,
+        snapshot.pac_enable
+    );
+    Ok(true)
+}
+
+#[cfg(test)]
+mod proxy_snapshot_tests {
+    use super::*;
+
+    fn live(host: &str, port: u16, pac_url: &str) -> ProxySnapshot {
+        ProxySnapshot {
+            enable: true,
+            host: host.into(),
+            port,
+            bypass: "localhost".into(),
+            pac_enable: true,
+            pac_url: pac_url.into(),
+            captured_at: 0,
+        }
+    }
+
+    #[test]
+    fn a_corporate_proxy_is_kept_verbatim() {
+        let corp = live("proxy.corp", 3128, "http://wpad.corp/wpad.dat");
+        assert_eq!(
+            ProxySnapshot::from_live(corp.clone(), 7890, |_| false),
+            corp
+        );
+    }
+
+    #[test]
+    fn a_leftover_onebox_proxy_is_remembered_as_off() {
+        let stale = live("127.0.0.1", 7890, "http://127.0.0.1:51234/proxy.pac?v=2");
+        let snapshot = ProxySnapshot::from_live(stale, 7890, |url| url.contains("/proxy.pac"));
+        assert!(!snapshot.enable);
+        assert!(!snapshot.pac_enable);
+        assert_eq!(snapshot.host, "127.0.0.1");
+        // Same loopback, different port: someone else's local proxy.
+        assert!(!points_at_onebox("127.0.0.1", 8080, 7890));
+    }
+}
@@ -1,12 +1,13 @@
 //! Readiness prober — elevates `Starting → Running` from "spawn returned"
 //! to "sing-box is actually serving traffic".
 //!
-//! The prober is spawned as a tokio task at the tail of the Start path. It
-//! polls a platform-independent liveness predicate every 200 ms until one of
-//! three terminal conditions:
+//! The prober is spawned as a tokio task at the tail of the Start path. Every
+//! 200 ms it runs a probe pipeline until one of three terminal conditions:
 //!
-//!   1. predicate satisfied → `transition(MarkRunning)`
-//!   2. 20 s elapsed        → `transition(Fail { reason: "startup timeout" })`
+//!   1. every probe passes → `transition(MarkRunning)`
+//!   2. startup timeout    → `transition(Fail { reason })`, where `reason`
+//!      names the probe that was still failing (or another attempt, when
+//!      this start was a crash-recovery attempt)
 //!   3. state changed away from `Starting` (user stop, crash, etc.) → exit
 //!
 //! Generation guard: the task captures the epoch at spawn. Before any
@@ -15,64 +16,412 @@
 //! superseded and exits silently. This prevents a stale prober from
 //! clobbering a restarted session.
 //!
-//! Liveness predicate: TCP connect to `127.0.0.1:9191` (sing-box clash API
-//! port). Works uniformly across platforms and both TUN and mixed modes;
-//! on TUN mode the clash API only comes up after the TUN device is
-//! initialised and DNS override has already been applied synchronously on
-//! the Start path, so a successful connect implies routable readiness.
+//! Pipeline, in order, stopping at the first failure:
+//!
+//!   - `clash-api` — `GET /version` on the Clash API with the stored
+//!     `clash_secret`. Proves sing-box finished loading the config.
+//!   - `http-head` — `HEAD <http_probe_url>` through the mixed inbound.
+//!     Proves outbounds actually carry traffic. Opt-in: a blocked or slow
+//!     probe URL would fail a perfectly good start, so it only runs when
+//!     `http_probe_url` is set and the config has a mixed inbound.
+//!   - `dns` — A query to the TUN gateway (TUN mode only). Proves
+//!     `hijack-dns` answers, i.e. the DNS override points somewhere live.
+//!
+//! `verify_reload` reuses the pipeline after a config reload (minus the
+//! HTTP probe — an upstream outage must not look like a broken config) so
+//! `reload_config` can revert to the last-known-good config when sing-box
+//! does not come back.
+//!
+//! Timeouts are tunable via `settings.json` → `engine_readiness`
+//! (see `ReadinessSettings`).
 
-use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::time::Duration;
 
+use serde::Deserialize;
 use tauri::{AppHandle, Manager};
-use tokio::net::TcpStream;
+use tauri_plugin_http::reqwest;
+use tauri_plugin_store::StoreExt;
 use tokio::time::{sleep, timeout, Instant};
 
 use super::state_machine::{transition, EngineState, EngineStateCell, Intent};
 
 const POLL_INTERVAL: Duration = Duration::from_millis(200);
-const STARTUP_TIMEOUT: Duration = Duration::from_secs(20);
-const PROBE_CONNECT_TIMEOUT: Duration = Duration::from_millis(150);
+const SETTINGS_STORE: &str = "settings.json";
+pub(crate) const READINESS_SETTINGS_KEY: &str = "engine_readiness";
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct ReadinessSettings {
+    /// Overall budget for the pipeline to pass once.
+    pub startup_timeout_secs: u64,
+    /// Budget for sing-box to serve again after a reload before it is
+    /// reverted to the last-known-good config.
+    pub reload_timeout_secs: u64,
+    pub clash_api_timeout_ms: u64,
+    /// Unset (the default), `null` or `""` disables the HTTP probe.
+    pub http_probe_url: Option<String>,
+    pub http_probe_timeout_ms: u64,
+    pub dns_probe_domain: String,
+    pub dns_probe_timeout_ms: u64,
+}
+
+impl Default for ReadinessSettings {
+    fn default() -> Self {
+        Self {
+            startup_timeout_secs: 20,
+            reload_timeout_secs: 10,
+            clash_api_timeout_ms: 500,
+            http_probe_url: None,
+            http_probe_timeout_ms: 5000,
+            dns_probe_domain: "www.google.com".into(),
+            dns_probe_timeout_ms: 2000,
+        }
+    }
+}
+
+pub(crate) fn load_settings(app: &AppHandle) -> ReadinessSettings {
+    app.get_store(SETTINGS_STORE)
+        .and_then(|store| store.get(READINESS_SETTINGS_KEY))
+        .and_then(|value| serde_json::from_value(value).ok())
+        .unwrap_or_default()
+}
+
+#[derive(Debug, PartialEq)]
+enum Probe {
+    ClashApi {
+        host: String,
+        port: u16,
+        secret: Option<String>,
+        timeout: Duration,
+    },
+    HttpHead {
+        proxy_port: u16,
+        url: String,
+        timeout: Duration,
+    },
+    Dns {
+        server: String,
+        domain: String,
+        timeout: Duration,
+    },
+}
+
+impl Probe {
+    fn name(&self) -> &'static str {
+        match self {
+            Probe::ClashApi { .. } => "clash-api",
+            Probe::HttpHead { .. } => "http-head",
+            Probe::Dns { .. } => "dns",
+        }
+    }
+
+    async fn run(&self) -> Result<(), String> {
+        match self {
+            Probe::ClashApi {
+                host,
+                port,
+                secret,
+                timeout,
+            } => crate::core::clash::ClashClient::with_base_url(
+                format!("http://{}:{}", host, port),
+                secret.clone(),
+            )
+            .timeout(*timeout)
+            .version()
+            .await
+            .map(|_| ()),
+            Probe::HttpHead {
+                proxy_port,
+                url,
+                timeout,
+            } => {
+                let proxy = reqwest::Proxy::all(format!("http://127.0.0.1:{}", proxy_port))
+                    .map_err(|e| e.to_string())?;
+                let client = reqwest::Client::builder()
+                    .proxy(proxy)
+                    .timeout(*timeout)
+                    .build()
+                    .map_err(|e| e.to_string())?;
+                let res = client.head(url).send().await.map_err(|e| e.to_string())?;
+                // Any answer from the far side proves the outbound works; 5xx
+                // is what sing-box itself returns when it cannot dial.
+                if res.status().is_server_error() {
+                    return Err(format!("HTTP {}", res.status().as_u16()));
+                }
+                Ok(())
+            }
+            Probe::Dns {
+                server,
+                domain,
+                timeout: limit,
+            } => {
+                match timeout(
+                    *limit,
+                    crate::commands::dns::resolve_a_record(domain, server),
+                )
+                .await
+                {
+                    Ok(Some(_)) => Ok(()),
+                    Ok(None) => Err(format!("no A record for {} from {}", domain, server)),
+                    Err(_) => Err(format!("timed out after {:?}", limit)),
+                }
+            }
+        }
+    }
+}
+
+fn clash_probe(
+    settings: &ReadinessSettings,
+    config: Option<&serde_json::Value>,
+    secret: Option<String>,
+) -> Probe {
+    let (host, port) = config
+        .and_then(crate::core::clash_api_addr_from_config)
+        .unwrap_or_else(|| ("127.0.0.1".into(), crate::core::DEFAULT_CLASH_API_PORT));
+    Probe::ClashApi {
+        host,
+        port,
+        secret,
+        timeout: Duration::from_millis(settings.clash_api_timeout_ms),
+    }
+}
+
+/// Build the pipeline for one start. `config` is the parsed sing-box config
+/// the engine was started with (if readable), `tun_gateway` its TUN gateway.
+fn build_plan(
+    settings: &ReadinessSettings,
+    config: Option<&serde_json::Value>,
+    tun_gateway: Option<String>,
+    secret: Option<String>,
+) -> Vec<Probe> {
+    let mut plan = vec![clash_probe(settings, config, secret)];
+    let url = settings
+        .http_probe_url
+        .as_deref()
+        .filter(|u| !u.trim().is_empty());
+    if let (Some(url), Some(port)) = (url, config.and_then(crate::core::mixed_port_from_config)) {
+        plan.push(Probe::HttpHead {
+            proxy_port: port,
+            url: url.to_string(),
+            timeout: Duration::from_millis(settings.http_probe_timeout_ms),
+        });
+    }
+    if let Some(server) = tun_gateway {
+        plan.push(Probe::Dns {
+            server,
+            domain: settings.dns_probe_domain.clone(),
+            timeout: Duration::from_millis(settings.dns_probe_timeout_ms),
+        });
+    }
+    plan
+}
+
+/// Run the pipeline; `Err((probe name, detail))` for the first failure.
+async fn run_plan(plan: &[Probe]) -> Result<(), (&'static str, String)> {
+    for probe in plan {
+        probe.run().await.map_err(|e| (probe.name(), e))?;
+    }
+    Ok(())
+}
+
+fn timeout_reason(last_failure: Option<&(&'static str, String)>) -> String {
+    match last_failure {
+        Some((name, detail)) => format!("startup timeout: {} probe failed ({})", name, detail),
+        None => "startup timeout".into(),
+    }
+}
+
+fn superseded(app: &AppHandle, start_epoch: u64) -> bool {
+    let snap = app.state::<EngineStateCell>().snapshot();
+    if !matches!(snap, EngineState::Starting { .. }) || snap.epoch() != start_epoch {
+        log::debug!(
+            "[readiness] superseded (kind={}, epoch={}, captured={}), exiting",
+            snap.kind(),
+            snap.epoch(),
+            start_epoch
+        );
+        return true;
+    }
+    false
+}
 
-/// Clash API liveness target: `127.0.0.1:<clash api port>`. Shares the port
-/// constant with the start-time guard (`crate::core::CLASH_API_PORT`) so the
-/// prober and the guard never drift apart.
-fn probe_addr() -> SocketAddr {
-    SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), crate::core::CLASH_API_PORT)
+fn read_config(config_path: Option<&str>) -> Option<serde_json::Value> {
+    config_path
+        .and_then(|p| std::fs::read_to_string(p).ok())
+        .and_then(|text| serde_json::from_str::<serde_json::Value>(&text).ok())
+}
+
+/// Wait for sing-box to serve again after a reload of `config_path`.
+/// `Err` carries the probe that was still failing when the deadline hit.
+pub async fn verify_reload(app: &AppHandle, config_path: &str, is_tun: bool) -> Result<(), String> {
+    verify_reload_with(
+        load_settings(app),
+        config_path,
+        is_tun,
+        crate::core::clash::clash_secret(app),
+    )
+    .await
+}
+
+/// `verify_reload` with settings and Clash secret already read, for the
+/// headless CLI.
+pub(crate) async fn verify_reload_with(
+    mut settings: ReadinessSettings,
+    config_path: &str,
+    is_tun: bool,
+    secret: Option<String>,
+) -> Result<(), String> {
+    settings.http_probe_url = None;
+    let config = read_config(Some(config_path));
+    let tun_gateway = if is_tun {
+        super::helper::extract_tun_gateway_from_config(config_path)
+    } else {
+        None
+    };
+    let plan = build_plan(&settings, config.as_ref(), tun_gateway, secret);
+    // The Clash API alone can answer from the listener that survives the
+    // reload; the mixed inbound is torn down and rebuilt, so require it too.
+    let mixed_port = config
+        .as_ref()
+        .and_then(crate::core::mixed_port_from_config);
+
+    let reload_timeout = Duration::from_secs(settings.reload_timeout_secs);
+    let deadline = Instant::now() + reload_timeout;
+    // sing-box needs a moment to close the old inbounds after SIGHUP;
+    // probing immediately could still hit them.
+    sleep(Duration::from_millis(500)).await;
+    loop {
+        let outcome = match mixed_port {
+            Some(port) if !crate::core::probe_port_listening(port) => {
+                Err(("mixed-port", format!(":{} not listening", port)))
+            }
+            _ => run_plan(&plan).await,
+        };
+        match outcome {
+            Ok(()) => return Ok(()),
+            Err((name, detail)) if Instant::now() >= deadline => {
+                return Err(format!(
+                    "reload timeout: {} probe failed ({}) after {:?}",
+                    name, detail, reload_timeout
+                ));
+            }
+            Err(_) => {}
+        }
+        sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// One Clash API probe against the controller `config_path` names. Tells
+/// whether a sing-box left behind by a previous session still serves.
+pub async fn probe_clash_api(app: &AppHandle, config_path: &str) -> Result<(), String> {
+    let settings = load_settings(app);
+    let config = read_config(Some(config_path));
+    clash_probe(
+        &settings,
+        config.as_ref(),
+        crate::core::clash::clash_secret(app),
+    )
+    .run()
+    .await
+}
+
+/// The start pipeline without a state machine to report to: probe until it
+/// passes or `startup_timeout_secs` runs out. The headless CLI's prober.
+pub(crate) async fn wait_until_ready(
+    settings: &ReadinessSettings,
+    config_path: &str,
+    is_tun: bool,
+    secret: Option<String>,
+) -> Result<(), String> {
+    let config = read_config(Some(config_path));
+    let tun_gateway = if is_tun {
+        super::helper::extract_tun_gateway_from_config(config_path)
+    } else {
+        None
+    };
+    let plan = build_plan(settings, config.as_ref(), tun_gateway, secret);
+    let deadline = Instant::now() + Duration::from_secs(settings.startup_timeout_secs);
+    loop {
+        match run_plan(&plan).await {
+            Ok(()) => return Ok(()),
+            Err(failure) if Instant::now() >= deadline => {
+                return Err(timeout_reason(Some(&failure)));
+            }
+            Err(_) => {}
+        }
+        sleep(POLL_INTERVAL).await;
+    }
 }
 
 /// Spawn a readiness prober. `start_epoch` must be the epoch observed right
 /// after the `Starting` transition completes.
 pub fn spawn(app: AppHandle, start_epoch: u64) {
     tokio::spawn(async move {
-        let deadline = Instant::now() + STARTUP_TIMEOUT;
+        let settings = load_settings(&app);
+        let is_tun = app.state::<EngineStateCell>().snapshot().mode() == Some("tun");
+        let config_path = crate::core::ProcessManager::acquire()
+            .config_path
+            .as_ref()
+            .map(|p| (**p).clone());
+        let config = read_config(config_path.as_deref());
+        let tun_gateway = if is_tun {
+            config_path
+                .as_deref()
+                .and_then(super::helper::extract_tun_gateway_from_config)
+        } else {
+            None
+        };
+        let plan = build_plan(
+            &settings,
+            config.as_ref(),
+            tun_gateway,
+            crate::core::clash::clash_secret(&app),
+        );
+        log::info!(
+            "[readiness] probes={:?} timeout={}s",
+            plan.iter().map(Probe::name).collect::<Vec<_>>(),
+            settings.startup_timeout_secs
+        );
+
+        let startup_timeout = Duration::from_secs(settings.startup_timeout_secs);
+        let deadline = Instant::now() + startup_timeout;
+        let mut last_failure;
         loop {
             // Superseded check (generation guard).
-            let snap = app.state::<EngineStateCell>().snapshot();
-            if !matches!(snap, EngineState::Starting { .. }) || snap.epoch() != start_epoch {
-                log::debug!(
-                    "[readiness] superseded (kind={}, epoch={}, captured={}), exiting",
-                    snap.kind(),
-                    snap.epoch(),
-                    start_epoch
-                );
+            if superseded(&app, start_epoch) {
                 return;
             }
 
-            if probe_once().await {
-                log::info!("[readiness] probe succeeded, transitioning to Running");
-                let _ = transition(&app, Intent::MarkRunning);
+            let outcome = run_plan(&plan).await;
+            // Probes can take seconds; re-check before acting on the result.
+            if superseded(&app, start_epoch) {
                 return;
             }
+            match outcome {
+                Ok(()) => {
+                    log::info!("[readiness] all probes passed, transitioning to Running");
+                    if transition(&app, Intent::MarkRunning).is_ok() {
+                        super::recovery::note_running();
+                        if let Some(path) = config_path.as_deref() {
+                            if let Err(e) = super::config_switch::commit(path) {
+                                log::warn!(
+                                    "[readiness] failed to record last-known-good config: {}",
+                                    e
+                                );
+                            }
+                        }
+                    }
+                    return;
+                }
+                Err(failure) => last_failure = Some(failure),
+            }
 
             if Instant::now() >= deadline {
-                log::warn!("[readiness] startup timeout after {:?}", STARTUP_TIMEOUT);
-                let _ = transition(
-                    &app,
-                    Intent::Fail {
-                        reason: "startup timeout".into(),
-                    },
-                );
+                let reason = timeout_reason(last_failure.as_ref());
+                log::warn!("[readiness] {} after {:?}", reason, startup_timeout);
+                if let Some(reason) = super::recovery::handle_failure(&app, reason, None, None) {
+                    let _ = transition(&app, Intent::Fail { reason });
+                }
                 return;
             }
 
@@ -81,9 +430,107 @@ pub fn spawn(app: AppHandle, start_epoch: u64) {
     });
 }
 
-async fn probe_once() -> bool {
-    matches!(
-        timeout(PROBE_CONNECT_TIMEOUT, TcpStream::connect(probe_addr())).await,
-        Ok(Ok(_))
-    )
+#[cfg(test)]
+mod readiness_tests {
+    use super::*;
+
+    fn config_with_mixed(port: u16) -> serde_json::Value {
+        serde_json::json!({
+            "inbounds": [{ "type": "mixed", "tag": "mixed", "listen_port": port }]
+        })
+    }
+
+    #[test]
+    fn plan_is_clash_only_without_config() {
+        let plan = build_plan(&ReadinessSettings::default(), None, None, None);
+        let names: Vec<_> = plan.iter().map(Probe::name).collect();
+        assert_eq!(names, vec!["clash-api"]);
+    }
+
+    #[test]
+    fn clash_probe_targets_the_configured_controller() {
+        let mut config = config_with_mixed(7890);
+        config["experimental"] =
+            serde_json::json!({ "clash_api": { "external_controller": "127.0.0.1:19090" } });
+        let plan = build_plan(&ReadinessSettings::default(), Some(&config), None, None);
+        assert!(matches!(&plan[0], Probe::ClashApi { port: 19090, .. }));
+    }
+
+    #[test]
+    fn plan_includes_http_and_dns_when_available() {
+        let config = config_with_mixed(7890);
+        let settings = ReadinessSettings {
+            http_probe_url: Some("https://www.google.com/generate_204".into()),
+            ..Default::default()
+        };
+        let plan = build_plan(
+            &settings,
+            Some(&config),
+            Some("172.19.0.1".into()),
+            Some("s3cret".into()),
+        );
+        let names: Vec<_> = plan.iter().map(Probe::name).collect();
+        assert_eq!(names, vec!["clash-api", "http-head", "dns"]);
+        assert!(matches!(
+            &plan[0],
+            Probe::ClashApi { secret: Some(s), .. } if s == "s3cret"
+        ));
+        assert!(matches!(&plan[0], Probe::ClashApi { port: 9191, .. }));
+        assert!(matches!(
+            &plan[1],
+            Probe::HttpHead {
+                proxy_port: 7890,
+                ..
+            }
+        ));
+        assert!(matches!(
+            &plan[2],
+            Probe::Dns { server, .. } if server == "172.19.0.1"
+        ));
+    }
+
+    #[test]
+    fn http_probe_is_off_unless_configured() {
+        let config = config_with_mixed(7890);
+        let plan = build_plan(&ReadinessSettings::default(), Some(&config), None, None);
+        assert_eq!(plan.len(), 1);
+        for url in [serde_json::Value::Null, serde_json::json!("")] {
+            let settings: ReadinessSettings =
+                serde_json::from_value(serde_json::json!({ "http_probe_url": url })).unwrap();
+            let plan = build_plan(&settings, Some(&config), None, None);
+            assert_eq!(plan.len(), 1);
+        }
+    }
+
+    #[test]
+    fn settings_fields_default_individually() {
+        let settings: ReadinessSettings =
+            serde_json::from_value(serde_json::json!({ "startup_timeout_secs": 45 })).unwrap();
+        assert_eq!(settings.startup_timeout_secs, 45);
+        assert_eq!(settings.clash_api_timeout_ms, 500);
+        assert!(settings.http_probe_url.is_none());
+    }
+
+    #[test]
+    fn timeout_reason_names_failing_probe() {
+        assert_eq!(timeout_reason(None), "startup timeout");
+        assert_eq!(
+            timeout_reason(Some(&("http-head", "HTTP 502".into()))),
+            "startup timeout: http-head probe failed (HTTP 502)"
+        );
+    }
+
+    #[tokio::test]
+    async fn clash_probe_fails_fast_on_closed_port() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+        let probe = Probe::ClashApi {
+            host: "127.0.0.1".into(),
+            port,
+            secret: None,
+            timeout: Duration::from_millis(300),
+        };
+        assert!(probe.run().await.is_err());
+    }
 }
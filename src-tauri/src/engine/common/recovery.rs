@@ -0,0 +1,363 @@
+//! Crash recovery — restarts sing-box after it dies unexpectedly.
+//!
+//! Entry point is `handle_failure`, called from the three places that
+//! would otherwise `transition(Fail)`:
+//!
+//!   - `core::monitor`  — sing-box exited non-zero (crash while running, or
+//!     while a recovery attempt was starting)
+//!   - `readiness`      — a recovery attempt never became ready
+//!   - `core::start`    — `PlatformEngine::start` failed during an attempt
+//!
+//! If the policy allows another attempt the state goes to
+//! `Recovering { attempt, next_at }` and a task re-runs start
+//! (`core::restart_after_crash`) with the mode/config captured from
+//! `ProcessManager` at crash time — on the live or last-known-good config,
+//! never a newly staged one. Otherwise the caller falls through to `Failed`
+//! with a "gave up" reason.
+//!
+//! A failing *user* start (bad config, port conflict) never enters recovery:
+//! only crashes of an engine that reached Running, and the follow-up
+//! attempts of such an incident, are retried.
+//!
+//! Policy lives in `settings.json` under `engine_recovery_policy` (all
+//! fields optional, see `RecoveryPolicy`). `core::stop` cancels any pending
+//! attempt and resets the counters.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_store::StoreExt;
+
+use super::state_machine::{
+    now_secs, transition, with_trigger, EngineState, EngineStateCell, Intent, Trigger,
+};
+use crate::core::ProxyMode;
+
+const SETTINGS_STORE: &str = "settings.json";
+const RECOVERY_POLICY_KEY: &str = "engine_recovery_policy";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct RecoveryPolicy {
+    pub enabled: bool,
+    /// Consecutive restart attempts per incident before giving up.
+    pub max_attempts: u32,
+    /// Delay before the first attempt.
+    pub initial_backoff_secs: u64,
+    /// Each further attempt waits `multiplier` times longer…
+    pub backoff_multiplier: f64,
+    /// …capped here.
+    pub max_backoff_secs: u64,
+    /// A run that stayed up this long counts as healthy: the next crash
+    /// starts a fresh incident with the attempt counter back at zero.
+    pub cooldown_secs: u64,
+    /// Give up outright once this many crashes land inside
+    /// `give_up_window_secs`, even if each incident recovered — a
+    /// crash-loop with long uptimes in between still needs a human.
+    pub give_up_crashes: u32,
+    pub give_up_window_secs: u64,
+}
+
+impl Default for RecoveryPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_attempts: 5,
+            initial_backoff_secs: 2,
+            backoff_multiplier: 2.0,
+            max_backoff_secs: 60,
+            cooldown_secs: 120,
+            give_up_crashes: 10,
+            give_up_window_secs: 3600,
+        }
+    }
+}
+
+impl RecoveryPolicy {
+    /// Delay before attempt `attempt` (1-based).
+    fn backoff(&self, attempt: u32) -> Duration {
+        let exp = attempt.saturating_sub(1).min(32) as i32;
+        let secs = self.initial_backoff_secs as f64 * self.backoff_multiplier.max(1.0).powi(exp);
+        Duration::from_secs_f64(secs.min(self.max_backoff_secs as f64).max(0.0))
+    }
+}
+
+fn load_policy(app: &AppHandle) -> RecoveryPolicy {
+    app.get_store(SETTINGS_STORE)
+        .and_then(|store| store.get(RECOVERY_POLICY_KEY))
+        .and_then(|value| serde_json::from_value(value).ok())
+        .unwrap_or_default()
+}
+
+#[derive(Default)]
+struct Tracker {
+    /// Attempts made in the current incident.
+    attempt: u32,
+    /// Crash timestamps inside the give-up window.
+    crashes: VecDeque<i64>,
+    /// A recovery attempt is in flight (between `core::start` and Running).
+    in_attempt: bool,
+    /// Mode and config to restart with, captured at the original crash.
+    target: Option<(ProxyMode, String)>,
+}
+
+static TRACKER: Mutex<Option<Tracker>> = Mutex::new(None);
+
+#[derive(Debug, PartialEq)]
+enum Decision {
+    Retry { attempt: u32, delay: Duration },
+    GiveUp(String),
+}
+
+/// Record a crash at `now` and decide what to do. `running_since` is the
+/// `since` of the Running state that just ended, if the crash came from
+/// Running.
+fn decide(
+    policy: &RecoveryPolicy,
+    tracker: &mut Tracker,
+    now: i64,
+    running_since: Option<i64>,
+) -> Decision {
+    if running_since.is_some_and(|since| now - since >= policy.cooldown_secs as i64) {
+        tracker.attempt = 0;
+    }
+    tracker.crashes.push_back(now);
+    let window_start = now - policy.give_up_window_secs as i64;
+    while tracker.crashes.front().is_some_and(|&t| t < window_start) {
+        tracker.crashes.pop_front();
+    }
+
+    if !policy.enabled {
+        return Decision::GiveUp("automatic recovery disabled".into());
+    }
+    if policy.give_up_crashes > 0 && tracker.crashes.len() >= policy.give_up_crashes as usize {
+        return Decision::GiveUp(format!(
+            "{} crashes within {}s",
+            tracker.crashes.len(),
+            policy.give_up_window_secs
+        ));
+    }
+    tracker.attempt += 1;
+    if tracker.attempt > policy.max_attempts {
+        return Decision::GiveUp(format!(
+            "gave up after {} restart attempts",
+            policy.max_attempts
+        ));
+    }
+    Decision::Retry {
+        attempt: tracker.attempt,
+        delay: policy.backoff(tracker.attempt),
+    }
+}
+
+/// Try to hand an unexpected failure over to recovery.
+///
+/// Returns `None` when recovery took over (state is now `Recovering`), or
+/// `Some(reason)` that the caller should `Fail` with.
+pub fn handle_failure(
+    app: &AppHandle,
+    reason: String,
+    running_since: Option<i64>,
+    target: Option<(ProxyMode, String)>,
+) -> Option<String> {
+    let mut guard = TRACKER.lock().unwrap_or_else(|e| e.into_inner());
+    let tracker = guard.get_or_insert_with(Tracker::default);
+
+    // A user start that never reached Running is not ours to retry.
+    if running_since.is_none() && !tracker.in_attempt {
+        return Some(reason);
+    }
+    if target.is_some() {
+        tracker.target = target;
+    }
+    let Some((mode, path)) = tracker.target.clone() else {
+        log::warn!("[recovery] no mode/config to restart with, not recovering");
+        return Some(reason);
+    };
+
+    let policy = load_policy(app);
+    let now = now_secs();
+    match decide(&policy, tracker, now, running_since) {
+        Decision::GiveUp(why) => {
+            log::warn!("[recovery] giving up: {} (last failure: {})", why, reason);
+            *guard = None;
+            Some(format!("{} ({})", reason, why))
+        }
+        Decision::Retry { attempt, delay } => {
+            let next_at = now + delay.as_secs() as i64;
+            let state = match transition(
+                app,
+                Intent::Recover {
+                    attempt,
+                    next_at,
+                    reason: reason.clone(),
+                },
+            ) {
+                Ok(state) => state,
+                Err(_) => return Some(reason),
+            };
+            tracker.in_attempt = true;
+            log::info!(
+                "[recovery] attempt {}/{} in {:?} (mode={:?}) after: {}",
+                attempt,
+                policy.max_attempts,
+                delay,
+                mode,
+                reason
+            );
+            spawn_attempt(app.clone(), state.epoch(), delay, mode, path);
+            None
+        }
+    }
+}
+
+/// Called when the engine reaches Running: the in-flight attempt (if any)
+/// succeeded. The attempt counter survives until `cooldown_secs` of uptime.
+pub fn note_running() {
+    if let Some(tracker) = TRACKER.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+        tracker.in_attempt = false;
+    }
+}
+
+/// Forget the current incident. Called on user stop.
+pub fn reset() {
+    *TRACKER.lock().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+fn spawn_attempt(app: AppHandle, epoch: u64, delay: Duration, mode: ProxyMode, path: String) {
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(delay).await;
+        let snap = app.state::<EngineStateCell>().snapshot();
+        if !matches!(snap, EngineState::Recovering { .. }) || snap.epoch() != epoch {
+            log::info!(
+                "[recovery] superseded (kind={}, epoch={}, captured={}), not restarting",
+                snap.kind(),
+                snap.epoch(),
+                epoch
+            );
+            return;
+        }
+        // Success and failure both flow back through the usual hooks:
+        // readiness → note_running, or monitor/readiness/start → handle_failure.
+        if let Err(e) = with_trigger(
+            Trigger::Recovery,
+            crate::core::restart_after_crash(app.clone(), path, mode),
+        )
+        .await
+        {
+            log::warn!("[recovery] restart attempt failed: {}", e);
+        }
+    });
+}
+
+#[cfg(test)]
+mod recovery_tests {
+    use super::*;
+
+    #[test]
+    fn backoff_grows_and_caps() {
+        let policy = RecoveryPolicy::default();
+        assert_eq!(policy.backoff(1), Duration::from_secs(2));
+        assert_eq!(policy.backoff(2), Duration::from_secs(4));
+        assert_eq!(policy.backoff(3), Duration::from_secs(8));
+        assert_eq!(policy.backoff(10), Duration::from_secs(60));
+        assert_eq!(policy.backoff(u32::MAX), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn retries_until_max_attempts_then_gives_up() {
+        let policy = RecoveryPolicy {
+            max_attempts: 3,
+            ..Default::default()
+        };
+        let mut tracker = Tracker::default();
+        for expected in 1..=3 {
+            match decide(&policy, &mut tracker, 1000 + expected as i64, None) {
+                Decision::Retry { attempt, .. } => assert_eq!(attempt, expected),
+                other => panic!("unexpected {:?}", other),
+            }
+        }
+        assert!(matches!(
+            decide(&policy, &mut tracker, 1010, None),
+            Decision::GiveUp(_)
+        ));
+    }
+
+    #[test]
+    fn healthy_uptime_resets_attempts() {
+        let policy = RecoveryPolicy {
+            max_attempts: 2,
+            cooldown_secs: 60,
+            ..Default::default()
+        };
+        let mut tracker = Tracker::default();
+        decide(&policy, &mut tracker, 100, Some(90));
+        decide(&policy, &mut tracker, 110, None);
+        assert_eq!(tracker.attempt, 2);
+        // Ran 500 s before this crash: fresh incident.
+        assert_eq!(
+            decide(&policy, &mut tracker, 1000, Some(500)),
+            Decision::Retry {
+                attempt: 1,
+                delay: Duration::from_secs(2)
+            }
+        );
+    }
+
+    #[test]
+    fn crash_loop_within_window_gives_up() {
+        let policy = RecoveryPolicy {
+            give_up_crashes: 3,
+            give_up_window_secs: 100,
+            cooldown_secs: 1,
+            ..Default::default()
+        };
+        let mut tracker = Tracker::default();
+        // Each crash follows a healthy run, so attempts keep resetting…
+        assert!(matches!(
+            decide(&policy, &mut tracker, 10, Some(0)),
+            Decision::Retry { .. }
+        ));
+        assert!(matches!(
+            decide(&policy, &mut tracker, 20, Some(12)),
+            Decision::Retry { .. }
+        ));
+        // …but three crashes in 100 s is a loop.
+        assert!(matches!(
+            decide(&policy, &mut tracker, 30, Some(22)),
+            Decision::GiveUp(_)
+        ));
+        // Old crashes age out of the window.
+        let mut tracker = Tracker::default();
+        decide(&policy, &mut tracker, 10, Some(0));
+        decide(&policy, &mut tracker, 20, Some(12));
+        assert!(matches!(
+            decide(&policy, &mut tracker, 500, Some(400)),
+            Decision::Retry { .. }
+        ));
+    }
+
+    #[test]
+    fn disabled_policy_gives_up_immediately() {
+        let policy = RecoveryPolicy {
+            enabled: false,
+            ..Default::default()
+        };
+        assert!(matches!(
+            decide(&policy, &mut Tracker::default(), 0, Some(0)),
+            Decision::GiveUp(_)
+        ));
+    }
+
+    #[test]
+    fn policy_fields_default_individually() {
+        let policy: RecoveryPolicy =
+            serde_json::from_value(serde_json::json!({ "max_attempts": 9 })).unwrap();
+        assert_eq!(policy.max_attempts, 9);
+        assert_eq!(policy.initial_backoff_secs, 2);
+        assert!(policy.enabled);
+    }
+}
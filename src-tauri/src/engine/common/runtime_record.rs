@@ -0,0 +1,447 @@
+//! Runtime record of the engine session, for orphan tracking across app
+//! restarts.
+//!
+//! Orphan detection used to be port-based only (`commands::prestart`), so
+//! after a GUI crash the next launch either killed a perfectly good tunnel
+//! to free the mixed port, or — for TUN, where sing-box runs under a helper
+//! — left it running untracked with DNS still pointing at it.
+//!
+//! `core::start` records the session in the PID file of
+//! `commands::prestart` once `EngineManager::start` succeeds: pid, mode,
+//! config path, start epoch and what hosts sing-box. Every path that resets
+//! `ProcessManager` clears it, so it only outlives its session when the GUI
+//! went away without stopping the engine. On the next launch `reconcile()`
+//!
+//!   - adopts a sing-box that is still ours and still answers its Clash API:
+//!     `ProcessManager` is re-seeded, the platform re-attaches its exit
+//!     watcher and the readiness prober takes the state machine to Running;
+//!   - otherwise cleans up what the session left behind — kills the
+//!     process, clears the system proxy, restores DNS — and drops the record.
+
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use super::state_machine::{
+    now_secs, transition, with_trigger, EngineState, EngineStateCell, Intent, Trigger,
+};
+use crate::commands::prestart::{self, PortHolder, SpawnedProcess};
+use crate::core::ProcessManager;
+use crate::engine::{EngineManager, PlatformEngine, ProxyMode};
+
+/// What runs the sing-box process of a session.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum EngineHost {
+    /// The bundled sidecar, spawned directly by OneBox.
+    Sidecar,
+    /// Linux TUN: `pkexec onebox-tun-helper start-tun …`; the recorded pid
+    /// is pkexec, sing-box is its child.
+    Pkexec,
+    /// macOS TUN: the SMJobBless XPC helper owns sing-box.
+    PrivilegedHelper,
+    /// Windows TUN: sing-box runs inside OneBoxTunService.
+    TunService,
+}
+
+impl EngineHost {
+    pub fn for_mode(mode: &ProxyMode) -> Self {
+        if !matches!(mode, ProxyMode::TunProxy) {
+            EngineHost::Sidecar
+        } else if cfg!(target_os = "linux") {
+            EngineHost::Pkexec
+        } else if cfg!(target_os = "macos") {
+            EngineHost::PrivilegedHelper
+        } else {
+            EngineHost::TunService
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RuntimeRecord {
+    /// Pid of the process OneBox spawned; `None` when a helper or service
+    /// spawned sing-box on our behalf.
+    pub pid: Option<u32>,
+    pub mode: ProxyMode,
+    pub config_path: String,
+    pub start_epoch: u64,
+    /// Unix seconds.
+    pub started_at: i64,
+    pub host: EngineHost,
+    /// Linux TUN: `(iface, original_dns)` to put back on cleanup.
+    #[serde(default)]
+    pub dns_override: Option<(String, String)>,
+    /// Linux TUN: helper name of the DNS backend that made the override.
+    /// Absent in records that predate the choice, which used `resolved`.
+    #[serde(default)]
+    pub dns_backend: Option<String>,
+}
+
+pub fn read(app: &AppHandle) -> Option<RuntimeRecord> {
+    prestart::pid_file_contents(app).session
+}
+
+fn write(app: &AppHandle, record: &RuntimeRecord) {
+    prestart::update_pid_file(app, |file| {
+        file.session = Some(record.clone());
+        true
+    });
+}
+
+/// Drop the record. Called wherever `ProcessManager` is reset.
+pub fn clear(app: &AppHandle) {
+    prestart::update_pid_file(app, |file| file.session.take().is_some());
+}
+
+/// Record the session `core::start` just brought up. Reads the child pid
+/// and the platform's DNS capture, so call it after `EngineManager::start`
+/// returned `Ok`.
+pub fn note_started(app: &AppHandle, mode: &ProxyMode, config_path: &str, start_epoch: u64) {
+    let pid = ProcessManager::acquire().child.as_ref().map(|c| c.pid());
+    let (dns_override, dns_backend) = platform_dns_override();
+    write(
+        app,
+        &RuntimeRecord {
+            pid,
+            mode: mode.clone(),
+            config_path: config_path.to_string(),
+            start_epoch,
+            started_at: now_secs(),
+            host: EngineHost::for_mode(mode),
+            dns_override,
+            dns_backend,
+        },
+    );
+}
+
+/// Keep the recorded DNS capture in step when the platform re-captures it
+/// (Linux NetworkUp).
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+pub fn note_dns_override(app: &AppHandle) {
+    if let Some(mut record) = read(app) {
+        (record.dns_override, record.dns_backend) = platform_dns_override();
+        write(app, &record);
+    }
+}
+
+fn platform_dns_override() -> (Option<(String, String)>, Option<String>) {
+    #[cfg(target_os = "linux")]
+    {
+        match crate::engine::linux::dns_override_snapshot() {
+            Some(o) => (
+                Some((o.iface, o.original_dns)),
+                Some(o.backend.helper_name().to_string()),
+            ),
+            None => (None, None),
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        (None, None)
+    }
+}
+
+/// `kill(pid, 0)` that also counts processes we may not signal (EPERM) —
+/// the Linux TUN pkexec runs as root.
+#[cfg(unix)]
+pub(crate) fn process_exists(pid: u32) -> bool {
+    if unsafe { libc::kill(pid as i32, 0) } == 0 {
+        return true;
+    }
+    std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// Whether `holder`, the process now behind the recorded pid, is the one
+/// the record describes: it is the process recorded at spawn (same start
+/// time, so not a reused pid) and it runs the recorded config.
+fn is_recorded_process(
+    holder: &PortHolder,
+    spawned: &[SpawnedProcess],
+    record: &RuntimeRecord,
+) -> bool {
+    spawned
+        .iter()
+        .any(|s| s.is(holder.pid, holder.started.as_deref(), holder.exe.as_deref()))
+        && holder
+            .cmdline
+            .as_deref()
+            .is_some_and(|cmdline| cmdline.contains(record.config_path.as_str()))
+}
+
+/// Nothing has claimed the engine since launch.
+fn engine_unclaimed(app: &AppHandle) -> bool {
+    ProcessManager::acquire().mode.is_none()
+        && matches!(
+            app.state::<EngineStateCell>().snapshot(),
+            EngineState::Idle { .. }
+        )
+}
+
+/// Reconcile the record a previous GUI process left behind. Called once
+/// from `app_setup`; returns the adopted mode, if any.
+pub async fn reconcile(app: &AppHandle) -> Option<ProxyMode> {
+    let record = read(app)?;
+    log::info!(
+        "[orphan] runtime record from previous session: pid={:?} mode={:?} host={:?} started_at={}",
+        record.pid,
+        record.mode,
+        record.host,
+        record.started_at
+    );
+
+    // For helper/service hosts there is no pid of ours to look at; the
+    // Clash API probe below is the only liveness signal.
+    let may_be_alive = match record.pid {
+        Some(pid) => {
+            let owned = prestart::owned_processes(app);
+            let holder = prestart::describe_holder(pid, &owned);
+            let alive = is_recorded_process(&holder, &owned.spawned, &record);
+            if !alive && (holder.exe.is_some() || holder.cmdline.is_some()) {
+                log::info!(
+                    "[orphan] pid {} is now {}, not the recorded sing-box",
+                    pid,
+                    holder.name
+                );
+            }
+            alive
+        }
+        None => true,
+    };
+    let healthy = may_be_alive
+        && Path::new(&record.config_path).exists()
+        && match super::readiness::probe_clash_api(app, &record.config_path).await {
+            Ok(()) => true,
+            Err(e) => {
+                log::info!(
+                    "[orphan] Clash API of previous session not answering: {}",
+                    e
+                );
+                false
+            }
+        };
+
+    // A start issued while we were probing owns the engine (and the record)
+    // now; leave both alone.
+    if !engine_unclaimed(app) {
+        log::info!("[orphan] engine claimed during reconcile, leaving it");
+        return None;
+    }
+    if healthy && adopt(app, &record).await {
+        return Some(record.mode);
+    }
+    clean_up(app, &record, may_be_alive).await;
+    None
+}
+
+async fn adopt(app: &AppHandle, record: &RuntimeRecord) -> bool {
+    {
+        let mut mgr = ProcessManager::acquire();
+        if mgr.mode.is_some() {
+            return false;
+        }
+        mgr.mode = Some(Arc::new(record.mode.clone()));
+        mgr.config_path = Some(Arc::new(record.config_path.clone()));
+        mgr.child = None;
+        mgr.adopted_pid = record.pid.filter(|_| record.host == EngineHost::Sidecar);
+        mgr.is_stopping = false;
+    }
+    super::journal::note_config(&record.config_path);
+    let started = with_trigger(Trigger::Adopt, async {
+        transition(
+            app,
+            Intent::Start {
+                mode: crate::core::state_mode_label(&record.mode).into(),
+            },
+        )
+    })
+    .await;
+    if let Err(e) = started {
+        log::warn!("[orphan] adoption rejected by the state machine: {}", e);
+        ProcessManager::acquire().reset();
+        return false;
+    }
+    let epoch = app.state::<EngineStateCell>().snapshot().epoch();
+    log::info!(
+        "[orphan] adopting sing-box pid={:?} mode={:?} epoch={}",
+        record.pid,
+        record.mode,
+        epoch
+    );
+
+    PlatformEngine::adopt(app, record, epoch);
+    if matches!(record.mode, ProxyMode::SystemProxy) {
+        if let Err(e) = crate::engine::apply_system_proxy(app).await {
+            log::warn!("[orphan] failed to re-apply system proxy: {}", e);
+        }
+    }
+    write(
+        app,
+        &RuntimeRecord {
+            start_epoch: epoch,
+            ..record.clone()
+        },
+    );
+    super::readiness::spawn(app.clone(), epoch);
+    true
+}
+
+async fn clean_up(app: &AppHandle, record: &RuntimeRecord, may_be_alive: bool) {
+    log::info!(
+        "[orphan] cleaning up previous session (mode={:?} may_be_alive={})",
+        record.mode,
+        may_be_alive
+    );
+    if matches!(record.mode, ProxyMode::SystemProxy) {
+        // Left pointing at the old session's mixed port.
+        if let Err(e) = crate::engine::clear_system_proxy(app).await {
+            log::warn!("[orphan] failed to clear system proxy: {}", e);
+        }
+    }
+    match (record.host, record.pid) {
+        (EngineHost::Sidecar, Some(pid)) if may_be_alive => match prestart::kill_pid(pid) {
+            Ok(()) => log::info!("[orphan] killed unhealthy sing-box pid={}", pid),
+            Err(e) => log::warn!("[orphan] failed to kill pid {}: {}", pid, e),
+        },
+        (EngineHost::Sidecar, _) => {}
+        _ => PlatformEngine::clean_up_orphan(app, record, may_be_alive).await,
+    }
+    clear(app);
+}
+
+/// Poll an adopted process once a second and run the regular termination
+/// path when it exits — an adopted process has no `CommandEvent` stream.
+/// Gives up once the session's epoch moves on.
+pub(crate) fn watch_pid(
+    app: AppHandle,
+    mode: Arc<ProxyMode>,
+    pid: u32,
+    start_epoch: u64,
+    is_alive: fn(u32) -> bool,
+) {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            if app.state::<EngineStateCell>().snapshot().epoch() != start_epoch {
+                return;
+            }
+            if is_alive(pid) {
+                continue;
+            }
+            log::info!("[orphan] adopted pid={} exited", pid);
+            let payload = tauri_plugin_shell::process::TerminatedPayload {
+                code: None,
+                signal: None,
+            };
+            crate::core::monitor::handle_process_termination(&app, &mode, payload, start_epoch)
+                .await;
+            return;
+        }
+    });
+}
+
+#[cfg(test)]
+mod runtime_record_tests {
+    use super::*;
+
+    fn record() -> RuntimeRecord {
+        RuntimeRecord {
+            pid: Some(4242),
+            mode: ProxyMode::SystemProxy,
+            config_path: "/home/u/.config/OneBox/config.json".into(),
+            start_epoch: 3,
+            started_at: 1_700_000_000,
+            host: EngineHost::Sidecar,
+            dns_override: None,
+            dns_backend: None,
+        }
+    }
+
+    fn holder(started: &str, cmdline: Option<&str>) -> PortHolder {
+        PortHolder {
+            pid: 4242,
+            cmdline: cmdline.map(str::to_string),
+            started: Some(started.into()),
+            name: "sing-box".into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn only_tun_runs_under_a_helper() {
+        assert_eq!(
+            EngineHost::for_mode(&ProxyMode::SystemProxy),
+            EngineHost::Sidecar
+        );
+        assert_eq!(
+            EngineHost::for_mode(&ProxyMode::ManualProxy),
+            EngineHost::Sidecar
+        );
+        assert_ne!(
+            EngineHost::for_mode(&ProxyMode::TunProxy),
+            EngineHost::Sidecar
+        );
+    }
+
+    #[test]
+    fn recorded_process_must_be_the_spawned_one_and_run_the_recorded_config() {
+        let record = record();
+        let spawned = [SpawnedProcess {
+            pid: 4242,
+            started: "boot-a:1000".into(),
+            exe: None,
+        }];
+        let cmdline =
+            "/opt/OneBox/sing-box run -c /home/u/.config/OneBox/config.json --disable-color";
+        assert!(is_recorded_process(
+            &holder("boot-a:1000", Some(cmdline)),
+            &spawned,
+            &record
+        ));
+        // the recorded process, but running something else
+        assert!(!is_recorded_process(
+            &holder("boot-a:1000", Some("/usr/bin/python3 server.py")),
+            &spawned,
+            &record
+        ));
+        // pid reused after a reboot by a sing-box with the same config
+        assert!(!is_recorded_process(
+            &holder("boot-b:1000", Some(cmdline)),
+            &spawned,
+            &record
+        ));
+        // never recorded at spawn
+        assert!(!is_recorded_process(
+            &holder("boot-a:1000", Some(cmdline)),
+            &[],
+            &record
+        ));
+        // gone: nothing readable behind the pid
+        assert!(!is_recorded_process(
+            &holder("boot-a:1000", None),
+            &spawned,
+            &record
+        ));
+    }
+
+    #[test]
+    fn record_round_trips_and_tolerates_missing_dns_override() {
+        let mut record = record();
+        record.dns_override = Some(("wlp2s0".into(), "192.168.1.1".into()));
+        record.dns_backend = Some("network-manager".into());
+        let json = serde_json::to_string(&record).unwrap();
+        assert_eq!(
+            serde_json::from_str::<RuntimeRecord>(&json).unwrap(),
+            record
+        );
+
+        let minimal = r#"{"pid":null,"mode":"TunProxy","config_path":"/c.json","start_epoch":1,"started_at":0,"host":"pkexec"}"#;
+        let parsed: RuntimeRecord = serde_json::from_str(minimal).unwrap();
+        assert_eq!(parsed.host, EngineHost::Pkexec);
+        assert_eq!(parsed.dns_override, None);
+        assert_eq!(parsed.dns_backend, None);
+    }
+}
@@ -9,15 +9,21 @@
 //! uses it to drop out-of-order events. Prober tasks capture the epoch at
 //! spawn and refuse to transition if it has advanced.
 
+use std::future::Future;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Mutex;
 
 use serde::Serialize;
 use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::broadcast;
 
 pub const EVENT_ENGINE_STATE: &str = "engine-state";
 
-#[derive(Serialize, Clone, Debug)]
+/// Capacity of the in-process state broadcast. Subscribers that fall this
+/// far behind get `RecvError::Lagged` and should re-read `snapshot()`.
+const STATE_BROADCAST_CAPACITY: usize = 64;
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
 #[serde(tag = "kind", rename_all = "lowercase")]
 pub enum EngineState {
     Idle {
@@ -42,6 +48,22 @@ pub enum EngineState {
         at: i64,
         epoch: u64,
     },
+    /// Running, but `engine::health` probes through the engine are failing
+    /// or slow. Returns to `Running` on its own when probes recover.
+    Degraded {
+        since: i64,
+        reason: String,
+        epoch: u64,
+        mode: String,
+    },
+    /// sing-box died unexpectedly; `engine::recovery` will re-run start at
+    /// `next_at` (Unix seconds). `reason` is the failure being recovered from.
+    Recovering {
+        attempt: u32,
+        next_at: i64,
+        reason: String,
+        epoch: u64,
+    },
 }
 
 impl EngineState {
@@ -52,6 +74,8 @@ impl EngineState {
             EngineState::Running { .. } => "running",
             EngineState::Stopping { .. } => "stopping",
             EngineState::Failed { .. } => "failed",
+            EngineState::Degraded { .. } => "degraded",
+            EngineState::Recovering { .. } => "recovering",
         }
     }
 
@@ -61,34 +85,143 @@ impl EngineState {
             | EngineState::Starting { epoch, .. }
             | EngineState::Running { epoch, .. }
             | EngineState::Stopping { epoch, .. }
-            | EngineState::Failed { epoch, .. } => *epoch,
+            | EngineState::Failed { epoch, .. }
+            | EngineState::Degraded { epoch, .. }
+            | EngineState::Recovering { epoch, .. } => *epoch,
         }
     }
 
     pub fn mode(&self) -> Option<&str> {
         match self {
-            EngineState::Starting { mode, .. } | EngineState::Running { mode, .. } => {
-                Some(mode.as_str())
-            }
+            EngineState::Starting { mode, .. }
+            | EngineState::Running { mode, .. }
+            | EngineState::Degraded { mode, .. } => Some(mode.as_str()),
             _ => None,
         }
     }
+
+    /// sing-box is up and serving, healthy or not.
+    pub fn is_running(&self) -> bool {
+        matches!(
+            self,
+            EngineState::Running { .. } | EngineState::Degraded { .. }
+        )
+    }
+}
+
+/// What caused a transition. Recorded by the state journal.
+///
+/// Automatic restarts run inside `with_trigger(..)`; everything else is
+/// inferred by `resolve_trigger`.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Trigger {
+    /// UI, tray, CLI or control socket.
+    User,
+    /// A periodic watchdog restart (e.g. macOS bypass-router refresh).
+    Watchdog,
+    /// Restart scheduled after a network outage.
+    NetworkUp,
+    /// Restart scheduled after system wake.
+    Wake,
+    /// The engine itself: sing-box exited or failed while running.
+    Engine,
+    /// An automatic restart attempt by `engine::recovery`.
+    Recovery,
+    /// Re-attaching to a sing-box left running by a previous GUI session
+    /// (`engine::runtime_record`).
+    Adopt,
+}
+
+impl Trigger {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Trigger::User => "user",
+            Trigger::Watchdog => "watchdog",
+            Trigger::NetworkUp => "network-up",
+            Trigger::Wake => "wake",
+            Trigger::Engine => "engine",
+            Trigger::Recovery => "recovery",
+            Trigger::Adopt => "adopt",
+        }
+    }
+}
+
+tokio::task_local! {
+    static TRIGGER: Trigger;
+}
+
+/// Run `fut` with every transition it performs (directly, not from tasks
+/// it spawns) attributed to `trigger`.
+pub async fn with_trigger<F: Future>(trigger: Trigger, fut: F) -> F::Output {
+    TRIGGER.scope(trigger, fut).await
+}
+
+/// Attribution for a transition with no explicit `with_trigger` scope.
+/// Follow-up transitions (readiness MarkRunning, startup Fail, final
+/// MarkIdle) inherit the trigger of the cycle that started them; leaving
+/// Running on its own is the engine's doing.
+fn resolve_trigger(
+    scoped: Option<Trigger>,
+    current: &EngineState,
+    intent: &Intent,
+    cycle: Trigger,
+) -> Trigger {
+    if let Some(trigger) = scoped {
+        return trigger;
+    }
+    match (current, intent) {
+        (_, Intent::Start { .. } | Intent::Stop | Intent::ClearFailure) => Trigger::User,
+        (
+            EngineState::Running { .. } | EngineState::Degraded { .. },
+            Intent::Fail { .. } | Intent::MarkIdle | Intent::Recover { .. },
+        ) => Trigger::Engine,
+        (_, Intent::Degrade { .. } | Intent::MarkHealthy) => Trigger::Engine,
+        _ => cycle,
+    }
+}
+
+/// One committed transition, as published on `EngineStateCell::subscribe`.
+#[derive(Serialize, Clone, Debug)]
+pub struct StateChange {
+    pub from: EngineState,
+    pub to: EngineState,
+    pub trigger: Trigger,
+    /// When the transition committed (Unix seconds): the new state's
+    /// `since` / `at`, also for states that carry neither.
+    pub at: i64,
+    /// `journal::note_config`'s hash as of the transition, not as of
+    /// whenever a subscriber gets to it.
+    pub config_hash: Option<String>,
 }
 
 /// Held in Tauri `State`, registered in `setup::app_setup`.
 pub struct EngineStateCell {
     inner: Mutex<EngineState>,
     counter: AtomicU64,
+    changes: broadcast::Sender<StateChange>,
+    /// Trigger of the current start/stop cycle; guarded by `inner`'s lock
+    /// discipline (only written inside `transition`).
+    cycle_trigger: Mutex<Trigger>,
 }
 
 impl EngineStateCell {
     pub fn new() -> Self {
+        let (changes, _) = broadcast::channel(STATE_BROADCAST_CAPACITY);
         Self {
             inner: Mutex::new(EngineState::Idle { epoch: 0 }),
             counter: AtomicU64::new(0),
+            changes,
+            cycle_trigger: Mutex::new(Trigger::User),
         }
     }
 
+    /// In-process feed of every committed transition, for Rust-side
+    /// consumers that are not webviews (e.g. the local control socket).
+    pub fn subscribe(&self) -> broadcast::Receiver<StateChange> {
+        self.changes.subscribe()
+    }
+
     pub fn snapshot(&self) -> EngineState {
         self.inner.lock().unwrap_or_else(|e| e.into_inner()).clone()
     }
@@ -121,14 +254,19 @@ impl EngineStateCell {
 /// mutate the cell.
 #[derive(Debug)]
 pub enum Intent {
-    /// Idle/Failed → Starting. `mode` is `"tun"` or `"mixed"`.
+    /// Idle/Failed/Recovering → Starting. `mode` is `"tun"` or `"mixed"`.
     Start { mode: String },
     /// Starting → Running.
     MarkRunning,
-    /// Running → Stopping.
+    /// Running/Degraded → Stopping.
     Stop,
-    /// Starting/Stopping/Running → Idle. Used by the termination path when
-    /// the child process is confirmed gone.
+    /// Running → Degraded. Only issued by `engine::health`.
+    Degrade { reason: String },
+    /// Degraded → Running. Only issued by `engine::health`.
+    MarkHealthy,
+    /// Starting/Stopping/Running/Degraded/Recovering → Idle. Used by the termination
+    /// path when the child process is confirmed gone, and by `stop` to
+    /// cancel a pending recovery.
     MarkIdle,
     /// Any transitional state → Failed with a reason.
     Fail { reason: String },
@@ -137,9 +275,17 @@ pub enum Intent {
     RollbackToRunning { mode: String },
     /// Failed → Idle. Explicit user acknowledgement.
     ClearFailure,
+    /// Running/Degraded/Starting → Recovering. Only issued by `engine::recovery`.
+    Recover {
+        attempt: u32,
+        next_at: i64,
+        reason: String,
+    },
 }
 
-fn now_secs() -> i64 {
+/// Current Unix time in seconds; the clock every engine and usage
+/// timestamp is taken from.
+pub(crate) fn now_secs() -> i64 {
     use std::time::{SystemTime, UNIX_EPOCH};
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -150,16 +296,30 @@ fn now_secs() -> i64 {
 /// Apply an `Intent` to the state cell under lock, emit `vpn://state`, and
 /// return the new state. Rejects illegal transitions.
 pub fn transition(app: &AppHandle, intent: Intent) -> Result<EngineState, String> {
-    let cell = app.state::<EngineStateCell>();
+    let new_state = apply(&app.state::<EngineStateCell>(), intent)?;
+    if let Err(e) = app.emit(EVENT_ENGINE_STATE, new_state.clone()) {
+        log::error!("[engine-state] emit {} failed: {}", EVENT_ENGINE_STATE, e);
+    }
+    Ok(new_state)
+}
+
+/// The lock-validate-commit half of `transition`, against any cell.
+/// Side-by-side instances (`core::instances`) own their cells and publish
+/// their own event, so they call this directly.
+pub fn apply(cell: &EngineStateCell, intent: Intent) -> Result<EngineState, String> {
     let mut guard = cell.inner.lock().unwrap_or_else(|e| e.into_inner());
     let current = guard.clone();
+    let cycle = *cell.cycle_trigger.lock().unwrap_or_else(|e| e.into_inner());
+    let trigger = resolve_trigger(TRIGGER.try_with(|t| *t).ok(), &current, &intent, cycle);
+    let at = now_secs();
 
     let new_state = match (&current, intent) {
         (EngineState::Idle { .. }, Intent::Start { mode })
-        | (EngineState::Failed { .. }, Intent::Start { mode }) => {
+        | (EngineState::Failed { .. }, Intent::Start { mode })
+        | (EngineState::Recovering { .. }, Intent::Start { mode }) => {
             let epoch = cell.counter.fetch_add(1, Ordering::SeqCst) + 1;
             EngineState::Starting {
-                since: now_secs(),
+                since: at,
                 epoch,
                 mode,
             }
@@ -167,22 +327,38 @@ pub fn transition(app: &AppHandle, intent: Intent) -> Result<EngineState, String
         (EngineState::Starting { mode, .. }, Intent::MarkRunning) => {
             let epoch = cell.counter.fetch_add(1, Ordering::SeqCst) + 1;
             EngineState::Running {
-                since: now_secs(),
+                since: at,
                 epoch,
                 mode: mode.clone(),
             }
         }
-        (EngineState::Running { .. }, Intent::Stop) => {
+        (EngineState::Running { mode, .. }, Intent::Degrade { reason }) => {
             let epoch = cell.counter.fetch_add(1, Ordering::SeqCst) + 1;
-            EngineState::Stopping {
-                since: now_secs(),
+            EngineState::Degraded {
+                since: at,
+                reason,
                 epoch,
+                mode: mode.clone(),
             }
         }
+        (EngineState::Degraded { mode, .. }, Intent::MarkHealthy) => {
+            let epoch = cell.counter.fetch_add(1, Ordering::SeqCst) + 1;
+            EngineState::Running {
+                since: at,
+                epoch,
+                mode: mode.clone(),
+            }
+        }
+        (EngineState::Running { .. } | EngineState::Degraded { .. }, Intent::Stop) => {
+            let epoch = cell.counter.fetch_add(1, Ordering::SeqCst) + 1;
+            EngineState::Stopping { since: at, epoch }
+        }
         (
             EngineState::Stopping { .. }
             | EngineState::Starting { .. }
-            | EngineState::Running { .. },
+            | EngineState::Running { .. }
+            | EngineState::Degraded { .. }
+            | EngineState::Recovering { .. },
             Intent::MarkIdle,
         ) => {
             let epoch = cell.counter.fetch_add(1, Ordering::SeqCst) + 1;
@@ -195,19 +371,33 @@ pub fn transition(app: &AppHandle, intent: Intent) -> Result<EngineState, String
         (EngineState::Stopping { .. }, Intent::RollbackToRunning { mode }) => {
             let epoch = cell.counter.fetch_add(1, Ordering::SeqCst) + 1;
             EngineState::Running {
-                since: now_secs(),
+                since: at,
                 epoch,
                 mode,
             }
         }
-        (cur, Intent::Fail { reason }) if !matches!(cur, EngineState::Idle { .. }) => {
+        (
+            EngineState::Running { .. }
+            | EngineState::Degraded { .. }
+            | EngineState::Starting { .. },
+            Intent::Recover {
+                attempt,
+                next_at,
+                reason,
+            },
+        ) => {
             let epoch = cell.counter.fetch_add(1, Ordering::SeqCst) + 1;
-            EngineState::Failed {
+            EngineState::Recovering {
+                attempt,
+                next_at,
                 reason,
-                at: now_secs(),
                 epoch,
             }
         }
+        (cur, Intent::Fail { reason }) if !matches!(cur, EngineState::Idle { .. }) => {
+            let epoch = cell.counter.fetch_add(1, Ordering::SeqCst) + 1;
+            EngineState::Failed { reason, at, epoch }
+        }
         (cur, intent) => {
             let msg = format!("illegal transition from {} via {:?}", cur.kind(), intent);
             log::warn!("[engine-state] {}", msg);
@@ -216,18 +406,25 @@ pub fn transition(app: &AppHandle, intent: Intent) -> Result<EngineState, String
     };
 
     log::info!(
-        "[engine-state] {} -> {} (epoch={})",
+        "[engine-state] {} -> {} (epoch={} trigger={})",
         current.kind(),
         new_state.kind(),
-        new_state.epoch()
+        new_state.epoch(),
+        trigger.as_str()
     );
 
     *guard = new_state.clone();
+    *cell.cycle_trigger.lock().unwrap_or_else(|e| e.into_inner()) = trigger;
     drop(guard);
 
-    if let Err(e) = app.emit(EVENT_ENGINE_STATE, new_state.clone()) {
-        log::error!("[engine-state] emit {} failed: {}", EVENT_ENGINE_STATE, e);
-    }
+    // Err only means nobody is subscribed right now.
+    let _ = cell.changes.send(StateChange {
+        from: current,
+        to: new_state.clone(),
+        trigger,
+        at,
+        config_hash: super::journal::current_config_hash(),
+    });
 
     Ok(new_state)
 }
@@ -268,6 +465,59 @@ mod tests {
         assert!(matches!(s1, EngineState::Idle { .. }));
     }
 
+    #[test]
+    fn trigger_scoped_wins_over_inference() {
+        let running = EngineState::Running {
+            since: 0,
+            epoch: 1,
+            mode: "tun".into(),
+        };
+        assert_eq!(
+            resolve_trigger(Some(Trigger::Wake), &running, &Intent::Stop, Trigger::User),
+            Trigger::Wake
+        );
+    }
+
+    #[test]
+    fn trigger_inference() {
+        let idle = EngineState::Idle { epoch: 0 };
+        let starting = EngineState::Starting {
+            since: 0,
+            epoch: 1,
+            mode: "tun".into(),
+        };
+        let running = EngineState::Running {
+            since: 0,
+            epoch: 2,
+            mode: "tun".into(),
+        };
+        let start = Intent::Start { mode: "tun".into() };
+        let fail = Intent::Fail { reason: "x".into() };
+
+        assert_eq!(
+            resolve_trigger(None, &idle, &start, Trigger::NetworkUp),
+            Trigger::User
+        );
+        // Readiness outcome inherits the cycle that started it.
+        assert_eq!(
+            resolve_trigger(None, &starting, &Intent::MarkRunning, Trigger::Wake),
+            Trigger::Wake
+        );
+        assert_eq!(
+            resolve_trigger(None, &starting, &fail, Trigger::Watchdog),
+            Trigger::Watchdog
+        );
+        // Dropping out of Running unprompted is the engine's doing.
+        assert_eq!(
+            resolve_trigger(None, &running, &fail, Trigger::User),
+            Trigger::Engine
+        );
+        assert_eq!(
+            resolve_trigger(None, &running, &Intent::MarkIdle, Trigger::User),
+            Trigger::Engine
+        );
+    }
+
     #[test]
     fn state_kind_labels() {
         assert_eq!(EngineState::Idle { epoch: 0 }.kind(), "idle");
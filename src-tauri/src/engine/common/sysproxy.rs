@@ -1,12 +1,19 @@
 //! Cross-platform system HTTP/SOCKS proxy override.
 //!
-//! All platforms shell through `onebox_sysproxy_rs` — the only thing that
-//! varies is the per-OS bypass-list syntax (comma vs semicolon, glob vs CIDR)
-//! and the clear strategy. The macOS service-name resolution, exit-status
-//! checking, and "disable proxy on every service pointing at us" clear all
-//! live in the crate (v0.0.2+), so there is no per-OS networksetup code here.
+//! macOS and Windows shell through `onebox_sysproxy_rs` — the only thing
+//! that varies is the per-OS bypass-list syntax (comma vs semicolon, glob vs
+//! CIDR) and the clear strategy. Linux goes through
+//! `engine::linux::desktop_proxy`, which covers GNOME, KDE and the
+//! command-line tool settings and restores exactly what it overwrote.
+//! The macOS service-name resolution, exit-status checking, and "disable
+//! proxy on every service pointing at us" clear all live in the crate
+//! (v0.0.2+), so there is no per-OS networksetup code here.
 //!
-//! Proxy always points at the Mixed inbound's listen port.
+//! Proxy always points at the Mixed inbound's listen port, either directly or
+//! through the PAC script `pac` serves when that strategy is enabled.
+//!
+//! What was applied is reported to `proxy_guard`, which watches for other
+//! software rewriting it.
 //!
 //! `set_*` emits a frontend log line (Windows historically did, macOS
 //! and Linux did not — we now do it on all three for symmetry); failure
@@ -15,30 +22,37 @@
 
 use tauri::{AppHandle, Emitter};
 
+use super::proxy_guard::Expected;
 use crate::{core::mixed_proxy_port, engine::EVENT_TAURI_LOG};
 
 const PROXY_HOST: &str = "127.0.0.1";
 
 /// Bypass-list syntax differs per platform — see the `onebox_sysproxy_rs`
 /// source for exactly how it's parsed. The values below were migrated
-/// verbatim from the previous per-platform duplicates.
+/// verbatim from the previous per-platform duplicates; user entries from
+/// `proxy_bypass` are appended on top.
 #[cfg(target_os = "macos")]
-const DEFAULT_BYPASS: &str =
+pub(crate) const DEFAULT_BYPASS: &str =
     "127.0.0.1,192.168.0.0/16,10.0.0.0/8,172.16.0.0/12,172.29.0.0/16,localhost,*.local,*.crashlytics.com,<local>";
 
 #[cfg(target_os = "linux")]
-const DEFAULT_BYPASS: &str =
+pub(crate) const DEFAULT_BYPASS: &str =
     "localhost,127.0.0.1,192.168.0.0/16,10.0.0.0/8,172.16.0.0/12,172.29.0.0/16,::1";
 
 #[cfg(target_os = "windows")]
-const DEFAULT_BYPASS: &str = "localhost;127.*;192.168.*;10.*;172.16.*;172.17.*;172.18.*;172.19.*;172.20.*;172.21.*;172.22.*;172.23.*;172.24.*;172.25.*;172.26.*;172.27.*;172.28.*;172.29.*;172.30.*;172.31.*;<local>";
+pub(crate) const DEFAULT_BYPASS: &str = "localhost;127.*;192.168.*;10.*;172.16.*;172.17.*;172.18.*;172.19.*;172.20.*;172.21.*;172.22.*;172.23.*;172.24.*;172.25.*;172.26.*;172.27.*;172.28.*;172.29.*;172.30.*;172.31.*;<local>";
 
 #[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
-const DEFAULT_BYPASS: &str = "localhost,127.0.0.1";
+pub(crate) const DEFAULT_BYPASS: &str = "localhost,127.0.0.1";
 
-/// Apply the HTTP/SOCKS system proxy pointing at the Mixed inbound.
+/// Apply the HTTP/SOCKS system proxy pointing at the Mixed inbound — or,
+/// with the PAC strategy enabled, the auto-config URL serving a script that
+/// does (see `pac`).
 pub(crate) async fn set_system_proxy(app: &AppHandle) -> anyhow::Result<()> {
     let proxy_port = mixed_proxy_port(app);
+    if super::pac::load_settings(app).enabled {
+        return set_pac_proxy(app, proxy_port).await;
+    }
     let _ = app.emit(
         EVENT_TAURI_LOG,
         (
@@ -46,17 +60,135 @@ pub(crate) async fn set_system_proxy(app: &AppHandle) -> anyhow::Result<()> {
             format!("Start set system proxy: {}:{}", PROXY_HOST, proxy_port),
         ),
     );
-    platform_set_system_proxy(proxy_port, DEFAULT_BYPASS)?;
+    let bypass = super::proxy_bypass::system_bypass(app).await;
+    let handle = app.clone();
+    blocking(move || {
+        platform_set_system_proxy(&handle, proxy_port, &bypass)?;
+        // Switched from PAC to static while running: drop the auto-config URL.
+        clear_pac_proxy()
+    })
+    .await?;
+    super::proxy_guard::note_applied(Expected::Static { port: proxy_port });
     log::info!("Proxy set to {}:{}", PROXY_HOST, proxy_port);
     Ok(())
 }
 
+/// Run a platform proxy call off the async runtime: they shell out
+/// (gsettings, networksetup, pkexec) and can wait on a polkit prompt.
+async fn blocking<F>(f: F) -> anyhow::Result<()>
+where
+    F: FnOnce() -> anyhow::Result<()> + Send + 'static,
+{
+    tauri::async_runtime::spawn_blocking(f)
+        .await
+        .map_err(|e| anyhow::anyhow!("{}", e))?
+}
+
+async fn set_pac_proxy(app: &AppHandle, proxy_port: u16) -> anyhow::Result<()> {
+    let script = super::pac::current_script(app, proxy_port).await;
+    let url = super::pac::serve(app, script)
+        .await
+        .map_err(|e| anyhow::anyhow!(e))?;
+    let _ = app.emit(
+        EVENT_TAURI_LOG,
+        (0, format!("Start set system proxy (PAC): {}", url)),
+    );
+    platform_set_auto_proxy(app, &url, proxy_port).await?;
+    super::proxy_guard::note_applied(Expected::Pac {
+        url: url.clone(),
+        port: proxy_port,
+    });
+    log::info!("Proxy auto-config set to {}", url);
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+async fn platform_set_auto_proxy(
+    _app: &AppHandle,
+    url: &str,
+    proxy_port: u16,
+) -> anyhow::Result<()> {
+    let url = url.to_string();
+    blocking(move || {
+        super::proxy_snapshot::capture(proxy_port);
+        // A static proxy would take precedence over the PAC on most platforms.
+        platform_disable_static_proxy()?;
+        onebox_sysproxy_rs::Autoproxy { enable: true, url }
+            .set_auto_proxy()
+            .map_err(|e| anyhow::anyhow!("Autoproxy::set_auto_proxy failed: {}", e))
+    })
+    .await
+}
+
+#[cfg(target_os = "linux")]
+async fn platform_set_auto_proxy(
+    app: &AppHandle,
+    url: &str,
+    proxy_port: u16,
+) -> anyhow::Result<()> {
+    use crate::engine::linux::desktop_proxy::{self, Target};
+    let bypass = super::proxy_bypass::system_bypass(app).await;
+    let settings = desktop_proxy::load_settings(app);
+    let url = url.to_string();
+    blocking(move || {
+        desktop_proxy::apply(
+            &settings,
+            Target::Pac {
+                url: &url,
+                port: proxy_port,
+                bypass: &bypass,
+            },
+        )
+    })
+    .await
+}
+
+/// Turn off the auto-config URL if it is ours and stop the PAC listener.
+/// Without a running listener OneBox never set one this session, so a read
+/// failure is not an error.
+#[cfg(not(target_os = "linux"))]
+fn clear_pac_proxy() -> anyhow::Result<()> {
+    let serving = super::pac::is_serving();
+    let result = onebox_sysproxy_rs::Autoproxy::get_auto_proxy()
+        .map_err(|e| anyhow::anyhow!("Autoproxy::get_auto_proxy failed: {}", e))
+        .and_then(|mut auto| {
+            if !auto.enable || !super::pac::is_onebox_url(&auto.url) {
+                return Ok(());
+            }
+            auto.enable = false;
+            auto.set_auto_proxy()
+                .map_err(|e| anyhow::anyhow!("Autoproxy::set_auto_proxy failed: {}", e))
+        });
+    super::pac::stop();
+    match result {
+        Err(e) if !serving => {
+            log::debug!("[pac] {}", e);
+            Ok(())
+        }
+        other => other,
+    }
+}
+
+/// Linux: `desktop_proxy::restore` already put the desktop's own proxy
+/// mode back, auto-config URL included; only the listener is left.
+#[cfg(target_os = "linux")]
+fn clear_pac_proxy() -> anyhow::Result<()> {
+    super::pac::stop();
+    Ok(())
+}
+
 /// Clear whatever proxy was set. On macOS this disables the proxy on every
 /// service still pointing at OneBox (handles an interface switch since start);
-/// on other platforms it flips the active service's `enable` to false.
+/// on Windows it flips the active service's `enable` to false, and on Linux
+/// it restores the captured desktop and tool settings. A PAC
+/// auto-config URL OneBox set is turned off as well.
 pub(crate) async fn clear_system_proxy(app: &AppHandle) -> anyhow::Result<()> {
+    // Not while the drift guard is re-applying, or it could win the race.
+    let _exclusive = super::proxy_guard::exclusive().await;
+    super::proxy_guard::note_cleared();
     let _ = app.emit(EVENT_TAURI_LOG, (0, "Start unset system proxy"));
-    if let Err(e) = platform_clear_system_proxy() {
+    let cleared = blocking(|| platform_clear_system_proxy().and(clear_pac_proxy())).await;
+    if let Err(e) = cleared {
         let msg = format!("clear system proxy failed: {}", e);
         let _ = app.emit(EVENT_TAURI_LOG, (1, msg.clone()));
         return Err(anyhow::anyhow!(msg));
@@ -72,13 +204,56 @@ pub(crate) async fn clear_system_proxy(app: &AppHandle) -> anyhow::Result<()> {
 /// crate's `get_system_proxy`, which choked on a renamed service during
 /// shutdown ("failed to parse string `port`").
 pub(crate) fn clear_system_proxy_blocking() -> anyhow::Result<()> {
-    platform_clear_system_proxy()
+    super::proxy_guard::note_cleared();
+    platform_clear_system_proxy().and(clear_pac_proxy())
+}
+
+/// Launch-time cleanup for a proxy a dead session left behind: restore the
+/// captured prior settings if there are any, otherwise turn off a proxy or
+/// PAC URL that still points at OneBox. Skipped when this session already
+/// drives the system proxy (e.g. an adopted engine).
+pub(crate) fn recover_stale_proxy(app: &AppHandle) {
+    let mode = crate::core::ProcessManager::acquire().mode.clone();
+    if matches!(mode.as_deref(), Some(crate::engine::ProxyMode::SystemProxy)) {
+        return;
+    }
+    if let Err(e) = platform_recover_stale_proxy(mixed_proxy_port(app)) {
+        log::warn!("[sysproxy] stale proxy cleanup failed: {}", e);
+    }
+}
+
+/// Linux: `restore` is a no-op without a restore file.
+#[cfg(target_os = "linux")]
+fn platform_recover_stale_proxy(_onebox_port: u16) -> anyhow::Result<()> {
+    crate::engine::linux::desktop_proxy::restore()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn platform_recover_stale_proxy(onebox_port: u16) -> anyhow::Result<()> {
+    use super::proxy_snapshot;
+    if proxy_snapshot::exists() {
+        log::info!("[sysproxy] restore file left by a previous session; restoring");
+        return platform_clear_system_proxy();
+    }
+    let sys = onebox_sysproxy_rs::Sysproxy::get_system_proxy()
+        .map_err(|e| anyhow::anyhow!("Sysproxy::get_system_proxy failed: {}", e))?;
+    if sys.enable && proxy_snapshot::points_at_onebox(&sys.host, sys.port, onebox_port) {
+        log::info!(
+            "[sysproxy] stale OneBox proxy {}:{} without a restore file; turning it off",
+            sys.host,
+            sys.port
+        );
+        platform_disable_static_proxy()?;
+    }
+    clear_pac_proxy()
 }
 
 /// Apply the proxy on the active service. Cross-platform: macOS service
 /// resolution + exit-status checking live in `onebox_sysproxy_rs`, so a failed
 /// `networksetup` call now returns an error here instead of being swallowed.
-fn platform_set_system_proxy(port: u16, bypass: &str) -> anyhow::Result<()> {
+#[cfg(not(target_os = "linux"))]
+fn platform_set_system_proxy(_app: &AppHandle, port: u16, bypass: &str) -> anyhow::Result<()> {
+    super::proxy_snapshot::capture(port);
     let sys = onebox_sysproxy_rs::Sysproxy {
         enable: true,
         host: PROXY_HOST.to_string(),
@@ -88,17 +263,43 @@ fn platform_set_system_proxy(port: u16, bypass: &str) -> anyhow::Result<()> {
     sys.set_system_proxy().map_err(|e| anyhow::anyhow!(e))
 }
 
+/// macOS / Windows: turn OneBox's proxy off, then put back whatever the
+/// user had before (`proxy_snapshot`). Both steps run even if the first
+/// one fails.
+#[cfg(not(target_os = "linux"))]
+fn platform_clear_system_proxy() -> anyhow::Result<()> {
+    let disabled = platform_disable_static_proxy();
+    let restored = super::proxy_snapshot::restore();
+    disabled.and(restored.map(|_| ()))
+}
+
 /// macOS: disable the proxy on every service still pointing at OneBox, so a
 /// stale proxy isn't left behind if the active interface changed since start.
 #[cfg(target_os = "macos")]
-fn platform_clear_system_proxy() -> anyhow::Result<()> {
+fn platform_disable_static_proxy() -> anyhow::Result<()> {
     onebox_sysproxy_rs::clear_proxy(PROXY_HOST).map_err(|e| anyhow::anyhow!(e))
 }
 
+/// Linux: capture what the desktop / tools had, then point them at OneBox.
+#[cfg(target_os = "linux")]
+fn platform_set_system_proxy(app: &AppHandle, port: u16, bypass: &str) -> anyhow::Result<()> {
+    use crate::engine::linux::desktop_proxy::{self, Target};
+    desktop_proxy::apply(
+        &desktop_proxy::load_settings(app),
+        Target::Static { port, bypass },
+    )
+}
+
+/// Linux: put back exactly what `platform_set_system_proxy` captured.
+#[cfg(target_os = "linux")]
+fn platform_clear_system_proxy() -> anyhow::Result<()> {
+    crate::engine::linux::desktop_proxy::restore()
+}
+
 /// Other platforms: read the current setting and flip `enable` off, keeping any
 /// non-proxy fields (bypass list) intact.
-#[cfg(not(target_os = "macos"))]
-fn platform_clear_system_proxy() -> anyhow::Result<()> {
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
+fn platform_disable_static_proxy() -> anyhow::Result<()> {
     let mut sysproxy = onebox_sysproxy_rs::Sysproxy::get_system_proxy()
         .map_err(|e| anyhow::anyhow!("Sysproxy::get_system_proxy failed: {}", e))?;
     sysproxy.enable = false;
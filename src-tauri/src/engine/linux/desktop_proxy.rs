@@ -0,0 +1,820 @@
+//! Linux system-proxy backends.
+//!
+//! `onebox_sysproxy_rs` only drives GNOME's gsettings, so KDE Plasma and
+//! tiling-WM users got nothing. Here the desktop is detected from
+//! `XDG_CURRENT_DESKTOP` (or forced in `settings.json` under
+//! `linux_sysproxy.desktop`):
+//!
+//!   * GNOME and its derivatives — `org.gnome.system.proxy` via `gsettings`
+//!   * KDE — `[Proxy Settings]` in `kioslaverc` via `kwriteconfig6/5`
+//!   * anything else — no desktop setting
+//!
+//! and, each opt-in, the places command-line tools look:
+//!
+//!   * `environment_d` — `~/.config/environment.d/90-onebox-proxy.conf`
+//!     (`http_proxy` / `https_proxy` / `all_proxy` / `no_proxy`, both
+//!     cases); picked up by new login sessions
+//!   * `git` — `git config --global http.proxy` / `https.proxy`
+//!   * `npm` — `npm config set proxy` / `https-proxy`
+//!   * `apt` — `/etc/apt/apt.conf.d/95onebox-proxy`, written through the
+//!     pkexec helper
+//!
+//! Before the first write, every value about to be overwritten is captured
+//! into `linux-proxy-restore.json` in the app data dir. `restore` puts those
+//! exact values back — including "unset" — and deletes the file, so a crash
+//! between set and clear is still undone on the next clear. A re-apply
+//! within the same session keeps the original capture.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+use tauri_plugin_store::StoreExt;
+
+use crate::engine::config_switch::write_atomic;
+use crate::engine::proxy_snapshot::ProxySnapshot;
+
+const SETTINGS_STORE: &str = "settings.json";
+const LINUX_SYSPROXY_KEY: &str = "linux_sysproxy";
+const RESTORE_FILE_NAME: &str = "linux-proxy-restore.json";
+const ENVIRONMENT_D_FILE_NAME: &str = "90-onebox-proxy.conf";
+const PROXY_HOST: &str = "127.0.0.1";
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DesktopChoice {
+    #[default]
+    Auto,
+    Gnome,
+    Kde,
+    None,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct LinuxProxySettings {
+    pub desktop: DesktopChoice,
+    pub environment_d: bool,
+    pub git: bool,
+    pub npm: bool,
+    pub apt: bool,
+}
+
+pub(crate) fn load_settings(app: &AppHandle) -> LinuxProxySettings {
+    app.get_store(SETTINGS_STORE)
+        .and_then(|store| store.get(LINUX_SYSPROXY_KEY))
+        .and_then(|value| serde_json::from_value(value).ok())
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Desktop {
+    Gnome,
+    Kde,
+    None,
+}
+
+/// `XDG_CURRENT_DESKTOP` is a colon-separated list, e.g. `ubuntu:GNOME`.
+/// Cinnamon, Budgie, Unity, Pantheon and MATE read the GNOME proxy schema.
+pub fn detect_desktop(xdg_current_desktop: &str) -> Desktop {
+    let names: Vec<String> = xdg_current_desktop
+        .split(':')
+        .map(|n| n.trim().to_ascii_uppercase())
+        .collect();
+    if names.iter().any(|n| n == "KDE") {
+        return Desktop::Kde;
+    }
+    const GNOME_LIKE: [&str; 7] = [
+        "GNOME",
+        "UNITY",
+        "CINNAMON",
+        "X-CINNAMON",
+        "BUDGIE",
+        "PANTHEON",
+        "MATE",
+    ];
+    if names.iter().any(|n| GNOME_LIKE.contains(&n.as_str())) {
+        Desktop::Gnome
+    } else {
+        Desktop::None
+    }
+}
+
+fn desktop(choice: DesktopChoice) -> Desktop {
+    match choice {
+        DesktopChoice::Auto => {
+            detect_desktop(&std::env::var("XDG_CURRENT_DESKTOP").unwrap_or_default())
+        }
+        DesktopChoice::Gnome => Desktop::Gnome,
+        DesktopChoice::Kde => Desktop::Kde,
+        DesktopChoice::None => Desktop::None,
+    }
+}
+
+/// What to point the desktop at. Command-line tools don't read PAC, so
+/// they get the static proxy either way.
+pub(crate) enum Target<'a> {
+    Static {
+        port: u16,
+        bypass: &'a str,
+    },
+    Pac {
+        url: &'a str,
+        port: u16,
+        bypass: &'a str,
+    },
+}
+
+impl Target<'_> {
+    fn port(&self) -> u16 {
+        match self {
+            Target::Static { port, .. } | Target::Pac { port, .. } => *port,
+        }
+    }
+
+    fn bypass(&self) -> &str {
+        match self {
+            Target::Static { bypass, .. } | Target::Pac { bypass, .. } => bypass,
+        }
+    }
+}
+
+/// A value OneBox overwrote and what it was before. `None` = was unset.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+enum Prior {
+    /// `value` is `gsettings get` output (GVariant text), which `gsettings
+    /// set` accepts back verbatim.
+    Gsettings {
+        schema: String,
+        key: String,
+        value: String,
+    },
+    Kioslaverc {
+        key: String,
+        value: Option<String>,
+    },
+    File {
+        path: PathBuf,
+        content: Option<String>,
+    },
+    Git {
+        key: String,
+        value: Option<String>,
+    },
+    Npm {
+        key: String,
+        value: Option<String>,
+    },
+    /// Our own drop-in; restoring means removing it.
+    Apt,
+}
+
+impl Prior {
+    /// What the value belongs to; one capture per target.
+    fn target(&self) -> String {
+        match self {
+            Prior::Gsettings { schema, key, .. } => format!("gsettings:{}:{}", schema, key),
+            Prior::Kioslaverc { key, .. } => format!("kioslaverc:{}", key),
+            Prior::File { path, .. } => format!("file:{}", path.display()),
+            Prior::Git { key, .. } => format!("git:{}", key),
+            Prior::Npm { key, .. } => format!("npm:{}", key),
+            Prior::Apt => "apt".into(),
+        }
+    }
+}
+
+/// Add `prior` unless its target was already captured — the first capture
+/// is the one that predates OneBox.
+fn remember(snapshot: &mut Vec<Prior>, prior: Prior) {
+    let target = prior.target();
+    if !snapshot.iter().any(|p| p.target() == target) {
+        snapshot.push(prior);
+    }
+}
+
+fn restore_file_path() -> Option<PathBuf> {
+    crate::engine::proxy_snapshot::app_data_dir().map(|dir| dir.join(RESTORE_FILE_NAME))
+}
+
+fn load_snapshot(path: &Path) -> Vec<Prior> {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default()
+}
+
+fn save_snapshot(path: &Path, snapshot: &[Prior]) -> anyhow::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let text = serde_json::to_string_pretty(snapshot)?;
+    write_atomic(path, text.as_bytes())
+        .map_err(|e| anyhow::anyhow!("failed to write {}: {}", path.display(), e))
+}
+
+fn run(program: &str, args: &[&str]) -> anyhow::Result<String> {
+    let out = Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| anyhow::anyhow!("{} failed to start: {}", program, e))?;
+    if !out.status.success() {
+        anyhow::bail!(
+            "{} {} exited with {}: {}",
+            program,
+            args.join(" "),
+            out.status,
+            String::from_utf8_lossy(&out.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).trim_end().to_string())
+}
+
+/// `Ok(None)` when the command ran but reported the value as unset
+/// (non-zero exit), `Err` when it couldn't run at all.
+fn run_optional(program: &str, args: &[&str]) -> anyhow::Result<Option<String>> {
+    let out = Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| anyhow::anyhow!("{} failed to start: {}", program, e))?;
+    Ok(out
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&out.stdout).trim_end().to_string()))
+}
+
+fn proxy_url(port: u16) -> String {
+    format!("http://{}:{}", PROXY_HOST, port)
+}
+
+// ── GNOME ─────────────────────────────────────────────────────────────
+
+const GNOME_KEYS: [(&str, &str); 9] = [
+    ("org.gnome.system.proxy", "mode"),
+    ("org.gnome.system.proxy", "autoconfig-url"),
+    ("org.gnome.system.proxy", "ignore-hosts"),
+    ("org.gnome.system.proxy.http", "host"),
+    ("org.gnome.system.proxy.http", "port"),
+    ("org.gnome.system.proxy.https", "host"),
+    ("org.gnome.system.proxy.https", "port"),
+    ("org.gnome.system.proxy.socks", "host"),
+    ("org.gnome.system.proxy.socks", "port"),
+];
+
+fn gvariant_string(s: &str) -> String {
+    format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+/// Comma-separated bypass list → GVariant `as`, e.g. `['localhost', '::1']`.
+fn gvariant_string_list(bypass: &str) -> String {
+    let items: Vec<String> = bypass
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(gvariant_string)
+        .collect();
+    if items.is_empty() {
+        "@as []".into()
+    } else {
+        format!("[{}]", items.join(", "))
+    }
+}
+
+fn gnome_capture(snapshot: &mut Vec<Prior>) -> anyhow::Result<()> {
+    for (schema, key) in GNOME_KEYS {
+        let value = run("gsettings", &["get", schema, key])?;
+        remember(
+            snapshot,
+            Prior::Gsettings {
+                schema: schema.into(),
+                key: key.into(),
+                value,
+            },
+        );
+    }
+    Ok(())
+}
+
+fn gnome_apply(target: &Target) -> anyhow::Result<()> {
+    let set = |schema: &str, key: &str, value: &str| -> anyhow::Result<()> {
+        run("gsettings", &["set", schema, key, value]).map(|_| ())
+    };
+    let host = gvariant_string(PROXY_HOST);
+    let port = target.port().to_string();
+    for schema in [
+        "org.gnome.system.proxy.http",
+        "org.gnome.system.proxy.https",
+        "org.gnome.system.proxy.socks",
+    ] {
+        set(schema, "host", &host)?;
+        set(schema, "port", &port)?;
+    }
+    set(
+        "org.gnome.system.proxy",
+        "ignore-hosts",
+        &gvariant_string_list(target.bypass()),
+    )?;
+    match target {
+        Target::Static { .. } => set("org.gnome.system.proxy", "mode", "'manual'"),
+        Target::Pac { url, .. } => {
+            set(
+                "org.gnome.system.proxy",
+                "autoconfig-url",
+                &gvariant_string(url),
+            )?;
+            set("org.gnome.system.proxy", "mode", "'auto'")
+        }
+    }
+}
+
+// ── KDE ───────────────────────────────────────────────────────────────
+
+const KDE_GROUP: &str = "Proxy Settings";
+const KDE_KEYS: [&str; 6] = [
+    "ProxyType",
+    "httpProxy",
+    "httpsProxy",
+    "socksProxy",
+    "NoProxyFor",
+    "Proxy Config Script",
+];
+
+/// Value of `key` in `[group]` of an INI-style KDE config, `None` if absent.
+fn ini_value(text: &str, group: &str, key: &str) -> Option<String> {
+    let header = format!("[{}]", group);
+    let mut in_group = false;
+    for line in text.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_group = line == header;
+            continue;
+        }
+        if !in_group {
+            continue;
+        }
+        if let Some((k, v)) = line.split_once('=') {
+            // `key[$e]=` marks a shell-expanded value; the key is the same.
+            if k.trim().trim_end_matches("[$e]") == key {
+                return Some(v.to_string());
+            }
+        }
+    }
+    None
+}
+
+fn kioslaverc_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("kioslaverc"))
+}
+
+/// Plasma 6 ships `kwriteconfig6`, Plasma 5 `kwriteconfig5`.
+fn kwriteconfig(args: &[&str]) -> anyhow::Result<()> {
+    let mut last = None;
+    for program in ["kwriteconfig6", "kwriteconfig5"] {
+        match run(program, args) {
+            Ok(_) => return Ok(()),
+            Err(e) => last = Some(e),
+        }
+    }
+    Err(last.unwrap_or_else(|| anyhow::anyhow!("kwriteconfig not found")))
+}
+
+fn kde_write(key: &str, value: Option<&str>) -> anyhow::Result<()> {
+    let mut args = vec!["--file", "kioslaverc", "--group", KDE_GROUP, "--key", key];
+    match value {
+        Some(value) => args.push(value),
+        None => args.push("--delete"),
+    }
+    kwriteconfig(&args)
+}
+
+/// Running KIO workers only re-read `kioslaverc` when told to.
+fn kde_notify() {
+    let _ = Command::new("dbus-send")
+        .args([
+            "--type=signal",
+            "/KIO/Scheduler",
+            "org.kde.KIO.Scheduler.reparseSlaveConfiguration",
+            "string:",
+        ])
+        .status();
+}
+
+fn kde_capture(snapshot: &mut Vec<Prior>) {
+    let text = kioslaverc_path()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .unwrap_or_default();
+    for key in KDE_KEYS {
+        remember(
+            snapshot,
+            Prior::Kioslaverc {
+                key: key.into(),
+                value: ini_value(&text, KDE_GROUP, key),
+            },
+        );
+    }
+}
+
+fn kde_apply(target: &Target) -> anyhow::Result<()> {
+    // KDE stores "scheme://host port" — a space, not a colon.
+    let endpoint = |scheme: &str| format!("{}://{} {}", scheme, PROXY_HOST, target.port());
+    kde_write("httpProxy", Some(&endpoint("http")))?;
+    kde_write("httpsProxy", Some(&endpoint("http")))?;
+    kde_write("socksProxy", Some(&endpoint("socks")))?;
+    kde_write("NoProxyFor", Some(target.bypass()))?;
+    match target {
+        Target::Static { .. } => kde_write("ProxyType", Some("1"))?,
+        Target::Pac { url, .. } => {
+            kde_write("Proxy Config Script", Some(url))?;
+            kde_write("ProxyType", Some("2"))?;
+        }
+    }
+    kde_notify();
+    Ok(())
+}
+
+// ── Command-line tools ────────────────────────────────────────────────
+
+fn environment_d_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("environment.d").join(ENVIRONMENT_D_FILE_NAME))
+}
+
+fn environment_file(port: u16, bypass: &str) -> String {
+    let http = proxy_url(port);
+    let socks = format!("socks5://{}:{}", PROXY_HOST, port);
+    let mut text =
+        String::from("# Managed by OneBox; removed when the system proxy is turned off.\n");
+    for (name, value) in [
+        ("http_proxy", http.as_str()),
+        ("https_proxy", http.as_str()),
+        ("all_proxy", socks.as_str()),
+        ("no_proxy", bypass),
+    ] {
+        text.push_str(&format!("{}={}\n", name, value));
+        text.push_str(&format!("{}={}\n", name.to_ascii_uppercase(), value));
+    }
+    text
+}
+
+const GIT_KEYS: [&str; 2] = ["http.proxy", "https.proxy"];
+const NPM_KEYS: [&str; 2] = ["proxy", "https-proxy"];
+
+fn git_get(key: &str) -> anyhow::Result<Option<String>> {
+    run_optional("git", &["config", "--global", "--get", key])
+}
+
+/// `npm config get` prints `null` for an unset key.
+fn npm_get(key: &str) -> anyhow::Result<Option<String>> {
+    run("npm", &["config", "get", key])
+        .map(|v| (v != "null" && v != "undefined" && !v.is_empty()).then_some(v))
+}
+
+/// Written by the helper's `apt-proxy-set`, removed by `apt-proxy-clear`.
+const APT_PROXY_FILE: &str = "/etc/apt/apt.conf.d/95onebox-proxy";
+
+/// What `apt-proxy-set <port>` writes; kept in step with the helper.
+fn apt_proxy_conf(port: u16) -> String {
+    let url = proxy_url(port);
+    format!(
+        "Acquire::http::Proxy \"{}\";\nAcquire::https::Proxy \"{}\";\n",
+        url, url
+    )
+}
+
+/// Both apt verbs go through pkexec, which prompts unless polkit still
+/// holds an admin authorisation; skip them when the file is already right.
+fn apt_set(port: u16) -> anyhow::Result<()> {
+    if std::fs::read_to_string(APT_PROXY_FILE).ok() == Some(apt_proxy_conf(port)) {
+        return Ok(());
+    }
+    run(
+        "pkexec",
+        &[super::HELPER_PATH, "apt-proxy-set", &port.to_string()],
+    )
+    .map(|_| ())
+}
+
+fn apt_clear() -> anyhow::Result<()> {
+    if !std::path::Path::new(APT_PROXY_FILE).exists() {
+        return Ok(());
+    }
+    run("pkexec", &[super::HELPER_PATH, "apt-proxy-clear"]).map(|_| ())
+}
+
+/// Capture `keys` of an opt-in tool. `false` — skip the tool — when it
+/// can't be queried (usually: not installed).
+fn capture_tool(
+    snapshot: &mut Vec<Prior>,
+    tool: &str,
+    keys: &[&str],
+    get: fn(&str) -> anyhow::Result<Option<String>>,
+    prior: fn(String, Option<String>) -> Prior,
+) -> bool {
+    let mut captured = Vec::new();
+    for key in keys {
+        match get(key) {
+            Ok(value) => captured.push(prior(key.to_string(), value)),
+            Err(e) => {
+                log::warn!("[sysproxy] skipping {} proxy: {}", tool, e);
+                return false;
+            }
+        }
+    }
+    for prior in captured {
+        remember(snapshot, prior);
+    }
+    true
+}
+
+// ── Live state ────────────────────────────────────────────────────────
+
+/// `'text'` → `text`; anything else (numbers, `@as []`) is returned as is.
+fn gvariant_unquote(value: &str) -> String {
+    let value = value.trim();
+    match value
+        .strip_prefix('\'')
+        .and_then(|rest| rest.strip_suffix('\''))
+    {
+        Some(inner) => inner.replace("\\'", "'").replace("\\\\", "\\"),
+        None => value.to_string(),
+    }
+}
+
+/// KDE's `"http://127.0.0.1 7890"` → host and port.
+fn kde_endpoint(value: &str) -> Option<(String, u16)> {
+    let rest = value.split_once("://").map_or(value, |(_, rest)| rest);
+    let (host, port) = rest.trim().rsplit_once(' ')?;
+    Some((host.trim().to_string(), port.trim().parse().ok()?))
+}
+
+fn gnome_live() -> anyhow::Result<ProxySnapshot> {
+    let get = |schema: &str, key: &str| -> anyhow::Result<String> {
+        run("gsettings", &["get", schema, key]).map(|v| gvariant_unquote(&v))
+    };
+    let mode = get("org.gnome.system.proxy", "mode")?;
+    Ok(ProxySnapshot {
+        enable: mode == "manual",
+        host: get("org.gnome.system.proxy.http", "host")?,
+        port: get("org.gnome.system.proxy.http", "port")?
+            .parse()
+            .unwrap_or(0),
+        pac_enable: mode == "auto",
+        pac_url: get("org.gnome.system.proxy", "autoconfig-url")?,
+        ..Default::default()
+    })
+}
+
+fn kde_live(text: &str) -> ProxySnapshot {
+    let value = |key: &str| ini_value(text, KDE_GROUP, key).unwrap_or_default();
+    let proxy_type = value("ProxyType");
+    let (host, port) = kde_endpoint(&value("httpProxy")).unwrap_or_default();
+    ProxySnapshot {
+        enable: proxy_type == "1",
+        host,
+        port,
+        pac_enable: proxy_type == "2",
+        pac_url: value("Proxy Config Script"),
+        ..Default::default()
+    }
+}
+
+/// What the desktop proxy setting currently says, in the same shape the
+/// other platforms read. `None` when no supported desktop is in use —
+/// there is nothing to compare against.
+pub(crate) fn read_live(settings: &LinuxProxySettings) -> anyhow::Result<Option<ProxySnapshot>> {
+    let mut live = match desktop(settings.desktop) {
+        Desktop::Gnome => gnome_live()?,
+        Desktop::Kde => kde_live(
+            &kioslaverc_path()
+                .and_then(|path| std::fs::read_to_string(path).ok())
+                .unwrap_or_default(),
+        ),
+        Desktop::None => return Ok(None),
+    };
+    live.captured_at = chrono::Local::now().timestamp();
+    Ok(Some(live))
+}
+
+// ── Entry points ──────────────────────────────────────────────────────
+
+/// Point the detected desktop (and enabled tools) at OneBox, capturing
+/// whatever they were set to first. The desktop setting is required to
+/// succeed; an opt-in tool that isn't installed is skipped with a warning.
+pub(crate) fn apply(settings: &LinuxProxySettings, target: Target) -> anyhow::Result<()> {
+    let restore_path =
+        restore_file_path().ok_or_else(|| anyhow::anyhow!("no data directory for restore file"))?;
+    let mut snapshot = load_snapshot(&restore_path);
+    let desktop = desktop(settings.desktop);
+
+    // Capture everything before touching anything.
+    match desktop {
+        Desktop::Gnome => gnome_capture(&mut snapshot)?,
+        Desktop::Kde => kde_capture(&mut snapshot),
+        Desktop::None => {}
+    }
+    let env_path = environment_d_path().filter(|_| settings.environment_d);
+    if let Some(path) = &env_path {
+        remember(
+            &mut snapshot,
+            Prior::File {
+                path: path.clone(),
+                content: std::fs::read_to_string(path).ok(),
+            },
+        );
+    }
+    let git = settings.git
+        && capture_tool(&mut snapshot, "git", &GIT_KEYS, git_get, |key, value| {
+            Prior::Git { key, value }
+        });
+    let npm = settings.npm
+        && capture_tool(&mut snapshot, "npm", &NPM_KEYS, npm_get, |key, value| {
+            Prior::Npm { key, value }
+        });
+    if settings.apt {
+        remember(&mut snapshot, Prior::Apt);
+    }
+    save_snapshot(&restore_path, &snapshot)?;
+
+    match desktop {
+        Desktop::Gnome => gnome_apply(&target)?,
+        Desktop::Kde => kde_apply(&target)?,
+        Desktop::None => log::info!(
+            "[sysproxy] no supported desktop detected; only opted-in tool settings are written"
+        ),
+    }
+    let port = target.port();
+    if let Some(path) = &env_path {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        write_atomic(path, environment_file(port, target.bypass()).as_bytes())
+            .map_err(|e| anyhow::anyhow!("failed to write {}: {}", path.display(), e))?;
+    }
+    let url = proxy_url(port);
+    for key in GIT_KEYS.iter().filter(|_| git) {
+        if let Err(e) = run("git", &["config", "--global", key, &url]) {
+            log::warn!("[sysproxy] git {}: {}", key, e);
+        }
+    }
+    for key in NPM_KEYS.iter().filter(|_| npm) {
+        if let Err(e) = run("npm", &["config", "set", key, &url]) {
+            log::warn!("[sysproxy] npm {}: {}", key, e);
+        }
+    }
+    if settings.apt {
+        if let Err(e) = apt_set(port) {
+            log::warn!("[sysproxy] apt proxy: {}", e);
+        }
+    }
+    Ok(())
+}
+
+fn restore_one(prior: &Prior) -> anyhow::Result<()> {
+    match prior {
+        Prior::Gsettings { schema, key, value } => {
+            run("gsettings", &["set", schema, key, value]).map(|_| ())
+        }
+        Prior::Kioslaverc { key, value } => kde_write(key, value.as_deref()),
+        Prior::File { path, content } => match content {
+            Some(content) => write_atomic(path, content.as_bytes())
+                .map_err(|e| anyhow::anyhow!("failed to write {}: {}", path.display(), e)),
+            None => match std::fs::remove_file(path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(anyhow::anyhow!(
+                    "failed to remove {}: {}",
+                    path.display(),
+                    e
+                )),
+                _ => Ok(()),
+            },
+        },
+        Prior::Git { key, value } => match value {
+            Some(value) => run("git", &["config", "--global", key, value]).map(|_| ()),
+            // Exit 5 = already unset; nothing to undo.
+            None => run_optional("git", &["config", "--global", "--unset", key]).map(|_| ()),
+        },
+        Prior::Npm { key, value } => match value {
+            Some(value) => run("npm", &["config", "set", key, value]).map(|_| ()),
+            None => run("npm", &["config", "delete", key]).map(|_| ()),
+        },
+        Prior::Apt => apt_clear(),
+    }
+}
+
+/// Put back every captured value, newest capture first. Values that fail to
+/// restore stay in the restore file so the next clear retries them.
+pub(crate) fn restore() -> anyhow::Result<()> {
+    let Some(restore_path) = restore_file_path() else {
+        return Ok(());
+    };
+    let snapshot = load_snapshot(&restore_path);
+    if snapshot.is_empty() {
+        let _ = std::fs::remove_file(&restore_path);
+        return Ok(());
+    }
+    let mut failed = Vec::new();
+    let mut errors = Vec::new();
+    for prior in snapshot.iter().rev() {
+        if let Err(e) = restore_one(prior) {
+            errors.push(format!("{}: {}", prior.target(), e));
+            failed.insert(0, prior.clone());
+        }
+    }
+    if snapshot
+        .iter()
+        .any(|p| matches!(p, Prior::Kioslaverc { .. }))
+    {
+        kde_notify();
+    }
+    if failed.is_empty() {
+        let _ = std::fs::remove_file(&restore_path);
+        return Ok(());
+    }
+    save_snapshot(&restore_path, &failed)?;
+    anyhow::bail!("restore incomplete: {}", errors.join("; "))
+}
+
+#[cfg(test)]
+mod desktop_proxy_tests {
+    use super::*;
+
+    #[test]
+    fn desktop_detection_follows_xdg_current_desktop() {
+        assert_eq!(detect_desktop("ubuntu:GNOME"), Desktop::Gnome);
+        assert_eq!(detect_desktop("X-Cinnamon"), Desktop::Gnome);
+        assert_eq!(detect_desktop("KDE"), Desktop::Kde);
+        assert_eq!(detect_desktop("sway"), Desktop::None);
+        assert_eq!(detect_desktop(""), Desktop::None);
+    }
+
+    #[test]
+    fn gvariant_lists_are_quoted_and_escaped() {
+        assert_eq!(
+            gvariant_string_list("localhost, 10.0.0.0/8,,it's"),
+            r"['localhost', '10.0.0.0/8', 'it\'s']"
+        );
+        assert_eq!(gvariant_string_list(""), "@as []");
+    }
+
+    #[test]
+    fn kioslaverc_values_distinguish_empty_from_absent() {
+        let text = "[Other]\nProxyType=9\n\n[Proxy Settings]\nProxyType=0\nNoProxyFor=\nhttpProxy[$e]=$HTTP\n";
+        assert_eq!(ini_value(text, KDE_GROUP, "ProxyType"), Some("0".into()));
+        assert_eq!(
+            ini_value(text, KDE_GROUP, "NoProxyFor"),
+            Some(String::new())
+        );
+        assert_eq!(
+            ini_value(text, KDE_GROUP, "httpProxy"),
+            Some("$HTTP".into())
+        );
+        assert_eq!(ini_value(text, KDE_GROUP, "socksProxy"), None);
+    }
+
+    #[test]
+    fn live_state_reads_back_what_apply_writes() {
+        assert_eq!(gvariant_unquote("'127.0.0.1'"), "127.0.0.1");
+        assert_eq!(gvariant_unquote(r"'it\'s'"), "it's");
+        assert_eq!(gvariant_unquote("7890"), "7890");
+
+        let text = "[Proxy Settings]\nProxyType=1\nhttpProxy=http://127.0.0.1 7890\n";
+        let live = kde_live(text);
+        assert!(live.enable && !live.pac_enable);
+        assert_eq!((live.host.as_str(), live.port), ("127.0.0.1", 7890));
+        assert_eq!(kde_endpoint("proxy.corp"), None);
+    }
+
+    #[test]
+    fn first_capture_wins_and_survives_a_round_trip() {
+        let mut snapshot = Vec::new();
+        remember(
+            &mut snapshot,
+            Prior::Git {
+                key: "http.proxy".into(),
+                value: None,
+            },
+        );
+        remember(
+            &mut snapshot,
+            Prior::Git {
+                key: "http.proxy".into(),
+                value: Some("http://127.0.0.1:7890".into()),
+            },
+        );
+        remember(&mut snapshot, Prior::Apt);
+        assert_eq!(snapshot.len(), 2);
+        let text = serde_json::to_string(&snapshot).unwrap();
+        assert!(text.contains(r#""kind":"git""#));
+        let back: Vec<Prior> = serde_json::from_str(&text).unwrap();
+        assert_eq!(back, snapshot);
+
+        let env = environment_file(7890, "localhost,::1");
+        assert!(env.contains("https_proxy=http://127.0.0.1:7890\n"));
+        assert!(env.contains("ALL_PROXY=socks5://127.0.0.1:7890\n"));
+        assert!(env.contains("NO_PROXY=localhost,::1\n"));
+
+        assert_eq!(
+            apt_proxy_conf(7890),
+            "Acquire::http::Proxy \"http://127.0.0.1:7890\";\n\
+             Acquire::https::Proxy \"http://127.0.0.1:7890\";\n"
+        );
+    }
+}
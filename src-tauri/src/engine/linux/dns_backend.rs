@@ -0,0 +1,275 @@
+//! Which mechanism the Linux TUN DNS override goes through.
+//!
+//! The override used to be hardwired to `resolvectl`, which does nothing
+//! for glibc when `/etc/resolv.conf` is not systemd-resolved's stub —
+//! NetworkManager with dnsmasq, resolvconf, or a plain hand-kept file. The
+//! backend is now picked per start from what the system actually uses (or
+//! forced in `settings.json` under `linux_dns.backend`):
+//!
+//!   * `resolved` — `resolv.conf` is the systemd-resolved stub:
+//!     `resolvectl dns <iface> <gw> <original…>`, restored by re-applying
+//!     the captured servers
+//!   * `network_manager` — NetworkManager manages the interface:
+//!     `nmcli device modify` on the applied connection only (the saved
+//!     profile is untouched), restored by `nmcli device reapply`
+//!   * `resolvconf` — `resolv.conf` is generated by resolvconf / openresolv:
+//!     a `lo.onebox` record (exclusive where openresolv supports it),
+//!     restored by deleting the record
+//!   * `resolv_conf_file` — anything else: `/etc/resolv.conf` is rewritten
+//!     after a backup to `/etc/resolv.conf.onebox-backup`, restored by
+//!     moving the backup back. The backup outlives a crash, so the restore
+//!     still works from the next launch's orphan cleanup.
+//!
+//! Only `resolved` keeps the original servers behind the gateway: the other
+//! resolvers may query every listed server in parallel, which would leak.
+//! The privileged half lives in `onebox-tun-helper`; the backend travels
+//! there as its helper name.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+use tauri_plugin_store::StoreExt;
+
+use crate::engine::helper::command_stdout;
+
+const SETTINGS_STORE: &str = "settings.json";
+pub(crate) const LINUX_DNS_KEY: &str = "linux_dns";
+const RESOLV_CONF: &str = "/etc/resolv.conf";
+const RESOLVED_STUB: &str = "127.0.0.53";
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DnsBackendChoice {
+    #[default]
+    Auto,
+    Resolved,
+    NetworkManager,
+    Resolvconf,
+    ResolvConfFile,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct LinuxDnsSettings {
+    pub backend: DnsBackendChoice,
+}
+
+pub(crate) fn load_settings(app: &AppHandle) -> LinuxDnsSettings {
+    app.get_store(SETTINGS_STORE)
+        .and_then(|store| store.get(LINUX_DNS_KEY))
+        .and_then(|value| serde_json::from_value(value).ok())
+        .unwrap_or_default()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DnsBackend {
+    Resolved,
+    NetworkManager,
+    Resolvconf,
+    ResolvConfFile,
+}
+
+impl DnsBackend {
+    /// The name `onebox-tun-helper` takes, also what the runtime record keeps.
+    pub fn helper_name(self) -> &'static str {
+        match self {
+            DnsBackend::Resolved => "resolved",
+            DnsBackend::NetworkManager => "network-manager",
+            DnsBackend::Resolvconf => "resolvconf",
+            DnsBackend::ResolvConfFile => "resolv-conf-file",
+        }
+    }
+
+    /// Inverse of `helper_name`. A record without a backend predates the
+    /// choice and was made with `resolvectl`.
+    pub fn from_helper_name(name: Option<&str>) -> Self {
+        match name {
+            Some("network-manager") => DnsBackend::NetworkManager,
+            Some("resolvconf") => DnsBackend::Resolvconf,
+            Some("resolv-conf-file") => DnsBackend::ResolvConfFile,
+            _ => DnsBackend::Resolved,
+        }
+    }
+
+    /// Whether restoring needs the original servers (the others undo their
+    /// own change without them).
+    pub fn restores_from_capture(self) -> bool {
+        self == DnsBackend::Resolved
+    }
+}
+
+/// What the system looks like, as far as picking a backend goes.
+#[derive(Debug, Default)]
+pub struct DnsSystem {
+    /// Where `/etc/resolv.conf` points, if it is a symlink.
+    pub resolv_conf_target: Option<PathBuf>,
+    pub resolv_conf: String,
+    pub resolved_active: bool,
+    pub nm_manages_iface: bool,
+    pub resolvconf_installed: bool,
+}
+
+pub fn detect(system: &DnsSystem) -> DnsBackend {
+    let target = system
+        .resolv_conf_target
+        .as_deref()
+        .map(|p| p.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let stub = target.contains("/systemd/resolve/")
+        || resolv_conf_nameservers(&system.resolv_conf) == [RESOLVED_STUB];
+    if system.resolved_active && stub {
+        return DnsBackend::Resolved;
+    }
+    if system.nm_manages_iface {
+        return DnsBackend::NetworkManager;
+    }
+    // Debian's resolvconf symlinks the file; openresolv writes it in place
+    // with its own header.
+    let by_resolvconf = target.contains("resolvconf")
+        || system
+            .resolv_conf
+            .lines()
+            .take_while(|l| l.starts_with('#'))
+            .any(|l| l.contains("resolvconf"));
+    if system.resolvconf_installed && by_resolvconf {
+        return DnsBackend::Resolvconf;
+    }
+    DnsBackend::ResolvConfFile
+}
+
+/// `nameserver` entries of a resolv.conf, in order.
+pub fn resolv_conf_nameservers(text: &str) -> Vec<&str> {
+    text.lines()
+        .filter_map(|line| {
+            let mut words = line.split_whitespace();
+            (words.next() == Some("nameserver"))
+                .then(|| words.next())
+                .flatten()
+        })
+        .collect()
+}
+
+/// `nmcli -t -f GENERAL.STATE dev show <iface>` → whether NM has the
+/// device up (`GENERAL.STATE:100 (connected)`).
+pub fn nm_device_connected(output: &str) -> bool {
+    output
+        .lines()
+        .filter_map(|line| line.strip_prefix("GENERAL.STATE:"))
+        .any(|state| state.trim_start().starts_with("100"))
+}
+
+fn in_path(program: &str) -> bool {
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    std::env::split_paths(&path)
+        .chain(["/usr/sbin", "/sbin"].map(PathBuf::from))
+        .any(|dir| dir.join(program).is_file())
+}
+
+fn probe(iface: &str) -> DnsSystem {
+    DnsSystem {
+        resolv_conf_target: std::fs::read_link(RESOLV_CONF).ok(),
+        resolv_conf: std::fs::read_to_string(RESOLV_CONF).unwrap_or_default(),
+        resolved_active: command_stdout("systemctl", &["is-active", "systemd-resolved"])
+            .is_some_and(|out| out.trim() == "active"),
+        nm_manages_iface: command_stdout(
+            "nmcli",
+            &["-t", "-f", "GENERAL.STATE", "dev", "show", iface],
+        )
+        .is_some_and(|out| nm_device_connected(&out)),
+        resolvconf_installed: in_path("resolvconf"),
+    }
+}
+
+/// The backend to override `iface`'s DNS with: the configured one, or
+/// whatever `detect` makes of the running system.
+pub(crate) fn select(app: &AppHandle, iface: &str) -> DnsBackend {
+    select_for(load_settings(app).backend, iface)
+}
+
+/// `select` for callers without an `AppHandle` (the headless CLI), which
+/// read `linux_dns` themselves.
+pub(crate) fn select_for(choice: DnsBackendChoice, iface: &str) -> DnsBackend {
+    let backend = match choice {
+        DnsBackendChoice::Auto => detect(&probe(iface)),
+        DnsBackendChoice::Resolved => DnsBackend::Resolved,
+        DnsBackendChoice::NetworkManager => DnsBackend::NetworkManager,
+        DnsBackendChoice::Resolvconf => DnsBackend::Resolvconf,
+        DnsBackendChoice::ResolvConfFile => DnsBackend::ResolvConfFile,
+    };
+    log::info!("[dns] backend for [{}]: {}", iface, backend.helper_name());
+    backend
+}
+
+/// The resolvers `/etc/resolv.conf` lists now — the best "original" the
+/// non-`resolved` backends have, kept for the log and the record.
+pub(crate) fn current_nameservers() -> String {
+    let text = std::fs::read_to_string(Path::new(RESOLV_CONF)).unwrap_or_default();
+    resolv_conf_nameservers(&text).join(" ")
+}
+
+#[cfg(test)]
+mod dns_backend_tests {
+    use super::*;
+
+    #[test]
+    fn detection_follows_what_resolv_conf_is() {
+        let stub = DnsSystem {
+            resolv_conf_target: Some("../run/systemd/resolve/stub-resolv.conf".into()),
+            resolv_conf: "nameserver 127.0.0.53\noptions edns0 trust-ad\n".into(),
+            resolved_active: true,
+            nm_manages_iface: true,
+            ..Default::default()
+        };
+        assert_eq!(detect(&stub), DnsBackend::Resolved);
+
+        // resolved running in "foreign" mode: glibc never asks it.
+        let nm_dnsmasq = DnsSystem {
+            resolv_conf_target: Some("/run/NetworkManager/resolv.conf".into()),
+            resolv_conf: "# Generated by NetworkManager\nnameserver 127.0.1.1\n".into(),
+            resolved_active: true,
+            nm_manages_iface: true,
+            ..Default::default()
+        };
+        assert_eq!(detect(&nm_dnsmasq), DnsBackend::NetworkManager);
+
+        let openresolv = DnsSystem {
+            resolv_conf: "# Generated by resolvconf\nnameserver 192.168.1.1\n".into(),
+            resolvconf_installed: true,
+            ..Default::default()
+        };
+        assert_eq!(detect(&openresolv), DnsBackend::Resolvconf);
+
+        let plain = DnsSystem {
+            resolv_conf: "nameserver 1.1.1.1\n".into(),
+            resolvconf_installed: true,
+            ..Default::default()
+        };
+        assert_eq!(detect(&plain), DnsBackend::ResolvConfFile);
+    }
+
+    #[test]
+    fn resolv_conf_and_nmcli_output_are_parsed() {
+        assert_eq!(
+            resolv_conf_nameservers(
+                "# c\nsearch lan\nnameserver 192.168.1.1\n nameserver  ::1 \nnameserver\n"
+            ),
+            ["192.168.1.1", "::1"]
+        );
+        assert!(nm_device_connected("GENERAL.STATE:100 (connected)\n"));
+        assert!(!nm_device_connected("GENERAL.STATE:10 (unmanaged)\n"));
+        assert!(!nm_device_connected("Error: Device 'eth9' not found.\n"));
+        for backend in [
+            DnsBackend::Resolved,
+            DnsBackend::NetworkManager,
+            DnsBackend::Resolvconf,
+            DnsBackend::ResolvConfFile,
+        ] {
+            assert_eq!(
+                DnsBackend::from_helper_name(Some(backend.helper_name())),
+                backend
+            );
+        }
+        assert_eq!(DnsBackend::from_helper_name(None), DnsBackend::Resolved);
+    }
+}
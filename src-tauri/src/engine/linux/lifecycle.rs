@@ -0,0 +1,427 @@
+//! Linux lifecycle events for `app::setup::spawn_lifecycle_listener`.
+//!
+//! `onebox_lifecycle` only has Windows and macOS backends, so on Linux
+//! `EngineManager::on_network_up` never ran: after a Wi-Fi switch the TUN
+//! DNS override stayed on the old link and the engine was never restarted.
+//! The same four events are produced here:
+//!
+//!   * NetworkManager `StateChanged` on the system bus — Up at
+//!     `CONNECTED_SITE` or better, Down below it — and `PrimaryConnection`
+//!     via `PropertiesChanged`: a handover from one active connection to
+//!     another (Wi-Fi → Ethernet, AP → AP) can keep the state connected
+//!     throughout, so a new primary connection counts as a fresh Up
+//!   * without NetworkManager on the bus, an rtnetlink socket subscribed to
+//!     link and route changes; the main table's IPv4 default route decides
+//!     Up / Down, and a default route that moved to another interface or
+//!     gateway counts as a fresh Up
+//!   * logind `PrepareForSleep(true / false)` → WillSleep / DidWake
+//!
+//! Each source runs on its own thread and feeds one channel. Only changes
+//! are sent — the first reading is a baseline, like the initial snapshot
+//! the other platforms' listener ignores. Whether NetworkManager is used
+//! is decided once at startup; after that a NetworkManager stream that
+//! ends or fails (NetworkManager or the system bus restarted) is
+//! subscribed again with backoff, keeping its baseline, so a change made
+//! while it was away still comes out as an event.
+
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::{Duration, Instant};
+
+use zbus::zvariant::OwnedObjectPath;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LifecycleEvent {
+    NetworkUp,
+    NetworkDown,
+    WillSleep,
+    DidWake,
+}
+
+/// `NM_STATE_CONNECTED_SITE`; `CONNECTED_LOCAL` (50) has no default route.
+const NM_STATE_CONNECTED_SITE: u32 = 60;
+/// Netlink sends a burst per change (link flags, addresses, routes); wait
+/// this long for it to go quiet before reading the routing table.
+const NETLINK_SETTLE_MS: i32 = 500;
+const PROC_NET_ROUTE: &str = "/proc/net/route";
+/// Wait before subscribing again to a NetworkManager stream that ended,
+/// doubling up to the max; a stream that lasted the max resets it.
+const RESUBSCRIBE_BACKOFF_MIN: Duration = Duration::from_secs(1);
+const RESUBSCRIBE_BACKOFF_MAX: Duration = Duration::from_secs(60);
+
+/// Start every source and return the merged event stream.
+pub fn start() -> Receiver<LifecycleEvent> {
+    let (tx, rx) = mpsc::channel();
+    let sleep_tx = tx.clone();
+    spawn("lifecycle-logind", move || {
+        if let Err(e) = watch_logind(&sleep_tx) {
+            log::warn!(
+                "[lifecycle] logind unavailable, no sleep/wake events: {}",
+                e
+            );
+        }
+    });
+    spawn("lifecycle-network", move || {
+        if let Err(e) = watch_network_manager(&tx) {
+            log::info!(
+                "[lifecycle] NetworkManager unavailable ({}), watching rtnetlink instead",
+                e
+            );
+            if let Err(e) = watch_netlink(&tx) {
+                log::warn!("[lifecycle] rtnetlink watcher stopped: {}", e);
+            }
+        }
+    });
+    rx
+}
+
+fn spawn(name: &str, f: impl FnOnce() + Send + 'static) {
+    if let Err(e) = std::thread::Builder::new().name(name.into()).spawn(f) {
+        log::warn!("[lifecycle] failed to spawn {}: {}", name, e);
+    }
+}
+
+/// The event for going from `*up` to `now_up`, if that is a change.
+fn transition(up: &mut bool, now_up: bool) -> Option<LifecycleEvent> {
+    if *up == now_up {
+        return None;
+    }
+    *up = now_up;
+    Some(if now_up {
+        LifecycleEvent::NetworkUp
+    } else {
+        LifecycleEvent::NetworkDown
+    })
+}
+
+fn watch_logind(tx: &Sender<LifecycleEvent>) -> zbus::Result<()> {
+    let conn = zbus::blocking::Connection::system()?;
+    let proxy = zbus::blocking::Proxy::new(
+        &conn,
+        "org.freedesktop.login1",
+        "/org/freedesktop/login1",
+        "org.freedesktop.login1.Manager",
+    )?;
+    for msg in proxy.receive_signal("PrepareForSleep")? {
+        let Ok(going_to_sleep) = msg.body().deserialize::<bool>() else {
+            continue;
+        };
+        let event = if going_to_sleep {
+            LifecycleEvent::WillSleep
+        } else {
+            LifecycleEvent::DidWake
+        };
+        if tx.send(event).is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn network_manager(
+    conn: &zbus::blocking::Connection,
+) -> zbus::Result<zbus::blocking::Proxy<'static>> {
+    zbus::blocking::Proxy::new(
+        conn,
+        "org.freedesktop.NetworkManager",
+        "/org/freedesktop/NetworkManager",
+        "org.freedesktop.NetworkManager",
+    )
+}
+
+/// Why a NetworkManager watcher returned without an error.
+#[derive(Debug, PartialEq)]
+enum StreamEnd {
+    /// The signal stream ended; subscribe again.
+    Closed,
+    /// Nobody reads events any more; stop for good.
+    ReceiverGone,
+}
+
+/// Errors only if NetworkManager can't be reached at all, so the caller
+/// can fall back to rtnetlink.
+fn watch_network_manager(tx: &Sender<LifecycleEvent>) -> zbus::Result<()> {
+    let conn = zbus::blocking::Connection::system()?;
+    let mut up = network_manager(&conn)?.get_property::<u32>("State")? >= NM_STATE_CONNECTED_SITE;
+    drop(conn);
+    log::info!("[lifecycle] watching NetworkManager (connected: {})", up);
+    let primary_tx = tx.clone();
+    spawn("lifecycle-nm-primary", move || {
+        let mut primary = None;
+        resubscribe("PrimaryConnection", || {
+            watch_primary_connection(&primary_tx, &mut primary)
+        });
+    });
+    resubscribe("StateChanged", || watch_state(tx, &mut up));
+    Ok(())
+}
+
+/// Run `watch` until the receiver is gone, subscribing again after
+/// `next_backoff` whenever its stream ends or it fails.
+fn resubscribe(name: &str, mut watch: impl FnMut() -> zbus::Result<StreamEnd>) {
+    let mut backoff = RESUBSCRIBE_BACKOFF_MIN;
+    loop {
+        let started = Instant::now();
+        let result = watch();
+        backoff = next_backoff(backoff, started.elapsed());
+        match result {
+            Ok(StreamEnd::ReceiverGone) => return,
+            Ok(StreamEnd::Closed) => log::warn!(
+                "[lifecycle] NetworkManager {} stream ended, resubscribing in {:?}",
+                name,
+                backoff
+            ),
+            Err(e) => log::warn!(
+                "[lifecycle] NetworkManager {} watcher failed ({}), retrying in {:?}",
+                name,
+                e,
+                backoff
+            ),
+        }
+        std::thread::sleep(backoff);
+    }
+}
+
+/// The wait after a subscription that lasted `lasted`, given the previous
+/// wait: back to the minimum after a long-lived one, otherwise doubled.
+fn next_backoff(previous: Duration, lasted: Duration) -> Duration {
+    if lasted >= RESUBSCRIBE_BACKOFF_MAX {
+        RESUBSCRIBE_BACKOFF_MIN
+    } else {
+        (previous * 2).min(RESUBSCRIBE_BACKOFF_MAX)
+    }
+}
+
+/// `StateChanged` against the baseline in `up`.
+fn watch_state(tx: &Sender<LifecycleEvent>, up: &mut bool) -> zbus::Result<StreamEnd> {
+    let conn = zbus::blocking::Connection::system()?;
+    let proxy = network_manager(&conn)?;
+    let signals = proxy.receive_signal("StateChanged")?;
+    // Read after subscribing so nothing falls in between; on a
+    // resubscription this reports what changed while unsubscribed.
+    let state = proxy.get_property::<u32>("State")?;
+    let states = std::iter::once(state)
+        .chain(signals.filter_map(|msg| msg.body().deserialize::<u32>().ok()));
+    for state in states {
+        if let Some(event) = transition(up, state >= NM_STATE_CONNECTED_SITE) {
+            if tx.send(event).is_err() {
+                return Ok(StreamEnd::ReceiverGone);
+            }
+        }
+    }
+    Ok(StreamEnd::Closed)
+}
+
+/// NetworkUp when the primary connection went from one active connection
+/// to another. `/` (none) on either side is a plain Up / Down, which
+/// `StateChanged` already reports.
+fn primary_event(before: &str, after: &str) -> Option<LifecycleEvent> {
+    (before != after && before != "/" && after != "/").then_some(LifecycleEvent::NetworkUp)
+}
+
+/// `PrimaryConnection` against the baseline in `primary`, which the first
+/// subscription sets.
+fn watch_primary_connection(
+    tx: &Sender<LifecycleEvent>,
+    primary: &mut Option<OwnedObjectPath>,
+) -> zbus::Result<StreamEnd> {
+    let conn = zbus::blocking::Connection::system()?;
+    let proxy = network_manager(&conn)?;
+    // Backed by `PropertiesChanged`; the first item is the current value.
+    for change in proxy.receive_property_changed::<OwnedObjectPath>("PrimaryConnection") {
+        let Ok(now) = change.get() else {
+            continue;
+        };
+        if let Some(before) = primary.as_ref() {
+            if let Some(event) = primary_event(before.as_str(), now.as_str()) {
+                log::debug!(
+                    "[lifecycle] primary connection {} → {}",
+                    before.as_str(),
+                    now.as_str()
+                );
+                if tx.send(event).is_err() {
+                    return Ok(StreamEnd::ReceiverGone);
+                }
+            }
+        }
+        *primary = Some(now);
+    }
+    Ok(StreamEnd::Closed)
+}
+
+/// `(iface, gateway)` of the lowest-metric IPv4 default route in
+/// `/proc/net/route`.
+fn default_route(proc_net_route: &str) -> Option<(String, String)> {
+    const RTF_UP: u32 = 0x1;
+    proc_net_route
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let cols: Vec<&str> = line.split_whitespace().collect();
+            let flags = u32::from_str_radix(cols.get(3)?, 16).ok()?;
+            let metric: u32 = cols.get(6)?.parse().ok()?;
+            (cols[1] == "00000000" && *cols.get(7)? == "00000000" && flags & RTF_UP != 0)
+                .then(|| (metric, cols[0].to_string(), cols[2].to_string()))
+        })
+        .min_by_key(|(metric, _, _)| *metric)
+        .map(|(_, iface, gateway)| (iface, gateway))
+}
+
+fn route_event(
+    before: &Option<(String, String)>,
+    after: &Option<(String, String)>,
+) -> Option<LifecycleEvent> {
+    match (before, after) {
+        (Some(_), None) => Some(LifecycleEvent::NetworkDown),
+        (None, Some(_)) => Some(LifecycleEvent::NetworkUp),
+        (Some(a), Some(b)) if a != b => Some(LifecycleEvent::NetworkUp),
+        _ => None,
+    }
+}
+
+fn read_default_route() -> Option<(String, String)> {
+    default_route(&std::fs::read_to_string(PROC_NET_ROUTE).unwrap_or_default())
+}
+
+fn watch_netlink(tx: &Sender<LifecycleEvent>) -> std::io::Result<()> {
+    let fd = unsafe {
+        libc::socket(
+            libc::AF_NETLINK,
+            libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+            libc::NETLINK_ROUTE,
+        )
+    };
+    if fd < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let sock = unsafe { OwnedFd::from_raw_fd(fd) };
+    let mut addr: libc::sockaddr_nl = unsafe { std::mem::zeroed() };
+    addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+    addr.nl_groups = (libc::RTMGRP_LINK | libc::RTMGRP_IPV4_ROUTE | libc::RTMGRP_IPV6_ROUTE) as u32;
+    let rc = unsafe {
+        libc::bind(
+            sock.as_raw_fd(),
+            &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+            std::mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+        )
+    };
+    if rc < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    let mut route = read_default_route();
+    log::info!(
+        "[lifecycle] watching rtnetlink (default route: {:?})",
+        route
+    );
+    let mut buf = [0u8; 16 * 1024];
+    loop {
+        // Block for the first message of a burst, then drain until quiet.
+        // The content doesn't matter — ENOBUFS (dropped messages) included,
+        // the routing table is re-read either way.
+        recv(&sock, &mut buf)?;
+        while wait_readable(&sock, NETLINK_SETTLE_MS)? {
+            recv(&sock, &mut buf)?;
+        }
+        let now = read_default_route();
+        if let Some(event) = route_event(&route, &now) {
+            log::debug!("[lifecycle] default route {:?} → {:?}", route, now);
+            if tx.send(event).is_err() {
+                return Ok(());
+            }
+        }
+        route = now;
+    }
+}
+
+fn recv(sock: &OwnedFd, buf: &mut [u8]) -> std::io::Result<()> {
+    let n = unsafe { libc::recv(sock.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len(), 0) };
+    if n >= 0 {
+        return Ok(());
+    }
+    let err = std::io::Error::last_os_error();
+    match err.raw_os_error() {
+        Some(libc::EINTR) | Some(libc::ENOBUFS) => Ok(()),
+        _ => Err(err),
+    }
+}
+
+fn wait_readable(sock: &OwnedFd, timeout_ms: i32) -> std::io::Result<bool> {
+    let mut pfd = libc::pollfd {
+        fd: sock.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let rc = unsafe { libc::poll(&mut pfd, 1, timeout_ms) };
+    if rc < 0 {
+        let err = std::io::Error::last_os_error();
+        return match err.raw_os_error() {
+            Some(libc::EINTR) => Ok(true),
+            _ => Err(err),
+        };
+    }
+    Ok(rc > 0)
+}
+
+#[cfg(test)]
+mod lifecycle_tests {
+    use super::*;
+
+    const ROUTES: &str =
+        "Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT\n\
+        wlp2s0\t00000000\t0101A8C0\t0003\t0\t0\t600\t00000000\t0\t0\t0\n\
+        enp3s0\t00000000\t0100000A\t0003\t0\t0\t100\t00000000\t0\t0\t0\n\
+        enp3s0\t0000000A\t00000000\t0001\t0\t0\t100\t00FFFFFF\t0\t0\t0\n";
+
+    #[test]
+    fn the_lowest_metric_default_route_wins() {
+        assert_eq!(
+            default_route(ROUTES),
+            Some(("enp3s0".into(), "0100000A".into()))
+        );
+        let header_only = ROUTES.lines().next().unwrap();
+        assert_eq!(default_route(header_only), None);
+    }
+
+    #[test]
+    fn only_changes_become_events() {
+        let wifi = Some(("wlp2s0".to_string(), "0101A8C0".to_string()));
+        let wired = Some(("enp3s0".to_string(), "0100000A".to_string()));
+        assert_eq!(route_event(&wifi, &wifi), None);
+        assert_eq!(route_event(&wifi, &None), Some(LifecycleEvent::NetworkDown));
+        assert_eq!(route_event(&None, &wifi), Some(LifecycleEvent::NetworkUp));
+        assert_eq!(route_event(&wifi, &wired), Some(LifecycleEvent::NetworkUp));
+
+        let mut up = true;
+        assert_eq!(transition(&mut up, true), None);
+        assert_eq!(
+            transition(&mut up, false),
+            Some(LifecycleEvent::NetworkDown)
+        );
+        assert_eq!(transition(&mut up, true), Some(LifecycleEvent::NetworkUp));
+
+        let wifi = "/org/freedesktop/NetworkManager/ActiveConnection/3";
+        let wired = "/org/freedesktop/NetworkManager/ActiveConnection/4";
+        assert_eq!(primary_event(wifi, wired), Some(LifecycleEvent::NetworkUp));
+        assert_eq!(primary_event(wifi, wifi), None);
+        assert_eq!(primary_event("/", wifi), None);
+        assert_eq!(primary_event(wifi, "/"), None);
+    }
+
+    #[test]
+    fn resubscribe_backoff_doubles_and_resets_after_a_long_session() {
+        let short = Duration::from_millis(10);
+        assert_eq!(
+            next_backoff(RESUBSCRIBE_BACKOFF_MIN, short),
+            RESUBSCRIBE_BACKOFF_MIN * 2
+        );
+        assert_eq!(
+            next_backoff(RESUBSCRIBE_BACKOFF_MAX, short),
+            RESUBSCRIBE_BACKOFF_MAX
+        );
+        assert_eq!(
+            next_backoff(RESUBSCRIBE_BACKOFF_MAX, RESUBSCRIBE_BACKOFF_MAX),
+            RESUBSCRIBE_BACKOFF_MIN
+        );
+    }
+}
@@ -5,24 +5,67 @@ use tauri_plugin_shell::process::Command as TauriCommand;
 use tauri_plugin_shell::ShellExt;
 
 use crate::engine::helper::extract_tun_gateway_from_config;
+use crate::engine::runtime_record::{self, RuntimeRecord};
 use crate::engine::sysproxy::{clear_system_proxy, set_system_proxy};
 use crate::engine::EngineManager;
 
+pub(crate) mod desktop_proxy;
+pub(crate) mod dns_backend;
+pub(crate) mod lifecycle;
+
+use self::dns_backend::DnsBackend;
+
+/// An interface-scoped DNS override: the backend that made it, the
+/// interface, and the servers it resolved with before.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct DnsOverride {
+    pub backend: DnsBackend,
+    pub iface: String,
+    pub original_dns: String,
+}
+
+impl DnsOverride {
+    /// From the runtime record a previous session left.
+    fn from_record(record: &RuntimeRecord) -> Option<Self> {
+        let (iface, original_dns) = record.dns_override.clone()?;
+        Some(Self {
+            backend: DnsBackend::from_helper_name(record.dns_backend.as_deref()),
+            iface,
+            original_dns,
+        })
+    }
+
+    /// Helper arguments `<backend> <iface> [gateway] <original…>`.
+    fn helper_args(&self, gateway: Option<&str>) -> Vec<String> {
+        let mut args = vec![self.backend.helper_name().to_string(), self.iface.clone()];
+        args.extend(gateway.map(str::to_string));
+        args.extend(self.original_dns.split_whitespace().map(str::to_string));
+        args
+    }
+}
+
 /// Private state for the interface-scoped DNS override.
 ///
-/// `apply_system_dns_override` captures (iface, original_dns) at start
-/// so the teardown path can restore exactly what was there before.
-/// This used to live in `ProcessManager.dns_override`, but that field
-/// leaked a Linux-shaped tuple into the shared cross-platform state
-/// container; moving it here keeps it a Linux engine implementation
-/// detail.
-static DNS_OVERRIDE: Mutex<Option<(String, String)>> = Mutex::new(None);
-
-fn set_dns_override(info: Option<(String, String)>) {
+/// `apply_system_dns_override` captures it at start so the teardown path
+/// can restore exactly what was there before. This used to live in
+/// `ProcessManager.dns_override`, but that field leaked a Linux-shaped
+/// tuple into the shared cross-platform state container; moving it here
+/// keeps it a Linux engine implementation detail.
+static DNS_OVERRIDE: Mutex<Option<DnsOverride>> = Mutex::new(None);
+
+fn set_dns_override(info: Option<DnsOverride>) {
     *DNS_OVERRIDE.lock().unwrap_or_else(|e| e.into_inner()) = info;
 }
 
-fn take_dns_override() -> Option<(String, String)> {
+/// Current capture, for the runtime record.
+pub(crate) fn dns_override_snapshot() -> Option<DnsOverride> {
+    DNS_OVERRIDE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+}
+
+fn take_dns_override() -> Option<DnsOverride> {
     DNS_OVERRIDE
         .lock()
         .unwrap_or_else(|e| e.into_inner())
@@ -35,12 +78,22 @@ pub const HELPER_PATH: &str = "/usr/lib/OneBox/onebox-tun-helper";
 /// privileged helper. DNS override + sing-box launch happen in a single
 /// pkexec call (one auth prompt). The helper uses `exec` so pkexec stays
 /// as parent and Tauri can monitor the process.
-pub fn create_privileged_command(
+pub(crate) fn create_privileged_command(
     app: &AppHandle,
     sidecar_path: String,
     path: String,
-    dns_override: Option<&(String, String)>,
+    dns_override: Option<&DnsOverride>,
 ) -> Option<TauriCommand> {
+    let args = start_tun_args(sidecar_path, path, dns_override);
+    Some(app.shell().command("pkexec").args(args))
+}
+
+/// `onebox-tun-helper start-tun …`, helper path first.
+pub(crate) fn start_tun_args(
+    sidecar_path: String,
+    path: String,
+    dns_override: Option<&DnsOverride>,
+) -> Vec<String> {
     let mut args = vec![
         HELPER_PATH.to_string(),
         "start-tun".to_string(),
@@ -48,41 +101,32 @@ pub fn create_privileged_command(
         path.clone(),
     ];
 
-    if let Some((iface, _original)) = dns_override {
+    if let Some(dns_override) = dns_override {
         let gateway = extract_tun_gateway_from_config(&path).unwrap_or_default();
         if !gateway.is_empty() {
-            args.push(iface.clone());
-            args.push(gateway);
-            args.extend(
-                _original
-                    .split_whitespace()
-                    .map(|server| server.to_string()),
-            );
+            args.extend(dns_override.helper_args(Some(&gateway)));
         }
     }
-
-    let args_ref: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
-    Some(app.shell().command("pkexec").args(args_ref))
+    args
 }
 
 /// Stop sing-box and restore DNS in a single pkexec call (one auth prompt).
-pub fn stop_tun_and_restore_dns(dns_override: Option<&(String, String)>) -> Result<(), String> {
-    let mut args = vec![HELPER_PATH, "stop-tun"];
-
-    let iface_owned;
-    let servers_owned;
-    if let Some((iface, original_dns)) = dns_override {
+/// Only `pid` is signalled, so side instances keep running; `None` restores
+/// DNS without stopping anything.
+pub(crate) fn stop_tun_and_restore_dns(
+    pid: Option<u32>,
+    dns_override: Option<&DnsOverride>,
+) -> Result<(), String> {
+    let pid = pid.map_or_else(|| "-".to_string(), |pid| pid.to_string());
+    let mut args = vec![HELPER_PATH.to_string(), "stop-tun".to_string(), pid];
+    if let Some(dns_override) = dns_override {
         log::info!(
-            "[dns] restore: setting [{}] DNS back to {}",
-            iface,
-            original_dns
+            "[dns] restore ({}): setting [{}] DNS back to {}",
+            dns_override.backend.helper_name(),
+            dns_override.iface,
+            dns_override.original_dns
         );
-        iface_owned = iface.clone();
-        servers_owned = original_dns.clone();
-        args.push(&iface_owned);
-        for server in servers_owned.split_whitespace() {
-            args.push(server);
-        }
+        args.extend(dns_override.helper_args(None));
     }
 
     let out = Command::new("pkexec")
@@ -98,10 +142,10 @@ pub fn stop_tun_and_restore_dns(dns_override: Option<&(String, String)>) -> Resu
 
 /// Legacy trait-compatible wrapper (unused on Linux, kept for trait signature).
 pub fn stop_tun_process() -> Result<(), String> {
-    stop_tun_and_restore_dns(None)
+    stop_tun_and_restore_dns(None, None)
 }
 
-// ========== Linux 系统 DNS 接管 (systemd-resolved) ==========
+// ========== Linux 系统 DNS 接管 ==========
 //
 // Ubuntu 18.04+ uses systemd-resolved as a stub resolver (127.0.0.53).
 // Recent versions bind upstream sockets to physical interfaces via
@@ -112,6 +156,9 @@ pub fn stop_tun_process() -> Result<(), String> {
 // (nmcli) on the single interface we overrode. We do NOT touch other
 // interfaces (e.g. tailscale0), and we do NOT use `resolvectl revert`
 // which clears DNS entirely in "foreign" resolv.conf mode.
+//
+// Systems without the resolved stub go through NetworkManager, resolvconf
+// or a managed /etc/resolv.conf instead — see `dns_backend`.
 
 /// Detect the default-route egress interface (e.g. "ens33", "wlp2s0").
 fn detect_active_iface() -> Result<String, String> {
@@ -130,22 +177,24 @@ fn detect_active_iface() -> Result<String, String> {
 
 /// Capture the current DNS servers for an interface from NetworkManager.
 /// Falls back to parsing `resolvectl status <iface>` if nmcli fails.
-fn capture_original_dns(iface: &str) -> Result<String, String> {
-    // Try nmcli first (most reliable on NM-managed systems).
-    let out = Command::new("nmcli")
+fn capture_link_dns(iface: &str) -> Result<String, String> {
+    // Try nmcli first (most reliable on NM-managed systems). Not every
+    // system has it (systemd-networkd), so a missing binary falls through.
+    if let Ok(out) = Command::new("nmcli")
         .args(["-t", "-f", "IP4.DNS", "dev", "show", iface])
         .output()
-        .map_err(|e| format!("nmcli failed: {}", e))?;
-    let stdout = String::from_utf8_lossy(&out.stdout);
-    // nmcli output looks like "IP4.DNS[1]:192.168.6.2\nIP4.DNS[2]:8.8.8.8"
-    let servers: Vec<&str> = stdout
-        .lines()
-        .filter_map(|l| l.split(':').nth(1))
-        .map(|s| s.trim())
-        .filter(|s| !s.is_empty())
-        .collect();
-    if !servers.is_empty() {
-        return Ok(servers.join(" "));
+    {
+        let stdout = String::from_utf8_lossy(&out.stdout);
+        // nmcli output looks like "IP4.DNS[1]:192.168.6.2\nIP4.DNS[2]:8.8.8.8"
+        let servers: Vec<&str> = stdout
+            .lines()
+            .filter_map(|l| l.split(':').nth(1))
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty())
+            .collect();
+        if !servers.is_empty() {
+            return Ok(servers.join(" "));
+        }
     }
 
     // Fallback: parse resolvectl status output.
@@ -169,67 +218,124 @@ fn capture_original_dns(iface: &str) -> Result<String, String> {
     Err(format!("could not determine original DNS for {}", iface))
 }
 
+/// The servers to remember for `iface`. Only `resolved` restores from them,
+/// so only there is an empty capture an error; the other backends keep
+/// whatever is known for the log and the runtime record.
+fn capture_original_dns(backend: DnsBackend, iface: &str) -> Result<String, String> {
+    if backend.restores_from_capture() {
+        return capture_link_dns(iface);
+    }
+    Ok(capture_link_dns(iface).unwrap_or_else(|_| dns_backend::current_nameservers()))
+}
+
 /// Capture the active interface and its current DNS servers WITHOUT applying
 /// the override yet. The actual override is baked into the pkexec call in
 /// `create_privileged_command` so only one auth prompt is needed.
-pub fn prepare_dns_override(config_path: &str) -> Result<(String, String), String> {
+pub(crate) fn prepare_dns_override(
+    app: &AppHandle,
+    config_path: &str,
+) -> Result<DnsOverride, String> {
+    let choice = dns_backend::load_settings(app).backend;
+    prepare_dns_override_for(choice, config_path)
+}
+
+/// `prepare_dns_override` with the `linux_dns.backend` setting already read.
+pub(crate) fn prepare_dns_override_for(
+    choice: dns_backend::DnsBackendChoice,
+    config_path: &str,
+) -> Result<DnsOverride, String> {
     // Verify the config has a TUN gateway (early fail before prompting user).
     let _gateway = extract_tun_gateway_from_config(config_path)
         .ok_or_else(|| format!("could not extract TUN gateway from {}", config_path))?;
     let iface = detect_active_iface()?;
-    let original_dns = capture_original_dns(&iface)?;
+    let backend = dns_backend::select_for(choice, &iface);
+    let original_dns = capture_original_dns(backend, &iface)?;
     log::info!(
         "[dns] captured original DNS for [{}]: {}",
         iface,
         original_dns
     );
-    Ok((iface, original_dns))
+    Ok(DnsOverride {
+        backend,
+        iface,
+        original_dns,
+    })
 }
 
 /// Override the active interface's DNS to point at the TUN gateway.
-/// Returns `(iface, original_dns)` for later restoration.
-/// Used by reapply_tun_dns_override_if_active (network change handler).
-pub fn apply_system_dns_override(config_path: &str) -> Result<(String, String), String> {
+/// Returns the override for later restoration. `previous` is the override
+/// already in place, if any: on another interface or backend it is restored
+/// first; on the same one its capture is kept whenever a fresh read only
+/// finds our own gateway.
+/// Used by `on_network_up` (network change handler).
+pub(crate) fn apply_system_dns_override(
+    app: &AppHandle,
+    config_path: &str,
+    previous: Option<&DnsOverride>,
+) -> Result<DnsOverride, String> {
     let gateway = extract_tun_gateway_from_config(config_path)
         .ok_or_else(|| format!("could not extract TUN gateway from {}", config_path))?;
     let iface = detect_active_iface()?;
-    let original_dns = capture_original_dns(&iface)?;
+    let backend = dns_backend::select(app, &iface);
+    let dns_override = match previous {
+        Some(previous) if previous.iface == iface && previous.backend == backend => {
+            let original_dns = capture_original_dns(backend, &iface)
+                .ok()
+                .filter(|dns| !dns.is_empty() && !dns.split_whitespace().any(|s| s == gateway))
+                .unwrap_or_else(|| previous.original_dns.clone());
+            DnsOverride {
+                original_dns,
+                ..previous.clone()
+            }
+        }
+        _ => {
+            if let Some(previous) = previous {
+                if let Err(e) = restore_system_dns(previous) {
+                    log::warn!("[dns] restoring the previous override failed: {}", e);
+                }
+            }
+            DnsOverride {
+                backend,
+                original_dns: capture_original_dns(backend, &iface)?,
+                iface,
+            }
+        }
+    };
 
     log::info!(
-        "[dns] resolvectl override → {} for [{}] (original: {})",
+        "[dns] {} override → {} for [{}] (original: {})",
+        backend.helper_name(),
         gateway,
-        iface,
-        original_dns
+        dns_override.iface,
+        dns_override.original_dns
     );
     let out = Command::new("pkexec")
         .arg(HELPER_PATH)
         .arg("dns-override")
-        .arg(&iface)
-        .arg(&gateway)
-        .args(original_dns.split_whitespace())
+        .args(dns_override.helper_args(Some(&gateway)))
         .output()
         .map_err(|e| format!("pkexec dns-override failed: {}", e))?;
     if !out.status.success() {
         let stderr = String::from_utf8_lossy(&out.stderr);
         log::warn!("[dns] dns-override non-zero exit: {}", stderr);
     }
-    Ok((iface, original_dns))
+    Ok(dns_override)
 }
 
-/// Restore DNS on the single interface we overrode, using the original
-/// servers captured at override time. Does NOT touch other interfaces.
-pub fn restore_system_dns(iface: &str, original_dns: &str) -> Result<(), String> {
+/// Undo the override on the single interface we overrode — the captured
+/// servers for `resolved`, the backend's own undo otherwise. Does NOT touch
+/// other interfaces.
+pub(crate) fn restore_system_dns(dns_override: &DnsOverride) -> Result<(), String> {
     log::info!(
-        "[dns] restore: setting [{}] DNS back to {}",
-        iface,
-        original_dns
+        "[dns] restore ({}): setting [{}] DNS back to {}",
+        dns_override.backend.helper_name(),
+        dns_override.iface,
+        dns_override.original_dns
     );
-    let mut args = vec![HELPER_PATH, "dns-restore", iface];
-    let servers: Vec<&str> = original_dns.split_whitespace().collect();
-    args.extend(servers);
-
     let out = Command::new("pkexec")
-        .args(&args)
+        .arg(HELPER_PATH)
+        .arg("dns-restore")
+        .args(dns_override.helper_args(None))
         .output()
         .map_err(|e| format!("pkexec dns-restore failed: {}", e))?;
     if !out.status.success() {
@@ -286,7 +392,7 @@ impl EngineManager for LinuxEngine {
                 // what was there before. Failure here is non-fatal — we'd
                 // rather start TUN without a captured override than refuse
                 // to start at all.
-                let dns_info = match prepare_dns_override(&config_path) {
+                let dns_info = match prepare_dns_override(app, &config_path) {
                     Ok(info) => {
                         set_dns_override(Some(info.clone()));
                         Some(info)
@@ -336,22 +442,22 @@ impl EngineManager for LinuxEngine {
     }
 
     async fn stop(app: &AppHandle) -> Result<(), String> {
-        let (mode, child) = {
+        let (mode, child, adopted_pid) = {
             let mut mgr = crate::core::ProcessManager::acquire();
             mgr.is_stopping = true;
-            (mgr.mode.clone(), mgr.child.take())
+            (mgr.mode.clone(), mgr.child.take(), mgr.adopted_pid.take())
         };
         let Some(mode) = mode else {
             return Ok(());
         };
+        let pid = child.map(|c| c.pid()).or(adopted_pid);
         match mode.as_ref() {
             crate::engine::ProxyMode::SystemProxy | crate::engine::ProxyMode::ManualProxy => {
                 if matches!(mode.as_ref(), crate::engine::ProxyMode::SystemProxy) {
                     let _ = clear_system_proxy(app).await;
                 }
-                if let Some(child) = child {
+                if let Some(pid) = pid {
                     use libc::{kill, SIGTERM};
-                    let pid = child.pid();
                     if unsafe { kill(pid as i32, SIGTERM) } != 0 {
                         let err = std::io::Error::last_os_error();
                         if crate::core::sigterm_target_already_exited(err.raw_os_error()) {
@@ -369,7 +475,7 @@ impl EngineManager for LinuxEngine {
                 // take_dns_override drains the stash so on_process_terminated
                 // doesn't double-restore when the monitor fires afterwards.
                 let dns_info = take_dns_override();
-                stop_tun_and_restore_dns(dns_info.as_ref()).map_err(|e| {
+                stop_tun_and_restore_dns(pid, dns_info.as_ref()).map_err(|e| {
                     log::error!("Failed to stop TUN process: {}", e);
                     e
                 })?;
@@ -378,7 +484,7 @@ impl EngineManager for LinuxEngine {
         Ok(())
     }
 
-    fn on_network_up(_app: &AppHandle) {
+    fn on_network_up(app: &AppHandle) {
         // NetworkUp → new default interface may need DNS overriding again.
         // Gate on "engine running in TUN mode" — we only have DNS state
         // to refresh in that case. Refresh the stashed (iface, original_dns)
@@ -392,8 +498,12 @@ impl EngineManager for LinuxEngine {
                 _ => return,
             }
         };
-        match apply_system_dns_override(&config_path) {
-            Ok(info) => set_dns_override(Some(info)),
+        let previous = dns_override_snapshot();
+        match apply_system_dns_override(app, &config_path, previous.as_ref()) {
+            Ok(info) => {
+                set_dns_override(Some(info));
+                runtime_record::note_dns_override(app);
+            }
             Err(e) => log::warn!("[dns] NetworkUp re-apply failed: {}", e),
         }
     }
@@ -404,13 +514,13 @@ impl EngineManager for LinuxEngine {
         // what we want, since restoring twice would clobber whatever the
         // user set afterwards.
         let dns_info = take_dns_override();
-        if let Some((iface, original_dns)) = dns_info {
+        if let Some(dns_override) = dns_info {
             log::info!(
                 "[dns] TUN process terminated — restoring [{}] DNS to {}",
-                iface,
-                original_dns
+                dns_override.iface,
+                dns_override.original_dns
             );
-            if let Err(e) = restore_system_dns(&iface, &original_dns) {
+            if let Err(e) = restore_system_dns(&dns_override) {
                 log::warn!("[dns] fallback restore_system_dns failed: {}", e);
             }
         } else if !was_user_stop {
@@ -422,6 +532,38 @@ impl EngineManager for LinuxEngine {
         }
     }
 
+    fn adopt(app: &AppHandle, record: &RuntimeRecord, start_epoch: u64) {
+        if matches!(record.mode, crate::engine::ProxyMode::TunProxy) {
+            set_dns_override(DnsOverride::from_record(record));
+        }
+        // Sidecar pid or, for TUN, the pkexec wrapping sing-box — either
+        // way it lives exactly as long as sing-box does.
+        match record.pid {
+            Some(pid) => runtime_record::watch_pid(
+                app.clone(),
+                std::sync::Arc::new(record.mode.clone()),
+                pid,
+                start_epoch,
+                runtime_record::process_exists,
+            ),
+            None => log::warn!("[orphan] adopted session has no pid to watch"),
+        }
+    }
+
+    async fn clean_up_orphan(_app: &AppHandle, record: &RuntimeRecord, may_be_alive: bool) {
+        let dns_override = DnsOverride::from_record(record);
+        let result = if may_be_alive {
+            stop_tun_and_restore_dns(record.pid, dns_override.as_ref())
+        } else if let Some(dns_override) = dns_override.as_ref() {
+            restore_system_dns(dns_override)
+        } else {
+            Ok(())
+        };
+        if let Err(e) = result {
+            log::warn!("[orphan] TUN cleanup failed: {}", e);
+        }
+    }
+
     async fn ensure_installed(_app: &AppHandle) -> Result<(), String> {
         // The helper script and polkit policy are installed by the .deb/.rpm
         // package; there is no runtime install step to perform. We still
@@ -445,22 +587,34 @@ impl EngineManager for LinuxEngine {
     }
 
     async fn restart(_app: &AppHandle) -> Result<(), String> {
-        // Helper's `reload` verb bundles `pkill -HUP sing-box` and
-        // `resolvectl flush-caches` in one pkexec call. The flush is needed
-        // because systemd-resolved honors sing-box's 600s FakeIP TTL, so
-        // without it a global → rules switch keeps returning the old
-        // FakeIP for up to 10 minutes after the reload.
-        let output = Command::new("pkexec")
-            .args([HELPER_PATH, "reload"])
-            .output()
-            .map_err(|e| format!("pkexec reload failed: {}", e))?;
-        if !output.status.success() {
-            return Err(format!(
-                "helper reload non-zero: {}",
-                String::from_utf8_lossy(&output.stderr)
-            ));
-        }
-        log::info!("[reload] SIGHUP + flush-caches via helper");
-        Ok(())
+        let pid = {
+            let manager = crate::core::ProcessManager::acquire();
+            manager
+                .child
+                .as_ref()
+                .map(|c| c.pid())
+                .or(manager.adopted_pid)
+        };
+        reload_via_helper(pid.ok_or("No running process found")?)
+    }
+}
+
+/// Helper's `reload` verb bundles `kill -HUP <pid>` and
+/// `resolvectl flush-caches` in one pkexec call. The flush is needed
+/// because systemd-resolved honors sing-box's 600s FakeIP TTL, so
+/// without it a global → rules switch keeps returning the old
+/// FakeIP for up to 10 minutes after the reload.
+pub(crate) fn reload_via_helper(pid: u32) -> Result<(), String> {
+    let output = Command::new("pkexec")
+        .args([HELPER_PATH, "reload", &pid.to_string()])
+        .output()
+        .map_err(|e| format!("pkexec reload failed: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "helper reload non-zero: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
     }
+    log::info!("[reload] SIGHUP + flush-caches via helper");
+    Ok(())
 }
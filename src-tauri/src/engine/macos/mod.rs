@@ -1,9 +1,11 @@
 pub mod dns_watcher;
 pub mod helper;
+pub(crate) mod proxy_watcher;
 pub(crate) mod watchdog;
 
 use self::helper as macos_helper;
 use crate::engine::helper::extract_tun_gateway_from_config;
+use crate::engine::runtime_record::{self, RuntimeRecord};
 use crate::engine::sysproxy::{clear_system_proxy, set_system_proxy};
 use crate::engine::EngineManager;
 use std::process::Command;
@@ -732,6 +734,88 @@ pub async fn restore_system_dns() -> Result<(), String> {
 // compiler; they delegate or no-op.
 // ============================================================================
 
+/// Bridge the XPC helper's sing-box exit event to the same cleanup path
+/// any other mode goes through.
+fn spawn_helper_exit_bridge(app: &AppHandle, start_epoch: u64) {
+    let mut exit_rx = macos_helper::subscribe_sing_box_exits();
+    let exit_app = app.clone();
+    let exit_mode = std::sync::Arc::new(crate::engine::ProxyMode::TunProxy);
+    tokio::spawn(async move {
+        if let Some(exit) = exit_rx.recv().await {
+            log::info!(
+                "[helper-bridge] sing-box exit event pid={} code={}",
+                exit.pid,
+                exit.exit_code
+            );
+            let payload = tauri_plugin_shell::process::TerminatedPayload {
+                code: Some(exit.exit_code),
+                signal: None,
+            };
+            crate::core::monitor::handle_process_termination(
+                &exit_app,
+                &exit_mode,
+                payload,
+                start_epoch,
+            )
+            .await;
+        }
+    });
+}
+
+/// Optional bypass-router watchdog: restart sing-box on the configured
+/// interval so macOS's auto_detect_interface can pick up routing table
+/// changes that accumulate without a clean refresh. All state (abort
+/// handle, restart-in-progress flag, interval handling) lives inside
+/// watchdog.rs, not in ProcessManager.
+fn spawn_bypass_router_watchdog(app: &AppHandle, config_path: std::sync::Arc<String>) {
+    let bypass_router_enabled = app
+        .get_store("settings.json")
+        .and_then(|store| store.get("enable_bypass_router_key"))
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    if bypass_router_enabled {
+        watchdog::spawn(app.clone(), config_path);
+    }
+}
+
+/// Rebuild the override slot for a TUN session inherited from a previous
+/// GUI process. The capture died with that process, so the active service's
+/// current DNS minus our gateway stands in for the original.
+fn recapture_active_override(config_path: &str) {
+    let Some(gateway) = extract_tun_gateway_from_config(config_path) else {
+        return;
+    };
+    let service = match detect_active_network_service() {
+        Ok(service) => service,
+        Err(e) => {
+            log::warn!("[dns] recapture: no active service: {}", e);
+            return;
+        }
+    };
+    let current = read_service_dns(&service);
+    if !dns_has_gateway_first(&current, &gateway) {
+        log::info!(
+            "[dns] recapture: [{}] is '{}', not overridden",
+            service,
+            current
+        );
+        return;
+    }
+    let captured = dns_without_gateway(&current, &gateway);
+    log::info!(
+        "[dns] recapture: [{}] original='{}' gateway={}",
+        service,
+        captured,
+        gateway
+    );
+    *ACTIVE_OVERRIDE.lock().unwrap_or_else(|e| e.into_inner()) = Some(ActiveOverride {
+        service,
+        captured,
+        gateway,
+        released: false,
+    });
+}
+
 pub struct MacOSEngine;
 
 impl EngineManager for MacOSEngine {
@@ -793,67 +877,27 @@ impl EngineManager for MacOSEngine {
                     .map_err(|e| format!("start_tun join error: {}", e))?
                     .map_err(|e| format!("start_tun_via_helper failed: {}", e))?;
 
-                // Bridge the XPC helper's sing-box exit event to the same
-                // cleanup path any other mode goes through.
-                let mut exit_rx = macos_helper::subscribe_sing_box_exits();
-                let exit_app = app.clone();
-                let mode_arc = Arc::new(crate::engine::ProxyMode::TunProxy);
-                let exit_mode = Arc::clone(&mode_arc);
-                let exit_spawn_epoch = start_epoch;
-                tokio::spawn(async move {
-                    if let Some(exit) = exit_rx.recv().await {
-                        log::info!(
-                            "[helper-bridge] sing-box exit event pid={} code={}",
-                            exit.pid,
-                            exit.exit_code
-                        );
-                        let payload = tauri_plugin_shell::process::TerminatedPayload {
-                            code: Some(exit.exit_code),
-                            signal: None,
-                        };
-                        crate::core::monitor::handle_process_termination(
-                            &exit_app,
-                            &exit_mode,
-                            payload,
-                            exit_spawn_epoch,
-                        )
-                        .await;
-                    }
-                });
+                spawn_helper_exit_bridge(app, start_epoch);
 
                 let config_path_arc = Arc::new(config_path);
                 {
                     let mut mgr = crate::core::ProcessManager::acquire();
-                    mgr.mode = Some(Arc::clone(&mode_arc));
+                    mgr.mode = Some(Arc::new(crate::engine::ProxyMode::TunProxy));
                     mgr.config_path = Some(Arc::clone(&config_path_arc));
                     mgr.child = None; // managed by helper
                     mgr.is_stopping = false;
                 }
-
-                // Optional bypass-router watchdog: restart sing-box on the
-                // configured interval so macOS's auto_detect_interface can
-                // pick up routing table changes that accumulate without a
-                // clean refresh. All state (abort handle,
-                // restart-in-progress flag, interval handling) lives inside
-                // watchdog.rs, not in ProcessManager.
-                let bypass_router_enabled = app
-                    .get_store("settings.json")
-                    .and_then(|store| store.get("enable_bypass_router_key"))
-                    .and_then(|v| v.as_bool())
-                    .unwrap_or(false);
-                if bypass_router_enabled {
-                    watchdog::spawn(app.clone(), Arc::clone(&config_path_arc));
-                }
+                spawn_bypass_router_watchdog(app, config_path_arc);
             }
         }
         Ok(())
     }
 
     async fn stop(app: &AppHandle) -> Result<(), String> {
-        let (mode, child) = {
+        let (mode, child, adopted_pid) = {
             let mut mgr = crate::core::ProcessManager::acquire();
             mgr.is_stopping = true;
-            (mgr.mode.clone(), mgr.child.take())
+            (mgr.mode.clone(), mgr.child.take(), mgr.adopted_pid.take())
         };
         let Some(mode) = mode else {
             return Ok(());
@@ -865,9 +909,8 @@ impl EngineManager for MacOSEngine {
                 if matches!(mode.as_ref(), crate::engine::ProxyMode::SystemProxy) {
                     let _ = clear_system_proxy(app).await;
                 }
-                if let Some(child) = child {
+                if let Some(pid) = child.map(|c| c.pid()).or(adopted_pid) {
                     use libc::{kill, SIGTERM};
-                    let pid = child.pid();
                     if unsafe { kill(pid as i32, SIGTERM) } != 0 {
                         let err = std::io::Error::last_os_error();
                         if crate::core::sigterm_target_already_exited(err.raw_os_error()) {
@@ -935,6 +978,39 @@ impl EngineManager for MacOSEngine {
         });
     }
 
+    fn adopt(app: &AppHandle, record: &RuntimeRecord, start_epoch: u64) {
+        match record.mode {
+            crate::engine::ProxyMode::TunProxy => {
+                recapture_active_override(&record.config_path);
+                dns_watcher::ensure_started();
+                spawn_helper_exit_bridge(app, start_epoch);
+                spawn_bypass_router_watchdog(app, std::sync::Arc::new(record.config_path.clone()));
+            }
+            _ => match record.pid {
+                Some(pid) => runtime_record::watch_pid(
+                    app.clone(),
+                    std::sync::Arc::new(record.mode.clone()),
+                    pid,
+                    start_epoch,
+                    runtime_record::process_exists,
+                ),
+                None => log::warn!("[orphan] adopted session has no pid to watch"),
+            },
+        }
+    }
+
+    async fn clean_up_orphan(_app: &AppHandle, record: &RuntimeRecord, may_be_alive: bool) {
+        recapture_active_override(&record.config_path);
+        let result = if may_be_alive {
+            stop_tun_process().await
+        } else {
+            restore_system_dns().await
+        };
+        if let Err(e) = result {
+            log::warn!("[orphan] TUN cleanup failed: {}", e);
+        }
+    }
+
     async fn ensure_installed(_app: &AppHandle) -> Result<(), String> {
         // SMJobBless requires a signed, notarized bundle with
         // SMPrivilegedExecutables set — see src-tauri/helper/README.md.
@@ -958,7 +1034,7 @@ impl EngineManager for MacOSEngine {
         // Read the current mode from shared state. TUN mode means sing-box
         // runs as root under the XPC helper — ask the helper to SIGHUP it,
         // then flush the OS resolver cache. SystemProxy mode means sing-box
-        // runs as the current user so `pkill -HUP` is enough, and DNS isn't
+        // runs as the current user so a plain SIGHUP is enough, and DNS isn't
         // overridden so no cache flush is needed.
         let is_tun = {
             let manager = crate::core::ProcessManager::acquire();
@@ -983,56 +1059,19 @@ impl EngineManager for MacOSEngine {
                 Err(e) => log::warn!("[reload] flush_dns_cache join error: {}", e),
             }
         } else {
-            // Snapshot which sing-box processes are currently alive BEFORE
-            // the SIGHUP. `pkill -HUP sing-box` matches by process name,
-            // so if there are multiple sing-box processes (e.g. a leaked
-            // one from a previous crash/force-quit), they'll ALL receive
-            // the signal — captured here for post-mortem analysis.
-            match Command::new("pgrep").args(["-lf", "sing-box"]).output() {
-                Ok(out) => {
-                    let stdout = String::from_utf8_lossy(&out.stdout);
-                    let lines: Vec<&str> = stdout.lines().collect();
-                    log::info!(
-                        "[reload] pgrep pre-pkill: {} sing-box process(es) {:?}",
-                        lines.len(),
-                        lines
-                    );
-                }
-                Err(e) => log::warn!("[reload] pgrep pre-pkill failed: {}", e),
-            }
-            let pm_pid = {
+            // Signal the tracked PID only: `pkill -HUP sing-box` matched by
+            // name and also reloaded side instances (`core::instances`).
+            let pid = {
                 let m = crate::core::ProcessManager::acquire();
-                m.child.as_ref().map(|c| c.pid())
-            };
-            log::info!(
-                "[reload] pm_child_pid={:?} (expected sole SIGHUP target)",
-                pm_pid
-            );
-
-            let output = Command::new("pkill")
-                .args(["-HUP", "sing-box"])
-                .output()
-                .map_err(|e| format!("Failed to send SIGHUP: {}", e))?;
-            // pkill exit codes: 0 = matched + signaled, 1 = none matched.
-            // Treat "none matched" as a warn so it doesn't silently fail.
-            let code = output.status.code();
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            if !output.status.success() {
-                if code == Some(1) {
-                    log::warn!(
-                        "[reload] pkill -HUP matched 0 processes (code=1) — sing-box may already be dead"
-                    );
-                    return Err(format!("pkill -HUP matched nothing: {}", stderr));
-                }
-                return Err(format!("pkill -HUP non-zero (code={:?}): {}", code, stderr));
+                m.child.as_ref().map(|c| c.pid()).or(m.adopted_pid)
             }
-            log::info!(
-                "[reload] SIGHUP sent via pkill code={:?} stdout={:?} stderr={:?}",
-                code,
-                stdout.trim(),
-                stderr.trim()
-            );
+            .ok_or("No running process found")?;
+            if unsafe { libc::kill(pid as i32, libc::SIGHUP) } != 0 {
+                let err = std::io::Error::last_os_error();
+                log::warn!("[reload] SIGHUP to PID {} failed: {}", pid, err);
+                return Err(format!("Failed to send SIGHUP to PID {}: {}", pid, err));
+            }
+            log::info!("[reload] SIGHUP sent to PID {}", pid);
         }
         Ok(())
     }
@@ -0,0 +1,70 @@
+//! SCDynamicStore watcher for the system proxy, feeding `proxy_guard`.
+//!
+//! Same shape as `dns_watcher`: `State:/Network/Global/Proxies` is the
+//! merged proxy configuration of the primary service, rewritten whenever
+//! System Settings, `networksetup`, an MDM profile or another VPN changes a
+//! service's proxies — or the primary service itself changes. A change
+//! wakes the guard right away instead of at its next poll; the guard does
+//! the comparison, so our own writes are harmless.
+//!
+//! One dedicated thread started via `ensure_started()` (idempotent), owning
+//! the `SCDynamicStore` and a CFRunLoop that runs for the app's lifetime.
+
+use std::sync::OnceLock;
+
+use core_foundation::array::CFArray;
+use core_foundation::runloop::{kCFRunLoopCommonModes, CFRunLoop};
+use core_foundation::string::CFString;
+use system_configuration::dynamic_store::{
+    SCDynamicStore, SCDynamicStoreBuilder, SCDynamicStoreCallBackContext,
+};
+
+static STARTED: OnceLock<()> = OnceLock::new();
+
+pub fn ensure_started() {
+    STARTED.get_or_init(|| {
+        let builder = std::thread::Builder::new().name("onebox-proxy-watcher".into());
+        if let Err(e) = builder.spawn(watcher_thread_main) {
+            log::warn!("[proxy-watch] failed to spawn watcher thread: {}", e);
+        }
+    });
+}
+
+fn watcher_thread_main() {
+    let ctx = SCDynamicStoreCallBackContext {
+        callout: on_dynamic_store_change,
+        info: (),
+    };
+    let Some(store) = SCDynamicStoreBuilder::new("cloud.oneoh.onebox.proxy-watcher")
+        .callback_context(ctx)
+        .build()
+    else {
+        log::warn!("[proxy-watch] SCDynamicStoreCreate failed, polling only");
+        return;
+    };
+
+    let watch_keys: CFArray<CFString> = CFArray::from_CFTypes(&[CFString::from_static_string(
+        "State:/Network/Global/Proxies",
+    )]);
+    let watch_patterns: CFArray<CFString> = CFArray::from_CFTypes(&[]);
+    if !store.set_notification_keys(&watch_keys, &watch_patterns) {
+        log::warn!("[proxy-watch] set_notification_keys failed, polling only");
+        return;
+    }
+
+    let Some(run_loop_source) = store.create_run_loop_source() else {
+        log::warn!("[proxy-watch] create_run_loop_source failed, polling only");
+        return;
+    };
+    CFRunLoop::get_current().add_source(&run_loop_source, unsafe { kCFRunLoopCommonModes });
+    CFRunLoop::run_current();
+    log::warn!("[proxy-watch] CFRunLoop exited unexpectedly, polling only");
+}
+
+fn on_dynamic_store_change(
+    _store: SCDynamicStore,
+    _changed_keys: CFArray<CFString>,
+    _info: &mut (),
+) {
+    crate::engine::proxy_guard::nudge();
+}
@@ -19,7 +19,7 @@ use tauri_plugin_store::StoreExt;
 
 use crate::core::monitor::handle_process_termination;
 use crate::core::{ProcessManager, ProxyMode};
-use crate::engine::state_machine::{transition, EngineStateCell, Intent};
+use crate::engine::state_machine::{transition, with_trigger, EngineStateCell, Intent, Trigger};
 use crate::engine::{readiness, EVENT_STATUS_CHANGED};
 
 use super::helper as macos_helper;
@@ -164,7 +164,8 @@ async fn run(app: AppHandle, path: Arc<String>) {
         tokio::time::sleep(Duration::from_secs(2)).await;
         RESTART_IN_PROGRESS.store(false, Ordering::SeqCst);
 
-        if let Err(e) = restart_tun_send_safe(app.clone(), Arc::clone(&path)).await {
+        let restart = restart_tun_send_safe(app.clone(), Arc::clone(&path));
+        if let Err(e) = with_trigger(Trigger::Watchdog, restart).await {
             log::error!("[bypass_router_watchdog] restart failed: {}", e);
         }
         elapsed = Duration::ZERO;
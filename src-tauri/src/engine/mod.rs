@@ -60,8 +60,9 @@ pub trait EngineManager {
     /// override DNS may release the Setup layer here so that OS-native
     /// captive detection on the next NetworkUp has a clean State to probe
     /// against. Only macOS implements this today — Windows needs a new
-    /// SCM service control verb and Linux has no lifecycle listener. See
-    /// docs/claude/dns-override.md "What we deliberately DON'T do".
+    /// SCM service control verb, and on Linux the override is simply
+    /// re-applied on the next NetworkUp. See docs/claude/dns-override.md
+    /// "What we deliberately DON'T do".
     fn on_network_down(_app: &AppHandle) {}
 
     /// Restore system DNS after the sing-box process has terminated.
@@ -72,6 +73,22 @@ pub trait EngineManager {
     /// fallback needed on Windows).
     fn on_process_terminated(_app: &AppHandle, _was_user_stop: bool) {}
 
+    /// Re-attach to a sing-box left running by a previous GUI session
+    /// (`runtime_record::reconcile`). `ProcessManager` is already seeded;
+    /// implementations restore their private teardown state (DNS capture)
+    /// and start whatever observes the process exiting, since an adopted
+    /// process has no `CommandEvent` stream.
+    fn adopt(app: &AppHandle, record: &runtime_record::RuntimeRecord, start_epoch: u64);
+
+    /// Tear down a TUN session a previous GUI session left behind and that
+    /// is not being adopted: stop sing-box when `may_be_alive`, then restore
+    /// DNS. Sidecar sessions never reach this; `runtime_record` kills them.
+    async fn clean_up_orphan(
+        app: &AppHandle,
+        record: &runtime_record::RuntimeRecord,
+        may_be_alive: bool,
+    );
+
     /// Idempotently install the platform's privileged companion:
     ///   - macOS: SMJobBless → /Library/PrivilegedHelperTools/…
     ///   - Windows: SCM CreateService → OneBoxTunService
@@ -106,7 +123,10 @@ pub trait EngineManager {
 
 pub mod common;
 pub(crate) use common::sysproxy;
-pub use common::{helper, readiness, state_machine};
+pub use common::{
+    config_switch, health, helper, journal, pac, preflight, proxy_bypass, proxy_guard,
+    proxy_snapshot, readiness, recovery, runtime_record, state_machine,
+};
 
 #[cfg(target_os = "linux")]
 pub mod linux;
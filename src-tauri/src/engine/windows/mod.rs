@@ -3,8 +3,10 @@ use tauri::AppHandle;
 use tauri::Emitter;
 
 use crate::engine::helper::extract_tun_gateway_from_config;
+use crate::engine::runtime_record::{self, RuntimeRecord};
 use crate::engine::sysproxy::{clear_system_proxy, set_system_proxy};
 pub mod native;
+pub(crate) mod proxy_watcher;
 pub(crate) mod watchdog;
 use self::native as windows_native;
 use crate::engine::EngineManager;
@@ -20,7 +22,7 @@ use crate::engine::EngineManager;
 //     helper 子命令":父进程(非提权)用 `OneBox.exe --onebox-tun-helper <sub> ...`
 //     ShellExecuteExW runas 启动一份新 exe,elevated 子进程在 lib.rs::run() 开头
 //     被 windows_native::run_helper 捕获,直接走注册表写 DNS / 起 sing-box /
-//     taskkill sing-box,跑完 exit,不进入 tauri runtime。
+//     taskkill /PID sing-box,跑完 exit,不进入 tauri runtime。
 //
 //     所有 DNS 操作都走 HKLM\SYSTEM\CurrentControlSet\Services\Tcpip\Parameters\
 //     Interfaces\{GUID}\NameServer 的 REG_SZ 值(见 windows_native 模块)。恢复走
@@ -242,10 +244,10 @@ impl EngineManager for WindowsEngine {
     }
 
     async fn stop(app: &AppHandle) -> Result<(), String> {
-        let (mode, child) = {
+        let (mode, child, adopted_pid) = {
             let mut mgr = crate::core::ProcessManager::acquire();
             mgr.is_stopping = true;
-            (mgr.mode.clone(), mgr.child.take())
+            (mgr.mode.clone(), mgr.child.take(), mgr.adopted_pid.take())
         };
         let Some(mode) = mode else {
             return Ok(());
@@ -283,6 +285,16 @@ impl EngineManager for WindowsEngine {
                         alive,
                         exit_code
                     );
+                } else if let Some(pid) = adopted_pid {
+                    // Adopted from a previous session: no CommandChild to kill.
+                    let kill_result = crate::commands::prestart::kill_pid(pid);
+                    log::info!(
+                        "[win-stop] adopted_kill_result={:?} pid={}",
+                        kill_result,
+                        pid
+                    );
+                    kill_result?;
+                    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
                 } else {
                     tokio::time::sleep(std::time::Duration::from_millis(500)).await;
                     log::info!("[win-stop] post_kill_alive_check skipped reason=no_child_pid");
@@ -315,6 +327,35 @@ impl EngineManager for WindowsEngine {
         }
     }
 
+    fn adopt(app: &AppHandle, record: &RuntimeRecord, start_epoch: u64) {
+        let mode = std::sync::Arc::new(record.mode.clone());
+        match record.mode {
+            crate::engine::ProxyMode::TunProxy => watchdog::spawn(app.clone(), mode, start_epoch),
+            _ => match record.pid {
+                Some(pid) => {
+                    runtime_record::watch_pid(app.clone(), mode, pid, start_epoch, |pid| {
+                        win32_pid_alive_check(pid).0
+                    })
+                }
+                None => log::warn!("[orphan] adopted session has no pid to watch"),
+            },
+        }
+    }
+
+    async fn clean_up_orphan(_app: &AppHandle, _record: &RuntimeRecord, _may_be_alive: bool) {
+        // Stopping the service resets DNS inside it; a service that is
+        // already gone left DNS behind, which only the elevated helper can
+        // reset.
+        use tun_service::scm::{query_state, QueriedState};
+        let result = match query_state() {
+            QueriedState::Running | QueriedState::StartPending => stop_tun_process(),
+            _ => restore_system_dns(),
+        };
+        if let Err(e) = result {
+            log::warn!("[orphan] TUN cleanup failed: {}", e);
+        }
+    }
+
     async fn ensure_installed(_app: &AppHandle) -> Result<(), String> {
         // Service installation self-elevates; Windows pops UAC the first
         // time. Once installed, the ACL granted at install time lets
@@ -22,8 +22,9 @@ use windows::core::{PCWSTR, PWSTR};
 use windows::Win32::Foundation::{CloseHandle, WAIT_OBJECT_0};
 use windows::Win32::Foundation::{ERROR_NO_MORE_ITEMS, ERROR_SUCCESS};
 use windows::Win32::System::Registry::{
-    RegCloseKey, RegEnumKeyExW, RegOpenKeyExW, RegQueryValueExW, RegSetValueExW, HKEY,
-    HKEY_LOCAL_MACHINE, KEY_READ, KEY_SET_VALUE, REG_SAM_FLAGS, REG_SZ, REG_VALUE_TYPE,
+    RegCloseKey, RegEnumKeyExW, RegNotifyChangeKeyValue, RegOpenKeyExW, RegQueryValueExW,
+    RegSetValueExW, HKEY, HKEY_CURRENT_USER, HKEY_LOCAL_MACHINE, KEY_NOTIFY, KEY_READ,
+    KEY_SET_VALUE, REG_NOTIFY_CHANGE_LAST_SET, REG_SAM_FLAGS, REG_SZ, REG_VALUE_TYPE,
 };
 use windows::Win32::System::Threading::{GetExitCodeProcess, WaitForSingleObject, INFINITE};
 use windows::Win32::UI::Shell::{ShellExecuteExW, SEE_MASK_NOCLOSEPROCESS, SHELLEXECUTEINFOW};
@@ -295,6 +296,25 @@ pub fn reset_all_interfaces_dns() -> (usize, usize) {
     (ok, err)
 }
 
+/// 阻塞监视 `HKCU\{path}` 及其子键的值变化,每次变化调用一次 `on_change`。
+/// 只在打开键或注册通知失败时返回。
+pub fn watch_current_user_key(path: &str, mut on_change: impl FnMut()) -> Result<(), String> {
+    let key = open_key(HKEY_CURRENT_USER, path, KEY_NOTIFY)?;
+    loop {
+        // 同步模式:没有 event 句柄,调用阻塞到下一次变化;每次只触发一次,需重新注册。
+        let rc = unsafe {
+            RegNotifyChangeKeyValue(key.0, true, REG_NOTIFY_CHANGE_LAST_SET, None, false)
+        };
+        if rc != ERROR_SUCCESS {
+            return Err(format!(
+                "RegNotifyChangeKeyValue({}) failed: {:?}",
+                path, rc.0
+            ));
+        }
+        on_change();
+    }
+}
+
 // ================= 参数引用转义 =================
 
 /// 按 Microsoft CommandLineToArgvW 约定转义单个参数。
@@ -412,14 +432,15 @@ pub fn self_elevate_helper(sub: &str, extra: &[&str]) -> Result<(), String> {
 ///   start <sidecar> <config> <gateway|->
 ///     gateway == "-" 时跳过 DNS 覆写;否则枚举非 TUN 网卡逐个写 NameServer。
 ///     DNS 写完后 spawn sing-box 并 detach(不等退出)。
-///   stop
-///     按"先恢复 DNS 再杀进程"的顺序:reset_all_interfaces_dns → taskkill sing-box.exe。
+///   stop [pid]
+///     按"先恢复 DNS 再杀进程"的顺序:reset_all_interfaces_dns → taskkill /PID <pid>。
+///     只杀这一个进程(不按映像名),side instance 不受影响;没有 pid 时只恢复 DNS。
 ///   restore-dns
 ///     只做 DNS scorched-earth reset,用于崩溃兜底。
 pub fn run_helper(args: &[String]) -> i32 {
     match args.first().map(|s| s.as_str()) {
         Some("start") => helper_start(&args[1..]),
-        Some("stop") => helper_stop(),
+        Some("stop") => helper_stop(args.get(1).map(String::as_str)),
         Some("restore-dns") => {
             let (ok, err) = reset_all_interfaces_dns();
             log_line(&format!("restore-dns: ok={} err={}", ok, err));
@@ -512,17 +533,21 @@ fn helper_start(args: &[String]) -> i32 {
     }
 }
 
-fn helper_stop() -> i32 {
+fn helper_stop(pid: Option<&str>) -> i32 {
     // 先恢复 DNS 再杀进程。若先杀 sing-box,TUN 立即 down,物理网卡 DNS 还指向
     // 已不可达的 172.19.0.1,会有数百毫秒的 DNS 查询超时窗口。
     let (ok, err) = reset_all_interfaces_dns();
     log_line(&format!("stop: dns reset ok={} err={}", ok, err));
 
+    let Some(pid) = pid.and_then(|p| p.parse::<u32>().ok()) else {
+        log_line(&format!("stop: no valid pid ({:?}), nothing to kill", pid));
+        return 0;
+    };
     match std::process::Command::new("taskkill")
-        .args(["/F", "/IM", "sing-box.exe"])
+        .args(["/F", "/PID", &pid.to_string()])
         .status()
     {
-        Ok(s) => log_line(&format!("taskkill sing-box.exe: {}", s)),
+        Ok(s) => log_line(&format!("taskkill /PID {}: {}", pid, s)),
         Err(e) => log_line(&format!("taskkill failed: {}", e)),
     }
     0
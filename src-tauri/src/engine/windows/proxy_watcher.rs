@@ -0,0 +1,37 @@
+//! Registry watcher for the WinINet proxy settings, feeding `proxy_guard`.
+//!
+//! `Sysproxy` / `Autoproxy` read and write
+//! `HKCU\Software\Microsoft\Windows\CurrentVersion\Internet Settings`, as do
+//! the Settings app, IE options and every other tool that sets a proxy. A
+//! write there wakes the guard right away instead of at its next poll; the
+//! guard itself decides whether anything actually diverged, so our own
+//! writes and unrelated values changing under the key are harmless.
+//!
+//! One dedicated thread started via `ensure_started()` (idempotent), blocked
+//! in `RegNotifyChangeKeyValue` for the app's lifetime.
+
+use std::sync::OnceLock;
+
+use super::windows_native;
+
+const INTERNET_SETTINGS: &str = r"Software\Microsoft\Windows\CurrentVersion\Internet Settings";
+
+static STARTED: OnceLock<()> = OnceLock::new();
+
+pub fn ensure_started() {
+    STARTED.get_or_init(|| {
+        let builder = std::thread::Builder::new().name("onebox-proxy-watcher".into());
+        let spawned = builder.spawn(|| {
+            let result = windows_native::watch_current_user_key(
+                INTERNET_SETTINGS,
+                crate::engine::proxy_guard::nudge,
+            );
+            if let Err(e) = result {
+                log::warn!("[proxy-watch] watcher stopped, polling only: {}", e);
+            }
+        });
+        if let Err(e) = spawned {
+            log::warn!("[proxy-watch] failed to spawn watcher thread: {}", e);
+        }
+    });
+}
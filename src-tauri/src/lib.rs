@@ -1,4 +1,5 @@
 mod app;
+mod cli;
 mod commands;
 mod core;
 pub mod engine;
@@ -20,6 +21,16 @@ pub fn run() {
         }
     }
 
+    let context = tauri::generate_context!();
+
+    // Headless engine control (`one-box engine start|stop|status|reload`).
+    // Must run before the GTK block below, which needs a display.
+    let cli_args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(command) = cli::parse(&cli_args) {
+        let code = cli::run(command, context);
+        std::process::exit(code);
+    }
+
     // On Linux/GNOME Wayland, tao creates a GTK HeaderBar for CSD which is
     // noticeably thicker than the X11 WM-provided titlebar. Inject custom
     // CSS before window creation to slim it down.
@@ -53,12 +64,26 @@ pub fn run() {
             commands::dns::get_optimal_local_dns_server,
             commands::config_fetch::fetch_config_with_optimal_dns,
             commands::config_fetch::verify_deep_link_url,
+            commands::usage::get_subscription_usage,
+            commands::subscription_refresh::set_subscription_refresh_interval,
+            commands::config_history::list_subscription_versions,
+            commands::config_history::diff_subscription_versions,
+            commands::config_history::rollback_subscription,
+            commands::config_history::unpin_subscription,
             core::stop,
             core::start,
             core::is_running,
             core::get_engine_state,
             core::clear_engine_error,
             core::reload_config,
+            core::instances::start_instance,
+            core::instances::stop_instance,
+            core::instances::list_instances,
+            core::traffic::get_traffic_snapshot,
+            engine::journal::get_engine_journal,
+            engine::proxy_bypass::preview_proxy_bypass,
+            engine::proxy_bypass::set_proxy_bypass,
+            engine::pac::preview_pac_script,
             commands::shell::version,
             commands::shell::read_logs,
             commands::shell::open_devtools,
@@ -77,7 +102,7 @@ pub fn run() {
         .setup(app::setup::app_setup)
         .on_menu_event(app::events::on_menu_event)
         .on_window_event(app::events::on_window_event)
-        .build(tauri::generate_context!())
+        .build(context)
         .expect("error while building tauri application")
         .run(app::events::on_run_event)
 }
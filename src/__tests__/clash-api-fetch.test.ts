@@ -7,13 +7,15 @@ vi.mock('@tauri-apps/plugin-http', () => ({
 }));
 
 vi.mock('../single/store', () => ({
+    getClashApiPort: vi.fn(),
     getClashApiSecret: vi.fn(),
 }));
 
-import { getClashApiSecret } from '../single/store';
+import { getClashApiPort, getClashApiSecret } from '../single/store';
 import { clashApiFetch } from '../utils/clash-api';
 
 const mockSecret = vi.mocked(getClashApiSecret);
+const mockPort = vi.mocked(getClashApiPort);
 
 // Read the options object passed to the plugin fetch on its most recent call.
 function lastCall(): [string, any] {
@@ -24,6 +26,7 @@ beforeEach(() => {
     vi.clearAllMocks();
     httpFetchMock.mockResolvedValue({ ok: true });
     mockSecret.mockResolvedValue('test-secret');
+    mockPort.mockResolvedValue(9191);
 });
 
 describe('clashApiFetch', () => {
@@ -40,6 +43,14 @@ describe('clashApiFetch', () => {
         expect(opts.headers['Content-Type']).toBe('application/json');
     });
 
+    it('follows the configured clash API port', async () => {
+        mockPort.mockResolvedValue(19090);
+        await clashApiFetch('/version');
+
+        const [url] = lastCall();
+        expect(url).toBe('http://127.0.0.1:19090/version');
+    });
+
     it('merges caller init (method/body) and lets caller headers override defaults', async () => {
         await clashApiFetch('/proxies/ExitGateway', {
             method: 'PUT',
@@ -172,7 +172,7 @@ export const useApplyPipelineRoot = () => {
     useEffect(() => {
         if (applyPhase !== 'start') return;
         if (engineState.epoch <= applyEpochRef.current) return;
-        if (engineState.kind === 'running') {
+        if (engineState.kind === 'running' || engineState.kind === 'degraded') {
             setApplyPhase('done');
         } else if (engineState.kind === 'failed') {
             setApplyErrorMessage(engineState.reason || t('connect_failed'));
@@ -313,10 +313,12 @@ export const useVPNOperations = () => {
     const { setActiveScreen } = useContext(NavContext);
 
     // 从权威状态派生出兼容变量
-    const isRunning = engineState.kind === 'running';
-    const isLoading = engineState.kind === 'starting' || engineState.kind === 'stopping';
+    // degraded: 进程仍在运行, 只是健康探测失败, 按运行中展示
+    const isRunning = engineState.kind === 'running' || engineState.kind === 'degraded';
+    // recovering: sing-box 崩溃后 Rust 侧正在自动重启, 按启动中展示
+    const isLoading = engineState.kind === 'starting' || engineState.kind === 'stopping' || engineState.kind === 'recovering';
     const operationStatus: OperationStatus =
-        engineState.kind === 'starting'
+        engineState.kind === 'starting' || engineState.kind === 'recovering'
             ? 'starting'
             : engineState.kind === 'stopping'
                 ? 'stopping'
@@ -360,10 +362,12 @@ export const useVPNOperations = () => {
             return message(t('please_add_subscription'), { title: t('tips'), kind: 'error' });
         }
 
-        // Pre-start check: if port is occupied by orphan processes, show repair modal
+        // Pre-start check: if port is occupied by our own orphan sing-box, show repair modal.
+        // A port held by some other program is never killed; start() offers a free port instead.
         const proxyPort = await getProxyPort();
-        const check = await invoke<{ port_occupied: boolean; orphan_pids: number[] }>('prestart_check', { port: proxyPort });
-        if (check.port_occupied && check.orphan_pids.length > 0) {
+        const check = await invoke<{ port_occupied: boolean; orphan_pids: number[]; holders: { ours: boolean }[] }>('prestart_check', { port: proxyPort });
+        const onlyOurs = check.holders.every((holder) => holder.ours);
+        if (check.port_occupied && check.orphan_pids.length > 0 && onlyOurs) {
             // Store what we would do after repair, then show the modal
             pendingStartRef.current = () => {
                 performSyncAndStart(async (error) => {
@@ -19,6 +19,7 @@ function normalizePort(value: string): number | null {
 
 export default function ProxyPortSetting() {
   const engineState = useEngineState();
+  const isRunning = engineState.kind === "running" || engineState.kind === "degraded";
   const [isOpen, setIsOpen] = useState(false);
   const [port, setPort] = useState(DEFAULT_PROXY_PORT.toString());
   const [currentPort, setCurrentPort] = useState(DEFAULT_PROXY_PORT);
@@ -55,7 +56,7 @@ export default function ProxyPortSetting() {
     };
 
     try {
-      if (engineState.kind === "running") {
+      if (isRunning) {
         await toast.promise(
           (async () => {
             await vpnServiceManager.stop();
@@ -96,7 +97,7 @@ export default function ProxyPortSetting() {
         window.dispatchEvent(new CustomEvent<number>(PROXY_PORT_CHANGED_EVENT, { detail: parsedPort }));
       };
 
-      if (engineState.kind === "running") {
+      if (isRunning) {
         await toast.promise(
           (async () => {
             await vpnServiceManager.stop();
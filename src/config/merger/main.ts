@@ -1,7 +1,7 @@
 import * as path from '@tauri-apps/api/path';
 import { getSubscriptionConfig } from '../../action/db';
-import { getAllowLan, getClashApiSecret, getCustomRuleSet, getStoreValue, isBypassRouterEnabled, setStoreValue } from '../../single/store';
-import { STAGE_VERSION_STORE_KEY } from '../../types/definition';
+import { getAllowLan, getClashApiPort, getClashApiSecret, getCustomRuleSet, getStoreValue, isBypassRouterEnabled, setStoreValue } from '../../single/store';
+import { STAGE_VERSION_STORE_KEY, STAGING_CONFIG_FILE } from '../../types/definition';
 import { configureMixedInbound, configureTunInbound, updateDHCPSettings2Config, updateVPNServerConfigFromDB } from './helper';
 
 import { configType, getConfigTemplateCacheKey } from '../common';
@@ -41,7 +41,7 @@ async function getConfigTemplate(mode: configType): Promise<any> {
 async function updateExperimentalConfig(newConfig: any, dbCacheFilePath: string) {
 
     newConfig["experimental"]["clash_api"] = {
-        "external_controller": "127.0.0.1:9191",
+        "external_controller": `127.0.0.1:${await getClashApiPort()}`,
         "secret": await getClashApiSecret(),
     };
 
@@ -76,7 +76,7 @@ export async function setMixedConfig(identifier: string) {
     await configureMixedInbound(newConfig, allowLan, bypassRouter);
 
     await updateDHCPSettings2Config(newConfig);
-    await updateVPNServerConfigFromDB('config.json', dbConfigData, newConfig);
+    await updateVPNServerConfigFromDB(STAGING_CONFIG_FILE, dbConfigData, newConfig);
 
 }
 
@@ -101,7 +101,7 @@ export async function setTunConfig(identifier: string) {
     await configureMixedInbound(newConfig, allowLan, bypassRouter);
 
     await updateDHCPSettings2Config(newConfig);
-    await updateVPNServerConfigFromDB('config.json', dbConfigData, newConfig);
+    await updateVPNServerConfigFromDB(STAGING_CONFIG_FILE, dbConfigData, newConfig);
 }
 
 
@@ -124,7 +124,7 @@ export async function setGlobalMixedConfig(identifier: string) {
     await configureMixedInbound(newConfig, allowLan, bypassRouter);
 
     await updateDHCPSettings2Config(newConfig);
-    await updateVPNServerConfigFromDB('config.json', dbConfigData, newConfig);
+    await updateVPNServerConfigFromDB(STAGING_CONFIG_FILE, dbConfigData, newConfig);
 
 }
 
@@ -150,5 +150,5 @@ export default async function setGlobalTunConfig(identifier: string) {
     await configureMixedInbound(newConfig, allowLan, bypassRouter);
 
     await updateDHCPSettings2Config(newConfig);
-    await updateVPNServerConfigFromDB('config.json', dbConfigData, newConfig);
+    await updateVPNServerConfigFromDB(STAGING_CONFIG_FILE, dbConfigData, newConfig);
 }
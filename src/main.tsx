@@ -1,14 +1,17 @@
 import { getCurrentWindow } from "@tauri-apps/api/window";
 import React from "react";
 import ReactDOM from "react-dom/client";
-import { setupStatusListener, setupTauriLogListener, setupTrayIcon } from "./tray";
+import { setupPortFallbackListener, setupStatusListener, setupSubscriptionRefreshListener, setupTauriLogListener, setupTraySyncListener, setupUsageAlertListener } from "./tray";
 import WindowManger from './window-manger';
 
 
 const appWindow = getCurrentWindow();
 
 if (appWindow.label === "main") {
-  setupTrayIcon();
+  setupTraySyncListener();
+  setupSubscriptionRefreshListener();
+  setupPortFallbackListener();
+  setupUsageAlertListener();
   setupStatusListener();
   setupTauriLogListener();
 }
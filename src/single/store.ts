@@ -4,7 +4,7 @@ import { LazyStore } from '@tauri-apps/plugin-store';
 import { toast } from 'sonner';
 import { configType, StageVersionType } from '../config/common';
 import { emptyRuleSet, type RuleAction, type RuleSet } from '../config/merger/custom-rules';
-import { ALLOWLAN_STORE_KEY, DEFAULT_PROXY_PORT, ENABLE_BYPASS_ROUTER_STORE_KEY, ENABLE_TUN_STORE_KEY, PROXY_PORT_STORE_KEY, SHOW_NODE_PROTOCOL_STORE_KEY, SING_BOX_MAJOR_VERSION, SING_BOX_VERSION, SKIP_SYSTEM_PROXY_STORE_KEY, STAGE_VERSION_STORE_KEY, USE_DHCP_STORE_KEY, USER_AGENT_STORE_KEY } from '../types/definition';
+import { ALLOWLAN_STORE_KEY, CLASH_API_PORT_STORE_KEY, DEFAULT_CLASH_API_PORT, DEFAULT_PROXY_PORT, ENABLE_BYPASS_ROUTER_STORE_KEY, ENABLE_TUN_STORE_KEY, PROXY_PORT_STORE_KEY, SHOW_NODE_PROTOCOL_STORE_KEY, SING_BOX_MAJOR_VERSION, SING_BOX_VERSION, SKIP_SYSTEM_PROXY_STORE_KEY, STAGE_VERSION_STORE_KEY, USE_DHCP_STORE_KEY, USER_AGENT_STORE_KEY } from '../types/definition';
 
 const OsType = type();
 export const LANGUAGE_STORE_KEY = 'language';
@@ -245,6 +245,23 @@ export async function setProxyPort(port: number): Promise<void> {
     await store.save();
 }
 
+export async function getClashApiPort(): Promise<number> {
+    const raw = await store.get(CLASH_API_PORT_STORE_KEY);
+    const port = typeof raw === 'number' ? raw : Number(raw);
+    if (Number.isInteger(port) && port > 0 && port <= 65535) {
+        return port;
+    }
+    return DEFAULT_CLASH_API_PORT;
+}
+
+export async function setClashApiPort(port: number): Promise<void> {
+    if (!Number.isInteger(port) || port <= 0 || port > 65535) {
+        throw new Error('invalid_clash_api_port');
+    }
+    await store.set(CLASH_API_PORT_STORE_KEY, port);
+    await store.save();
+}
+
 export async function getConfigTemplateURLKey(mode: configType): Promise<string> {
     // zh: 返回配置模版 URL 的存储键，格式为 `key-sing-box-{主版本号}-{模式}-template-path`, 如非必要请勿更改此格式。
     // en: Returns the storage key for the config template URL in the format `key-sing-box-{major-version}-{mode}-template-path`. Do not change this format unless necessary.
@@ -1,30 +1,27 @@
-import { defaultWindowIcon } from '@tauri-apps/api/app';
 import { invoke } from "@tauri-apps/api/core";
+import bytes from "bytes";
 import { listen } from '@tauri-apps/api/event';
-import { Menu, MenuOptions } from '@tauri-apps/api/menu';
-import { TrayIcon, TrayIconEvent } from '@tauri-apps/api/tray';
 import { getCurrentWindow } from '@tauri-apps/api/window';
 import { message } from '@tauri-apps/plugin-dialog';
-import { type } from '@tauri-apps/plugin-os';
-import { getClashApiSecret, getProxyPort, getStoreValue } from './single/store';
-import { DEVELOPER_TOGGLE_STORE_KEY } from './types/definition';
-import { copyEnvToClipboard, initLanguage, t, vpnServiceManager } from './utils/helper';
-
-// 常量
-const PROXY_HOST = "127.0.0.1";
-const STATUS_POLL_INTERVAL = 500;
+import { mutate } from 'swr';
+import { getDataBaseInstance } from './single/db';
+import { setClashApiPort, setProxyPort } from './single/store';
+import { GET_SUBSCRIPTIONS_LIST_SWR_KEY, PROXY_PORT_CHANGED_EVENT } from './types/definition';
+import { t, vpnServiceManager } from './utils/helper';
+
+// 托盘图标与菜单由 Rust 端构建（src-tauri/src/app/tray.rs）。
+// 启动、切换模式或切换订阅需要重新生成配置，而配置合并逻辑在前端，
+// 因此 Rust 端通过该事件请求前端执行 syncConfig + start。
+const TRAY_SYNC_REQUEST_EVENT = "tray-sync-request";
+// 后台自动更新订阅完成（src-tauri/src/commands/subscription_refresh.rs）
+const SUBSCRIPTION_REFRESHED_EVENT = "subscription-refreshed";
+// 端口被其他程序占用时后端改用的空闲端口（src-tauri/src/core/mod.rs）
+const PORT_FALLBACK_EVENT = "port-fallback";
+// 订阅流量即将用尽或即将过期（src-tauri/src/commands/usage.rs）
+const USAGE_ALERT_EVENT = "subscription-usage-alert";
 
 const appWindow = getCurrentWindow();
-let trayInstance: TrayIcon | null = null;
-let lastStatus: boolean | null = null;
-let statusPollerId: number | null = null;
-let statusPollInFlight = false;
-
-// 获取当前运行状态
-async function getRunningStatus(): Promise<boolean> {
-    const secret = await getClashApiSecret();
-    return await invoke<boolean>("is_running", { secret });
-}
+let traySyncInFlight = false;
 
 // 设置窗口控制按钮事件
 function setupWindowControls() {
@@ -39,195 +36,90 @@ function setupWindowControls() {
         ?.addEventListener('click', () => appWindow.hide());
 }
 
-// 切换代理状态
-async function toggleProxyStatus(status: boolean) {
-    if (status) {
-        await vpnServiceManager.stop();
-    } else {
-        await vpnServiceManager.syncConfig({});
-        await vpnServiceManager.start();
-    }
-    await updateTrayMenu();
-}
-
-// 创建基础菜单项
-async function createBaseMenuItems(status: boolean): Promise<NonNullable<MenuOptions['items']>> {
-    const proxyPort = await getProxyPort();
-    return [
-        {
-            id: 'show',
-            text: t("menu_dashboard"),
-        },
-        {
-            id: "enable",
-            text: t("menu_enable_proxy"),
-            checked: status,
-            enabled: true,
-            action: () => toggleProxyStatus(status),
-        },
-        {
-            id: 'copy_proxy',
-            text: t("menu_copy_env"),
-            action: () => copyEnvToClipboard(PROXY_HOST, proxyPort.toString()),
-        },
-    ];
-}
-
-// 创建开发者菜单项
-async function createDeveloperMenuItems(): Promise<NonNullable<MenuOptions['items']>[number] | null> {
-    const isDeveloperMode = await getStoreValue(DEVELOPER_TOGGLE_STORE_KEY, false);
-    if (!isDeveloperMode) return null;
-
-    const appPaths = await invoke<{
-        log_dir: string;
-        data_dir: string;
-        cache_dir: string;
-        config_dir: string;
-        local_data_dir: string;
-    }>('get_app_paths');
-
-    const openDirectory = (path: string) => async () => {
-        try {
-            await invoke('open_directory', { path });
-        } catch (e) {
-            console.error('Failed to open directory:', e);
-        }
-    };
-
-    return {
-        id: 'developer_menu',
-        text: t("menu_developer") || "Developer",
-        items: [
-            {
-                id: 'open_advanced_settings',
-                text: t("open_advanced_settings"),
-                action: async () => {
-                    await invoke('create_window', {
-                        app: appWindow,
-                        title: "Log",
-                        label: "sing-box-log",
-                        windowTag: "sing-box-log",
-                    });
-                },
-            },
-            {
-                id: 'devtools',
-                text: t("menu_devtools"),
-                action: async () => {
-                    await invoke("open_devtools");
-                },
-            },
-            {
-                id: 'open_log_dir',
-                text: t("menu_log_dir") || "Log Directory",
-                action: openDirectory(appPaths.log_dir),
-            },
-            {
-                id: 'open_config_dir',
-                text: t("menu_config_dir") || "Config Directory",
-                action: openDirectory(appPaths.config_dir),
-            },
-        ],
-    };
-}
-
-// 创建托盘菜单
-async function createTrayMenu() {
-    await initLanguage();
-
-    const status = await getRunningStatus();
-    lastStatus = status;
-
+// 响应托盘的配置同步请求
+export async function setupTraySyncListener() {
     setupWindowControls();
 
-    const baseItems = await createBaseMenuItems(status);
-    const developerMenu = await createDeveloperMenuItems();
-
-    const menuItems = [
-        ...baseItems,
-        ...(developerMenu ? [developerMenu] : []),
-        {
-            id: 'quit',
-            text: t("menu_quit"),
-        },
-    ];
-
-    return await Menu.new({ items: menuItems });
-}
-
-// 每秒轮询状态，如有变化则更新托盘菜单
-function startStatusPolling() {
-    if (statusPollerId !== null) return;
-
-    statusPollerId = window.setInterval(async () => {
-        if (statusPollInFlight) return;
+    await listen<{ restart: boolean }>(TRAY_SYNC_REQUEST_EVENT, async (event) => {
+        if (traySyncInFlight) return;
 
-        statusPollInFlight = true;
+        traySyncInFlight = true;
         try {
-            const status = await getRunningStatus();
-            if (lastStatus !== null && status !== lastStatus) {
-                lastStatus = status;
-                await updateTrayMenu();
-            } else if (lastStatus === null) {
-                lastStatus = status;
+            if (event.payload?.restart) {
+                await vpnServiceManager.stop();
             }
+            await vpnServiceManager.syncConfig({});
+            await vpnServiceManager.start();
         } catch (error) {
-            console.error('Failed to poll running status:', error);
+            console.error('Failed to handle tray sync request:', error);
         } finally {
-            statusPollInFlight = false;
+            traySyncInFlight = false;
         }
-    }, STATUS_POLL_INTERVAL);
-}
-
-// 处理托盘图标事件
-async function handleTrayIconAction(event: TrayIconEvent) {
-    if (event.type === 'Leave') {
-        await updateTrayMenu();
-    }
+    });
 }
 
-// 创建托盘图标配置
-async function createTrayIconOptions(menu: Menu) {
-    const trayIconData = await invoke<ArrayBuffer>('get_tray_icon', { app: appWindow });
-    const defaultIcon = await defaultWindowIcon();
-
-    return {
-        menu,
-        icon: trayIconData || defaultIcon,
-        tooltip: "OneBox",
-        action: handleTrayIconAction,
-    };
+// 监听后台订阅自动更新：刷新列表，当前订阅内容变化时重新生成配置并重载
+export async function setupSubscriptionRefreshListener() {
+    await listen<{ identifier: string; changed: boolean; reload: boolean }>(
+        SUBSCRIPTION_REFRESHED_EVENT,
+        async (event) => {
+            await mutate(GET_SUBSCRIPTIONS_LIST_SWR_KEY);
+            if (!event.payload?.reload) return;
+
+            try {
+                await vpnServiceManager.syncConfig({});
+                await vpnServiceManager.reload(0);
+            } catch (error) {
+                console.error('Failed to reload refreshed subscription:', error);
+            }
+        },
+    );
 }
 
-// 初始化托盘
-export async function setupTrayIcon() {
-    if (trayInstance) return trayInstance;
-
-    try {
-        const menu = await createTrayMenu();
-        const options = await createTrayIconOptions(menu);
-
-        trayInstance = await TrayIcon.new(options);
-
-        // macOS 特殊处理
-        if (type() === 'macos' && trayInstance) {
-            trayInstance.setIconAsTemplate(true);
-        }
-
-        startStatusPolling();
-        return trayInstance;
-    } catch (error) {
-        console.error('Error setting up tray icon:', error);
-        return null;
-    }
+// 记住后端改用的端口，否则下次生成配置又会写回被占用的端口
+export async function setupPortFallbackListener() {
+    await listen<{ role: 'mixed' | 'clash-api'; from: number; to: number }>(
+        PORT_FALLBACK_EVENT,
+        async (event) => {
+            const { role, to } = event.payload;
+            if (role === 'mixed') {
+                await setProxyPort(to);
+                window.dispatchEvent(new CustomEvent<number>(PROXY_PORT_CHANGED_EVENT, { detail: to }));
+            } else {
+                await setClashApiPort(to);
+            }
+        },
+    );
 }
 
-// 更新托盘菜单
-export async function updateTrayMenu() {
-    if (!trayInstance) return;
-
-    const newMenu = await createTrayMenu();
-    await trayInstance.setMenu(newMenu);
+// 订阅流量或有效期提醒，每次对账每种提醒只触发一次
+export async function setupUsageAlertListener() {
+    await listen<{
+        identifier: string;
+        kind: 'traffic' | 'expire';
+        estimated_used: number;
+        total_traffic: number;
+        expire_time: number;
+    }>(USAGE_ALERT_EVENT, async (event) => {
+        const { identifier, kind, estimated_used, total_traffic, expire_time } = event.payload;
+        const db = await getDataBaseInstance();
+        const rows: { name: string | null }[] = await db.select(
+            'SELECT name FROM subscriptions WHERE identifier = ?',
+            [identifier],
+        );
+        const name = rows[0]?.name || identifier;
+        const msg = kind === 'traffic'
+            ? t('usage_alert_traffic', {
+                name,
+                used: bytes(estimated_used) ?? '0',
+                total: bytes(total_traffic) ?? '0',
+            }, 'Subscription "{{name}}" has used about {{used}} of {{total}}.')
+            : t('usage_alert_expire', {
+                name,
+                date: new Date(expire_time * 1000).toLocaleDateString(),
+            }, 'Subscription "{{name}}" expires on {{date}}.');
+        await message(msg, { title: t('usage_alert_title', 'Subscription reminder'), kind: 'warning' });
+    });
 }
 
 // 处理连接失败
@@ -268,8 +160,6 @@ export async function setupStatusListener() {
         if (event.payload.code === 1) {
             await handleConnectionError();
         }
-
-        await updateTrayMenu();
     });
 }
 
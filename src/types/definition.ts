@@ -24,6 +24,12 @@ export const USER_AGENT_STORE_KEY = 'user_agent_key'
 export const DEFAULT_PROXY_PORT = 6789
 export const PROXY_PORT_STORE_KEY = 'proxy_port_key'
 export const PROXY_PORT_CHANGED_EVENT = 'onebox-proxy-port-changed'
+// Clash API（external_controller）端口，需与 src-tauri/src/core/mod.rs 的 DEFAULT_CLASH_API_PORT 一致
+export const DEFAULT_CLASH_API_PORT = 9191
+export const CLASH_API_PORT_STORE_KEY = 'clash_api_port_key'
+// 生成的配置先写到这里，由后端 start / reload_config 校验后原子地替换 config.json
+// （src-tauri/src/engine/common/config_switch.rs 的 STAGING_FILE_NAME）
+export const STAGING_CONFIG_FILE = 'config.staging.json'
 
 // 上次检查更新的时间戳（ms），跨会话持久化
 export const LAST_UPDATE_CHECK_TIME_KEY = 'last_update_check_time_key'
@@ -69,6 +75,7 @@ export type Subscription = {
     official_website: string
     expire_time: number
     last_update_time: number
+    refresh_interval?: number | null
 }
 
 export type SubscriptionConfig = {
@@ -1,13 +1,15 @@
 // Mirror of src-tauri/src/engine/state_machine.rs::EngineState. Keep in sync.
 
-export type EngineStateKind = 'idle' | 'starting' | 'running' | 'stopping' | 'failed';
+export type EngineStateKind = 'idle' | 'starting' | 'running' | 'stopping' | 'failed' | 'recovering' | 'degraded';
 
 export type EngineState =
     | { kind: 'idle'; epoch: number }
     | { kind: 'starting'; since: number; epoch: number; mode: string }
     | { kind: 'running'; since: number; epoch: number; mode: string }
     | { kind: 'stopping'; since: number; epoch: number }
-    | { kind: 'failed'; reason: string; at: number; epoch: number };
+    | { kind: 'failed'; reason: string; at: number; epoch: number }
+    | { kind: 'recovering'; attempt: number; next_at: number; reason: string; epoch: number }
+    | { kind: 'degraded'; since: number; reason: string; epoch: number; mode: string };
 
 export const ENGINE_STATE_EVENT = 'engine-state';
 
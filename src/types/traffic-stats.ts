@@ -0,0 +1,23 @@
+// Mirror of src-tauri/src/core/traffic.rs::TrafficSnapshot. Keep in sync.
+
+export interface UsageEntry {
+    name: string;
+    upload: number;
+    download: number;
+}
+
+export interface TrafficSnapshot {
+    epoch: number;
+    at: number;
+    up_rate: number;
+    down_rate: number;
+    upload_total: number;
+    download_total: number;
+    active_connections: number;
+    by_outbound: UsageEntry[];
+    by_process: UsageEntry[];
+}
+
+// Pushed once per second while the engine runs; breakdowns are top-N only,
+// use the `get_traffic_snapshot` command for the full lists.
+export const TRAFFIC_STATS_EVENT = 'traffic-stats';
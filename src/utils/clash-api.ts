@@ -3,12 +3,15 @@
 import { fetch as httpFetch } from '@tauri-apps/plugin-http';
 import { useEffect, useState } from 'react';
 import { LogEntry } from '../components/log/types';
-import { getClashApiSecret } from '../single/store';
+import { getClashApiPort, getClashApiSecret } from '../single/store';
 
-const CLASH_API_BASE_URL = 'http://127.0.0.1:9191';
+// 端口与 merger 写入 experimental.clash_api.external_controller 的值保持一致
+async function clashApiBaseUrl(host = '127.0.0.1') {
+    return `http://${host}:${await getClashApiPort()}`;
+}
 
 // plugin-http 的 reqwest 默认读取系统代理（auto_sys_proxy=true）；在“不设置系统代理”模式下，
-// 若机器已有外部代理，发往 127.0.0.1:9191 的请求会被带进代理而失败（reqwest 对回环地址无隐式豁免）。
+// 若机器已有外部代理，发往 127.0.0.1:<clash 端口> 的请求会被带进代理而失败（reqwest 对回环地址无隐式豁免）。
 // 加一个“永远被绕过的占位代理”会让 reqwest 置 auto_sys_proxy=false（等价 Rust 侧
 // build_no_redirect_client 的 .no_proxy()），从而不再读取系统代理；noProxy 再把目标豁免为直连。
 //
@@ -23,7 +26,7 @@ export async function clashApiFetch(
     init: NonNullable<Parameters<typeof httpFetch>[1]> = {},
 ) {
     const secret = await getClashApiSecret();
-    return httpFetch(`${CLASH_API_BASE_URL}${path}`, {
+    return httpFetch(`${await clashApiBaseUrl()}${path}`, {
         ...init,
         proxy: NO_SYSTEM_PROXY,
         headers: {
@@ -40,7 +43,7 @@ export async function clashApiFetch(
 export const ClashService = {
     async fetchLogs() {
         const secret = await getClashApiSecret();
-        return fetch('http://localhost:9191/logs', {
+        return fetch(`${await clashApiBaseUrl('localhost')}/logs`, {
             headers: {
                 'Authorization': `Bearer ${secret}`
             }
@@ -48,7 +51,7 @@ export const ClashService = {
     },
     async fetchTraffic() {
         const secret = await getClashApiSecret();
-        return fetch('http://localhost:9191/traffic', {
+        return fetch(`${await clashApiBaseUrl('localhost')}/traffic`, {
             headers: {
                 'Authorization': `Bearer ${secret}`
             }
@@ -56,7 +59,7 @@ export const ClashService = {
     },
     async deleteConnections() {
         const secret = await getClashApiSecret();
-        return fetch('http://localhost:9191/connections', {
+        return fetch(`${await clashApiBaseUrl('localhost')}/connections`, {
             method: 'DELETE',
             headers: {
                 'Authorization': `Bearer ${secret}`
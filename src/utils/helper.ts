@@ -5,7 +5,7 @@ import { arch, locale, type, version } from '@tauri-apps/plugin-os';
 import { OsInfo, RULE_MODE_STORE_KEY, SING_BOX_VERSION, SSI_STORE_KEY } from '../types/definition';
 
 import { getCurrentWindow } from '@tauri-apps/api/window';
-import { message } from '@tauri-apps/plugin-dialog';
+import { ask, message } from '@tauri-apps/plugin-dialog';
 import en from '../../lang/en.json';
 import zh from '../../lang/zh.json';
 import setGlobalTunConfig, { setGlobalMixedConfig, setMixedConfig, setTunConfig } from '../config/merger/main';
@@ -163,7 +163,8 @@ async function syncConfig(props: SyncConfigProps) {
 
 
 export const vpnServiceManager = {
-    start: async () => {
+    // portFallback：端口被其他程序占用时改用空闲端口（见 src-tauri/src/core/mod.rs）
+    start: async (portFallback?: boolean) => {
         try {
             const configPath = await getSingBoxConfigPath();
             const tunMode: boolean | undefined = await getEnableTun();
@@ -173,7 +174,7 @@ export const vpnServiceManager = {
             console.log("模式:", mode);
             console.log("配置文件路径:", configPath);
 
-            await invoke("start", { app: appWindow, path: configPath, mode: mode });
+            await invoke("start", { app: appWindow, path: configPath, mode: mode, portFallback });
 
         } catch (error: any) {
             console.error('Failed to start VPN service:', error);
@@ -190,6 +191,38 @@ export const vpnServiceManager = {
                 );
                 throw error;
             }
+            // 端口被非 OneBox 启动的进程占用，后端拒绝结束该进程；询问是否改用空闲端口
+            const heldBy = errorText.match(/PORT_HELD_BY_OTHER_PROCESS:(\d+):([^\n]*)/);
+            if (heldBy) {
+                const useFreePort = !portFallback && await ask(
+                    t(
+                        'port_held_by_other_process',
+                        { port: heldBy[1], name: heldBy[2] },
+                        'Port {{port}} is used by {{name}}. OneBox will not stop other programs. Switch to a free port?'
+                    ),
+                    {
+                        title: t('error'),
+                        kind: 'warning',
+                        okLabel: t('use_free_port', 'Use a free port'),
+                        cancelLabel: t('cancel'),
+                    },
+                );
+                if (useFreePort) {
+                    return vpnServiceManager.start(true);
+                }
+                throw error;
+            }
+            // 启动前配置校验失败（src-tauri/src/engine/common/preflight.rs）
+            const invalid = errorText.match(/CONFIG_INVALID:(\[.*\])/s)?.[1];
+            if (invalid) {
+                const issues: { path: string; message: string }[] = JSON.parse(invalid);
+                const details = issues.map((issue) => `${issue.path}: ${issue.message}`).join('\n');
+                await message(
+                    `${t('config_invalid', 'The configuration failed validation:')}\n\n${details}`,
+                    { title: t('error'), kind: 'error' },
+                );
+                throw error;
+            }
             // 如果是权限问题，抛出特定错误让上层处理
             if (errorText.includes('REQUIRE_PRIVILEGE')) {
                 throw new Error('REQUIRE_PRIVILEGE');
@@ -222,7 +255,19 @@ export const vpnServiceManager = {
                 await new Promise(resolve => setTimeout(resolve, 1000)); // 等待1秒确保服务完全停止
                 await vpnServiceManager.start();
             } else {
-                await invoke("reload_config");
+                try {
+                    await invoke("reload_config");
+                } catch (error: any) {
+                    // 新配置未能就绪，后端已回滚到上一个可用配置（src-tauri/src/engine/common/config_switch.rs）
+                    const reason = String(error?.message ?? error ?? '').match(/CONFIG_RELOAD_REVERTED:(.*)/s)?.[1];
+                    if (reason) {
+                        await message(
+                            t('config_reload_reverted', { reason }, 'The new configuration did not come up and the previous one was restored: {{reason}}'),
+                            { title: t('error'), kind: 'error' },
+                        );
+                    }
+                    throw error;
+                }
             }
         } else {
             console.warn("VPN service is not running, cannot reload config");